@@ -0,0 +1,238 @@
+use reqwest::{RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{ClientError, Result};
+use crate::types::*;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.listen-rs.com/v1/adapter";
+
+/// Client of the listen-adapter REST API
+#[derive(Debug, Clone)]
+pub struct ListenAdapterClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl Default for ListenAdapterClient {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_URL)
+    }
+}
+
+impl ListenAdapterClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+        }
+    }
+
+    /// Uses `LISTEN_ADAPTER_URL` and `LISTEN_ADAPTER_API_KEY` if set
+    pub fn from_env() -> Self {
+        let client = match std::env::var("LISTEN_ADAPTER_URL") {
+            Ok(url) => Self::new(url),
+            Err(_) => Self::default(),
+        };
+        match std::env::var("LISTEN_ADAPTER_API_KEY") {
+            Ok(key) => client.with_api_key(key),
+            Err(_) => client,
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.http.get(format!("{}{}", self.base_url, path)))
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.http.post(format!("{}{}", self.base_url, path)))
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    /// Sends the request, a 404 is None
+    async fn send_optional<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<Option<T>> {
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let message = serde_json::from_str::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|v| v["error"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            return Err(ClientError::Api {
+                status: status.as_u16(),
+                message,
+            });
+        }
+
+        serde_json::from_str(&body)
+            .map(Some)
+            .map_err(|error| ClientError::Decode { error, body })
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        self.send_optional(request)
+            .await?
+            .ok_or_else(|| ClientError::Api {
+                status: StatusCode::NOT_FOUND.as_u16(),
+                message: "Not found".to_string(),
+            })
+    }
+
+    pub async fn top_tokens(&self, params: &TopTokensParams) -> Result<Vec<TopToken>> {
+        self.send(self.get("/top-tokens").query(params)).await
+    }
+
+    pub async fn trending(&self, params: &TrendingParams) -> Result<Vec<TrendingToken>> {
+        self.send(self.get("/trending").query(params)).await
+    }
+
+    pub async fn search(&self, q: &str, limit: Option<usize>) -> Result<Vec<SearchResult>> {
+        #[derive(Serialize)]
+        struct Query<'a> {
+            q: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            limit: Option<usize>,
+        }
+        self.send(self.get("/search").query(&Query { q, limit }))
+            .await
+    }
+
+    /// Candles oldest first, a response in any other order is an error
+    pub async fn candlesticks(&self, params: &CandlestickParams) -> Result<Vec<Candlestick>> {
+        let candlesticks: Vec<Candlestick> =
+            self.send(self.get("/candlesticks").query(params)).await?;
+        if candlesticks
+            .windows(2)
+            .any(|pair| pair[0].timestamp >= pair[1].timestamp)
+        {
+            return Err(ClientError::Invalid(
+                "candlesticks are not oldest first".to_string(),
+            ));
+        }
+        Ok(candlesticks)
+    }
+
+    pub async fn metadata(&self, mint: &str) -> Result<Option<TokenMetadata>> {
+        self.send_optional(self.get("/metadata").query(&[("mint", mint)]))
+            .await
+    }
+
+    /// Latest trade of the token
+    pub async fn price(&self, mint: &str) -> Result<PriceUpdate> {
+        self.send(self.get("/price").query(&[("mint", mint)])).await
+    }
+
+    /// First trade of the current UTC day
+    pub async fn open_price_24h(&self, mint: &str) -> Result<Option<OpenPrice>> {
+        self.send(self.get("/24h-open").query(&[("mint", mint)]))
+            .await
+    }
+
+    pub async fn wallet_trades(
+        &self,
+        address: &str,
+        params: &WalletTradesParams,
+    ) -> Result<Vec<PriceUpdate>> {
+        self.send(
+            self.get(&format!("/wallet/{}/trades", address))
+                .query(params),
+        )
+        .await
+    }
+
+    pub async fn wallet_pnl(&self, address: &str, mint: Option<&str>) -> Result<WalletPnl> {
+        let mut request = self.get(&format!("/wallet/{}/pnl", address));
+        if let Some(mint) = mint {
+            request = request.query(&[("mint", mint)]);
+        }
+        self.send(request).await
+    }
+
+    pub async fn token_leaderboard(
+        &self,
+        mint: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        let mut request = self.get(&format!("/token/{}/leaderboard", mint));
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        self.send(request).await
+    }
+
+    /// Latest holder snapshot taken at or before `at` (unix seconds), None
+    /// if the token has not been snapshotted
+    pub async fn token_holders(
+        &self,
+        mint: &str,
+        at: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<Option<HolderSnapshot>> {
+        let mut request = self.get(&format!("/token/{}/holders", mint));
+        if let Some(at) = at {
+            request = request.query(&[("at", at)]);
+        }
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        self.send_optional(request).await
+    }
+
+    pub async fn analytics(
+        &self,
+        query: &AnalyticsQuery,
+    ) -> Result<Vec<serde_json::Map<String, serde_json::Value>>> {
+        self.send(self.post("/analytics").json(query)).await
+    }
+
+    pub async fn get_chat(&self, chat_id: &str) -> Result<Option<serde_json::Value>> {
+        self.send_optional(self.get("/get-chat").query(&[("chat_id", chat_id)]))
+            .await
+    }
+
+    /// Returns the id the chat was saved under, a new one if `chat_id` is
+    /// taken by a chat shared by someone else
+    pub async fn save_chat(&self, chat_id: &str, chat: &serde_json::Value) -> Result<String> {
+        #[derive(serde::Deserialize)]
+        struct Saved {
+            chat_id: String,
+        }
+        let saved: Saved = self
+            .send(self.post("/save-chat").json(&serde_json::json!({
+                "chat_id": chat_id,
+                "chat": chat,
+            })))
+            .await?;
+        Ok(saved.chat_id)
+    }
+}
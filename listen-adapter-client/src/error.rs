@@ -0,0 +1,33 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("[ListenAdapter] HTTP request error: {0}")]
+    Request(#[from] reqwest::Error),
+
+    /// Non-success status, with the `error` of the body if there was one
+    #[error("[ListenAdapter] {status}: {message}")]
+    Api { status: u16, message: String },
+
+    #[error("[ListenAdapter] Failed to parse response: {error} - Response: {body}")]
+    Decode {
+        error: serde_json::Error,
+        body: String,
+    },
+
+    /// The response decoded but breaks a guarantee of the API
+    #[error("[ListenAdapter] Invalid response: {0}")]
+    Invalid(String),
+}
+
+impl ClientError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Api { status, .. } => Some(*status),
+            ClientError::Request(e) => e.status().map(|s| s.as_u16()),
+            ClientError::Decode { .. } | ClientError::Invalid(_) => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
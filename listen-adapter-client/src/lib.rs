@@ -0,0 +1,12 @@
+//! Typed client for the listen-adapter API.
+//!
+//! The types follow the OpenAPI document generated by listen-adapter, a
+//! copy of it is kept next to this crate in `openapi.json`.
+
+pub mod client;
+pub mod error;
+pub mod types;
+
+pub use client::{ListenAdapterClient, DEFAULT_BASE_URL};
+pub use error::{ClientError, Result};
+pub use types::*;
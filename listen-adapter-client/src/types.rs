@@ -0,0 +1,404 @@
+//! Request and response types of the listen-adapter API, kept in line with
+//! the schemas of `openapi.json` by `tests/openapi.rs`.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceUpdate {
+    pub name: String,
+    pub pubkey: String,
+    pub price: f64,
+    pub market_cap: f64,
+    pub timestamp: u64,
+    pub slot: u64,
+    /// denoted in USD
+    pub swap_amount: f64,
+    pub owner: String,
+    pub signature: String,
+    pub multi_hop: bool,
+    pub is_buy: bool,
+    pub is_pump: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TopToken {
+    pub name: String,
+    pub pubkey: String,
+    pub price: f64,
+    pub market_cap: f64,
+    pub volume_24h: f64,
+    pub price_change_24h: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Candlestick {
+    pub timestamp: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenPrice {
+    pub price: f64,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MplTokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub ipfs_metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SplTokenMetadata {
+    pub mint_authority: Option<String>,
+    pub supply: u64,
+    pub decimals: u8,
+    pub is_initialized: bool,
+    pub freeze_authority: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub mint: String,
+    pub mpl: MplTokenMetadata,
+    pub spl: SplTokenMetadata,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    #[default]
+    Mint,
+    Symbol,
+    Name,
+    MintPrefix,
+    SymbolPrefix,
+    NamePrefix,
+    NameContains,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub mint: String,
+    pub name: String,
+    pub symbol: String,
+    pub match_kind: MatchKind,
+    pub volume_24h: f64,
+    pub liquidity_usd: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendingMode {
+    #[default]
+    VolumeAcceleration,
+    UniqueTraders,
+    BuyPressure,
+    NewTokens,
+    PriceChange,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrendingWindow {
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+    #[default]
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "6h")]
+    SixHours,
+    #[serde(rename = "24h")]
+    OneDay,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrendingToken {
+    pub name: String,
+    pub pubkey: String,
+    pub price: f64,
+    pub market_cap: f64,
+    pub is_pump: bool,
+    pub volume: f64,
+    pub previous_volume: f64,
+    pub volume_acceleration: Option<f64>,
+    pub unique_traders: u64,
+    pub buys: u64,
+    pub sells: u64,
+    pub buy_ratio: f64,
+    pub first_seen: u64,
+    pub age_secs: u64,
+    pub price_change_5m: f64,
+    pub price_change_1h: f64,
+    pub price_change_6h: f64,
+    pub price_change_24h: f64,
+    pub price_change_window: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenPnl {
+    pub mint: String,
+    pub name: String,
+    pub trades: usize,
+    pub bought_amount: f64,
+    pub bought_usd: f64,
+    pub sold_amount: f64,
+    pub sold_usd: f64,
+    pub realized_pnl: f64,
+    pub holding: f64,
+    pub cost_basis: f64,
+    pub current_price: Option<f64>,
+    pub unrealized_pnl: f64,
+    pub unmatched_sold_amount: f64,
+    pub unmatched_sold_usd: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WalletPnl {
+    pub wallet: String,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub total_pnl: f64,
+    pub truncated: bool,
+    pub tokens: Vec<TokenPnl>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub wallet: String,
+    pub trades: u64,
+    pub bought_usd: f64,
+    pub sold_usd: f64,
+    pub holding: f64,
+    pub holding_usd: f64,
+    pub total_pnl: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Holder {
+    pub rank: u32,
+    pub owner: String,
+    pub amount: f64,
+    /// share of the supply, 0 to 1
+    pub percentage: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HolderSnapshot {
+    pub mint: String,
+    pub timestamp: u64,
+    pub holder_count: u64,
+    pub holders: Vec<Holder>,
+}
+
+/// Query of `GET /top-tokens`, unset filters use the adapter defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TopTokensParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_volume: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_market_cap: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_market_cap: Option<f64>,
+    /// seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeframe: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only_pumpfun_tokens: Option<bool>,
+}
+
+/// Query of `GET /trending`; the mode-specific filters are rejected with
+/// other modes
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TrendingParams {
+    pub mode: TrendingMode,
+    pub window: TrendingWindow,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_volume: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_market_cap: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_market_cap: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only_pumpfun_tokens: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_previous_volume: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_traders: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_trades: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+}
+
+/// Query of `GET /candlesticks`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CandlestickParams {
+    pub mint: String,
+    /// one of 15s, 30s, 1m, 5m, 15m, 30m, 1h, 4h, 1d
+    pub interval: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// unix seconds, inclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<u64>,
+}
+
+/// Query of `GET /wallet/{address}/trades`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WalletTradesParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mint: Option<String>,
+    /// unix seconds, exclusive, for paging back from the oldest trade seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entity {
+    /// every price update
+    #[default]
+    Prices,
+    /// price updates as swaps, deduplicated on the signature
+    Swaps,
+    /// latest state and volume per token within the time range
+    Tokens,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Pubkey,
+    Name,
+    Price,
+    MarketCap,
+    SwapAmount,
+    Owner,
+    Signature,
+    IsBuy,
+    MultiHop,
+    IsPump,
+    Timestamp,
+    Slot,
+    Volume,
+    Swaps,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FilterValue {
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    List(Vec<FilterValue>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+    pub field: Field,
+    pub op: Op,
+    pub value: FilterValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationFn {
+    Count,
+    Uniq,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    /// value at the earliest timestamp
+    First,
+    /// value at the latest timestamp
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Aggregation {
+    #[serde(rename = "fn")]
+    pub func: AggregationFn,
+    /// all aggregations but `count` need one
+    pub field: Option<Field>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeBucket {
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "4h")]
+    FourHours,
+    #[serde(rename = "1d")]
+    OneDay,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrderBy {
+    /// a selected field, an aggregation alias or `bucket`
+    pub column: String,
+    pub desc: bool,
+}
+
+/// Body of `POST /analytics`; rows come back keyed by the selected fields,
+/// `bucket` and the aggregation aliases such as `sum_swap_amount`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnalyticsQuery {
+    pub entity: Entity,
+    pub filters: Vec<Filter>,
+    /// unix seconds, defaults to `to` minus a day
+    pub from: Option<u64>,
+    /// unix seconds, exclusive, defaults to now
+    pub to: Option<u64>,
+    /// fields returned when there are no aggregations, all by default
+    pub select: Vec<Field>,
+    pub group_by: Vec<Field>,
+    pub time_bucket: Option<TimeBucket>,
+    pub aggregations: Vec<Aggregation>,
+    pub order_by: Option<OrderBy>,
+    pub limit: Option<usize>,
+}
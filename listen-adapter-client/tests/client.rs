@@ -0,0 +1,371 @@
+use listen_adapter_client::*;
+use serde_json::json;
+use wiremock::matchers::{body_json, header, method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const MINT: &str = "9BB6NFEcjBCtnNLFko2FqVQBq8HHM13kCyYcdQbgpump";
+
+async fn setup() -> (MockServer, ListenAdapterClient) {
+    let server = MockServer::start().await;
+    let client = ListenAdapterClient::new(server.uri());
+    (server, client)
+}
+
+fn price_update() -> serde_json::Value {
+    json!({
+        "name": "TOKEN",
+        "pubkey": MINT,
+        "price": 0.5,
+        "market_cap": 500000.0,
+        "timestamp": 1700000000,
+        "slot": 1,
+        "swap_amount": 100.0,
+        "owner": "owner",
+        "signature": "signature",
+        "multi_hop": false,
+        "is_buy": true,
+        "is_pump": true
+    })
+}
+
+#[tokio::test]
+async fn test_top_tokens() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/top-tokens"))
+        .and(query_param("limit", "4"))
+        .and(query_param("min_market_cap", "250000.5"))
+        .and(query_param_is_missing("max_market_cap"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "name": "TOKEN",
+            "pubkey": MINT,
+            "price": 0.5,
+            "market_cap": 500000.0,
+            "volume_24h": 1000.0,
+            "price_change_24h": -2.5
+        }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let tokens = client
+        .top_tokens(&TopTokensParams {
+            limit: Some(4),
+            min_market_cap: Some(250000.5),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].pubkey, MINT);
+    assert_eq!(tokens[0].price_change_24h, -2.5);
+}
+
+#[tokio::test]
+async fn test_candlesticks() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/candlesticks"))
+        .and(query_param("mint", MINT))
+        .and(query_param("interval", "5m"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"timestamp": 1700000000, "open": 1.0, "high": 2.0, "low": 0.5, "close": 1.5, "volume": 10.0},
+            {"timestamp": 1700000300, "open": 1.5, "high": 1.5, "low": 1.5, "close": 1.5, "volume": 0.0}
+        ])))
+        .mount(&server)
+        .await;
+
+    let candlesticks = client
+        .candlesticks(&CandlestickParams {
+            mint: MINT.to_string(),
+            interval: "5m".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(candlesticks.len(), 2);
+    assert_eq!(candlesticks[0].close, 1.5);
+    assert_eq!(candlesticks[1].timestamp, 1700000300);
+}
+
+#[tokio::test]
+async fn test_candlesticks_newest_first_fails() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/candlesticks"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"timestamp": 1700000300, "open": 1.5, "high": 1.5, "low": 1.5, "close": 1.5, "volume": 0.0},
+            {"timestamp": 1700000000, "open": 1.0, "high": 2.0, "low": 0.5, "close": 1.5, "volume": 10.0}
+        ])))
+        .mount(&server)
+        .await;
+
+    let err = client
+        .candlesticks(&CandlestickParams {
+            mint: MINT.to_string(),
+            interval: "5m".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::Invalid(_)));
+}
+
+#[tokio::test]
+async fn test_price() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/price"))
+        .and(query_param("mint", MINT))
+        .respond_with(ResponseTemplate::new(200).set_body_json(price_update()))
+        .mount(&server)
+        .await;
+
+    let price = client.price(MINT).await.unwrap();
+    assert_eq!(price.price, 0.5);
+    assert!(price.is_buy);
+}
+
+#[tokio::test]
+async fn test_open_price_null() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/24h-open"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(null)))
+        .mount(&server)
+        .await;
+
+    assert_eq!(client.open_price_24h(MINT).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_metadata_not_found() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/metadata"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "error": "Metadata not found",
+            "mint": MINT
+        })))
+        .mount(&server)
+        .await;
+
+    assert_eq!(client.metadata(MINT).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_metadata() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/metadata"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "mint": MINT,
+            "mpl": {"name": "Token", "symbol": "TKN", "uri": "ipfs://x", "ipfs_metadata": null},
+            "spl": {
+                "mint_authority": null,
+                "supply": 1000000000,
+                "decimals": 6,
+                "is_initialized": true,
+                "freeze_authority": null
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    let metadata = client.metadata(MINT).await.unwrap().unwrap();
+    assert_eq!(metadata.mpl.symbol, "TKN");
+    assert_eq!(metadata.spl.decimals, 6);
+}
+
+#[tokio::test]
+async fn test_token_holders() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/token/{}/holders", MINT)))
+        .and(query_param("limit", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "mint": MINT,
+            "timestamp": 1700000000,
+            "holder_count": 1200,
+            "holders": [
+                {"rank": 1, "owner": "a", "amount": 500.0, "percentage": 0.5},
+                {"rank": 2, "owner": "b", "amount": 100.0, "percentage": 0.1}
+            ]
+        })))
+        .mount(&server)
+        .await;
+
+    let snapshot = client
+        .token_holders(MINT, None, Some(2))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot.holder_count, 1200);
+    assert_eq!(snapshot.holders[1].owner, "b");
+}
+
+#[tokio::test]
+async fn test_wallet_trades() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/wallet/owner/trades"))
+        .and(query_param("before", "1700000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([price_update()])))
+        .mount(&server)
+        .await;
+
+    let trades = client
+        .wallet_trades(
+            "owner",
+            &WalletTradesParams {
+                before: Some(1700000001),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(trades[0].signature, "signature");
+}
+
+#[tokio::test]
+async fn test_trending() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/trending"))
+        .and(query_param("mode", "price_change"))
+        .and(query_param("window", "6h"))
+        .and(query_param("direction", "down"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let tokens = client
+        .trending(&TrendingParams {
+            mode: TrendingMode::PriceChange,
+            window: TrendingWindow::SixHours,
+            direction: Some(Direction::Down),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(tokens.is_empty());
+}
+
+#[tokio::test]
+async fn test_api_key() {
+    let server = MockServer::start().await;
+    let client = ListenAdapterClient::new(format!("{}/", server.uri())).with_api_key("lsn_key");
+    Mock::given(method("GET"))
+        .and(path("/search"))
+        .and(query_param("q", "bonk"))
+        .and(header("authorization", "Bearer lsn_key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.search("bonk", None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_save_chat() {
+    let (server, client) = setup().await;
+    let chat = json!({"messages": []});
+    Mock::given(method("POST"))
+        .and(path("/save-chat"))
+        .and(body_json(json!({"chat_id": "chat", "chat": chat})))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "message": "Chat saved",
+            "chat_id": "copy"
+        })))
+        .mount(&server)
+        .await;
+
+    // taken ids are saved under a new one
+    let chat_id = client.save_chat("chat", &chat).await.unwrap();
+    assert_eq!(chat_id, "copy");
+}
+
+#[tokio::test]
+async fn test_analytics() {
+    let (server, client) = setup().await;
+    Mock::given(method("POST"))
+        .and(path("/analytics"))
+        .and(body_json(json!({
+            "entity": "swaps",
+            "filters": [{"field": "pubkey", "op": "in", "value": [MINT]}],
+            "from": null,
+            "to": null,
+            "select": [],
+            "group_by": ["pubkey"],
+            "time_bucket": "1h",
+            "aggregations": [{"fn": "sum", "field": "swap_amount"}],
+            "order_by": {"column": "bucket", "desc": true},
+            "limit": 10
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"pubkey": MINT, "bucket": 1700000000, "sum_swap_amount": 1500.5}
+        ])))
+        .mount(&server)
+        .await;
+
+    let rows = client
+        .analytics(&AnalyticsQuery {
+            entity: Entity::Swaps,
+            filters: vec![Filter {
+                field: Field::Pubkey,
+                op: Op::In,
+                value: FilterValue::List(vec![FilterValue::Str(MINT.to_string())]),
+            }],
+            group_by: vec![Field::Pubkey],
+            time_bucket: Some(TimeBucket::OneHour),
+            aggregations: vec![Aggregation {
+                func: AggregationFn::Sum,
+                field: Some(Field::SwapAmount),
+            }],
+            order_by: Some(OrderBy {
+                column: "bucket".to_string(),
+                desc: true,
+            }),
+            limit: Some(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["sum_swap_amount"], 1500.5);
+}
+
+#[tokio::test]
+async fn test_renamed_field_fails_to_decode() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/24h-open"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"open": 1.0, "timestamp": 1})),
+        )
+        .mount(&server)
+        .await;
+
+    let err = client.open_price_24h(MINT).await.unwrap_err();
+    assert!(matches!(err, ClientError::Decode { .. }));
+}
+
+#[tokio::test]
+async fn test_server_error() {
+    let (server, client) = setup().await;
+    Mock::given(method("GET"))
+        .and(path("/price"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("No price found"))
+        .mount(&server)
+        .await;
+
+    match client.price(MINT).await.unwrap_err() {
+        ClientError::Api { status, message } => {
+            assert_eq!(status, 500);
+            assert_eq!(message, "No price found");
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
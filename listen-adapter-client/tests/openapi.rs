@@ -0,0 +1,298 @@
+//! Checks the client types against the schemas of the checked-in OpenAPI
+//! document, so renaming a field on either side fails here instead of in
+//! the agents.
+
+use std::collections::BTreeSet;
+
+use listen_adapter_client::*;
+use serde::Serialize;
+use serde_json::Value;
+
+fn spec() -> Value {
+    let spec = include_str!("../openapi.json");
+    serde_json::from_str(spec).unwrap()
+}
+
+fn keys(value: &Value) -> BTreeSet<String> {
+    value
+        .as_object()
+        .map(|o| o.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Fields of the type are the properties of the schema, and the non-Option
+/// ones the required ones
+fn assert_matches_schema<T: Default + Serialize>(spec: &Value, name: &str) {
+    let schema = &spec["components"]["schemas"][name];
+    assert!(schema.is_object(), "no {} schema", name);
+
+    let value = serde_json::to_value(T::default()).unwrap();
+    let fields = keys(&value);
+    assert_eq!(fields, keys(&schema["properties"]), "fields of {}", name);
+
+    let required: BTreeSet<String> = schema["required"]
+        .as_array()
+        .map(|r| r.iter().map(|v| v.as_str().unwrap().to_string()).collect())
+        .unwrap_or_default();
+    let non_null: BTreeSet<String> = value
+        .as_object()
+        .unwrap()
+        .iter()
+        .filter(|(_, v)| !v.is_null())
+        .map(|(k, _)| k.clone())
+        .collect();
+    assert_eq!(required, non_null, "required fields of {}", name);
+}
+
+/// Fields of a request body are the properties of the schema and include
+/// the required ones, the adapter defaults the others
+fn assert_request_matches_schema<T: Serialize>(spec: &Value, name: &str, body: &T) {
+    let schema = &spec["components"]["schemas"][name];
+    assert!(schema.is_object(), "no {} schema", name);
+
+    let fields = keys(&serde_json::to_value(body).unwrap());
+    assert_eq!(fields, keys(&schema["properties"]), "fields of {}", name);
+    let required: BTreeSet<String> = schema["required"]
+        .as_array()
+        .map(|r| r.iter().map(|v| v.as_str().unwrap().to_string()).collect())
+        .unwrap_or_default();
+    assert!(required.is_subset(&fields), "required fields of {}", name);
+}
+
+/// Every set query parameter is one the route accepts
+fn assert_params<T: Serialize>(spec: &Value, path: &str, params: &T) {
+    let operation = &spec["paths"][path]["get"];
+    assert!(operation.is_object(), "no GET {}", path);
+    let accepted: BTreeSet<String> = operation["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|p| p["in"] == "query")
+        .map(|p| p["name"].as_str().unwrap().to_string())
+        .collect();
+    let sent = keys(&serde_json::to_value(params).unwrap());
+    assert!(
+        sent.is_subset(&accepted),
+        "{} does not accept {:?}",
+        path,
+        sent.difference(&accepted).collect::<Vec<_>>()
+    );
+}
+
+fn enum_values(spec: &Value, name: &str) -> BTreeSet<String> {
+    spec["components"]["schemas"][name]["enum"]
+        .as_array()
+        .unwrap_or_else(|| panic!("no {} enum", name))
+        .iter()
+        .map(|v| v.as_str().unwrap().to_string())
+        .collect()
+}
+
+fn serialized<T: Serialize>(values: &[T]) -> BTreeSet<String> {
+    values
+        .iter()
+        .map(|v| {
+            serde_json::to_value(v)
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn test_response_types_match_spec() {
+    let spec = spec();
+    assert_matches_schema::<PriceUpdate>(&spec, "PriceUpdate");
+    assert_matches_schema::<TopToken>(&spec, "TopToken");
+    assert_matches_schema::<Candlestick>(&spec, "Candlestick");
+    assert_matches_schema::<OpenPrice>(&spec, "OpenPrice");
+    assert_matches_schema::<MplTokenMetadata>(&spec, "MplTokenMetadata");
+    assert_matches_schema::<SplTokenMetadata>(&spec, "SplTokenMetadata");
+    assert_matches_schema::<TokenMetadata>(&spec, "TokenMetadata");
+    assert_matches_schema::<SearchResult>(&spec, "SearchResult");
+    assert_matches_schema::<TrendingToken>(&spec, "TrendingToken");
+    assert_matches_schema::<TokenPnl>(&spec, "TokenPnl");
+    assert_matches_schema::<WalletPnl>(&spec, "WalletPnl");
+    assert_matches_schema::<LeaderboardEntry>(&spec, "LeaderboardEntry");
+    assert_matches_schema::<Holder>(&spec, "Holder");
+    assert_matches_schema::<HolderSnapshot>(&spec, "HolderSnapshot");
+}
+
+#[test]
+fn test_request_types_match_spec() {
+    let spec = spec();
+    assert_request_matches_schema(&spec, "AnalyticsQuery", &AnalyticsQuery::default());
+    assert_request_matches_schema(
+        &spec,
+        "Filter",
+        &Filter {
+            field: Field::Price,
+            op: Op::Gt,
+            value: FilterValue::Num(1.0),
+        },
+    );
+    assert_request_matches_schema(
+        &spec,
+        "Aggregation",
+        &Aggregation {
+            func: AggregationFn::Count,
+            field: None,
+        },
+    );
+    assert_request_matches_schema(&spec, "OrderBy", &OrderBy::default());
+    let analytics = &spec["paths"]["/analytics"]["post"]["requestBody"];
+    assert_eq!(
+        analytics["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/AnalyticsQuery"
+    );
+}
+
+#[test]
+fn test_enums_match_spec() {
+    let spec = spec();
+    use MatchKind::*;
+    assert_eq!(
+        serialized(&[
+            Mint,
+            Symbol,
+            Name,
+            MintPrefix,
+            SymbolPrefix,
+            NamePrefix,
+            NameContains
+        ]),
+        enum_values(&spec, "MatchKind")
+    );
+    use TrendingMode::*;
+    assert_eq!(
+        serialized(&[
+            VolumeAcceleration,
+            UniqueTraders,
+            BuyPressure,
+            NewTokens,
+            PriceChange
+        ]),
+        enum_values(&spec, "TrendingMode")
+    );
+    use TrendingWindow::*;
+    assert_eq!(
+        serialized(&[FiveMinutes, FifteenMinutes, OneHour, SixHours, OneDay]),
+        enum_values(&spec, "TrendingWindow")
+    );
+    assert_eq!(
+        serialized(&[Direction::Up, Direction::Down]),
+        enum_values(&spec, "Direction")
+    );
+}
+
+#[test]
+fn test_analytics_enums_match_spec() {
+    let spec = spec();
+    assert_eq!(
+        serialized(&[Entity::Prices, Entity::Swaps, Entity::Tokens]),
+        enum_values(&spec, "Entity")
+    );
+    assert_eq!(
+        serialized(&[
+            Field::Pubkey,
+            Field::Name,
+            Field::Price,
+            Field::MarketCap,
+            Field::SwapAmount,
+            Field::Owner,
+            Field::Signature,
+            Field::IsBuy,
+            Field::MultiHop,
+            Field::IsPump,
+            Field::Timestamp,
+            Field::Slot,
+            Field::Volume,
+            Field::Swaps
+        ]),
+        enum_values(&spec, "Field")
+    );
+    assert_eq!(
+        serialized(&[Op::Eq, Op::Ne, Op::Gt, Op::Gte, Op::Lt, Op::Lte, Op::In]),
+        enum_values(&spec, "Op")
+    );
+    assert_eq!(
+        serialized(&[
+            AggregationFn::Count,
+            AggregationFn::Uniq,
+            AggregationFn::Sum,
+            AggregationFn::Avg,
+            AggregationFn::Min,
+            AggregationFn::Max,
+            AggregationFn::First,
+            AggregationFn::Last
+        ]),
+        enum_values(&spec, "AggregationFn")
+    );
+    assert_eq!(
+        serialized(&[
+            TimeBucket::OneMinute,
+            TimeBucket::FiveMinutes,
+            TimeBucket::FifteenMinutes,
+            TimeBucket::OneHour,
+            TimeBucket::FourHours,
+            TimeBucket::OneDay
+        ]),
+        enum_values(&spec, "TimeBucket")
+    );
+}
+
+#[test]
+fn test_params_match_spec() {
+    let spec = spec();
+    assert_params(
+        &spec,
+        "/top-tokens",
+        &TopTokensParams {
+            limit: Some(1),
+            min_volume: Some(1.0),
+            min_market_cap: Some(1.0),
+            max_market_cap: Some(1.0),
+            timeframe: Some(1),
+            only_pumpfun_tokens: Some(true),
+        },
+    );
+    assert_params(
+        &spec,
+        "/trending",
+        &TrendingParams {
+            limit: Some(1),
+            min_volume: Some(1.0),
+            min_market_cap: Some(1.0),
+            max_market_cap: Some(1.0),
+            only_pumpfun_tokens: Some(true),
+            min_previous_volume: Some(1.0),
+            min_traders: Some(1),
+            min_trades: Some(1),
+            max_age_secs: Some(1),
+            direction: Some(Direction::Down),
+            ..Default::default()
+        },
+    );
+    assert_params(
+        &spec,
+        "/candlesticks",
+        &CandlestickParams {
+            mint: "mint".to_string(),
+            interval: "1m".to_string(),
+            limit: Some(1),
+            from: Some(1),
+            to: Some(1),
+        },
+    );
+    assert_params(
+        &spec,
+        "/wallet/{address}/trades",
+        &WalletTradesParams {
+            mint: Some("mint".to_string()),
+            before: Some(1),
+            limit: Some(1),
+        },
+    );
+}
@@ -1,24 +1,36 @@
 use actix_cors::Cors;
-use actix_web::{middleware::Logger, web, App, HttpServer};
+use actix_web::{
+    middleware::{from_fn, Logger},
+    web, App, HttpServer,
+};
 use dotenv::dotenv;
 use listen_tracing::setup_tracing;
+use std::sync::Arc;
 use tracing::info;
 
 use listen_adapter::{
+    auth::{authenticate, Auth, AuthConfig},
+    cache::ResponseCache,
     db::make_db,
+    metrics::{init_metrics, metrics_handler},
+    openapi::openapi_handler,
     redis_client::make_redis_client,
     redis_subscriber::create_redis_subscriber,
     routes::{
-        get_24h_open_price, get_candlesticks, get_chat, get_metadata, get_price, health_check,
-        query_db, save_chat, top_tokens, version, ws_route,
+        analytics_query, get_24h_open_price, get_candlesticks, get_chat, get_metadata, get_price,
+        get_token_holders, get_token_leaderboard, get_wallet_pnl, get_wallet_trades, health_check,
+        save_chat, search, top_tokens, trending, version, ws_route,
     },
+    search::SearchIndex,
     state::AppState,
+    websocket::top_tokens::TopTokensFeed,
 };
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
     setup_tracing();
+    init_metrics();
 
     let redis_url = std::env::var("REDIS_URL").expect("REDIS_URL must be set");
     let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
@@ -33,27 +45,59 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to create Redis client");
 
+    let top_tokens_feed = Arc::new(TopTokensFeed::new());
+    top_tokens_feed.start(clickhouse_db.clone());
+
+    let search_index = Arc::new(SearchIndex::new());
+    search_index.start(redis_client.clone(), clickhouse_db.clone());
+
+    let auth_config = AuthConfig::from_env().expect("Invalid auth configuration");
+    let auth = Arc::new(Auth::new(auth_config, redis_client.clone()));
+
+    let response_cache = Arc::new(ResponseCache::new(Some(redis_client.clone())));
+    response_cache.start_invalidation(
+        redis_subscriber.subscribe(),
+        redis_subscriber.subscribe_rollups(),
+    );
+
     let app_state = AppState {
         redis_subscriber,
         redis_client,
         clickhouse_db,
+        top_tokens_feed,
+        search_index,
+        auth,
+        response_cache,
     };
     let app_data = web::Data::new(app_state);
 
     let app_factory = move || {
         App::new()
+            .wrap(from_fn(authenticate))
             .wrap(Logger::default())
             .wrap(Cors::permissive())
             .app_data(app_data.clone())
             .route("/ws", web::get().to(ws_route))
             .route("/healthz", web::get().to(health_check))
+            .route("/metrics", web::get().to(metrics_handler))
+            .route("/openapi.json", web::get().to(openapi_handler))
             .route("/top-tokens", web::get().to(top_tokens))
+            .route("/trending", web::get().to(trending))
+            .route("/search", web::get().to(search))
+            .route("/wallet/{address}/trades", web::get().to(get_wallet_trades))
+            .route("/wallet/{address}/pnl", web::get().to(get_wallet_pnl))
+            .route(
+                "/token/{mint}/leaderboard",
+                web::get().to(get_token_leaderboard),
+            )
+            .route("/token/{mint}/holders", web::get().to(get_token_holders))
             .route("/candlesticks", web::get().to(get_candlesticks))
             .route("/metadata", web::get().to(get_metadata))
-            .route("/query", web::post().to(query_db))
+            .route("/analytics", web::post().to(analytics_query))
             .route("/price", web::get().to(get_price))
             .route("/24h-open", web::get().to(get_24h_open_price))
-            // get and save chat routes are unauthenticated, those are for "shared" chats
+            // shared chats, anyone can read and share them; only the API key
+            // that shared a chat can update it
             .route("/get-chat", web::get().to(get_chat))
             .route("/save-chat", web::post().to(save_chat))
             .route("/version", web::get().to(version))
@@ -0,0 +1,70 @@
+//! Manages the API keys of the adapter
+//!
+//! api-keys create <name> <scopes> [capacity] [refill_per_sec]
+//! api-keys disable <key>
+
+use dotenv::dotenv;
+
+use listen_adapter::{
+    auth::{generate_key, hash_key, ApiKey, RateLimit, Scope},
+    redis_client::make_redis_client,
+};
+
+const USAGE: &str = "usage:
+  api-keys create <name> <scopes> [capacity] [refill_per_sec]
+  api-keys disable <key>
+
+scopes are comma separated: market_data, chats, analytics, metrics";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let redis_client = make_redis_client().await?;
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["create", name, scopes, rest @ ..] => {
+            let scopes = Scope::parse_list(scopes).map_err(anyhow::Error::msg)?;
+            let mut rate_limit = RateLimit::default();
+            if let Some(capacity) = rest.first() {
+                rate_limit.capacity = capacity.parse()?;
+            }
+            if let Some(refill_per_sec) = rest.get(1) {
+                rate_limit.refill_per_sec = refill_per_sec.parse()?;
+            }
+
+            let key = generate_key();
+            let api_key = ApiKey {
+                name: name.to_string(),
+                scopes,
+                rate_limit,
+                disabled: false,
+                created_at: chrono::Utc::now().timestamp() as u64,
+            };
+            redis_client.save_api_key(&hash_key(&key), &api_key).await?;
+            // only the hash is stored, the key cannot be shown again
+            println!("{}", key);
+        }
+        ["disable", key] => {
+            let hash = hash_key(key);
+            let Some(mut api_key) = redis_client.get_api_key(&hash).await? else {
+                anyhow::bail!("Unknown API key");
+            };
+            api_key.disabled = true;
+            redis_client.save_api_key(&hash, &api_key).await?;
+            println!("Disabled {}", api_key.name);
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,521 @@
+//! API keys, scopes and rate limits.
+//!
+//! Keys are stored in Redis under the sha256 of the key, so a leaked
+//! database does not leak usable keys. Requests without a key are served
+//! with the anonymous scopes and rate limited per IP, which keeps the
+//! public app working while partners get their own keys and limits.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web, Error, HttpMessage, HttpResponse,
+};
+use anyhow::Result;
+use lru::LruCache;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::redis_client::RedisClient;
+use crate::state::AppState;
+
+/// How long known keys are cached in-process; revoked keys stop working
+/// within this long
+pub const KEY_CACHE_TTL: Duration = Duration::from_secs(60);
+/// Least recently used keys are evicted past this many
+pub const KEY_CACHE_CAPACITY: usize = 10_000;
+pub const KEY_PREFIX: &str = "lsn_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// prices, candles, rankings, search, wallets and the websocket
+    MarketData,
+    /// reading and saving shared chats
+    Chats,
+    /// the analytics query API
+    Analytics,
+    /// Prometheus metrics, which name the keys
+    Metrics,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::MarketData => "market_data",
+            Scope::Chats => "chats",
+            Scope::Analytics => "analytics",
+            Scope::Metrics => "metrics",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "market_data" => Some(Scope::MarketData),
+            "chats" => Some(Scope::Chats),
+            "analytics" => Some(Scope::Analytics),
+            "metrics" => Some(Scope::Metrics),
+            _ => None,
+        }
+    }
+
+    /// Comma separated list, as used in the environment and the key CLI
+    pub fn parse_list(s: &str) -> std::result::Result<Vec<Self>, String> {
+        s.split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| Scope::parse(s).ok_or_else(|| format!("Unknown scope: {}", s.trim())))
+            .collect()
+    }
+}
+
+/// Token bucket: `capacity` requests at once, refilled continuously
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub refill_per_sec: f64,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            capacity: 120,
+            refill_per_sec: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ApiKey {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    #[serde(default)]
+    pub disabled: bool,
+    pub created_at: u64,
+}
+
+/// Who made a request, stored in the request extensions for handlers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Principal {
+    Key { hash: String, key: ApiKey },
+    Anonymous { ip: String },
+}
+
+impl Principal {
+    /// Metrics label, key names are chosen by us so the set stays small
+    pub fn label(&self) -> &str {
+        match self {
+            Principal::Key { key, .. } => &key.name,
+            Principal::Anonymous { .. } => "anonymous",
+        }
+    }
+
+    pub fn has_scope(&self, scope: Scope, config: &AuthConfig) -> bool {
+        match self {
+            Principal::Key { key, .. } => key.scopes.contains(&scope),
+            Principal::Anonymous { .. } => config.anonymous_scopes.contains(&scope),
+        }
+    }
+
+    fn bucket(&self) -> String {
+        match self {
+            Principal::Key { hash, .. } => format!("key:{}", hash),
+            Principal::Anonymous { ip } => format!("ip:{}", ip),
+        }
+    }
+
+    fn rate_limit(&self, config: &AuthConfig) -> RateLimit {
+        match self {
+            Principal::Key { key, .. } => key.rate_limit,
+            Principal::Anonymous { .. } => config.anonymous_rate_limit,
+        }
+    }
+}
+
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+pub fn generate_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", KEY_PREFIX, hex)
+}
+
+/// Scope a route needs, None for the public ones
+pub fn required_scope(path: &str) -> Option<Scope> {
+    match path {
+        "/healthz" | "/version" | "/openapi.json" => None,
+        "/analytics" => Some(Scope::Analytics),
+        "/metrics" => Some(Scope::Metrics),
+        "/save-chat" | "/get-chat" => Some(Scope::Chats),
+        _ => Some(Scope::MarketData),
+    }
+}
+
+/// Key from the `Authorization: Bearer` or `X-API-Key` header, or the
+/// `api_key` query parameter for browser websockets, which cannot set
+/// headers
+fn extract_key(req: &ServiceRequest) -> Option<String> {
+    let headers = req.headers();
+    if let Some(key) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+    {
+        return Some(key.trim().to_string());
+    }
+    if let Some(key) = headers.get("x-api-key").and_then(|h| h.to_str().ok()) {
+        return Some(key.trim().to_string());
+    }
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| query.get("api_key").cloned())
+}
+
+/// IP the request came from. `X-Forwarded-For` is only believed when the
+/// peer is one of our proxies, and then only up to the first hop that is
+/// not, so clients cannot pick the IP they are rate limited under
+fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted: &[IpAddr],
+) -> Option<IpAddr> {
+    let mut ip = peer?;
+    if !trusted.contains(&ip) {
+        return Some(ip);
+    }
+    let hops = forwarded_for.unwrap_or_default().rsplit(',');
+    for hop in hops {
+        let Ok(hop) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+        ip = hop;
+        if !trusted.contains(&ip) {
+            break;
+        }
+    }
+    Some(ip)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthConfig {
+    pub anonymous_scopes: Vec<Scope>,
+    pub anonymous_rate_limit: RateLimit,
+    /// Proxies in front of the adapter whose `X-Forwarded-For` is trusted
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            anonymous_scopes: vec![Scope::MarketData, Scope::Chats],
+            anonymous_rate_limit: RateLimit {
+                capacity: 60,
+                refill_per_sec: 1.0,
+            },
+            trusted_proxies: vec![],
+        }
+    }
+}
+
+impl AuthConfig {
+    /// `ADAPTER_ANONYMOUS_SCOPES` (empty requires a key for every route),
+    /// `ADAPTER_ANONYMOUS_CAPACITY`, `ADAPTER_ANONYMOUS_REFILL_PER_SEC` and
+    /// `ADAPTER_TRUSTED_PROXIES` (comma separated IPs)
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+        if let Ok(scopes) = std::env::var("ADAPTER_ANONYMOUS_SCOPES") {
+            config.anonymous_scopes = Scope::parse_list(&scopes).map_err(anyhow::Error::msg)?;
+        }
+        if let Ok(capacity) = std::env::var("ADAPTER_ANONYMOUS_CAPACITY") {
+            config.anonymous_rate_limit.capacity = capacity.parse()?;
+        }
+        if let Ok(refill) = std::env::var("ADAPTER_ANONYMOUS_REFILL_PER_SEC") {
+            config.anonymous_rate_limit.refill_per_sec = refill.parse()?;
+        }
+        if let Ok(proxies) = std::env::var("ADAPTER_TRUSTED_PROXIES") {
+            config.trusted_proxies = proxies
+                .split(',')
+                .filter(|ip| !ip.trim().is_empty())
+                .map(|ip| ip.trim().parse())
+                .collect::<std::result::Result<_, _>>()?;
+        }
+        Ok(config)
+    }
+}
+
+pub struct Auth {
+    config: AuthConfig,
+    redis_client: Arc<RedisClient>,
+    cache: Mutex<LruCache<String, (Instant, ApiKey)>>,
+}
+
+impl Auth {
+    pub fn new(config: AuthConfig, redis_client: Arc<RedisClient>) -> Self {
+        Self {
+            config,
+            redis_client,
+            cache: Mutex::new(LruCache::new(
+                NonZeroUsize::new(KEY_CACHE_CAPACITY).expect("capacity is not zero"),
+            )),
+        }
+    }
+
+    /// Unknown keys are not cached, so guessing keys cannot push the
+    /// known ones out
+    async fn lookup(&self, hash: &str) -> Result<Option<ApiKey>> {
+        if let Some((at, key)) = self.cache.lock().expect("key cache poisoned").get(hash) {
+            if at.elapsed() < KEY_CACHE_TTL {
+                return Ok(Some(key.clone()));
+            }
+        }
+        let key = self.redis_client.get_api_key(hash).await?;
+        let mut cache = self.cache.lock().expect("key cache poisoned");
+        match &key {
+            Some(key) => {
+                cache.put(hash.to_string(), (Instant::now(), key.clone()));
+            }
+            None => {
+                cache.pop(hash);
+            }
+        }
+        Ok(key)
+    }
+
+    /// Authenticates the request, checks the scope of the route and takes
+    /// a token from the caller's bucket
+    async fn check(&self, req: &ServiceRequest) -> std::result::Result<Principal, HttpResponse> {
+        let principal = match extract_key(req) {
+            Some(key) => {
+                let hash = hash_key(&key);
+                match self.lookup(&hash).await {
+                    Ok(Some(key)) if !key.disabled => Principal::Key { hash, key },
+                    Ok(_) => {
+                        metrics::counter!("adapter_auth_failures", 1, "reason" => "invalid_key");
+                        return Err(HttpResponse::Unauthorized().json(json!({
+                            "error": "Invalid API key"
+                        })));
+                    }
+                    Err(e) => {
+                        error!("Failed to look up API key: {}", e);
+                        return Err(HttpResponse::ServiceUnavailable().json(json!({
+                            "error": "Could not verify API key"
+                        })));
+                    }
+                }
+            }
+            None => Principal::Anonymous {
+                ip: client_ip(
+                    req.peer_addr().map(|addr| addr.ip()),
+                    req.headers()
+                        .get("x-forwarded-for")
+                        .and_then(|h| h.to_str().ok()),
+                    &self.config.trusted_proxies,
+                )
+                .map_or_else(|| "unknown".to_string(), |ip| ip.to_string()),
+            },
+        };
+
+        if let Some(scope) = required_scope(req.path()) {
+            if !principal.has_scope(scope, &self.config) {
+                return Err(match principal {
+                    Principal::Anonymous { .. } => {
+                        metrics::counter!("adapter_auth_failures", 1, "reason" => "missing_key");
+                        HttpResponse::Unauthorized().json(json!({
+                            "error": "An API key is required",
+                            "scope": scope.as_str()
+                        }))
+                    }
+                    Principal::Key { .. } => {
+                        metrics::counter!("adapter_auth_failures", 1, "reason" => "missing_scope");
+                        HttpResponse::Forbidden().json(json!({
+                            "error": "API key is missing a scope",
+                            "scope": scope.as_str()
+                        }))
+                    }
+                });
+            }
+        }
+
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        match self
+            .redis_client
+            .take_rate_limit_token(
+                &principal.bucket(),
+                &principal.rate_limit(&self.config),
+                now_ms,
+            )
+            .await
+        {
+            Ok(None) => {}
+            Ok(Some(retry_after)) => {
+                metrics::counter!("adapter_rate_limited", 1, "key" => principal.label().to_string());
+                return Err(HttpResponse::TooManyRequests()
+                    .insert_header((
+                        header::RETRY_AFTER,
+                        retry_after.as_secs_f64().ceil().to_string(),
+                    ))
+                    .json(json!({
+                        "error": "Rate limit exceeded"
+                    })));
+            }
+            // an unavailable limiter should not take the API down with it
+            Err(e) => {
+                error!("Failed to check rate limit: {}", e);
+                metrics::counter!("adapter_rate_limit_errors", 1);
+            }
+        }
+
+        Ok(principal)
+    }
+}
+
+/// Middleware for the whole app, see [`Auth::check`]
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> std::result::Result<ServiceResponse<BoxBody>, Error> {
+    let scope = required_scope(req.path());
+    let Some(scope) = scope else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let Some(state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let (label, res) = match state.auth.check(&req).await {
+        Ok(principal) => {
+            let label = principal.label().to_string();
+            req.extensions_mut().insert(principal);
+            (label, next.call(req).await?.map_into_boxed_body())
+        }
+        Err(response) => ("unauthenticated".to_string(), req.into_response(response)),
+    };
+    metrics::counter!(
+        "adapter_requests",
+        1,
+        "key" => label,
+        "scope" => scope.as_str(),
+        "status" => res.status().as_u16().to_string()
+    );
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_required_scope() {
+        assert_eq!(required_scope("/healthz"), None);
+        assert_eq!(required_scope("/metrics"), Some(Scope::Metrics));
+        assert_eq!(required_scope("/openapi.json"), None);
+        assert_eq!(required_scope("/analytics"), Some(Scope::Analytics));
+        assert_eq!(required_scope("/save-chat"), Some(Scope::Chats));
+        assert_eq!(required_scope("/candlesticks"), Some(Scope::MarketData));
+        assert_eq!(required_scope("/wallet/abc/pnl"), Some(Scope::MarketData));
+    }
+
+    #[test]
+    fn test_extract_key() {
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer lsn_abc"))
+            .to_srv_request();
+        assert_eq!(extract_key(&req), Some("lsn_abc".to_string()));
+
+        let req = TestRequest::default()
+            .insert_header(("X-API-Key", "lsn_def"))
+            .to_srv_request();
+        assert_eq!(extract_key(&req), Some("lsn_def".to_string()));
+
+        let req = TestRequest::with_uri("/ws?api_key=lsn_ghi").to_srv_request();
+        assert_eq!(extract_key(&req), Some("lsn_ghi".to_string()));
+
+        let req = TestRequest::with_uri("/ws").to_srv_request();
+        assert_eq!(extract_key(&req), None);
+    }
+
+    #[test]
+    fn test_scopes() {
+        assert_eq!(
+            Scope::parse_list("market_data, analytics"),
+            Ok(vec![Scope::MarketData, Scope::Analytics])
+        );
+        assert_eq!(Scope::parse_list(""), Ok(vec![]));
+        assert!(Scope::parse_list("market_data,admin").is_err());
+
+        let config = AuthConfig::default();
+        let anonymous = Principal::Anonymous {
+            ip: "127.0.0.1".to_string(),
+        };
+        assert!(anonymous.has_scope(Scope::MarketData, &config));
+        assert!(!anonymous.has_scope(Scope::Analytics, &config));
+
+        let partner = Principal::Key {
+            hash: hash_key("lsn_abc"),
+            key: ApiKey {
+                name: "partner".to_string(),
+                scopes: vec![Scope::Analytics],
+                rate_limit: RateLimit::default(),
+                disabled: false,
+                created_at: 0,
+            },
+        };
+        assert!(partner.has_scope(Scope::Analytics, &config));
+        assert!(!partner.has_scope(Scope::Chats, &config));
+        assert_eq!(partner.label(), "partner");
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        // untrusted peers cannot pick their IP
+        assert_eq!(
+            client_ip(Some(ip("1.2.3.4")), Some("5.6.7.8"), &proxies),
+            Some(ip("1.2.3.4"))
+        );
+        assert_eq!(
+            client_ip(
+                Some(ip("10.0.0.1")),
+                Some("9.9.9.9, 1.2.3.4, 10.0.0.2"),
+                &proxies
+            ),
+            Some(ip("1.2.3.4"))
+        );
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), Some("garbage"), &proxies),
+            Some(ip("10.0.0.1"))
+        );
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), None, &proxies),
+            Some(ip("10.0.0.1"))
+        );
+        assert_eq!(client_ip(None, Some("1.2.3.4"), &proxies), None);
+    }
+
+    #[test]
+    fn test_keys() {
+        let key = generate_key();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(key.len(), KEY_PREFIX.len() + 64);
+        assert_ne!(key, generate_key());
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_eq!(hash_key(&key).len(), 64);
+    }
+}
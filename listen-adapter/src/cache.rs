@@ -0,0 +1,629 @@
+//! Read-through cache for the hot market data endpoints.
+//!
+//! Responses are cached as serialized JSON in-process and in Redis, so the
+//! instances share them. Concurrent misses for the same key are collapsed
+//! into a single load per instance, and across instances through a short
+//! Redis lock. Entries that depend on the latest candle of a mint are
+//! dropped once the indexer announces a candlestick rollup that covers a
+//! newer trade of that mint from the price_updates stream; until then a
+//! reload would read the same candles.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use lru::LruCache;
+use serde::Serialize;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tracing::{error, warn};
+
+use crate::redis_client::RedisClient;
+use crate::redis_subscriber::{CandlestickRollup, TradeUpdate};
+
+/// Most entries kept in-process, the least recently used are evicted
+pub const MAX_LOCAL_ENTRIES: usize = 10_000;
+/// Most mints whose latest trade is remembered, and most waiting for a
+/// rollup; the least recently traded are forgotten
+pub const MAX_TRACKED_MINTS: usize = 100_000;
+/// Entries younger than this are served even if a trade came in, so the
+/// hottest mints are not reloaded on every swap
+pub const MIN_FRESH: Duration = Duration::from_secs(1);
+pub const TOP_TOKENS_TTL: Duration = Duration::from_secs(15);
+/// Candles up to now, also dropped once trades of the mint are rolled up
+pub const LATEST_CANDLESTICKS_TTL: Duration = Duration::from_secs(60);
+/// Candles up to a given end only change with late inserts
+pub const HISTORICAL_CANDLESTICKS_TTL: Duration = Duration::from_secs(3600);
+const OPEN_PRICE_TTL: Duration = Duration::from_secs(300);
+
+/// The open price is fixed once the day has a trade, but is looked up
+/// again at the start of the next UTC day
+pub fn open_price_ttl(now: u64) -> Duration {
+    let until_next_day = 86400 - now % 86400;
+    OPEN_PRICE_TTL.min(Duration::from_secs(until_next_day))
+}
+
+/// How long another instance's load is waited for before loading anyway
+const LOCK_WAIT: Duration = Duration::from_secs(2);
+const LOCK_POLL: Duration = Duration::from_millis(50);
+const LOCK_TTL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheKey {
+    key: String,
+    endpoint: &'static str,
+    /// mint whose trades invalidate the entry
+    invalidated_by: Option<String>,
+}
+
+impl CacheKey {
+    /// Parameters are joined in the order given, callers pass them with
+    /// defaults applied so equivalent requests share an entry
+    pub fn new(endpoint: &'static str, params: &[&dyn std::fmt::Display]) -> Self {
+        let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+        Self {
+            key: format!("{}:{}", endpoint, params.join(":")),
+            endpoint,
+            invalidated_by: None,
+        }
+    }
+
+    pub fn invalidated_by(mut self, mint: &str) -> Self {
+        self.invalidated_by = Some(mint.to_string());
+        self
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    cached_at_ms: u64,
+    expires_at_ms: u64,
+    body: Arc<String>,
+    invalidated_by: Option<String>,
+}
+
+struct Local {
+    entries: LruCache<String, Entry>,
+    /// keys of the entries depending on each mint
+    by_mint: HashMap<String, HashSet<String>>,
+    /// trades seen on the stream that no rollup covers yet, by mint
+    pending_trades: LruCache<String, u64>,
+    /// when the latest rolled up trade of every mint was announced, entries
+    /// cached by other instances are checked against it too
+    last_trades: LruCache<String, u64>,
+    /// latest trade of the mints forgotten from `last_trades`, which are
+    /// assumed to have traded then
+    forgotten_trades_ms: u64,
+    /// a lagging trade stream invalidates everything up to this point
+    invalidated_at_ms: u64,
+}
+
+impl Default for Local {
+    fn default() -> Self {
+        Self::with_capacity(MAX_LOCAL_ENTRIES, MAX_TRACKED_MINTS)
+    }
+}
+
+impl Local {
+    fn with_capacity(entries: usize, mints: usize) -> Self {
+        let capacity = |n| NonZeroUsize::new(n).expect("capacity is not zero");
+        Self {
+            entries: LruCache::new(capacity(entries)),
+            by_mint: HashMap::new(),
+            pending_trades: LruCache::new(capacity(mints)),
+            last_trades: LruCache::new(capacity(mints)),
+            forgotten_trades_ms: 0,
+            invalidated_at_ms: 0,
+        }
+    }
+
+    fn is_fresh(&self, key: &CacheKey, cached_at_ms: u64, now_ms: u64) -> bool {
+        let Some(mint) = &key.invalidated_by else {
+            return true;
+        };
+        if now_ms.saturating_sub(cached_at_ms) < MIN_FRESH.as_millis() as u64 {
+            return true;
+        }
+        let last_trade = self
+            .last_trades
+            .peek(mint)
+            .copied()
+            .unwrap_or(self.forgotten_trades_ms);
+        last_trade.max(self.invalidated_at_ms) <= cached_at_ms
+    }
+
+    fn get(&mut self, key: &CacheKey, now_ms: u64) -> Option<Arc<String>> {
+        let entry = self.entries.peek(&key.key)?;
+        if entry.expires_at_ms <= now_ms || !self.is_fresh(key, entry.cached_at_ms, now_ms) {
+            self.remove(&key.key);
+            return None;
+        }
+        self.entries.get(&key.key).map(|entry| entry.body.clone())
+    }
+
+    fn insert(&mut self, key: &CacheKey, entry: Entry) {
+        if let Some(mint) = &entry.invalidated_by {
+            self.by_mint
+                .entry(mint.clone())
+                .or_default()
+                .insert(key.key.clone());
+        }
+        if let Some((evicted, entry)) = self.entries.push(key.key.clone(), entry) {
+            if evicted != key.key {
+                self.unindex(&evicted, &entry);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.pop(key) {
+            self.unindex(key, &entry);
+        }
+    }
+
+    fn unindex(&mut self, key: &str, entry: &Entry) {
+        let Some(mint) = &entry.invalidated_by else {
+            return;
+        };
+        if let Some(keys) = self.by_mint.get_mut(mint) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.by_mint.remove(mint);
+            }
+        }
+    }
+
+    /// Holds the trade until a rollup covers it, a mint pushed out before
+    /// that is invalidated right away
+    fn on_trade(&mut self, mint: &str, now_ms: u64) {
+        if let Some((evicted, _)) = self.pending_trades.push(mint.to_string(), now_ms) {
+            if evicted != mint {
+                self.invalidate(&evicted, now_ms);
+            }
+        }
+    }
+
+    /// Invalidates the mints whose trades the rollup covers
+    fn on_rollup(&mut self, rollup: &CandlestickRollup, now_ms: u64) {
+        let covered: Vec<String> = self
+            .pending_trades
+            .iter()
+            .filter(|(_, traded_at_ms)| **traded_at_ms <= rollup.covers_until_ms)
+            .map(|(mint, _)| mint.clone())
+            .collect();
+        for mint in covered {
+            self.pending_trades.pop(&mint);
+            self.invalidate(&mint, now_ms);
+        }
+    }
+
+    /// Drops the entries of the mint, except the very fresh ones
+    fn invalidate(&mut self, mint: &str, now_ms: u64) {
+        if let Some((_, forgotten)) = self.last_trades.push(mint.to_string(), now_ms) {
+            self.forgotten_trades_ms = self.forgotten_trades_ms.max(forgotten);
+        }
+        let Some(keys) = self.by_mint.remove(mint) else {
+            return;
+        };
+        let mut kept = HashSet::new();
+        for key in keys {
+            match self.entries.peek(&key) {
+                Some(entry)
+                    if now_ms.saturating_sub(entry.cached_at_ms) < MIN_FRESH.as_millis() as u64 =>
+                {
+                    kept.insert(key);
+                }
+                _ => {
+                    self.entries.pop(&key);
+                }
+            }
+        }
+        if !kept.is_empty() {
+            self.by_mint.insert(mint.to_string(), kept);
+        }
+    }
+}
+
+/// Redis values are the time they were cached and the body
+fn encode(cached_at_ms: u64, body: &str) -> String {
+    format!("{}\n{}", cached_at_ms, body)
+}
+
+fn decode(value: &str) -> Option<(u64, &str)> {
+    let (cached_at_ms, body) = value.split_once('\n')?;
+    Some((cached_at_ms.parse().ok()?, body))
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+pub struct ResponseCache {
+    local: Mutex<Local>,
+    inflight: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    redis_client: Option<Arc<RedisClient>>,
+}
+
+impl ResponseCache {
+    /// Without a Redis client the cache is in-process only
+    pub fn new(redis_client: Option<Arc<RedisClient>>) -> Self {
+        Self {
+            local: Mutex::new(Local::default()),
+            inflight: Mutex::new(HashMap::new()),
+            redis_client,
+        }
+    }
+
+    fn get_local(&self, key: &CacheKey) -> Option<Arc<String>> {
+        self.local
+            .lock()
+            .expect("cache lock poisoned")
+            .get(key, now_ms())
+    }
+
+    fn insert_local(&self, key: &CacheKey, cached_at_ms: u64, ttl: Duration, body: Arc<String>) {
+        let entry = Entry {
+            cached_at_ms,
+            expires_at_ms: now_ms() + ttl.as_millis() as u64,
+            body,
+            invalidated_by: key.invalidated_by.clone(),
+        };
+        self.local
+            .lock()
+            .expect("cache lock poisoned")
+            .insert(key, entry);
+    }
+
+    /// Fresh entry from Redis, with the TTL it has left
+    async fn get_shared(&self, key: &CacheKey) -> Option<(u64, Duration, Arc<String>)> {
+        let redis_client = self.redis_client.as_ref()?;
+        let (value, ttl) = match redis_client.cache_get(key.as_str()).await {
+            Ok(Some(found)) => found,
+            Ok(None) => return None,
+            Err(e) => {
+                error!("Failed to read cache: {}", e);
+                return None;
+            }
+        };
+        let (cached_at_ms, body) = decode(&value)?;
+        let fresh =
+            self.local
+                .lock()
+                .expect("cache lock poisoned")
+                .is_fresh(key, cached_at_ms, now_ms());
+        fresh.then(|| (cached_at_ms, ttl, Arc::new(body.to_string())))
+    }
+
+    /// Returns the cached JSON of `load` for the key, loading it at most
+    /// once at a time
+    pub async fn get_or_load<T, F, Fut>(
+        &self,
+        key: &CacheKey,
+        ttl: Duration,
+        load: F,
+    ) -> Result<Arc<String>>
+    where
+        T: Serialize,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if let Some(body) = self.get_local(key) {
+            metrics::counter!("adapter_cache", 1, "endpoint" => key.endpoint, "result" => "hit_local");
+            return Ok(body);
+        }
+
+        let lock = self
+            .inflight
+            .lock()
+            .expect("cache lock poisoned")
+            .entry(key.key.clone())
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = lock.lock().await;
+            self.load_once(key, ttl, load).await
+        };
+        {
+            let mut inflight = self.inflight.lock().expect("cache lock poisoned");
+            // the map and this function hold the last references
+            if Arc::strong_count(&lock) <= 2 {
+                inflight.remove(&key.key);
+            }
+        }
+        result
+    }
+
+    async fn load_once<T, F, Fut>(
+        &self,
+        key: &CacheKey,
+        ttl: Duration,
+        load: F,
+    ) -> Result<Arc<String>>
+    where
+        T: Serialize,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        // filled by whoever held the lock before
+        if let Some(body) = self.get_local(key) {
+            metrics::counter!("adapter_cache", 1, "endpoint" => key.endpoint, "result" => "hit_local");
+            return Ok(body);
+        }
+        if let Some((cached_at_ms, ttl_left, body)) = self.get_shared(key).await {
+            metrics::counter!("adapter_cache", 1, "endpoint" => key.endpoint, "result" => "hit_redis");
+            self.insert_local(key, cached_at_ms, ttl_left.min(ttl), body.clone());
+            return Ok(body);
+        }
+
+        if let Some(redis_client) = &self.redis_client {
+            if !redis_client
+                .cache_lock(key.as_str(), LOCK_TTL)
+                .await
+                .unwrap_or(true)
+            {
+                // another instance is loading it
+                let deadline = tokio::time::Instant::now() + LOCK_WAIT;
+                while tokio::time::Instant::now() < deadline {
+                    tokio::time::sleep(LOCK_POLL).await;
+                    if let Some((cached_at_ms, ttl_left, body)) = self.get_shared(key).await {
+                        metrics::counter!("adapter_cache", 1, "endpoint" => key.endpoint, "result" => "hit_redis");
+                        self.insert_local(key, cached_at_ms, ttl_left.min(ttl), body.clone());
+                        return Ok(body);
+                    }
+                }
+            }
+        }
+
+        metrics::counter!("adapter_cache", 1, "endpoint" => key.endpoint, "result" => "miss");
+        let cached_at_ms = now_ms();
+        let body = Arc::new(serde_json::to_string(&load().await?)?);
+        self.insert_local(key, cached_at_ms, ttl, body.clone());
+        if let Some(redis_client) = &self.redis_client {
+            if let Err(e) = redis_client
+                .cache_set(key.as_str(), &encode(cached_at_ms, &body), ttl)
+                .await
+            {
+                error!("Failed to write cache: {}", e);
+            }
+        }
+        Ok(body)
+    }
+
+    pub fn on_trade(&self, mint: &str) {
+        self.local
+            .lock()
+            .expect("cache lock poisoned")
+            .on_trade(mint, now_ms());
+    }
+
+    pub fn on_rollup(&self, rollup: &CandlestickRollup) {
+        self.local
+            .lock()
+            .expect("cache lock poisoned")
+            .on_rollup(rollup, now_ms());
+    }
+
+    fn invalidate_all(&self) {
+        self.local
+            .lock()
+            .expect("cache lock poisoned")
+            .invalidated_at_ms = now_ms();
+    }
+
+    /// Drops entries depending on the latest candle of traded mints once
+    /// their trades are rolled up
+    pub fn start_invalidation(
+        self: &Arc<Self>,
+        mut trades: Receiver<Arc<TradeUpdate>>,
+        mut rollups: Receiver<CandlestickRollup>,
+    ) {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    trade = trades.recv() => match trade {
+                        Ok(update) => cache.on_trade(&update.price_update.pubkey),
+                        Err(RecvError::Lagged(n)) => {
+                            warn!("Cache invalidation lagged behind by {} trades", n);
+                            cache.invalidate_all();
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    rollup = rollups.recv() => match rollup {
+                        Ok(rollup) => cache.on_rollup(&rollup),
+                        Err(RecvError::Lagged(n)) => {
+                            warn!("Cache invalidation lagged behind by {} rollups", n);
+                            cache.invalidate_all();
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_cache_key() {
+        let key = CacheKey::new("candlesticks", &[&"mint", &"1m", &200]);
+        assert_eq!(key.as_str(), "candlesticks:mint:1m:200");
+        assert_eq!(key.invalidated_by, None);
+        assert_eq!(
+            key.invalidated_by("mint").invalidated_by,
+            Some("mint".to_string())
+        );
+    }
+
+    #[test]
+    fn test_open_price_ttl() {
+        assert_eq!(open_price_ttl(86400 * 10), OPEN_PRICE_TTL);
+        assert_eq!(open_price_ttl(86400 * 11 - 20), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_encode() {
+        let value = encode(42, "{\"a\":\n1}");
+        assert_eq!(decode(&value), Some((42, "{\"a\":\n1}")));
+        assert_eq!(decode("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_through() {
+        let cache = ResponseCache::new(None);
+        let key = CacheKey::new("top_tokens", &[&20]);
+        let loads = AtomicUsize::new(0);
+        for _ in 0..3 {
+            let body = cache
+                .get_or_load(&key, Duration::from_secs(60), || async {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    Ok(vec![1, 2, 3])
+                })
+                .await
+                .unwrap();
+            assert_eq!(body.as_str(), "[1,2,3]");
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        // expired entries are loaded again
+        let body = cache
+            .get_or_load(&key, Duration::ZERO, || async { Ok(vec![4]) })
+            .await
+            .unwrap();
+        assert_eq!(body.as_str(), "[1,2,3]");
+        cache
+            .local
+            .lock()
+            .unwrap()
+            .entries
+            .get_mut(key.as_str())
+            .unwrap()
+            .expires_at_ms = 0;
+        let body = cache
+            .get_or_load(&key, Duration::from_secs(60), || async { Ok(vec![4]) })
+            .await
+            .unwrap();
+        assert_eq!(body.as_str(), "[4]");
+    }
+
+    #[tokio::test]
+    async fn test_stampede_loads_once() {
+        let cache = Arc::new(ResponseCache::new(None));
+        let loads = Arc::new(AtomicUsize::new(0));
+        let key = CacheKey::new("candlesticks", &[&"mint"]);
+
+        let requests = (0..20).map(|_| {
+            let cache = cache.clone();
+            let loads = loads.clone();
+            let key = key.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_or_load(&key, Duration::from_secs(60), || async {
+                        loads.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok("loaded")
+                    })
+                    .await
+                    .unwrap()
+            })
+        });
+        for body in futures::future::join_all(requests).await {
+            assert_eq!(body.unwrap().as_str(), "\"loaded\"");
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(cache.inflight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_trades_invalidate_candles() {
+        let mut local = Local::default();
+        let candles = CacheKey::new("candlesticks", &[&"mint"]).invalidated_by("mint");
+        let other = CacheKey::new("top_tokens", &[&20]);
+        local.insert(&candles, entry(&candles, 10_000));
+        local.insert(&other, entry(&other, 10_000));
+        assert!(local.get(&candles, 12_000).is_some());
+
+        // the candles only change once a rollup covers the trade
+        local.on_trade("mint", 11_000);
+        assert!(local.get(&candles, 12_000).is_some());
+        local.on_rollup(&rollup(10_500), 12_000);
+        assert!(local.get(&candles, 12_000).is_some());
+        local.on_rollup(&rollup(11_000), 12_000);
+        assert!(local.get(&candles, 12_000).is_none());
+        assert!(local.get(&other, 12_000).is_some());
+        assert!(!local.by_mint.contains_key("mint"));
+        assert!(local.pending_trades.is_empty());
+
+        // very fresh entries are served through rollups
+        local.insert(&candles, entry(&candles, 12_500));
+        local.on_trade("mint", 12_600);
+        local.on_rollup(&rollup(12_600), 13_000);
+        assert!(local.get(&candles, 13_000).is_some());
+        assert!(local.get(&candles, 14_000).is_none());
+
+        // trades of mints without local entries are tracked too, so entries
+        // other instances cached in Redis are checked against them
+        let shared = CacheKey::new("candlesticks", &[&"shared"]).invalidated_by("shared");
+        local.on_trade("shared", 12_000);
+        local.on_rollup(&rollup(12_000), 13_000);
+        assert!(!local.is_fresh(&shared, 12_000, 14_000));
+        assert!(local.is_fresh(&shared, 13_000, 14_000));
+    }
+
+    fn rollup(covers_until_ms: u64) -> CandlestickRollup {
+        CandlestickRollup { covers_until_ms }
+    }
+
+    fn entry(key: &CacheKey, cached_at_ms: u64) -> Entry {
+        Entry {
+            cached_at_ms,
+            expires_at_ms: 100_000,
+            body: Arc::new("[]".to_string()),
+            invalidated_by: key.invalidated_by.clone(),
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut local = Local::with_capacity(2, 2);
+        let key = |mint: &str| CacheKey::new("candlesticks", &[&mint]).invalidated_by(mint);
+        local.insert(&key("a"), entry(&key("a"), 10_000));
+        local.insert(&key("b"), entry(&key("b"), 10_000));
+        assert!(local.get(&key("a"), 10_000).is_some());
+
+        // the least recently used entry makes room, even if unexpired
+        local.insert(&key("c"), entry(&key("c"), 10_000));
+        assert!(local.get(&key("b"), 10_000).is_none());
+        assert!(local.get(&key("a"), 10_000).is_some());
+        assert!(local.get(&key("c"), 10_000).is_some());
+        assert!(!local.by_mint.contains_key("b"));
+
+        // forgotten mints are assumed to have traded when they were last
+        // seen, so entries older than that are not trusted
+        for (mint, at_ms) in [("x", 11_000), ("y", 12_000), ("z", 13_000)] {
+            local.on_trade(mint, at_ms);
+            local.on_rollup(&rollup(at_ms), at_ms);
+        }
+        assert_eq!(local.forgotten_trades_ms, 11_000);
+        assert!(!local.is_fresh(&key("x"), 10_000, 20_000));
+        assert!(local.is_fresh(&key("x"), 11_000, 20_000));
+
+        // trades pushed out before a rollup covers them invalidate at once
+        let mut local = Local::with_capacity(2, 2);
+        local.on_trade("x", 11_000);
+        local.on_trade("y", 12_000);
+        local.on_trade("z", 13_000);
+        assert!(!local.is_fresh(&key("x"), 12_000, 20_000));
+        assert!(local.is_fresh(&key("y"), 12_000, 20_000));
+    }
+}
@@ -0,0 +1,711 @@
+//! Typed analytics queries over the indexed price updates.
+//!
+//! Queries are described as data (entity, filters, grouping, aggregations)
+//! and compiled to ClickHouse SQL where every user supplied value is a bound
+//! parameter and every identifier comes from a fixed set of columns.
+
+use super::ClickhouseDb;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+/// Most rows a single query returns
+pub const MAX_ROWS: usize = 1000;
+pub const DEFAULT_ROWS: usize = 100;
+/// Widest time range a single query may scan
+pub const MAX_RANGE_SECS: u64 = 30 * 86400;
+pub const DEFAULT_RANGE_SECS: u64 = 86400;
+pub const MAX_EXECUTION_SECS: u64 = 10;
+pub const MAX_MEMORY_BYTES: u64 = 2_000_000_000;
+const MAX_FILTERS: usize = 16;
+const MAX_LIST_VALUES: usize = 100;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum QueryError {
+    #[error("field `{field}` is not available for {entity}")]
+    UnknownField {
+        entity: &'static str,
+        field: &'static str,
+    },
+
+    #[error("`{func}` needs a numeric field, `{field}` is not")]
+    NotNumeric {
+        func: &'static str,
+        field: &'static str,
+    },
+
+    #[error("`{func}` needs a field")]
+    MissingField { func: &'static str },
+
+    #[error("filter on `{field}` has a value of the wrong type")]
+    InvalidValue { field: &'static str },
+
+    #[error("`in` filters take a non-empty list of at most {MAX_LIST_VALUES} values")]
+    InvalidList,
+
+    #[error("at most {MAX_FILTERS} filters are allowed")]
+    TooManyFilters,
+
+    #[error("group_by and time_bucket need at least one aggregation")]
+    GroupWithoutAggregation,
+
+    #[error("cannot order by `{0}`, it is not part of the result")]
+    UnknownOrderColumn(String),
+
+    #[error("time range is invalid or wider than {MAX_RANGE_SECS} seconds")]
+    InvalidRange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Entity {
+    /// every price update
+    Prices,
+    /// price updates as swaps, deduplicated on the signature
+    Swaps,
+    /// latest state and volume per token within the time range
+    Tokens,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Pubkey,
+    Name,
+    Price,
+    MarketCap,
+    SwapAmount,
+    Owner,
+    Signature,
+    IsBuy,
+    MultiHop,
+    IsPump,
+    Timestamp,
+    Slot,
+    Volume,
+    Swaps,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Str,
+    Num,
+    Bool,
+}
+
+impl Field {
+    pub fn column(&self) -> &'static str {
+        match self {
+            Field::Pubkey => "pubkey",
+            Field::Name => "name",
+            Field::Price => "price",
+            Field::MarketCap => "market_cap",
+            Field::SwapAmount => "swap_amount",
+            Field::Owner => "owner",
+            Field::Signature => "signature",
+            Field::IsBuy => "is_buy",
+            Field::MultiHop => "multi_hop",
+            Field::IsPump => "is_pump",
+            Field::Timestamp => "timestamp",
+            Field::Slot => "slot",
+            Field::Volume => "volume",
+            Field::Swaps => "swaps",
+        }
+    }
+
+    fn kind(&self) -> Kind {
+        match self {
+            Field::Pubkey | Field::Name | Field::Owner | Field::Signature => Kind::Str,
+            Field::IsBuy | Field::MultiHop | Field::IsPump => Kind::Bool,
+            _ => Kind::Num,
+        }
+    }
+}
+
+impl Entity {
+    fn name(&self) -> &'static str {
+        match self {
+            Entity::Prices => "prices",
+            Entity::Swaps => "swaps",
+            Entity::Tokens => "tokens",
+        }
+    }
+
+    fn fields(&self) -> &'static [Field] {
+        match self {
+            Entity::Prices => &[
+                Field::Pubkey,
+                Field::Name,
+                Field::Price,
+                Field::MarketCap,
+                Field::IsPump,
+                Field::Timestamp,
+                Field::Slot,
+            ],
+            Entity::Swaps => &[
+                Field::Pubkey,
+                Field::Name,
+                Field::Price,
+                Field::MarketCap,
+                Field::SwapAmount,
+                Field::Owner,
+                Field::Signature,
+                Field::IsBuy,
+                Field::MultiHop,
+                Field::IsPump,
+                Field::Timestamp,
+                Field::Slot,
+            ],
+            Entity::Tokens => &[
+                Field::Pubkey,
+                Field::Name,
+                Field::Price,
+                Field::MarketCap,
+                Field::IsPump,
+                Field::Timestamp,
+                Field::Volume,
+                Field::Swaps,
+            ],
+        }
+    }
+
+    fn check(&self, field: Field) -> Result<Field, QueryError> {
+        match self.fields().contains(&field) {
+            true => Ok(field),
+            false => Err(QueryError::UnknownField {
+                entity: self.name(),
+                field: field.column(),
+            }),
+        }
+    }
+
+    /// The rows of the entity within `[from, to)`, binds two parameters
+    fn source(&self) -> &'static str {
+        match self {
+            Entity::Prices => {
+                "(SELECT * FROM price_updates WHERE timestamp >= ? AND timestamp < ?)"
+            }
+            Entity::Swaps => {
+                "(SELECT * FROM price_updates FINAL WHERE timestamp >= ? AND timestamp < ?)"
+            }
+            Entity::Tokens => {
+                r#"(
+                SELECT
+                    pubkey,
+                    argMax(name, timestamp) AS name,
+                    argMax(price, timestamp) AS price,
+                    argMax(market_cap, timestamp) AS market_cap,
+                    max(is_pump) AS is_pump,
+                    max(timestamp) AS timestamp,
+                    sum(swap_amount) AS volume,
+                    count() AS swaps
+                FROM price_updates FINAL
+                WHERE timestamp >= ? AND timestamp < ?
+                GROUP BY pubkey
+            )"#
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum FilterValue {
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    #[schema(no_recursion)]
+    List(Vec<FilterValue>),
+}
+
+impl FilterValue {
+    fn kind(&self) -> Option<Kind> {
+        match self {
+            FilterValue::Bool(_) => Some(Kind::Bool),
+            FilterValue::Num(_) => Some(Kind::Num),
+            FilterValue::Str(_) => Some(Kind::Str),
+            FilterValue::List(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct Filter {
+    pub field: Field,
+    pub op: Op,
+    pub value: FilterValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationFn {
+    Count,
+    Uniq,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    /// value at the earliest timestamp
+    First,
+    /// value at the latest timestamp
+    Last,
+}
+
+impl AggregationFn {
+    fn name(&self) -> &'static str {
+        match self {
+            AggregationFn::Count => "count",
+            AggregationFn::Uniq => "uniq",
+            AggregationFn::Sum => "sum",
+            AggregationFn::Avg => "avg",
+            AggregationFn::Min => "min",
+            AggregationFn::Max => "max",
+            AggregationFn::First => "first",
+            AggregationFn::Last => "last",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct Aggregation {
+    #[serde(rename = "fn")]
+    pub func: AggregationFn,
+    pub field: Option<Field>,
+}
+
+impl Aggregation {
+    /// Column name of the aggregate in the result, e.g. `sum_swap_amount`
+    pub fn alias(&self) -> String {
+        match self.field {
+            Some(field) => format!("{}_{}", self.func.name(), field.column()),
+            None => self.func.name().to_string(),
+        }
+    }
+
+    fn expr(&self, entity: Entity) -> Result<String, QueryError> {
+        let func = self.func.name();
+        let field = match self.field {
+            Some(field) => Some(entity.check(field)?),
+            None => None,
+        };
+        let expr = match (self.func, field) {
+            (AggregationFn::Count, None) => "count()".to_string(),
+            (_, None) => return Err(QueryError::MissingField { func }),
+            (AggregationFn::Count | AggregationFn::Uniq, Some(field)) => {
+                format!("{}({})", func, field.column())
+            }
+            (AggregationFn::First, Some(field)) => {
+                format!("argMin({}, timestamp)", field.column())
+            }
+            (AggregationFn::Last, Some(field)) => {
+                format!("argMax({}, timestamp)", field.column())
+            }
+            (_, Some(field)) if field.kind() != Kind::Num => {
+                return Err(QueryError::NotNumeric {
+                    func,
+                    field: field.column(),
+                })
+            }
+            (_, Some(field)) => format!("{}({})", func, field.column()),
+        };
+        Ok(format!("{} AS {}", expr, self.alias()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+pub enum TimeBucket {
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "4h")]
+    FourHours,
+    #[serde(rename = "1d")]
+    OneDay,
+}
+
+impl TimeBucket {
+    pub fn seconds(&self) -> u64 {
+        match self {
+            TimeBucket::OneMinute => 60,
+            TimeBucket::FiveMinutes => 300,
+            TimeBucket::FifteenMinutes => 900,
+            TimeBucket::OneHour => 3600,
+            TimeBucket::FourHours => 14400,
+            TimeBucket::OneDay => 86400,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct OrderBy {
+    /// a selected field, an aggregation alias or `bucket`
+    pub column: String,
+    #[serde(default)]
+    pub desc: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct AnalyticsQuery {
+    pub entity: Entity,
+    #[serde(default)]
+    pub filters: Vec<Filter>,
+    /// unix seconds, defaults to `to` minus a day
+    pub from: Option<u64>,
+    /// unix seconds, exclusive, defaults to now
+    pub to: Option<u64>,
+    /// fields returned when there are no aggregations, all by default
+    #[serde(default)]
+    pub select: Vec<Field>,
+    #[serde(default)]
+    pub group_by: Vec<Field>,
+    pub time_bucket: Option<TimeBucket>,
+    #[serde(default)]
+    pub aggregations: Vec<Aggregation>,
+    pub order_by: Option<OrderBy>,
+    pub limit: Option<usize>,
+}
+
+/// A value bound to a `?` placeholder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Param {
+    Int(u64),
+    Value(FilterValue),
+}
+
+#[derive(Debug)]
+pub struct CompiledQuery {
+    pub sql: String,
+    pub params: Vec<Param>,
+    /// names of the result columns, in order
+    pub columns: Vec<String>,
+}
+
+fn filter_sql(
+    entity: Entity,
+    filter: &Filter,
+    params: &mut Vec<Param>,
+) -> Result<String, QueryError> {
+    let field = entity.check(filter.field)?;
+    let invalid = || QueryError::InvalidValue {
+        field: field.column(),
+    };
+
+    if filter.op == Op::In {
+        let FilterValue::List(values) = &filter.value else {
+            return Err(QueryError::InvalidList);
+        };
+        if values.is_empty() || values.len() > MAX_LIST_VALUES {
+            return Err(QueryError::InvalidList);
+        }
+        if values.iter().any(|v| v.kind() != Some(field.kind())) {
+            return Err(invalid());
+        }
+        params.push(Param::Value(filter.value.clone()));
+        return Ok(format!("has(?, {})", field.column()));
+    }
+
+    if filter.value.kind() != Some(field.kind()) {
+        return Err(invalid());
+    }
+    let op = match filter.op {
+        Op::Eq => "=",
+        Op::Ne => "!=",
+        Op::Gt => ">",
+        Op::Gte => ">=",
+        Op::Lt => "<",
+        Op::Lte => "<=",
+        Op::In => unreachable!(),
+    };
+    params.push(Param::Value(filter.value.clone()));
+    Ok(format!("{} {} ?", field.column(), op))
+}
+
+impl AnalyticsQuery {
+    /// Compiles the query, `now` is the default end of the time range
+    pub fn compile(&self, now: u64) -> Result<CompiledQuery, QueryError> {
+        let entity = self.entity;
+        let to = self.to.unwrap_or(now);
+        let from = self
+            .from
+            .unwrap_or_else(|| to.saturating_sub(DEFAULT_RANGE_SECS));
+        if from >= to || to - from > MAX_RANGE_SECS {
+            return Err(QueryError::InvalidRange);
+        }
+        if self.filters.len() > MAX_FILTERS {
+            return Err(QueryError::TooManyFilters);
+        }
+
+        let mut params = vec![Param::Int(from), Param::Int(to)];
+        let conditions = self
+            .filters
+            .iter()
+            .map(|filter| filter_sql(entity, filter, &mut params))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut columns = Vec::new();
+        let mut exprs = Vec::new();
+        let mut group_by = Vec::new();
+
+        if self.aggregations.is_empty() {
+            if !self.group_by.is_empty() || self.time_bucket.is_some() {
+                return Err(QueryError::GroupWithoutAggregation);
+            }
+            let fields = match self.select.is_empty() {
+                true => entity.fields(),
+                false => &self.select,
+            };
+            for field in fields {
+                let column = entity.check(*field)?.column();
+                exprs.push(column.to_string());
+                columns.push(column.to_string());
+            }
+        } else {
+            if let Some(bucket) = self.time_bucket {
+                let seconds = bucket.seconds();
+                exprs.push(format!(
+                    "intDiv(timestamp, {seconds}) * {seconds} AS bucket"
+                ));
+                columns.push("bucket".to_string());
+                group_by.push("bucket".to_string());
+            }
+            for field in &self.group_by {
+                let column = entity.check(*field)?.column();
+                exprs.push(column.to_string());
+                columns.push(column.to_string());
+                group_by.push(column.to_string());
+            }
+            for aggregation in &self.aggregations {
+                exprs.push(aggregation.expr(entity)?);
+                columns.push(aggregation.alias());
+            }
+        }
+
+        let mut sql = format!("SELECT {} FROM {}", exprs.join(", "), entity.source());
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        if !group_by.is_empty() {
+            sql.push_str(&format!(" GROUP BY {}", group_by.join(", ")));
+        }
+        if let Some(order_by) = &self.order_by {
+            if !columns.contains(&order_by.column) {
+                return Err(QueryError::UnknownOrderColumn(order_by.column.clone()));
+            }
+            let direction = if order_by.desc { "DESC" } else { "ASC" };
+            sql.push_str(&format!(" ORDER BY {} {}", order_by.column, direction));
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_ROWS).min(MAX_ROWS);
+        sql.push_str(&format!(" LIMIT {limit}"));
+
+        Ok(CompiledQuery {
+            sql,
+            params,
+            columns,
+        })
+    }
+}
+
+impl ClickhouseDb {
+    /// Runs a compiled analytics query with execution time, memory and
+    /// result size limits; rows come back as JSON objects keyed by column.
+    pub async fn run_analytics_query(
+        &self,
+        query: &CompiledQuery,
+    ) -> Result<Vec<serde_json::Map<String, serde_json::Value>>> {
+        // result columns differ per query, so rows are serialized to JSON
+        // arrays by ClickHouse and zipped with the known column names
+        let sql = format!(
+            "SELECT toJSONString(tuple({})) FROM ({})",
+            query.columns.join(", "),
+            query.sql
+        );
+        let mut request = self.client.query(&sql);
+        for param in &query.params {
+            request = request.bind(param);
+        }
+        let rows = request
+            .with_option("max_execution_time", MAX_EXECUTION_SECS.to_string())
+            .with_option("max_memory_usage", MAX_MEMORY_BYTES.to_string())
+            .with_option("max_result_rows", MAX_ROWS.to_string())
+            .with_option("output_format_json_quote_64bit_integers", "0")
+            .fetch_all::<String>()
+            .await?;
+
+        rows.iter()
+            .map(|row| {
+                let values: Vec<serde_json::Value> = serde_json::from_str(row)?;
+                Ok(query.columns.iter().cloned().zip(values).collect())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn query(json: serde_json::Value) -> AnalyticsQuery {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_compile_select() {
+        let compiled = query(serde_json::json!({
+            "entity": "swaps",
+            "select": ["pubkey", "swap_amount"],
+            "filters": [
+                {"field": "pubkey", "op": "eq", "value": "mint'; DROP TABLE x"},
+                {"field": "swap_amount", "op": "gte", "value": 100}
+            ],
+            "order_by": {"column": "swap_amount", "desc": true},
+            "limit": 5000
+        }))
+        .compile(NOW)
+        .unwrap();
+
+        assert_eq!(
+            compiled.sql,
+            "SELECT pubkey, swap_amount FROM (SELECT * FROM price_updates FINAL \
+             WHERE timestamp >= ? AND timestamp < ?) WHERE pubkey = ? AND swap_amount >= ? \
+             ORDER BY swap_amount DESC LIMIT 1000"
+        );
+        // user values only ever end up in parameters
+        assert_eq!(
+            compiled.params,
+            vec![
+                Param::Int(NOW - DEFAULT_RANGE_SECS),
+                Param::Int(NOW),
+                Param::Value(FilterValue::Str("mint'; DROP TABLE x".to_string())),
+                Param::Value(FilterValue::Num(100.0)),
+            ]
+        );
+        assert_eq!(compiled.columns, vec!["pubkey", "swap_amount"]);
+    }
+
+    #[test]
+    fn test_compile_aggregation() {
+        let compiled = query(serde_json::json!({
+            "entity": "swaps",
+            "from": NOW - 3600,
+            "time_bucket": "5m",
+            "group_by": ["is_buy"],
+            "aggregations": [
+                {"fn": "sum", "field": "swap_amount"},
+                {"fn": "count"},
+                {"fn": "last", "field": "price"}
+            ],
+            "filters": [{"field": "pubkey", "op": "in", "value": ["a", "b"]}],
+            "order_by": {"column": "bucket"}
+        }))
+        .compile(NOW)
+        .unwrap();
+
+        assert_eq!(
+            compiled.sql,
+            "SELECT intDiv(timestamp, 300) * 300 AS bucket, is_buy, \
+             sum(swap_amount) AS sum_swap_amount, count() AS count, \
+             argMax(price, timestamp) AS last_price FROM (SELECT * FROM price_updates FINAL \
+             WHERE timestamp >= ? AND timestamp < ?) WHERE has(?, pubkey) \
+             GROUP BY bucket, is_buy ORDER BY bucket ASC LIMIT 100"
+        );
+        assert_eq!(
+            compiled.columns,
+            vec!["bucket", "is_buy", "sum_swap_amount", "count", "last_price"]
+        );
+        assert_eq!(compiled.params[0], Param::Int(NOW - 3600));
+    }
+
+    #[test]
+    fn test_compile_rejects_invalid_queries() {
+        let compile = |json| query(json).compile(NOW).unwrap_err();
+
+        assert_eq!(
+            compile(serde_json::json!({"entity": "prices", "select": ["owner"]})),
+            QueryError::UnknownField {
+                entity: "prices",
+                field: "owner"
+            }
+        );
+        assert_eq!(
+            compile(serde_json::json!({
+                "entity": "swaps",
+                "aggregations": [{"fn": "sum", "field": "owner"}]
+            })),
+            QueryError::NotNumeric {
+                func: "sum",
+                field: "owner"
+            }
+        );
+        assert_eq!(
+            compile(serde_json::json!({
+                "entity": "swaps",
+                "filters": [{"field": "price", "op": "gt", "value": "1 OR 1=1"}]
+            })),
+            QueryError::InvalidValue { field: "price" }
+        );
+        assert_eq!(
+            compile(serde_json::json!({"entity": "swaps", "group_by": ["pubkey"]})),
+            QueryError::GroupWithoutAggregation
+        );
+        assert_eq!(
+            compile(serde_json::json!({
+                "entity": "swaps",
+                "order_by": {"column": "price; DROP TABLE x"}
+            })),
+            QueryError::UnknownOrderColumn("price; DROP TABLE x".to_string())
+        );
+        assert_eq!(
+            compile(serde_json::json!({"entity": "swaps", "from": 0})),
+            QueryError::InvalidRange
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_do_not_deserialize() {
+        let result = serde_json::from_value::<AnalyticsQuery>(serde_json::json!({
+            "entity": "system.users",
+        }));
+        assert!(result.is_err());
+        let result = serde_json::from_value::<AnalyticsQuery>(serde_json::json!({
+            "entity": "swaps",
+            "select": ["password"]
+        }));
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_analytics_query() {
+        let db = crate::db::make_db().unwrap();
+        let compiled = query(serde_json::json!({
+            "entity": "tokens",
+            "aggregations": [{"fn": "count"}]
+        }))
+        .compile(chrono::Utc::now().timestamp() as u64)
+        .unwrap();
+        let rows = db.run_analytics_query(&compiled).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].keys().collect::<Vec<_>>(), vec!["count"]);
+        assert!(rows[0]["count"].is_u64());
+    }
+}
@@ -1,8 +1,9 @@
 use super::ClickhouseDb;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Candlestick {
     pub timestamp: u64, // TODO standardize to regular iso string
     pub open: f64,
@@ -12,7 +13,7 @@ pub struct Candlestick {
     pub volume: f64,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum CandlestickInterval {
     FifteenSeconds,
     ThirtySeconds,
@@ -41,6 +42,21 @@ impl CandlestickInterval {
         }
     }
 
+    /// Short form as accepted by `from_str`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CandlestickInterval::FifteenSeconds => "15s",
+            CandlestickInterval::ThirtySeconds => "30s",
+            CandlestickInterval::OneMinute => "1m",
+            CandlestickInterval::FiveMinutes => "5m",
+            CandlestickInterval::FifteenMinutes => "15m",
+            CandlestickInterval::ThirtyMinutes => "30m",
+            CandlestickInterval::OneHour => "1h",
+            CandlestickInterval::FourHours => "4h",
+            CandlestickInterval::OneDay => "1d",
+        }
+    }
+
     pub fn to_string(&self) -> String {
         match self {
             CandlestickInterval::FifteenSeconds => "15 SECOND".to_string(),
@@ -75,51 +91,86 @@ impl<'de> serde::Deserialize<'de> for CandlestickInterval {
     }
 }
 
+/// Upper bound on the candles returned by a single request
+pub const MAX_CANDLESTICKS: usize = 1000;
+
+impl CandlestickInterval {
+    pub fn seconds(&self) -> u64 {
+        match self {
+            CandlestickInterval::FifteenSeconds => 15,
+            CandlestickInterval::ThirtySeconds => 30,
+            CandlestickInterval::OneMinute => 60,
+            CandlestickInterval::FiveMinutes => 300,
+            CandlestickInterval::FifteenMinutes => 900,
+            CandlestickInterval::ThirtyMinutes => 1800,
+            CandlestickInterval::OneHour => 3600,
+            CandlestickInterval::FourHours => 14400,
+            CandlestickInterval::OneDay => 86400,
+        }
+    }
+
+    /// Whether candles ending at `to` reach into the bucket that is still
+    /// being traded, no end means up to now
+    pub fn includes_latest(&self, to: Option<u64>, now: u64) -> bool {
+        to.is_none_or(|to| to >= now.saturating_sub(self.seconds()))
+    }
+
+    /// Table the listen-data indexer rolls the candles up into
+    pub fn table(&self) -> &'static str {
+        match self {
+            CandlestickInterval::FifteenSeconds => "candlesticks_15s",
+            CandlestickInterval::ThirtySeconds => "candlesticks_30s",
+            CandlestickInterval::OneMinute => "candlesticks_1m",
+            CandlestickInterval::FiveMinutes => "candlesticks_5m",
+            CandlestickInterval::FifteenMinutes => "candlesticks_15m",
+            CandlestickInterval::ThirtyMinutes => "candlesticks_30m",
+            CandlestickInterval::OneHour => "candlesticks_1h",
+            CandlestickInterval::FourHours => "candlesticks_4h",
+            CandlestickInterval::OneDay => "candlesticks_1d",
+        }
+    }
+}
+
 impl ClickhouseDb {
+    /// Returns up to `limit` candles (oldest first) ending at `to`, or the
+    /// latest ones if `to` is not set; buckets without trades are filled
+    /// with flat candles at the previous close.
     pub async fn get_candlesticks(
         &self,
         mint: &str,
-        interval: &str,
+        interval: &CandlestickInterval,
+        from: Option<u64>,
+        to: Option<u64>,
         limit: Option<usize>,
     ) -> Result<Vec<Candlestick>> {
-        let interval_seconds = match interval {
-            "15 SECOND" => 15,
-            "30 SECOND" => 30,
-            "1 MINUTE" => 60,
-            "5 MINUTE" => 300,
-            "15 MINUTE" => 900,
-            "30 MINUTE" => 1800,
-            "1 HOUR" => 3600,
-            "4 HOUR" => 14400,
-            "1 DAY" => 86400,
-            _ => return Err(anyhow::anyhow!("Invalid interval")),
-        };
+        let limit = limit.unwrap_or(200).min(MAX_CANDLESTICKS);
 
+        // buckets are recomputed by the rollup, FINAL keeps the latest
+        // version of the ones that were not merged yet
         let query = format!(
             r#"
-            SELECT
-                intDiv(timestamp, {interval_seconds}) * {interval_seconds} as interval_timestamp,
-                argMin(price, timestamp) as open,
-                max(price) as high,
-                min(price) as low,
-                argMax(price, timestamp) as close,
-                sum(swap_amount) as volume
-            FROM price_updates
-            WHERE pubkey = '{mint}'
-            GROUP BY interval_timestamp
+            SELECT interval_timestamp, open, high, low, close, volume
+            FROM {table} FINAL
+            WHERE pubkey = ?
+              AND interval_timestamp >= ?
+              AND interval_timestamp <= ?
             ORDER BY interval_timestamp DESC
-            LIMIT {limit}
+            LIMIT ?
             "#,
-            limit = limit.unwrap_or(200)
+            table = interval.table()
         );
 
         let result = self
             .client
             .query(&query)
+            .bind(mint)
+            .bind(from.unwrap_or(0))
+            .bind(to.unwrap_or(u64::MAX))
+            .bind(limit as u64)
             .fetch_all::<(u64, f64, f64, f64, f64, f64)>()
             .await?;
 
-        let mut candlesticks = result
+        let rows = result
             .into_iter()
             .map(|(timestamp, open, high, low, close, volume)| Candlestick {
                 timestamp,
@@ -131,14 +182,65 @@ impl ClickhouseDb {
             })
             .collect::<Vec<_>>();
 
-        // Reverse to maintain chronological order (oldest first)
-        candlesticks.reverse();
+        Ok(chart_candles(rows, interval.seconds(), limit))
+    }
+}
 
-        // Post-process to remove extreme wicks
-        filter_extreme_wicks(&mut candlesticks);
+/// Turns the latest candles as selected (newest first) into the chart
+/// series, oldest first with the gaps filled and extreme wicks removed
+fn chart_candles(
+    mut candlesticks: Vec<Candlestick>,
+    interval_seconds: u64,
+    limit: usize,
+) -> Vec<Candlestick> {
+    candlesticks.sort_by_key(|c| c.timestamp);
 
-        Ok(candlesticks)
+    // filled buckets count towards the limit
+    let mut candlesticks = fill_gaps(candlesticks, interval_seconds, limit);
+
+    // Post-process to remove extreme wicks
+    filter_extreme_wicks(&mut candlesticks);
+
+    candlesticks
+}
+
+/// Inserts flat, zero volume candles for the buckets without trades between
+/// the candles (oldest first), so charts keep an even time axis. Walks back from the latest
+/// candle and stops at `limit`, sparse history on short intervals would
+/// otherwise expand into a huge number of empty buckets.
+fn fill_gaps(
+    candlesticks: Vec<Candlestick>,
+    interval_seconds: u64,
+    limit: usize,
+) -> Vec<Candlestick> {
+    let mut filled = Vec::with_capacity(limit.min(MAX_CANDLESTICKS));
+    let mut candles = candlesticks.into_iter().rev().peekable();
+    while let Some(candle) = candles.next() {
+        if filled.len() >= limit {
+            break;
+        }
+        let mut timestamp = candle.timestamp;
+        filled.push(candle);
+
+        let Some(prev) = candles.peek() else {
+            break;
+        };
+        let close = prev.close;
+        while timestamp >= prev.timestamp + 2 * interval_seconds && filled.len() < limit {
+            timestamp -= interval_seconds;
+            filled.push(Candlestick {
+                timestamp,
+                open: close,
+                high: close,
+                low: close,
+                close,
+                volume: 0.0,
+            });
+        }
     }
+    // Reverse to maintain chronological order (oldest first)
+    filled.reverse();
+    filled
 }
 
 /// Filter out extreme price wicks from candlestick data
@@ -178,7 +280,7 @@ fn filter_extreme_wicks(candlesticks: &mut Vec<Candlestick>) {
 mod tests {
     use crate::db::{candlesticks::Candlestick, make_db};
 
-    use super::{filter_extreme_wicks, CandlestickInterval};
+    use super::{chart_candles, fill_gaps, filter_extreme_wicks, CandlestickInterval};
     use crate::routes::CandlestickParams;
 
     #[test]
@@ -201,13 +303,84 @@ mod tests {
         assert_eq!(params.interval, CandlestickInterval::OneMinute);
     }
 
+    fn candle(timestamp: u64, close: f64) -> Candlestick {
+        Candlestick {
+            timestamp,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_fill_gaps() {
+        let candles = || vec![candle(60, 1.0), candle(240, 2.0), candle(300, 3.0)];
+        let filled = fill_gaps(candles(), 60, 200);
+        let timestamps = filled.iter().map(|c| c.timestamp).collect::<Vec<_>>();
+        assert_eq!(timestamps, vec![60, 120, 180, 240, 300]);
+        // gaps carry the previous close without volume
+        assert_eq!(filled[1].open, 1.0);
+        assert_eq!(filled[2].close, 1.0);
+        assert_eq!(filled[2].volume, 0.0);
+        assert_eq!(filled[3].close, 2.0);
+        assert!(fill_gaps(vec![], 60, 200).is_empty());
+
+        // the limit keeps the latest buckets
+        let filled = fill_gaps(candles(), 60, 3);
+        let timestamps = filled.iter().map(|c| c.timestamp).collect::<Vec<_>>();
+        assert_eq!(timestamps, vec![180, 240, 300]);
+    }
+
+    #[test]
+    fn test_chart_candles_from_latest_first() {
+        // rows come back newest first from the query
+        let rows = vec![candle(300, 3.0), candle(240, 2.0), candle(60, 1.0)];
+        let candles = chart_candles(rows, 60, 200);
+        let timestamps = candles.iter().map(|c| c.timestamp).collect::<Vec<_>>();
+        assert_eq!(timestamps, vec![60, 120, 180, 240, 300]);
+        assert_eq!(candles[2].close, 1.0);
+        assert_eq!(candles[2].volume, 0.0);
+        assert_eq!(candles[4].close, 3.0);
+    }
+
+    #[test]
+    fn test_includes_latest() {
+        let interval = CandlestickInterval::OneMinute;
+        assert!(interval.includes_latest(None, 10_000));
+        assert!(interval.includes_latest(Some(10_000), 10_000));
+        assert!(interval.includes_latest(Some(9_940), 10_000));
+        assert!(!interval.includes_latest(Some(9_939), 10_000));
+    }
+
+    #[test]
+    fn test_candlestick_interval_table() {
+        assert_eq!(
+            CandlestickInterval::FifteenSeconds.table(),
+            "candlesticks_15s"
+        );
+        assert_eq!(CandlestickInterval::OneDay.table(), "candlesticks_1d");
+        assert_eq!(CandlestickInterval::FourHours.seconds(), 14400);
+    }
+
+    #[test]
+    fn test_deserialize_candlestick_range() {
+        let payload = r#"{"mint": "mint", "interval": "1h", "from": 1700000000, "to": 1700086400}"#;
+        let params: CandlestickParams = serde_json::from_str(payload).unwrap();
+        assert_eq!(params.from, Some(1700000000));
+        assert_eq!(params.to, Some(1700086400));
+    }
+
     #[tokio::test]
     async fn test_get_candlesticks() {
         let db = make_db().unwrap();
         let candlesticks = db
             .get_candlesticks(
                 "GJAFwWjJ3vnTsrQVabjBVK2TYB1YtRCQXRDfDgUnpump",
-                &CandlestickInterval::OneMinute.to_string(),
+                &CandlestickInterval::OneMinute,
+                None,
+                None,
                 None,
             )
             .await
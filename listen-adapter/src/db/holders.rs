@@ -0,0 +1,117 @@
+use anyhow::Result;
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::ClickhouseDb;
+
+pub const DEFAULT_HOLDERS: usize = 100;
+pub const MAX_HOLDERS: usize = 500;
+
+/// Row of holder_snapshots, written by the listen-data holder-snapshots job
+#[derive(Debug, Clone, Deserialize, Row, Serialize)]
+pub struct HolderRow {
+    pub timestamp: u64,
+    pub rank: u32,
+    pub owner: String,
+    pub amount: f64,
+    pub percentage: f64,
+    pub holder_count: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ToSchema)]
+pub struct Holder {
+    pub rank: u32,
+    pub owner: String,
+    pub amount: f64,
+    /// share of the supply, 0 to 1
+    pub percentage: f64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ToSchema)]
+pub struct HolderSnapshot {
+    pub mint: String,
+    pub timestamp: u64,
+    pub holder_count: u64,
+    pub holders: Vec<Holder>,
+}
+
+impl HolderSnapshot {
+    /// Rows are of a single snapshot, ordered by rank
+    pub fn from_rows(mint: &str, rows: Vec<HolderRow>) -> Option<Self> {
+        let first = rows.first()?;
+        Some(Self {
+            mint: mint.to_string(),
+            timestamp: first.timestamp,
+            holder_count: first.holder_count,
+            holders: rows
+                .into_iter()
+                .map(|row| Holder {
+                    rank: row.rank,
+                    owner: row.owner,
+                    amount: row.amount,
+                    percentage: row.percentage,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl ClickhouseDb {
+    /// Latest snapshot of the mint taken at or before `at`
+    pub async fn get_holder_snapshot(
+        &self,
+        mint: &str,
+        at: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<Option<HolderSnapshot>> {
+        let at = at.unwrap_or(u64::MAX);
+        let limit = limit.unwrap_or(DEFAULT_HOLDERS).min(MAX_HOLDERS);
+        let rows = self
+            .client
+            .query(
+                r#"
+                SELECT timestamp, rank, owner, amount, percentage, holder_count
+                FROM holder_snapshots
+                WHERE mint = ? AND timestamp = (
+                    SELECT max(timestamp)
+                    FROM holder_snapshots
+                    WHERE mint = ? AND timestamp <= ?
+                )
+                ORDER BY rank
+                LIMIT ?
+                "#,
+            )
+            .bind(mint)
+            .bind(mint)
+            .bind(at)
+            .bind(limit)
+            .fetch_all::<HolderRow>()
+            .await?;
+        Ok(HolderSnapshot::from_rows(mint, rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_from_rows() {
+        assert_eq!(HolderSnapshot::from_rows("mint", vec![]), None);
+
+        let row = |rank, percentage| HolderRow {
+            timestamp: 42,
+            rank,
+            owner: format!("owner{}", rank),
+            amount: percentage * 1000.0,
+            percentage,
+            holder_count: 7,
+        };
+        let snapshot = HolderSnapshot::from_rows("mint", vec![row(1, 0.5), row(2, 0.25)]).unwrap();
+        assert_eq!(snapshot.timestamp, 42);
+        assert_eq!(snapshot.holder_count, 7);
+        assert_eq!(snapshot.holders.len(), 2);
+        assert_eq!(snapshot.holders[1].owner, "owner2");
+    }
+}
@@ -3,12 +3,17 @@ use clickhouse::{Client, Row};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::debug;
+use utoipa::ToSchema;
 
+pub mod analytics;
 pub mod candlesticks;
+pub mod holders;
 pub mod query;
 pub mod top_tokens;
+pub mod trending;
+pub mod wallets;
 
-#[derive(Debug, Deserialize, Row, Serialize)]
+#[derive(Debug, Clone, Deserialize, Row, Serialize, ToSchema)]
 pub struct PriceUpdate {
     pub name: String,
     pub pubkey: String,
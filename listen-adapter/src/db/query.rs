@@ -2,33 +2,87 @@ use crate::db::{ClickhouseDb, PriceUpdate};
 use anyhow::Result;
 use clickhouse::Row;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Row, Serialize)]
+#[derive(Debug, Deserialize, Row, Serialize, ToSchema)]
 pub struct OpenPrice {
     pub price: f64,
     pub timestamp: u64,
 }
 
+#[derive(Debug, Deserialize, Row)]
+struct MintVolume {
+    pubkey: String,
+    volume: f64,
+}
+
 impl ClickhouseDb {
     pub async fn get_by_mint(&self, mint: &str) -> Result<Vec<PriceUpdate>> {
-        let query = format!(
-            r#"
-            SELECT * FROM price_updates
-            WHERE pubkey = '{mint}'
-            ORDER BY timestamp DESC
-            LIMIT 50
-            "#
-        );
-
-        let result = self.client.query(&query).fetch_all::<PriceUpdate>().await?;
+        let result = self
+            .client
+            .query(
+                r#"
+                SELECT ?fields FROM price_updates
+                WHERE pubkey = ?
+                ORDER BY timestamp DESC
+                LIMIT 50
+                "#,
+            )
+            .bind(mint)
+            .fetch_all::<PriceUpdate>()
+            .await?;
 
         Ok(result)
     }
 
-    pub async fn generic_query(&self, sql: &str) -> Result<Vec<PriceUpdate>> {
-        let result = self.client.query(sql).fetch_all::<PriceUpdate>().await?;
+    /// Swap volume over the last 24h of each of the mints, mints without
+    /// swaps are left out
+    pub async fn get_volumes_24h(&self, mints: &[String]) -> Result<HashMap<String, f64>> {
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let start_time = current_time - 86400;
+
+        let result = self
+            .client
+            .query(
+                r#"
+                SELECT pubkey, sum(swap_amount) AS volume
+                FROM price_updates
+                WHERE pubkey IN ?
+                AND timestamp >= ?
+                GROUP BY pubkey
+                "#,
+            )
+            .bind(mints)
+            .bind(start_time)
+            .fetch_all::<MintVolume>()
+            .await?;
 
-        Ok(result)
+        Ok(result.into_iter().map(|v| (v.pubkey, v.volume)).collect())
+    }
+
+    /// Swap volume over the last 24h of every mint that traded, for ranking
+    /// search matches before their volume is looked up
+    pub async fn get_all_volumes_24h(&self) -> Result<HashMap<String, f64>> {
+        let start_time = chrono::Utc::now().timestamp() as u64 - 86400;
+
+        let result = self
+            .client
+            .query(
+                r#"
+                SELECT pubkey, sum(swap_amount) AS volume
+                FROM price_updates
+                WHERE timestamp >= ?
+                GROUP BY pubkey
+                "#,
+            )
+            .bind(start_time)
+            .fetch_all::<MintVolume>()
+            .await?;
+
+        Ok(result.into_iter().map(|v| (v.pubkey, v.volume)).collect())
     }
 
     pub async fn get_24h_open_price(&self, mint: &str) -> Result<Option<OpenPrice>> {
@@ -38,22 +92,22 @@ impl ClickhouseDb {
         // Get the start of the current day in UTC
         let day_start = current_time - (current_time % 86400);
 
-        let query = format!(
-            r#"
-            SELECT 
-                price,
-                timestamp
-            FROM price_updates
-            WHERE pubkey = '{mint}'
-            AND timestamp >= {day_start}
-            ORDER BY timestamp ASC
-            LIMIT 1
-            "#
-        );
-
         let result = self
             .client
-            .query(&query)
+            .query(
+                r#"
+                SELECT
+                    price,
+                    timestamp
+                FROM price_updates
+                WHERE pubkey = ?
+                AND timestamp >= ?
+                ORDER BY timestamp ASC
+                LIMIT 1
+                "#,
+            )
+            .bind(mint)
+            .bind(day_start)
             .fetch_optional::<OpenPrice>()
             .await?;
         Ok(result)
@@ -74,13 +128,6 @@ mod tests {
         println!("{:#?}", result);
     }
 
-    #[tokio::test]
-    async fn test_generic_query() {
-        let db = make_db().unwrap();
-        let result = db.generic_query("SELECT 1").await.unwrap();
-        println!("{:#?}", result);
-    }
-
     #[tokio::test]
     async fn test_get_24h_open_price() {
         let db = make_db().unwrap();
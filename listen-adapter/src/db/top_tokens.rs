@@ -2,8 +2,9 @@ use super::ClickhouseDb;
 use anyhow::Result;
 use clickhouse::Row;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize, Row)]
+#[derive(Debug, Serialize, Deserialize, Row, ToSchema)]
 pub struct TopToken {
     pub name: String,
     pub pubkey: String,
@@ -50,7 +51,7 @@ impl ClickhouseDb {
                         name,
                         pubkey,
                         sum(swap_amount) as volume_24h
-                    FROM price_updates
+                    FROM price_updates FINAL
                     WHERE timestamp >= {start_time}
                     GROUP BY name, pubkey
                 ),
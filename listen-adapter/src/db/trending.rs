@@ -0,0 +1,392 @@
+//! Trending rankings over the recent price updates.
+//!
+//! Every mode ranks the tokens traded within a window on a different
+//! signal and takes its own filters on top of the shared volume and market
+//! cap ones.
+
+use super::ClickhouseDb;
+use anyhow::Result;
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::{IntoParams, ToSchema};
+
+pub const MAX_TRENDING: usize = 100;
+pub const DEFAULT_TRENDING: usize = 20;
+pub const DEFAULT_MIN_PREVIOUS_VOLUME: f64 = 1000.0;
+pub const DEFAULT_MIN_TRADERS: u64 = 10;
+pub const DEFAULT_MIN_TRADES: u64 = 20;
+pub const DEFAULT_MAX_AGE_SECS: u64 = 3600;
+pub const MAX_AGE_SECS: u64 = 86400;
+/// Price changes are always reported up to the widest of these windows
+const PRICE_CHANGE_WINDOWS: [(&str, u64); 4] =
+    [("5m", 300), ("1h", 3600), ("6h", 21600), ("24h", 86400)];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TrendingError {
+    #[error("`{filter}` does not apply to mode={mode}")]
+    FilterNotApplicable {
+        filter: &'static str,
+        mode: &'static str,
+    },
+
+    #[error("max_age_secs must be between 1 and {MAX_AGE_SECS}")]
+    InvalidMaxAge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendingMode {
+    /// volume within the window relative to the window before it
+    VolumeAcceleration,
+    /// distinct wallets trading within the window
+    UniqueTraders,
+    /// share of buys among the trades within the window
+    BuyPressure,
+    /// tokens that started trading recently, newest first
+    NewTokens,
+    /// price change within the window
+    PriceChange,
+}
+
+impl TrendingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrendingMode::VolumeAcceleration => "volume_acceleration",
+            TrendingMode::UniqueTraders => "unique_traders",
+            TrendingMode::BuyPressure => "buy_pressure",
+            TrendingMode::NewTokens => "new_tokens",
+            TrendingMode::PriceChange => "price_change",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize, ToSchema)]
+pub enum TrendingWindow {
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+    #[default]
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "6h")]
+    SixHours,
+    #[serde(rename = "24h")]
+    OneDay,
+}
+
+impl TrendingWindow {
+    pub fn seconds(&self) -> u64 {
+        match self {
+            TrendingWindow::FiveMinutes => 300,
+            TrendingWindow::FifteenMinutes => 900,
+            TrendingWindow::OneHour => 3600,
+            TrendingWindow::SixHours => 21600,
+            TrendingWindow::OneDay => 86400,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Up,
+    Down,
+}
+
+/// Query string of `GET /trending`
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+pub struct TrendingParams {
+    pub mode: TrendingMode,
+    #[serde(default)]
+    pub window: TrendingWindow,
+    pub limit: Option<usize>,
+    // shared filters
+    pub min_volume: Option<f64>,
+    pub min_market_cap: Option<f64>,
+    pub max_market_cap: Option<f64>,
+    pub only_pumpfun_tokens: Option<bool>,
+    // volume_acceleration
+    pub min_previous_volume: Option<f64>,
+    // unique_traders
+    pub min_traders: Option<u64>,
+    // buy_pressure
+    pub min_trades: Option<u64>,
+    // new_tokens
+    pub max_age_secs: Option<u64>,
+    // price_change
+    pub direction: Option<Direction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Row, Serialize, ToSchema)]
+pub struct TrendingToken {
+    pub name: String,
+    pub pubkey: String,
+    pub price: f64,
+    pub market_cap: f64,
+    pub is_pump: bool,
+    pub volume: f64,
+    pub previous_volume: f64,
+    /// volume over the previous window's, None if there was none
+    pub volume_acceleration: Option<f64>,
+    pub unique_traders: u64,
+    pub buys: u64,
+    pub sells: u64,
+    pub buy_ratio: f64,
+    pub first_seen: u64,
+    pub age_secs: u64,
+    pub price_change_5m: f64,
+    pub price_change_1h: f64,
+    pub price_change_6h: f64,
+    pub price_change_24h: f64,
+    /// price change within the requested window
+    pub price_change_window: f64,
+}
+
+impl TrendingParams {
+    fn check_filters(&self) -> Result<(), TrendingError> {
+        let mode_filters: [(&'static str, bool, TrendingMode); 5] = [
+            (
+                "min_previous_volume",
+                self.min_previous_volume.is_some(),
+                TrendingMode::VolumeAcceleration,
+            ),
+            (
+                "min_traders",
+                self.min_traders.is_some(),
+                TrendingMode::UniqueTraders,
+            ),
+            (
+                "min_trades",
+                self.min_trades.is_some(),
+                TrendingMode::BuyPressure,
+            ),
+            (
+                "max_age_secs",
+                self.max_age_secs.is_some(),
+                TrendingMode::NewTokens,
+            ),
+            (
+                "direction",
+                self.direction.is_some(),
+                TrendingMode::PriceChange,
+            ),
+        ];
+        for (filter, set, mode) in mode_filters {
+            if set && mode != self.mode {
+                return Err(TrendingError::FilterNotApplicable {
+                    filter,
+                    mode: self.mode.as_str(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles the ranking to SQL, every interpolated value is numeric.
+    /// Aggregates are aliased apart from the columns so they do not shadow
+    /// them in the other aggregates; rows are read by position.
+    pub fn to_sql(&self, now: u64) -> Result<String, TrendingError> {
+        self.check_filters()?;
+
+        let window = self.window.seconds();
+        let window_start = now.saturating_sub(window);
+        let previous_start = now.saturating_sub(2 * window);
+        let mut scan_start = previous_start.min(now.saturating_sub(86400));
+
+        let mut having = vec!["volume > 0".to_string()];
+        if let Some(min_volume) = self.min_volume {
+            having.push(format!("volume >= {min_volume}"));
+        }
+        if let Some(min_market_cap) = self.min_market_cap {
+            having.push(format!("latest_market_cap >= {min_market_cap}"));
+        }
+        if let Some(max_market_cap) = self.max_market_cap {
+            having.push(format!("latest_market_cap <= {max_market_cap}"));
+        }
+        if self.only_pumpfun_tokens.unwrap_or(false) {
+            having.push("any_pump".to_string());
+        }
+
+        let mut where_clause = String::new();
+        let order_by = match self.mode {
+            TrendingMode::VolumeAcceleration => {
+                let min_previous_volume = self
+                    .min_previous_volume
+                    .unwrap_or(DEFAULT_MIN_PREVIOUS_VOLUME);
+                having.push(format!("previous_volume >= {min_previous_volume}"));
+                "volume_acceleration DESC, volume DESC".to_string()
+            }
+            TrendingMode::UniqueTraders => {
+                let min_traders = self.min_traders.unwrap_or(DEFAULT_MIN_TRADERS);
+                having.push(format!("unique_traders >= {min_traders}"));
+                "unique_traders DESC, volume DESC".to_string()
+            }
+            TrendingMode::BuyPressure => {
+                let min_trades = self.min_trades.unwrap_or(DEFAULT_MIN_TRADES);
+                having.push(format!("buys + sells >= {min_trades}"));
+                "buy_ratio DESC, volume DESC".to_string()
+            }
+            TrendingMode::NewTokens => {
+                let max_age = self.max_age_secs.unwrap_or(DEFAULT_MAX_AGE_SECS);
+                if max_age == 0 || max_age > MAX_AGE_SECS {
+                    return Err(TrendingError::InvalidMaxAge);
+                }
+                let born_after = now.saturating_sub(max_age);
+                // tokens with daily candles before the day they would have
+                // been born are not new, the scan covers the rest of it
+                let day_start = born_after - born_after % 86400;
+                scan_start = scan_start.min(day_start);
+                where_clause = format!(
+                    "AND pubkey NOT IN (SELECT DISTINCT pubkey FROM candlesticks_1d WHERE interval_timestamp < {day_start})"
+                );
+                having.push(format!("first_seen >= {born_after}"));
+                "first_seen DESC, volume DESC".to_string()
+            }
+            TrendingMode::PriceChange => match self.direction.unwrap_or_default() {
+                Direction::Up => "price_change_window DESC, volume DESC".to_string(),
+                Direction::Down => "price_change_window ASC, volume DESC".to_string(),
+            },
+        };
+
+        let price_changes = PRICE_CHANGE_WINDOWS
+            .iter()
+            .map(|(suffix, seconds)| price_change_sql(suffix, now.saturating_sub(*seconds)))
+            .chain(std::iter::once(price_change_sql("window", window_start)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        let limit = self.limit.unwrap_or(DEFAULT_TRENDING).min(MAX_TRENDING);
+        let having = having.join(" AND ");
+
+        Ok(format!(
+            r#"
+            SELECT
+                argMax(name, timestamp) AS latest_name,
+                pubkey,
+                argMax(price, timestamp) AS latest_price,
+                argMax(market_cap, timestamp) AS latest_market_cap,
+                max(is_pump) AS any_pump,
+                sumIf(swap_amount, timestamp >= {window_start}) AS volume,
+                sumIf(swap_amount, timestamp >= {previous_start} AND timestamp < {window_start}) AS previous_volume,
+                if(previous_volume > 0, volume / previous_volume, NULL) AS volume_acceleration,
+                uniqExactIf(owner, timestamp >= {window_start}) AS unique_traders,
+                countIf(is_buy AND timestamp >= {window_start}) AS buys,
+                countIf(NOT is_buy AND timestamp >= {window_start}) AS sells,
+                if(buys + sells > 0, buys / (buys + sells), 0) AS buy_ratio,
+                min(timestamp) AS first_seen,
+                toUInt64(greatest(toInt64({now}) - toInt64(first_seen), 0)) AS age_secs,
+                {price_changes}
+            FROM price_updates
+            WHERE timestamp >= {scan_start}
+            {where_clause}
+            GROUP BY pubkey
+            HAVING {having}
+            ORDER BY {order_by}
+            LIMIT {limit}
+            "#
+        ))
+    }
+}
+
+/// Percent change from the first price at or after `since` to the latest
+fn price_change_sql(suffix: &str, since: u64) -> String {
+    format!(
+        "if(argMinIf(price, timestamp, timestamp >= {since}) > 0, \
+         (argMax(price, timestamp) - argMinIf(price, timestamp, timestamp >= {since})) \
+         / argMinIf(price, timestamp, timestamp >= {since}) * 100, 0) AS price_change_{suffix}"
+    )
+}
+
+impl ClickhouseDb {
+    pub async fn get_trending(&self, sql: &str) -> Result<Vec<TrendingToken>> {
+        let result = self.client.query(sql).fetch_all::<TrendingToken>().await?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn params(query: &str) -> TrendingParams {
+        actix_web::web::Query::<TrendingParams>::from_query(query)
+            .unwrap()
+            .into_inner()
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let p = params("mode=unique_traders&window=5m&min_traders=25&limit=10");
+        assert_eq!(p.mode, TrendingMode::UniqueTraders);
+        assert_eq!(p.window, TrendingWindow::FiveMinutes);
+        assert_eq!(p.min_traders, Some(25));
+
+        let p = params("mode=new_tokens");
+        assert_eq!(p.window, TrendingWindow::OneHour);
+
+        assert!(actix_web::web::Query::<TrendingParams>::from_query("mode=hot").is_err());
+    }
+
+    #[test]
+    fn test_mode_filters() {
+        assert_eq!(
+            params("mode=new_tokens&min_traders=5").to_sql(NOW),
+            Err(TrendingError::FilterNotApplicable {
+                filter: "min_traders",
+                mode: "new_tokens",
+            })
+        );
+        assert_eq!(
+            params("mode=new_tokens&max_age_secs=172800").to_sql(NOW),
+            Err(TrendingError::InvalidMaxAge)
+        );
+
+        let sql = params("mode=unique_traders&min_traders=25")
+            .to_sql(NOW)
+            .unwrap();
+        assert!(sql.contains("unique_traders >= 25"));
+        assert!(sql.contains("ORDER BY unique_traders DESC"));
+
+        let sql = params("mode=volume_acceleration&window=1h")
+            .to_sql(NOW)
+            .unwrap();
+        assert!(sql.contains(&format!("timestamp >= {}", NOW - 7200)));
+        assert!(sql.contains("previous_volume >= 1000"));
+
+        let sql = params("mode=buy_pressure&min_volume=5000")
+            .to_sql(NOW)
+            .unwrap();
+        assert!(sql.contains("buys + sells >= 20"));
+        assert!(sql.contains("volume >= 5000"));
+
+        let sql = params("mode=price_change&direction=down&window=6h")
+            .to_sql(NOW)
+            .unwrap();
+        assert!(sql.contains("ORDER BY price_change_window ASC"));
+    }
+
+    #[test]
+    fn test_new_tokens_sql() {
+        let sql = params("mode=new_tokens&max_age_secs=600")
+            .to_sql(NOW)
+            .unwrap();
+        let born_after = NOW - 600;
+        let day_start = born_after - born_after % 86400;
+        assert!(sql.contains(&format!("first_seen >= {born_after}")));
+        assert!(sql.contains(&format!("interval_timestamp < {day_start}")));
+        assert!(sql.contains(&format!("WHERE timestamp >= {}", NOW - 86400)));
+    }
+
+    #[test]
+    fn test_limit_is_capped() {
+        let sql = params("mode=unique_traders&limit=100000")
+            .to_sql(NOW)
+            .unwrap();
+        assert!(sql.contains(&format!("LIMIT {MAX_TRENDING}")));
+    }
+}
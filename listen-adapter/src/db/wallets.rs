@@ -0,0 +1,463 @@
+//! Trade history and PnL of the wallets paying for the indexed swaps.
+//!
+//! Token amounts are derived from the USD value of each swap and the price
+//! it executed at, so only tokens acquired through indexed swaps have a
+//! cost basis.
+
+use super::{ClickhouseDb, PriceUpdate};
+use anyhow::Result;
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use utoipa::ToSchema;
+
+pub const MAX_WALLET_TRADES: usize = 500;
+pub const DEFAULT_WALLET_TRADES: usize = 50;
+/// Most trades replayed when computing the PnL of a wallet
+pub const MAX_PNL_TRADES: usize = 20_000;
+pub const MAX_LEADERBOARD: usize = 100;
+pub const DEFAULT_LEADERBOARD: usize = 20;
+
+/// Solana addresses are 32 bytes of base58, 32 to 44 characters
+pub fn is_valid_address(address: &str) -> bool {
+    (32..=44).contains(&address.len())
+        && address
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l'))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct TokenPnl {
+    pub mint: String,
+    pub name: String,
+    pub trades: usize,
+    pub bought_amount: f64,
+    pub bought_usd: f64,
+    pub sold_amount: f64,
+    pub sold_usd: f64,
+    pub realized_pnl: f64,
+    /// tokens still held out of the indexed buys
+    pub holding: f64,
+    /// cost of the held tokens
+    pub cost_basis: f64,
+    pub current_price: Option<f64>,
+    pub unrealized_pnl: f64,
+    /// sells of tokens that were not bought through indexed swaps, these
+    /// have no cost basis and are left out of the realized PnL
+    pub unmatched_sold_amount: f64,
+    pub unmatched_sold_usd: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct WalletPnl {
+    pub wallet: String,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub total_pnl: f64,
+    /// whether older trades were left out
+    pub truncated: bool,
+    pub tokens: Vec<TokenPnl>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Row, Serialize, ToSchema)]
+pub struct LeaderboardEntry {
+    pub wallet: String,
+    pub trades: u64,
+    pub bought_usd: f64,
+    pub sold_usd: f64,
+    /// tokens held out of the indexed swaps, never negative
+    pub holding: f64,
+    pub holding_usd: f64,
+    /// proceeds and value held minus the cost, sells of tokens that were not
+    /// bought through indexed swaps are left out like the FIFO matching
+    /// does, so it equals the FIFO realized plus unrealized PnL
+    pub total_pnl: f64,
+}
+
+#[derive(Debug, Deserialize, Row)]
+struct LatestPrice {
+    pubkey: String,
+    price: f64,
+}
+
+/// Replays the trades (oldest first) of a single token with FIFO lots
+fn token_pnl(trades: &[&PriceUpdate], current_price: Option<f64>) -> TokenPnl {
+    let mut pnl = TokenPnl {
+        mint: trades[0].pubkey.clone(),
+        name: trades[trades.len() - 1].name.clone(),
+        trades: trades.len(),
+        current_price,
+        ..Default::default()
+    };
+    // (amount, price) of the tokens still held
+    let mut lots: VecDeque<(f64, f64)> = VecDeque::new();
+
+    for trade in trades {
+        if trade.price <= 0.0 || trade.swap_amount <= 0.0 {
+            continue;
+        }
+        let amount = trade.swap_amount / trade.price;
+        if trade.is_buy {
+            pnl.bought_amount += amount;
+            pnl.bought_usd += trade.swap_amount;
+            lots.push_back((amount, trade.price));
+            continue;
+        }
+
+        pnl.sold_amount += amount;
+        pnl.sold_usd += trade.swap_amount;
+        let mut remaining = amount;
+        while remaining > 0.0 {
+            let Some((lot_amount, lot_price)) = lots.front_mut() else {
+                break;
+            };
+            let matched = remaining.min(*lot_amount);
+            pnl.realized_pnl += matched * (trade.price - *lot_price);
+            *lot_amount -= matched;
+            remaining -= matched;
+            if *lot_amount <= f64::EPSILON * amount.max(1.0) {
+                lots.pop_front();
+            }
+        }
+        if remaining > 0.0 {
+            pnl.unmatched_sold_amount += remaining;
+            pnl.unmatched_sold_usd += remaining * trade.price;
+        }
+    }
+
+    pnl.holding = lots.iter().map(|(amount, _)| amount).sum();
+    pnl.cost_basis = lots.iter().map(|(amount, price)| amount * price).sum();
+    if let Some(price) = current_price {
+        pnl.unrealized_pnl = pnl.holding * price - pnl.cost_basis;
+    }
+    pnl
+}
+
+/// PnL per token of a wallet's trades, biggest absolute PnL first
+pub fn wallet_pnl(
+    wallet: &str,
+    trades: &[PriceUpdate],
+    current_prices: &HashMap<String, f64>,
+) -> WalletPnl {
+    let mut by_mint: HashMap<&str, Vec<&PriceUpdate>> = HashMap::new();
+    for trade in trades {
+        by_mint.entry(&trade.pubkey).or_default().push(trade);
+    }
+
+    let mut tokens: Vec<TokenPnl> = by_mint
+        .into_values()
+        .map(|mut trades| {
+            trades.sort_by_key(|t| (t.timestamp, t.slot));
+            let price = current_prices.get(&trades[0].pubkey).copied();
+            token_pnl(&trades, price)
+        })
+        .collect();
+    tokens.sort_by(|a, b| {
+        let total = |t: &TokenPnl| (t.realized_pnl + t.unrealized_pnl).abs();
+        total(b)
+            .total_cmp(&total(a))
+            .then_with(|| a.mint.cmp(&b.mint))
+    });
+
+    let realized_pnl = tokens.iter().map(|t| t.realized_pnl).sum();
+    let unrealized_pnl = tokens.iter().map(|t| t.unrealized_pnl).sum();
+    WalletPnl {
+        wallet: wallet.to_string(),
+        realized_pnl,
+        unrealized_pnl,
+        total_pnl: realized_pnl + unrealized_pnl,
+        truncated: trades.len() >= MAX_PNL_TRADES,
+        tokens,
+    }
+}
+
+impl ClickhouseDb {
+    /// Most recent trades of a wallet, optionally of a single token and
+    /// before a timestamp for paging
+    pub async fn get_wallet_trades(
+        &self,
+        wallet: &str,
+        mint: Option<&str>,
+        before: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<PriceUpdate>> {
+        let limit = limit
+            .unwrap_or(DEFAULT_WALLET_TRADES)
+            .min(MAX_WALLET_TRADES);
+        let result = self
+            .client
+            .query(
+                r#"
+                SELECT ?fields FROM price_updates FINAL
+                WHERE owner = ?
+                AND (? = '' OR pubkey = ?)
+                AND timestamp < ?
+                ORDER BY timestamp DESC, slot DESC
+                LIMIT ?
+                "#,
+            )
+            .bind(wallet)
+            .bind(mint.unwrap_or_default())
+            .bind(mint.unwrap_or_default())
+            .bind(before.unwrap_or(u64::MAX))
+            .bind(limit)
+            .fetch_all::<PriceUpdate>()
+            .await?;
+        Ok(result)
+    }
+
+    pub async fn get_wallet_pnl(&self, wallet: &str, mint: Option<&str>) -> Result<WalletPnl> {
+        let mut trades = self
+            .get_wallet_trades_for_pnl(wallet, mint.unwrap_or_default())
+            .await?;
+        // fetched newest first so the cap drops the oldest trades
+        trades.reverse();
+
+        let mut mints: Vec<String> = trades.iter().map(|t| t.pubkey.clone()).collect();
+        mints.sort();
+        mints.dedup();
+        let current_prices = self.get_latest_prices(&mints).await?;
+
+        Ok(wallet_pnl(wallet, &trades, &current_prices))
+    }
+
+    async fn get_wallet_trades_for_pnl(
+        &self,
+        wallet: &str,
+        mint: &str,
+    ) -> Result<Vec<PriceUpdate>> {
+        let result = self
+            .client
+            .query(
+                r#"
+                SELECT ?fields FROM price_updates FINAL
+                WHERE owner = ?
+                AND (? = '' OR pubkey = ?)
+                ORDER BY timestamp DESC, slot DESC
+                LIMIT ?
+                "#,
+            )
+            .bind(wallet)
+            .bind(mint)
+            .bind(mint)
+            .bind(MAX_PNL_TRADES)
+            .fetch_all::<PriceUpdate>()
+            .await?;
+        Ok(result)
+    }
+
+    pub async fn get_latest_prices(&self, mints: &[String]) -> Result<HashMap<String, f64>> {
+        if mints.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let result = self
+            .client
+            .query(
+                r#"
+                SELECT pubkey, argMax(price, timestamp) AS latest_price
+                FROM price_updates
+                WHERE pubkey IN ?
+                GROUP BY pubkey
+                "#,
+            )
+            .bind(mints)
+            .fetch_all::<LatestPrice>()
+            .await?;
+        Ok(result.into_iter().map(|p| (p.pubkey, p.price)).collect())
+    }
+
+    /// Wallets with the highest PnL on a token
+    pub async fn get_token_leaderboard(
+        &self,
+        mint: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        let limit = limit.unwrap_or(DEFAULT_LEADERBOARD).min(MAX_LEADERBOARD);
+        let result = self
+            .client
+            .query(
+                r#"
+                WITH (
+                    SELECT argMax(price, timestamp)
+                    FROM price_updates
+                    WHERE pubkey = ?
+                ) AS current_price
+                SELECT
+                    owner AS wallet,
+                    count() AS trades,
+                    sumIf(swap_amount, is_buy) AS bought_usd,
+                    sumIf(swap_amount, NOT is_buy) AS sold_usd,
+                    greatest(
+                        sumIf(amount, is_buy) - sumIf(amount, NOT is_buy) + max(unmatched),
+                        0
+                    ) AS holding,
+                    holding * current_price AS holding_usd,
+                    sold_usd - sum(unmatched_usd) + holding_usd - bought_usd AS total_pnl
+                FROM (
+                    -- the part of each sell that went past the buys before it
+                    SELECT
+                        owner,
+                        is_buy,
+                        swap_amount,
+                        amount,
+                        unmatched,
+                        (unmatched - lagInFrame(unmatched, 1, toFloat64(0)) OVER w) * price AS unmatched_usd
+                    FROM (
+                        -- tokens sold without indexed buys so far, the most
+                        -- the running balance ever went negative
+                        SELECT
+                            owner,
+                            is_buy,
+                            swap_amount,
+                            price,
+                            amount,
+                            timestamp,
+                            slot,
+                            greatest(max(oversold) OVER w, 0) AS unmatched
+                        FROM (
+                            SELECT
+                                owner,
+                                is_buy,
+                                swap_amount,
+                                price,
+                                timestamp,
+                                slot,
+                                swap_amount / price AS amount,
+                                sum(if(is_buy, -amount, amount)) OVER w AS oversold
+                            FROM price_updates FINAL
+                            WHERE pubkey = ? AND price > 0 AND swap_amount > 0
+                            WINDOW w AS (PARTITION BY owner ORDER BY timestamp, slot ROWS UNBOUNDED PRECEDING)
+                        )
+                        WINDOW w AS (PARTITION BY owner ORDER BY timestamp, slot ROWS UNBOUNDED PRECEDING)
+                    )
+                    WINDOW w AS (PARTITION BY owner ORDER BY timestamp, slot ROWS UNBOUNDED PRECEDING)
+                )
+                GROUP BY owner
+                ORDER BY total_pnl DESC
+                LIMIT ?
+                "#,
+            )
+            .bind(mint)
+            .bind(mint)
+            .bind(limit)
+            .fetch_all::<LeaderboardEntry>()
+            .await?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(
+        mint: &str,
+        price: f64,
+        swap_amount: f64,
+        is_buy: bool,
+        timestamp: u64,
+    ) -> PriceUpdate {
+        PriceUpdate {
+            name: "Test".to_string(),
+            pubkey: mint.to_string(),
+            price,
+            market_cap: 0.0,
+            timestamp,
+            slot: timestamp,
+            swap_amount,
+            owner: "wallet".to_string(),
+            signature: format!("sig{timestamp}"),
+            multi_hop: false,
+            is_buy,
+            is_pump: false,
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_fifo_pnl() {
+        let trades = vec![
+            // 100 tokens at 1, then 100 at 2
+            trade("a", 1.0, 100.0, true, 1),
+            trade("a", 2.0, 200.0, true, 2),
+            // 150 tokens at 3: 100 from the first lot, 50 from the second
+            trade("a", 3.0, 450.0, false, 3),
+        ];
+        let prices = HashMap::from([("a".to_string(), 4.0)]);
+        let pnl = wallet_pnl("wallet", &trades, &prices);
+
+        let token = &pnl.tokens[0];
+        assert_close(token.realized_pnl, 100.0 * 2.0 + 50.0 * 1.0);
+        assert_close(token.holding, 50.0);
+        assert_close(token.cost_basis, 100.0);
+        assert_close(token.unrealized_pnl, 50.0 * 4.0 - 100.0);
+        assert_close(pnl.total_pnl, 250.0 + 100.0);
+        // total PnL does not depend on the lot matching
+        assert_close(
+            pnl.total_pnl,
+            token.sold_usd + token.holding * 4.0 - token.bought_usd,
+        );
+    }
+
+    #[test]
+    fn test_trades_are_replayed_in_order() {
+        let trades = vec![
+            trade("a", 3.0, 300.0, false, 3),
+            trade("a", 1.0, 100.0, true, 1),
+        ];
+        let pnl = wallet_pnl("wallet", &trades, &HashMap::new());
+        assert_close(pnl.tokens[0].realized_pnl, 200.0);
+        assert_close(pnl.tokens[0].unmatched_sold_amount, 0.0);
+        // without a current price nothing is unrealized
+        assert_eq!(pnl.tokens[0].current_price, None);
+    }
+
+    #[test]
+    fn test_unmatched_sells() {
+        let trades = vec![
+            trade("a", 1.0, 10.0, true, 1),
+            // 20 tokens sold, only 10 were bought through indexed swaps
+            trade("a", 2.0, 40.0, false, 2),
+        ];
+        let pnl = wallet_pnl("wallet", &trades, &HashMap::new());
+        let token = &pnl.tokens[0];
+        assert_close(token.realized_pnl, 10.0);
+        assert_close(token.unmatched_sold_amount, 10.0);
+        assert_close(token.unmatched_sold_usd, 20.0);
+        assert_close(token.holding, 0.0);
+    }
+
+    #[test]
+    fn test_tokens_are_separate() {
+        let trades = vec![
+            trade("a", 1.0, 100.0, true, 1),
+            trade("b", 5.0, 50.0, true, 2),
+            trade("b", 10.0, 100.0, false, 3),
+        ];
+        let prices = HashMap::from([("a".to_string(), 0.8), ("b".to_string(), 10.0)]);
+        let pnl = wallet_pnl("wallet", &trades, &prices);
+        assert_eq!(pnl.tokens.len(), 2);
+        assert_eq!(pnl.tokens[0].mint, "b");
+        assert_close(pnl.tokens[0].realized_pnl, 50.0);
+        assert_close(pnl.tokens[1].unrealized_pnl, -20.0);
+        assert_close(pnl.realized_pnl, 50.0);
+        assert_close(pnl.unrealized_pnl, -20.0);
+    }
+
+    #[test]
+    fn test_is_valid_address() {
+        assert!(is_valid_address(
+            "9BB6NFEcjBCtnNLFko2FqVQBq8HHM13kCyYcdQbgpump"
+        ));
+        assert!(!is_valid_address("short"));
+        assert!(!is_valid_address(
+            "9BB6NFEcjBCtnNLFko2FqVQBq8HHM13kCyYcdQbgpum0"
+        ));
+        assert!(!is_valid_address(
+            "' OR 1=1 --aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+        ));
+    }
+}
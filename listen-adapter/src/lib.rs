@@ -1,8 +1,13 @@
+pub mod auth;
+pub mod cache;
 pub mod db;
 pub mod error;
+pub mod metrics;
+pub mod openapi;
 pub mod redis_client;
 pub mod redis_subscriber;
 pub mod routes;
+pub mod search;
 pub mod state;
 pub mod version;
 pub mod websocket;
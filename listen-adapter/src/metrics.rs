@@ -0,0 +1,56 @@
+use actix_web::{HttpResponse, Responder};
+use metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::OnceCell;
+
+static PROMETHEUS_HANDLE: OnceCell<PrometheusHandle> = OnceCell::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetricsError {
+    #[error("Failed to install metrics recorder")]
+    InstallRecorderError(BuildError),
+}
+
+pub fn setup_metrics_exporter() -> Result<PrometheusHandle, MetricsError> {
+    let handle = PrometheusBuilder::new()
+        .add_global_label("service", "listen-adapter")
+        .install_recorder()
+        .map_err(MetricsError::InstallRecorderError)?;
+
+    let _ = PROMETHEUS_HANDLE.set(handle.clone());
+
+    Ok(handle)
+}
+
+pub async fn metrics_handler() -> impl Responder {
+    let handle = PROMETHEUS_HANDLE
+        .get()
+        .expect("Prometheus handle not initialized");
+    let metrics = handle.render();
+
+    HttpResponse::Ok().content_type("text/plain").body(metrics)
+}
+
+pub fn init_metrics() {
+    let _handle = setup_metrics_exporter().expect("Failed to setup metrics exporter");
+
+    metrics::describe_counter!(
+        "adapter_requests",
+        "Number of requests, labeled by API key, scope and status"
+    );
+    metrics::describe_counter!(
+        "adapter_rate_limited",
+        "Number of requests rejected by the rate limit, labeled by API key"
+    );
+    metrics::describe_counter!(
+        "adapter_auth_failures",
+        "Number of requests with a missing or invalid API key, labeled by reason"
+    );
+    metrics::describe_counter!(
+        "adapter_rate_limit_errors",
+        "Number of requests let through because the rate limit could not be checked"
+    );
+    metrics::describe_counter!(
+        "adapter_cache",
+        "Number of cached endpoint lookups, labeled by endpoint and result"
+    );
+}
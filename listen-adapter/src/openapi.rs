@@ -0,0 +1,114 @@
+//! OpenAPI document of the REST routes, served at `/openapi.json`.
+//!
+//! A copy is checked in with listen-adapter-client, whose types are tested
+//! against it; `test_openapi_up_to_date` fails once the routes change
+//! until it is regenerated with `UPDATE_OPENAPI=1 cargo test openapi`.
+
+use actix_web::HttpResponse;
+use serde::Serialize;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::db::trending::{Direction, TrendingMode, TrendingWindow};
+use crate::version::VERSION;
+
+/// Body of the 4xx responses
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "listen-adapter", description = "Solana market data indexed by listen-data"),
+    paths(
+        crate::routes::top_tokens,
+        crate::routes::trending,
+        crate::routes::search,
+        crate::routes::get_candlesticks,
+        crate::routes::get_metadata,
+        crate::routes::get_price,
+        crate::routes::get_24h_open_price,
+        crate::routes::analytics_query,
+        crate::routes::get_wallet_trades,
+        crate::routes::get_wallet_pnl,
+        crate::routes::get_token_leaderboard,
+        crate::routes::get_token_holders,
+        crate::routes::get_chat,
+        crate::routes::save_chat,
+    ),
+    // enums only used by query parameters are not picked up from the paths
+    components(schemas(ErrorResponse, TrendingMode, TrendingWindow, Direction)),
+    modifiers(&SecurityAddon),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub struct ApiDoc;
+
+/// The document with the adapter version filled in
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    openapi.info.version = VERSION.to_string();
+    openapi
+}
+
+pub async fn openapi_handler() -> HttpResponse {
+    HttpResponse::Ok().json(openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_SPEC: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../listen-adapter-client/openapi.json"
+    );
+
+    #[test]
+    fn test_openapi_paths() {
+        let openapi = openapi();
+        for path in [
+            "/top-tokens",
+            "/candlesticks",
+            "/token/{mint}/holders",
+            "/wallet/{address}/pnl",
+        ] {
+            assert!(openapi.paths.paths.contains_key(path), "{}", path);
+        }
+        let schemas = openapi.components.unwrap().schemas;
+        for schema in ["TopToken", "Candlestick", "TokenMetadata", "WalletPnl"] {
+            assert!(schemas.contains_key(schema), "{}", schema);
+        }
+    }
+
+    #[test]
+    fn test_openapi_up_to_date() {
+        let spec = openapi().to_pretty_json().unwrap() + "\n";
+        if std::env::var("UPDATE_OPENAPI").is_ok() {
+            std::fs::write(CLIENT_SPEC, &spec).unwrap();
+            return;
+        }
+        let checked_in = std::fs::read_to_string(CLIENT_SPEC).unwrap_or_default();
+        assert!(
+            checked_in == spec,
+            "{} is out of date, regenerate it with UPDATE_OPENAPI=1 cargo test openapi",
+            CLIENT_SPEC
+        );
+    }
+}
@@ -1,14 +1,44 @@
 use anyhow::{Context, Result};
 use bb8_redis::{bb8, redis::cmd, RedisConnectionManager};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::auth::{ApiKey, RateLimit};
 
 pub struct RedisClient {
     pool: bb8::Pool<RedisConnectionManager>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Refills the bucket for the time since it was last touched and takes a
+/// token if there is one. Returns whether it was taken and, if not, the
+/// milliseconds until the next token. Buckets expire once they would be
+/// full again.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local refill_per_ms = tonumber(ARGV[2]) / 1000
+local now = tonumber(ARGV[3])
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * refill_per_ms)
+local taken = 0
+local retry_after = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    taken = 1
+else
+    retry_after = math.ceil((1 - tokens) / refill_per_ms)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / refill_per_ms) + 1000)
+return {taken, retry_after}
+"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct MplTokenMetadata {
     pub name: String,
     pub symbol: String,
@@ -16,7 +46,7 @@ pub struct MplTokenMetadata {
     pub ipfs_metadata: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct SplTokenMetadata {
     pub mint_authority: Option<String>,
     pub supply: u64,
@@ -25,13 +55,28 @@ pub struct SplTokenMetadata {
     pub freeze_authority: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct TokenMetadata {
     pub mint: String,
     pub mpl: MplTokenMetadata,
     pub spl: SplTokenMetadata,
 }
 
+/// Reserves of a single pool, as tracked by the indexer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolLiquidity {
+    pub pool: String,
+    pub dex: String,
+    pub mint: String,
+    pub quote_mint: String,
+    pub base_reserve: f64,
+    pub quote_reserve: f64,
+    pub price: f64,
+    pub tvl_usd: f64,
+    pub slot: u64,
+    pub timestamp: u64,
+}
+
 impl RedisClient {
     pub async fn new(redis_url: &str) -> Result<Self> {
         let manager = RedisConnectionManager::new(redis_url)?;
@@ -46,6 +91,9 @@ impl RedisClient {
     fn make_price_key(&self, mint: &str) -> String {
         format!("solana:price:{}", mint)
     }
+    fn make_liquidity_key(&self, mint: &str) -> String {
+        format!("solana:liquidity:{}", mint)
+    }
 
     pub async fn get_price(&self, mint: &str) -> Result<serde_json::Value> {
         let mut conn = self
@@ -99,10 +147,288 @@ impl RedisClient {
         }
     }
 
+    /// One SCAN step over the stored metadata, returns the next cursor (0
+    /// once done) and the metadata found in this step
+    pub async fn scan_metadata(
+        &self,
+        cursor: u64,
+        count: usize,
+    ) -> Result<(u64, Vec<TokenMetadata>)> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let (next, keys): (u64, Vec<String>) = cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(self.make_metadata_key("*"))
+            .arg("COUNT")
+            .arg(count)
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to scan metadata keys")?;
+        if keys.is_empty() {
+            return Ok((next, Vec::new()));
+        }
+
+        let values: Vec<Option<String>> = cmd("MGET")
+            .arg(&keys)
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to get metadata")?;
+        let metadata = values
+            .into_iter()
+            .flatten()
+            .filter_map(|json_str| match serde_json::from_str(&json_str) {
+                Ok(metadata) => Some(metadata),
+                Err(e) => {
+                    debug!("Skipping invalid metadata: {}", e);
+                    None
+                }
+            })
+            .collect();
+        Ok((next, metadata))
+    }
+
+    pub async fn get_liquidity(&self, mint: &str) -> Result<Vec<PoolLiquidity>> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let key = self.make_liquidity_key(mint);
+        let pools: Vec<String> = cmd("HVALS")
+            .arg(key)
+            .query_async(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to get liquidity for mint: {}", mint))?;
+        pools
+            .iter()
+            .map(|json_str| {
+                serde_json::from_str(json_str)
+                    .with_context(|| format!("Failed to deserialize liquidity for mint: {}", mint))
+            })
+            .collect()
+    }
+
+    /// Total value locked across the pools of each mint, mints without
+    /// tracked pools are left out
+    pub async fn get_liquidity_usd(&self, mints: &[String]) -> Result<HashMap<String, f64>> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let mut pipe = bb8_redis::redis::pipe();
+        for mint in mints {
+            pipe.cmd("HVALS").arg(self.make_liquidity_key(mint));
+        }
+        let pools: Vec<Vec<String>> = pipe
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to get liquidity")?;
+
+        Ok(mints
+            .iter()
+            .zip(pools)
+            .filter(|(_, pools)| !pools.is_empty())
+            .map(|(mint, pools)| {
+                let tvl_usd = pools
+                    .iter()
+                    .filter_map(|json_str| serde_json::from_str::<PoolLiquidity>(json_str).ok())
+                    .map(|pool| pool.tvl_usd)
+                    .sum();
+                (mint.clone(), tvl_usd)
+            })
+            .collect())
+    }
+
+    fn make_api_key_key(&self, hash: &str) -> String {
+        format!("adapter:api_key:{}", hash)
+    }
+
+    fn make_rate_limit_key(&self, bucket: &str) -> String {
+        format!("adapter:rate_limit:{}", bucket)
+    }
+
+    pub async fn get_api_key(&self, hash: &str) -> Result<Option<ApiKey>> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let value: Option<String> = cmd("GET")
+            .arg(self.make_api_key_key(hash))
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to get API key")?;
+
+        value
+            .map(|json_str| {
+                serde_json::from_str(&json_str).context("Failed to deserialize API key")
+            })
+            .transpose()
+    }
+
+    pub async fn save_api_key(&self, hash: &str, key: &ApiKey) -> Result<()> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let _: () = cmd("SET")
+            .arg(self.make_api_key_key(hash))
+            .arg(serde_json::to_string(key)?)
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to save API key")?;
+
+        Ok(())
+    }
+
+    /// Takes a token from the bucket, returns how long to wait if it is
+    /// empty
+    pub async fn take_rate_limit_token(
+        &self,
+        bucket: &str,
+        rate_limit: &RateLimit,
+        now_ms: u64,
+    ) -> Result<Option<Duration>> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let (taken, retry_after_ms): (u8, u64) = cmd("EVAL")
+            .arg(TOKEN_BUCKET_SCRIPT)
+            .arg(1)
+            .arg(self.make_rate_limit_key(bucket))
+            .arg(rate_limit.capacity)
+            .arg(rate_limit.refill_per_sec)
+            .arg(now_ms)
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to take rate limit token")?;
+
+        Ok(match taken {
+            1 => None,
+            _ => Some(Duration::from_millis(retry_after_ms)),
+        })
+    }
+
+    fn make_cache_key(&self, key: &str) -> String {
+        format!("adapter:cache:{}", key)
+    }
+
+    fn make_cache_lock_key(&self, key: &str) -> String {
+        format!("adapter:cache_lock:{}", key)
+    }
+
+    /// Cached value with the TTL it has left
+    pub async fn cache_get(&self, key: &str) -> Result<Option<(String, Duration)>> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let key = self.make_cache_key(key);
+        let (value, ttl_ms): (Option<String>, i64) = bb8_redis::redis::pipe()
+            .cmd("GET")
+            .arg(&key)
+            .cmd("PTTL")
+            .arg(&key)
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to get cached value")?;
+
+        Ok(match (value, ttl_ms) {
+            (Some(value), ttl_ms) if ttl_ms > 0 => {
+                Some((value, Duration::from_millis(ttl_ms as u64)))
+            }
+            _ => None,
+        })
+    }
+
+    /// Stores the value and releases the lock taken to load it
+    pub async fn cache_set(&self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let _: () = bb8_redis::redis::pipe()
+            .cmd("SET")
+            .arg(self.make_cache_key(key))
+            .arg(value)
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .ignore()
+            .cmd("DEL")
+            .arg(self.make_cache_lock_key(key))
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to set cached value")?;
+
+        Ok(())
+    }
+
+    /// Returns true if the caller should load the value
+    pub async fn cache_lock(&self, key: &str, ttl: Duration) -> Result<bool> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let locked: Option<String> = cmd("SET")
+            .arg(self.make_cache_lock_key(key))
+            .arg(1)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut *conn)
+            .await
+            .context("Failed to take cache lock")?;
+
+        Ok(locked.is_some())
+    }
+
     fn make_chat_key(&self, chat_id: &str) -> String {
         format!("chats:shared:{}", chat_id)
     }
 
+    fn make_chat_owner_key(&self, chat_id: &str) -> String {
+        format!("chats:shared:{}:owner", chat_id)
+    }
+
+    /// Key hash of the caller that shared the chat, chats shared
+    /// anonymously have none
+    pub async fn get_chat_owner(&self, chat_id: &str) -> Result<Option<String>> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let owner: Option<String> = cmd("GET")
+            .arg(self.make_chat_owner_key(chat_id))
+            .query_async(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to get chat owner: {}", chat_id))?;
+
+        Ok(owner)
+    }
+
     pub async fn get_chat(&self, chat_id: &str) -> Result<Option<serde_json::Value>> {
         let mut conn = self
             .pool
@@ -144,6 +470,41 @@ impl RedisClient {
 
         Ok(())
     }
+
+    /// Saves the chat and its owner unless one with the id exists, returns
+    /// whether it was saved
+    pub async fn save_chat_if_absent(
+        &self,
+        chat_id: &str,
+        chat: &serde_json::Value,
+        owner: Option<&str>,
+    ) -> Result<bool> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .context("Failed to get Redis connection")?;
+
+        let key = self.make_chat_key(chat_id);
+        let reply: Option<String> = cmd("SET")
+            .arg(key)
+            .arg(serde_json::to_string(chat)?)
+            .arg("NX")
+            .query_async(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to save chat: {}", chat_id))?;
+
+        if let (Some(_), Some(owner)) = (&reply, owner) {
+            let _: () = cmd("SET")
+                .arg(self.make_chat_owner_key(chat_id))
+                .arg(owner)
+                .query_async(&mut *conn)
+                .await
+                .with_context(|| format!("Failed to save chat owner: {}", chat_id))?;
+        }
+
+        Ok(reply.is_some())
+    }
 }
 
 pub async fn make_redis_client() -> Result<Arc<RedisClient>> {
@@ -165,4 +526,33 @@ mod tests {
             .unwrap();
         println!("Metadata: {:?}", metadata);
     }
+
+    #[tokio::test]
+    async fn test_rate_limit_bucket() {
+        let client = make_redis_client().await.unwrap();
+        let bucket = format!("test:{}", crate::auth::generate_key());
+        let rate_limit = RateLimit {
+            capacity: 2,
+            refill_per_sec: 1.0,
+        };
+        let now = 1_700_000_000_000;
+        for _ in 0..2 {
+            let wait = client
+                .take_rate_limit_token(&bucket, &rate_limit, now)
+                .await
+                .unwrap();
+            assert_eq!(wait, None);
+        }
+        let wait = client
+            .take_rate_limit_token(&bucket, &rate_limit, now)
+            .await
+            .unwrap();
+        assert_eq!(wait, Some(Duration::from_secs(1)));
+        // refilled after a second
+        let wait = client
+            .take_rate_limit_token(&bucket, &rate_limit, now + 1000)
+            .await
+            .unwrap();
+        assert_eq!(wait, None);
+    }
 }
@@ -2,25 +2,75 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 use tracing::{debug, error};
 
+use crate::db::PriceUpdate;
+
+/// A price update from the indexer, parsed once for all of the websocket
+/// connections. `json` is the message forwarded to trade subscribers.
+#[derive(Debug)]
+pub struct TradeUpdate {
+    pub price_update: PriceUpdate,
+    pub json: String,
+}
+
+#[derive(Serialize)]
+struct TradeMessage<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(flatten)]
+    price_update: &'a PriceUpdate,
+}
+
+impl TradeUpdate {
+    pub fn from_payload(payload: &str) -> Result<Self> {
+        let price_update: PriceUpdate = serde_json::from_str(payload)?;
+        let json = serde_json::to_string(&TradeMessage {
+            kind: "trade",
+            price_update: &price_update,
+        })?;
+        Ok(Self { price_update, json })
+    }
+}
+
+/// Channel the indexer announces its candlestick rollups on
+pub const CANDLESTICK_ROLLUPS_CHANNEL: &str = "candlestick_rollups";
+
+/// A finished candlestick rollup of the indexer, swaps published before
+/// `covers_until_ms` are counted in the candles
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CandlestickRollup {
+    pub covers_until_ms: u64,
+}
+
 pub struct RedisSubscriber {
     client: redis::Client,
-    tx: broadcast::Sender<String>,
+    tx: broadcast::Sender<Arc<TradeUpdate>>,
+    rollups_tx: broadcast::Sender<CandlestickRollup>,
 }
 
 impl RedisSubscriber {
     pub fn new(redis_url: &str) -> Result<Self> {
         let client = redis::Client::open(redis_url)?;
-        let (tx, _) = broadcast::channel(200);
-        Ok(Self { client, tx })
+        let (tx, _) = broadcast::channel(1024);
+        let (rollups_tx, _) = broadcast::channel(16);
+        Ok(Self {
+            client,
+            tx,
+            rollups_tx,
+        })
     }
 
-    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<TradeUpdate>> {
         self.tx.subscribe()
     }
 
+    pub fn subscribe_rollups(&self) -> broadcast::Receiver<CandlestickRollup> {
+        self.rollups_tx.subscribe()
+    }
+
     pub async fn start_listening(&self, channel: &str) -> Result<()> {
         let conn = self.client.get_async_connection().await?;
         debug!("Subscribing to Redis channel: {}", channel);
@@ -34,9 +84,44 @@ impl RedisSubscriber {
 
             while let Some(msg) = msg_stream.next().await {
                 match msg.get_payload::<String>() {
-                    Ok(payload) => {
-                        let _ = tx.send(payload);
+                    Ok(payload) => match TradeUpdate::from_payload(&payload) {
+                        Ok(update) => {
+                            let _ = tx.send(Arc::new(update));
+                        }
+                        Err(e) => error!("Failed to parse price update: {}", e),
+                    },
+                    Err(e) => {
+                        error!("Failed to get message payload: {}", e);
                     }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    pub async fn start_listening_rollups(&self) -> Result<()> {
+        let conn = self.client.get_async_connection().await?;
+        debug!(
+            "Subscribing to Redis channel: {}",
+            CANDLESTICK_ROLLUPS_CHANNEL
+        );
+
+        let mut pubsub = conn.into_pubsub();
+        pubsub.subscribe(CANDLESTICK_ROLLUPS_CHANNEL).await?;
+        let tx = self.rollups_tx.clone();
+
+        tokio::spawn(async move {
+            let mut msg_stream = pubsub.on_message();
+
+            while let Some(msg) = msg_stream.next().await {
+                match msg.get_payload::<String>() {
+                    Ok(payload) => match serde_json::from_str(&payload) {
+                        Ok(rollup) => {
+                            let _ = tx.send(rollup);
+                        }
+                        Err(e) => error!("Failed to parse candlestick rollup: {}", e),
+                    },
                     Err(e) => {
                         error!("Failed to get message payload: {}", e);
                     }
@@ -51,6 +136,7 @@ impl RedisSubscriber {
 pub async fn create_redis_subscriber(redis_url: &str) -> anyhow::Result<Arc<RedisSubscriber>> {
     let subscriber = RedisSubscriber::new(redis_url)?;
     subscriber.start_listening("price_updates").await?;
+    subscriber.start_listening_rollups().await?;
 
     Ok(Arc::new(subscriber))
 }
@@ -68,6 +154,17 @@ mod tests {
 
         let mut sub = subscriber.subscribe();
         let msg = sub.recv().await.unwrap();
-        assert!(msg != "");
+        assert!(!msg.price_update.pubkey.is_empty());
+    }
+
+    #[test]
+    fn test_trade_update_from_payload() {
+        let payload = r#"{"name":"Test","pubkey":"mint","price":1.5,"market_cap":1000.0,"timestamp":1700000000,"slot":1,"swap_amount":100.0,"owner":"owner","signature":"sig","multi_hop":false,"is_buy":true,"is_pump":false}"#;
+        let update = TradeUpdate::from_payload(payload).unwrap();
+        assert_eq!(update.price_update.pubkey, "mint");
+        let json: serde_json::Value = serde_json::from_str(&update.json).unwrap();
+        assert_eq!(json["type"], "trade");
+        assert_eq!(json["price"], 1.5);
+        assert!(TradeUpdate::from_payload("not json").is_err());
     }
 }
@@ -1,11 +1,33 @@
+use crate::auth::Principal;
+use crate::cache::{self, CacheKey};
+use crate::openapi::ErrorResponse;
 use crate::version::VERSION;
 use crate::websocket::handle_ws_connection;
-use crate::{db::candlesticks::CandlestickInterval, state::AppState};
-use actix_web::{error::InternalError, http::StatusCode, web, Error, HttpRequest, HttpResponse};
-use regex::Regex;
+use crate::{
+    db::{
+        analytics::AnalyticsQuery,
+        candlesticks::Candlestick,
+        candlesticks::CandlestickInterval,
+        holders::HolderSnapshot,
+        query::OpenPrice,
+        top_tokens::TopToken,
+        trending::TrendingParams,
+        trending::TrendingToken,
+        wallets::is_valid_address,
+        wallets::{LeaderboardEntry, WalletPnl},
+        PriceUpdate,
+    },
+    redis_client::{RedisClient, TokenMetadata},
+    search::SearchResult,
+    state::AppState,
+};
+use actix_web::{
+    error::InternalError, http::StatusCode, web, Error, HttpMessage, HttpRequest, HttpResponse,
+};
 use serde::Deserialize;
 use serde_json::json;
 use tracing::error;
+use utoipa::{IntoParams, ToSchema};
 
 pub async fn ws_route(
     req: HttpRequest,
@@ -19,11 +41,19 @@ pub async fn ws_route(
         session,
         msg_stream,
         state.redis_subscriber.clone(),
+        state.top_tokens_feed.clone(),
+        state.clickhouse_db.clone(),
     ));
 
     Ok(res)
 }
 
+fn cached_json(body: std::sync::Arc<String>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body.as_str().to_owned())
+}
+
 pub async fn health_check() -> HttpResponse {
     let timestamp = chrono::Utc::now().timestamp();
     HttpResponse::Ok().json(json!({
@@ -38,7 +68,8 @@ pub async fn version() -> HttpResponse {
     }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct TopTokensQuery {
     pub limit: Option<usize>,
     pub min_volume: Option<f64>,
@@ -48,24 +79,48 @@ pub struct TopTokensQuery {
     pub only_pumpfun_tokens: Option<bool>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/top-tokens",
+    params(TopTokensQuery),
+    responses(
+        (status = 200, description = "Tokens by volume", body = Vec<TopToken>),
+        (status = 500, description = "Internal error"),
+    )
+)]
 pub async fn top_tokens(
     state: web::Data<AppState>,
     query: web::Query<TopTokensQuery>,
 ) -> Result<HttpResponse, Error> {
+    let limit = query.limit.unwrap_or(20);
+    let only_pumpfun_tokens = query.only_pumpfun_tokens.unwrap_or(true);
+    let key = CacheKey::new(
+        "top_tokens",
+        &[
+            &limit,
+            &format!("{:?}", query.min_volume),
+            &format!("{:?}", query.min_market_cap),
+            &format!("{:?}", query.max_market_cap),
+            &format!("{:?}", query.timeframe),
+            &only_pumpfun_tokens,
+        ],
+    );
     let tokens = state
-        .clickhouse_db
-        .get_top_tokens(
-            query.limit.unwrap_or(20),
-            query.min_volume,
-            query.min_market_cap,
-            query.max_market_cap,
-            query.timeframe,
-            query.only_pumpfun_tokens.unwrap_or(true),
-        )
+        .response_cache
+        .get_or_load(&key, cache::TOP_TOKENS_TTL, || {
+            state.clickhouse_db.get_top_tokens(
+                limit,
+                query.min_volume,
+                query.min_market_cap,
+                query.max_market_cap,
+                query.timeframe,
+                only_pumpfun_tokens,
+            )
+        })
         .await;
 
     match tokens {
-        Ok(tokens) => Ok(HttpResponse::Ok().json(tokens)),
+        Ok(tokens) => Ok(cached_json(tokens)),
         Err(e) => {
             error!("Error getting top tokens: {}", e);
             Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
@@ -73,25 +128,327 @@ pub async fn top_tokens(
     }
 }
 
-#[derive(Deserialize)]
+#[utoipa::path(
+    get,
+    path = "/trending",
+    params(TrendingParams),
+    responses(
+        (status = 200, description = "Trending tokens", body = Vec<TrendingToken>),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
+pub async fn trending(
+    state: web::Data<AppState>,
+    query: web::Query<TrendingParams>,
+) -> Result<HttpResponse, Error> {
+    let now = chrono::Utc::now().timestamp() as u64;
+    let sql = match query.to_sql(now) {
+        Ok(sql) => sql,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": e.to_string()
+            })))
+        }
+    };
+
+    match state.clickhouse_db.get_trending(&sql).await {
+        Ok(tokens) => Ok(HttpResponse::Ok().json(tokens)),
+        Err(e) => {
+            error!("Error getting trending tokens: {}", e);
+            Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WalletTradesQuery {
+    pub mint: Option<String>,
+    /// unix seconds, exclusive, for paging back from the oldest trade seen
+    pub before: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+fn invalid_address(address: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": "Invalid address",
+        "address": address
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/wallet/{address}/trades",
+    params(("address" = String, Path, description = "Wallet address"), WalletTradesQuery),
+    responses(
+        (status = 200, description = "Trades of the wallet, newest first", body = Vec<PriceUpdate>),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
+pub async fn get_wallet_trades(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<WalletTradesQuery>,
+) -> Result<HttpResponse, Error> {
+    let wallet = path.into_inner();
+    if !is_valid_address(&wallet) {
+        return Ok(invalid_address(&wallet));
+    }
+    let trades = state
+        .clickhouse_db
+        .get_wallet_trades(&wallet, query.mint.as_deref(), query.before, query.limit)
+        .await;
+
+    match trades {
+        Ok(trades) => Ok(HttpResponse::Ok().json(trades)),
+        Err(e) => {
+            error!("Error getting wallet trades: {}", e);
+            Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WalletPnlQuery {
+    pub mint: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/wallet/{address}/pnl",
+    params(("address" = String, Path, description = "Wallet address"), WalletPnlQuery),
+    responses(
+        (status = 200, description = "PnL of the wallet", body = WalletPnl),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
+pub async fn get_wallet_pnl(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<WalletPnlQuery>,
+) -> Result<HttpResponse, Error> {
+    let wallet = path.into_inner();
+    if !is_valid_address(&wallet) {
+        return Ok(invalid_address(&wallet));
+    }
+    let pnl = state
+        .clickhouse_db
+        .get_wallet_pnl(&wallet, query.mint.as_deref())
+        .await;
+
+    match pnl {
+        Ok(pnl) => Ok(HttpResponse::Ok().json(pnl)),
+        Err(e) => {
+            error!("Error getting wallet pnl: {}", e);
+            Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardQuery {
+    pub limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/token/{mint}/leaderboard",
+    params(("mint" = String, Path, description = "Token mint"), LeaderboardQuery),
+    responses(
+        (status = 200, description = "Wallets by PnL on the token", body = Vec<LeaderboardEntry>),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
+pub async fn get_token_leaderboard(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<LeaderboardQuery>,
+) -> Result<HttpResponse, Error> {
+    let mint = path.into_inner();
+    if !is_valid_address(&mint) {
+        return Ok(invalid_address(&mint));
+    }
+    let leaderboard = state
+        .clickhouse_db
+        .get_token_leaderboard(&mint, query.limit)
+        .await;
+
+    match leaderboard {
+        Ok(leaderboard) => Ok(HttpResponse::Ok().json(leaderboard)),
+        Err(e) => {
+            error!("Error getting token leaderboard: {}", e);
+            Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HoldersQuery {
+    /// unix seconds, the latest snapshot at or before it
+    pub at: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/token/{mint}/holders",
+    params(("mint" = String, Path, description = "Token mint"), HoldersQuery),
+    responses(
+        (status = 200, description = "Top holders of the token", body = HolderSnapshot),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 404, description = "No holder snapshot", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
+pub async fn get_token_holders(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<HoldersQuery>,
+) -> Result<HttpResponse, Error> {
+    let mint = path.into_inner();
+    if !is_valid_address(&mint) {
+        return Ok(invalid_address(&mint));
+    }
+    let snapshot = state
+        .clickhouse_db
+        .get_holder_snapshot(&mint, query.at, query.limit)
+        .await;
+
+    match snapshot {
+        Ok(Some(snapshot)) => Ok(HttpResponse::Ok().json(snapshot)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "error": "No holder snapshot",
+            "mint": mint
+        }))),
+        Err(e) => {
+            error!("Error getting holder snapshot: {}", e);
+            Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching tokens, best first", body = Vec<SearchResult>),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
+pub async fn search(
+    state: web::Data<AppState>,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, Error> {
+    if query.q.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "`q` must not be empty"
+        })));
+    }
+    let results = state
+        .search_index
+        .search(
+            &query.q,
+            query.limit.unwrap_or(10),
+            &state.clickhouse_db,
+            &state.redis_client,
+        )
+        .await;
+
+    match results {
+        Ok(results) => Ok(HttpResponse::Ok().json(results)),
+        Err(e) => {
+            error!("Error searching tokens: {}", e);
+            Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CandlestickParams {
     pub mint: String,
+    #[param(value_type = String, example = "1m")]
     pub interval: CandlestickInterval,
     pub limit: Option<usize>,
+    /// unix seconds, inclusive
+    pub from: Option<u64>,
+    pub to: Option<u64>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/candlesticks",
+    params(CandlestickParams),
+    responses(
+        (status = 200, description = "Candlesticks, oldest first", body = Vec<Candlestick>),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
 pub async fn get_candlesticks(
     state: web::Data<AppState>,
     query: web::Query<CandlestickParams>,
 ) -> Result<HttpResponse, Error> {
     let params = query.into_inner();
+    if let (Some(from), Some(to)) = (params.from, params.to) {
+        if from > to {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "`from` must not be after `to`"
+            })));
+        }
+    }
+    let key = CacheKey::new(
+        "candlesticks",
+        &[
+            &params.mint,
+            &params.interval.to_string(),
+            &params.limit.unwrap_or(200),
+            &format!("{:?}", params.from),
+            &format!("{:?}", params.to),
+        ],
+    );
+    // ends in the current bucket or without an end, the latest candle is
+    // included and changes with trades
+    let now = chrono::Utc::now().timestamp() as u64;
+    let (key, ttl) = if params.interval.includes_latest(params.to, now) {
+        (
+            key.invalidated_by(&params.mint),
+            cache::LATEST_CANDLESTICKS_TTL,
+        )
+    } else {
+        (key, cache::HISTORICAL_CANDLESTICKS_TTL)
+    };
     let candlesticks = state
-        .clickhouse_db
-        .get_candlesticks(&params.mint, &params.interval.to_string(), params.limit)
+        .response_cache
+        .get_or_load(&key, ttl, || {
+            state.clickhouse_db.get_candlesticks(
+                &params.mint,
+                &params.interval,
+                params.from,
+                params.to,
+                params.limit,
+            )
+        })
         .await;
 
     match candlesticks {
-        Ok(candlesticks) => Ok(HttpResponse::Ok().json(candlesticks)),
+        Ok(candlesticks) => Ok(cached_json(candlesticks)),
         Err(e) => {
             error!("Error getting candlesticks: {}", e);
             Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
@@ -99,6 +456,16 @@ pub async fn get_candlesticks(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/metadata",
+    params(MetadataQuery),
+    responses(
+        (status = 200, description = "Token metadata", body = TokenMetadata),
+        (status = 404, description = "Metadata not found", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
 pub async fn get_metadata(
     state: web::Data<AppState>,
     query: web::Query<MetadataQuery>,
@@ -116,11 +483,21 @@ pub async fn get_metadata(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PriceQuery {
     pub mint: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/price",
+    params(PriceQuery),
+    responses(
+        (status = 200, description = "Latest trade of the token", body = PriceUpdate),
+        (status = 500, description = "Internal error"),
+    )
+)]
 pub async fn get_price(
     state: web::Data<AppState>,
     query: web::Query<PriceQuery>,
@@ -135,13 +512,29 @@ pub async fn get_price(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/24h-open",
+    params(PriceQuery),
+    responses(
+        (status = 200, description = "First trade of the UTC day, null if there was none", body = Option<OpenPrice>),
+        (status = 500, description = "Internal error"),
+    )
+)]
 pub async fn get_24h_open_price(
     state: web::Data<AppState>,
     query: web::Query<PriceQuery>,
 ) -> Result<HttpResponse, Error> {
-    let open_price = state.clickhouse_db.get_24h_open_price(&query.mint).await;
+    let now = chrono::Utc::now().timestamp() as u64;
+    let key = CacheKey::new("24h_open", &[&query.mint, &(now / 86400)]);
+    let open_price = state
+        .response_cache
+        .get_or_load(&key, cache::open_price_ttl(now), || {
+            state.clickhouse_db.get_24h_open_price(&query.mint)
+        })
+        .await;
     match open_price {
-        Ok(price) => Ok(HttpResponse::Ok().json(price)),
+        Ok(price) => Ok(cached_json(price)),
         Err(e) => {
             error!("Error getting 24h open price: {}", e);
             Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
@@ -149,65 +542,62 @@ pub async fn get_24h_open_price(
     }
 }
 
-#[derive(Deserialize)]
-pub struct QueryParams {
-    pub sql: String,
-}
-
-pub async fn query_db(
+#[utoipa::path(
+    post,
+    path = "/analytics",
+    request_body = AnalyticsQuery,
+    responses(
+        (status = 200, description = "Result rows, keyed by column name", body = Vec<Object>),
+        (status = 400, description = "Invalid query", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
+pub async fn analytics_query(
     state: web::Data<AppState>,
-    query: web::Json<QueryParams>,
+    query: web::Json<AnalyticsQuery>,
 ) -> Result<HttpResponse, Error> {
-    // Sanitize the SQL query
-    let sql = query.sql.trim();
-
-    // Only allow SELECT queries (case insensitive check)
-    if !sql.to_uppercase().starts_with("SELECT ") {
-        return Ok(HttpResponse::BadRequest().json(json!({
-            "error": "Only SELECT queries are allowed"
-        })));
-    }
-
-    // Block dangerous SQL commands while allowing table names containing these words
-    const BLOCKED_PATTERNS: [&str; 7] = [
-        r"(?i)\bDELETE\b",
-        r"(?i)\bDROP\b",
-        r"(?i)\bUPDATE\b",
-        r"(?i)\bINSERT\b",
-        r"(?i)\bALTER\b",
-        r"(?i)\bTRUNCATE\b",
-        r"(?i)\bGRANT\b",
-    ];
-
-    for pattern in BLOCKED_PATTERNS {
-        if Regex::new(pattern).unwrap().is_match(sql) {
+    let now = chrono::Utc::now().timestamp() as u64;
+    let compiled = match query.compile(now) {
+        Ok(compiled) => compiled,
+        Err(e) => {
             return Ok(HttpResponse::BadRequest().json(json!({
-                "error": "Query contains forbidden SQL commands"
-            })));
+                "error": e.to_string()
+            })))
         }
-    }
+    };
 
-    // Execute the validated query
-    let result = state.clickhouse_db.generic_query(sql).await;
+    let result = state.clickhouse_db.run_analytics_query(&compiled).await;
     match result {
-        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Ok(rows) => Ok(HttpResponse::Ok().json(rows)),
         Err(e) => {
-            error!("Error querying database: {}", e);
+            error!("Error running analytics query: {}", e);
             Err(InternalError::new(e, StatusCode::INTERNAL_SERVER_ERROR).into())
         }
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MetadataQuery {
     mint: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ChatQuery {
     pub chat_id: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/get-chat",
+    params(ChatQuery),
+    responses(
+        (status = 200, description = "Shared chat", body = Object),
+        (status = 404, description = "Chat not found", body = ErrorResponse),
+        (status = 500, description = "Internal error"),
+    )
+)]
 pub async fn get_chat(
     state: web::Data<AppState>,
     query: web::Query<ChatQuery>,
@@ -226,24 +616,44 @@ pub async fn get_chat(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SaveChatRequest {
     pub chat_id: String,
+    #[schema(value_type = Object)]
     pub chat: serde_json::Value,
 }
 
+#[utoipa::path(
+    post,
+    path = "/save-chat",
+    request_body = SaveChatRequest,
+    responses(
+        (status = 200, description = "Chat saved, under a new `chat_id` if the id is taken by a chat the caller does not own", body = Object),
+        (status = 500, description = "Internal error"),
+    )
+)]
 pub async fn save_chat(
+    req: HttpRequest,
     state: web::Data<AppState>,
     body: web::Json<SaveChatRequest>,
 ) -> Result<HttpResponse, Error> {
-    let chat = state
-        .redis_client
-        .save_chat(&body.chat_id, &body.chat)
-        .await;
-    match chat {
-        Ok(_) => Ok(HttpResponse::Ok().json(json!({
+    // chats shared with an API key can be updated with the same key,
+    // anonymous shares are never overwritten
+    let owner = match req.extensions().get::<Principal>() {
+        Some(Principal::Key { hash, .. }) => Some(hash.clone()),
+        _ => None,
+    };
+    match share_chat(
+        &state.redis_client,
+        &body.chat_id,
+        &body.chat,
+        owner.as_deref(),
+    )
+    .await
+    {
+        Ok(chat_id) => Ok(HttpResponse::Ok().json(json!({
             "message": "Chat saved",
-            "chat_id": body.chat_id
+            "chat_id": chat_id
         }))),
         Err(e) => {
             error!("Error saving chat: {}", e);
@@ -251,3 +661,30 @@ pub async fn save_chat(
         }
     }
 }
+
+/// Saves the chat under its id if it is new or the owner's, otherwise under
+/// a new id, which the interfaces link to instead
+async fn share_chat(
+    redis_client: &RedisClient,
+    chat_id: &str,
+    chat: &serde_json::Value,
+    owner: Option<&str>,
+) -> anyhow::Result<String> {
+    if redis_client
+        .save_chat_if_absent(chat_id, chat, owner)
+        .await?
+    {
+        return Ok(chat_id.to_string());
+    }
+    if let Some(owner) = owner {
+        if redis_client.get_chat_owner(chat_id).await?.as_deref() == Some(owner) {
+            redis_client.save_chat(chat_id, chat).await?;
+            return Ok(chat_id.to_string());
+        }
+    }
+    let new_id = format!("{:032x}", rand::random::<u128>());
+    redis_client
+        .save_chat_if_absent(&new_id, chat, owner)
+        .await?;
+    Ok(new_id)
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::db::ClickhouseDb;
+use crate::redis_client::{RedisClient, TokenMetadata};
+
+/// Full rescans of the indexed metadata, new tokens show up in search
+/// within this long
+pub const SEARCH_INDEX_REFRESH_SECS: u64 = 300;
+pub const MAX_SEARCH_RESULTS: usize = 50;
+/// Text matches that get their volume and liquidity looked up for ranking,
+/// the most traded ones of the last volume refresh
+pub const SEARCH_CANDIDATES: usize = 200;
+/// Shorter queries only match exactly, substrings of them are everywhere
+pub const MIN_PARTIAL_QUERY_LEN: usize = 2;
+const SCAN_BATCH: usize = 1000;
+
+/// How a token matched the query, better matches rank first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Mint,
+    Symbol,
+    Name,
+    MintPrefix,
+    SymbolPrefix,
+    NamePrefix,
+    NameContains,
+}
+
+impl MatchKind {
+    fn is_exact(&self) -> bool {
+        matches!(self, MatchKind::Mint | MatchKind::Symbol | MatchKind::Name)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    mint: String,
+    name: String,
+    symbol: String,
+    name_lower: String,
+    symbol_lower: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct SearchResult {
+    pub mint: String,
+    pub name: String,
+    pub symbol: String,
+    pub match_kind: MatchKind,
+    pub volume_24h: f64,
+    pub liquidity_usd: f64,
+}
+
+/// In-memory index of the token metadata stored by the indexer
+#[derive(Default)]
+pub struct SearchIndex {
+    entries: RwLock<HashMap<String, IndexEntry>>,
+    /// 24h volume of the traded mints as of the last refresh
+    volumes: RwLock<HashMap<String, f64>>,
+}
+
+/// Lowercased query with a leading `$` (as in "$WIF") removed
+fn normalize_query(query: &str) -> String {
+    query.trim().trim_start_matches('$').trim().to_lowercase()
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .read()
+            .expect("search index lock poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn insert(&self, metadata: &TokenMetadata) {
+        let name = metadata.mpl.name.trim().to_string();
+        let symbol = metadata.mpl.symbol.trim().to_string();
+        let entry = IndexEntry {
+            mint: metadata.mint.clone(),
+            name_lower: name.to_lowercase(),
+            symbol_lower: normalize_query(&symbol),
+            name,
+            symbol,
+        };
+        self.entries
+            .write()
+            .expect("search index lock poisoned")
+            .insert(entry.mint.clone(), entry);
+    }
+
+    pub fn set_volumes(&self, volumes: HashMap<String, f64>) {
+        *self.volumes.write().expect("search index lock poisoned") = volumes;
+    }
+
+    /// Text matches of the query, exact matches first, then by the volume
+    /// of the last refresh, so common words keep the traded tokens
+    fn candidates(&self, query: &str, limit: usize) -> Vec<(IndexEntry, MatchKind)> {
+        let raw = query.trim().trim_start_matches('$').trim();
+        let query = normalize_query(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let partial = query.chars().count() >= MIN_PARTIAL_QUERY_LEN;
+
+        let entries = self.entries.read().expect("search index lock poisoned");
+        let mut matches: Vec<(IndexEntry, MatchKind)> = entries
+            .values()
+            .filter_map(|entry| {
+                let kind = if entry.mint == raw {
+                    MatchKind::Mint
+                } else if entry.symbol_lower == query {
+                    MatchKind::Symbol
+                } else if entry.name_lower == query {
+                    MatchKind::Name
+                } else if !partial {
+                    return None;
+                } else if raw.len() >= 4 && entry.mint.starts_with(raw) {
+                    // mints are base58, prefixes are case sensitive
+                    MatchKind::MintPrefix
+                } else if entry.symbol_lower.starts_with(&query) {
+                    MatchKind::SymbolPrefix
+                } else if entry.name_lower.starts_with(&query) {
+                    MatchKind::NamePrefix
+                } else if entry.name_lower.contains(&query) {
+                    MatchKind::NameContains
+                } else {
+                    return None;
+                };
+                Some((entry.clone(), kind))
+            })
+            .collect();
+        drop(entries);
+
+        let volumes = self.volumes.read().expect("search index lock poisoned");
+        let volume = |entry: &IndexEntry| volumes.get(&entry.mint).copied().unwrap_or_default();
+        matches.sort_by(|(a, a_kind), (b, b_kind)| {
+            b_kind
+                .is_exact()
+                .cmp(&a_kind.is_exact())
+                .then_with(|| volume(b).total_cmp(&volume(a)))
+                .then_with(|| a_kind.cmp(b_kind))
+                .then_with(|| a.mint.cmp(&b.mint))
+        });
+        matches.truncate(limit);
+        matches
+    }
+
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        clickhouse_db: &ClickhouseDb,
+        redis_client: &RedisClient,
+    ) -> Result<Vec<SearchResult>> {
+        let candidates = self.candidates(query, SEARCH_CANDIDATES);
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mints: Vec<String> = candidates.iter().map(|(e, _)| e.mint.clone()).collect();
+        let (volumes, liquidity) = tokio::try_join!(
+            clickhouse_db.get_volumes_24h(&mints),
+            redis_client.get_liquidity_usd(&mints),
+        )?;
+
+        let results = candidates
+            .into_iter()
+            .map(|(entry, match_kind)| SearchResult {
+                volume_24h: volumes.get(&entry.mint).copied().unwrap_or_default(),
+                liquidity_usd: liquidity.get(&entry.mint).copied().unwrap_or_default(),
+                mint: entry.mint,
+                name: entry.name,
+                symbol: entry.symbol,
+                match_kind,
+            })
+            .collect();
+        Ok(rank(results, limit))
+    }
+
+    /// Indexes all of the stored metadata and the volumes, and keeps
+    /// rescanning them
+    pub fn start(
+        self: &Arc<Self>,
+        redis_client: Arc<RedisClient>,
+        clickhouse_db: Arc<ClickhouseDb>,
+    ) {
+        let index = self.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(SEARCH_INDEX_REFRESH_SECS));
+            loop {
+                interval.tick().await;
+                if let Err(e) = index.refresh(&redis_client).await {
+                    error!("Failed to refresh search index: {}", e);
+                }
+                match clickhouse_db.get_all_volumes_24h().await {
+                    Ok(volumes) => index.set_volumes(volumes),
+                    Err(e) => error!("Failed to refresh search volumes: {}", e),
+                }
+            }
+        });
+    }
+
+    pub async fn refresh(&self, redis_client: &RedisClient) -> Result<()> {
+        let mut cursor = 0;
+        loop {
+            let (next, batch) = redis_client.scan_metadata(cursor, SCAN_BATCH).await?;
+            for metadata in &batch {
+                self.insert(metadata);
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        info!("Search index holds {} tokens", self.len());
+        Ok(())
+    }
+}
+
+/// Exact matches first, then by recent volume and liquidity, so the real
+/// token wins over copycats with the same symbol
+fn rank(mut results: Vec<SearchResult>, limit: usize) -> Vec<SearchResult> {
+    results.sort_by(|a, b| {
+        b.match_kind
+            .is_exact()
+            .cmp(&a.match_kind.is_exact())
+            .then_with(|| {
+                let activity = |r: &SearchResult| r.volume_24h + r.liquidity_usd;
+                activity(b).total_cmp(&activity(a))
+            })
+            .then_with(|| a.match_kind.cmp(&b.match_kind))
+    });
+    results.truncate(limit.min(MAX_SEARCH_RESULTS));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis_client::MplTokenMetadata;
+
+    fn metadata(mint: &str, name: &str, symbol: &str) -> TokenMetadata {
+        TokenMetadata {
+            mint: mint.to_string(),
+            mpl: MplTokenMetadata {
+                name: name.to_string(),
+                symbol: symbol.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn index() -> SearchIndex {
+        let index = SearchIndex::new();
+        index.insert(&metadata(
+            "EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm",
+            "dogwifhat",
+            "$WIF",
+        ));
+        index.insert(&metadata(
+            "WiFcopy111111111111111111111111111111111111",
+            "wif",
+            "WIF",
+        ));
+        index.insert(&metadata(
+            "Bonk1111111111111111111111111111111111111111",
+            "Bonk",
+            "BONK",
+        ));
+        index.insert(&metadata(
+            "Cat11111111111111111111111111111111111111111",
+            "cat wif hat",
+            "CWH",
+        ));
+        index
+    }
+
+    fn kinds(index: &SearchIndex, query: &str) -> Vec<(String, MatchKind)> {
+        index
+            .candidates(query, 10)
+            .into_iter()
+            .map(|(entry, kind)| (entry.symbol, kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_candidates() {
+        let index = index();
+        assert_eq!(
+            kinds(&index, "$WIF"),
+            vec![
+                ("$WIF".to_string(), MatchKind::Symbol),
+                ("WIF".to_string(), MatchKind::Symbol),
+                ("CWH".to_string(), MatchKind::NameContains),
+            ]
+        );
+        assert_eq!(
+            kinds(&index, "bon"),
+            vec![("BONK".to_string(), MatchKind::SymbolPrefix)]
+        );
+        assert_eq!(
+            kinds(&index, "wif hat"),
+            vec![("CWH".to_string(), MatchKind::NameContains)]
+        );
+        assert_eq!(
+            kinds(&index, "dogwif"),
+            vec![("$WIF".to_string(), MatchKind::NamePrefix)]
+        );
+        assert_eq!(
+            kinds(&index, "EKpQGS"),
+            vec![("$WIF".to_string(), MatchKind::MintPrefix)]
+        );
+        // single characters only match exactly
+        assert!(kinds(&index, "b").is_empty());
+        assert!(kinds(&index, "  ").is_empty());
+    }
+
+    #[test]
+    fn test_candidates_by_volume() {
+        let index = SearchIndex::new();
+        for i in 0..10 {
+            index.insert(&metadata(
+                &format!("Dead{}", i),
+                &format!("doge {}", i),
+                "X",
+            ));
+        }
+        index.insert(&metadata("Zreal", "the doge", "DOGE2"));
+        index.insert(&metadata("Zexact", "doge", "D"));
+        index.set_volumes(HashMap::from([("Zreal".to_string(), 1e6)]));
+
+        // the traded token makes the cut over the better text matches,
+        // exact matches still come first
+        let mints: Vec<String> = index
+            .candidates("doge", 3)
+            .into_iter()
+            .map(|(entry, _)| entry.mint)
+            .collect();
+        assert_eq!(mints, vec!["Zexact", "Zreal", "Dead0"]);
+    }
+
+    #[test]
+    fn test_rank_by_activity() {
+        let result = |mint: &str, match_kind, volume_24h, liquidity_usd| SearchResult {
+            mint: mint.to_string(),
+            name: String::new(),
+            symbol: String::new(),
+            match_kind,
+            volume_24h,
+            liquidity_usd,
+        };
+        let ranked = rank(
+            vec![
+                result("copycat", MatchKind::Symbol, 10.0, 100.0),
+                result("prefix", MatchKind::SymbolPrefix, 1e9, 1e9),
+                result("real", MatchKind::Symbol, 5e7, 1e8),
+                result("dead", MatchKind::Name, 0.0, 0.0),
+            ],
+            10,
+        );
+        let mints: Vec<&str> = ranked.iter().map(|r| r.mint.as_str()).collect();
+        assert_eq!(mints, vec!["real", "copycat", "dead", "prefix"]);
+        assert_eq!(rank(ranked, 1).len(), 1);
+    }
+}
@@ -1,12 +1,20 @@
 use std::sync::Arc;
 
+use crate::auth::Auth;
+use crate::cache::ResponseCache;
 use crate::db::ClickhouseDb;
 use crate::redis_client::RedisClient;
 use crate::redis_subscriber::RedisSubscriber;
+use crate::search::SearchIndex;
+use crate::websocket::top_tokens::TopTokensFeed;
 
 #[derive(Clone)]
 pub struct AppState {
     pub redis_subscriber: Arc<RedisSubscriber>,
     pub redis_client: Arc<RedisClient>,
     pub clickhouse_db: Arc<ClickhouseDb>,
+    pub top_tokens_feed: Arc<TopTokensFeed>,
+    pub search_index: Arc<SearchIndex>,
+    pub auth: Arc<Auth>,
+    pub response_cache: Arc<ResponseCache>,
 }
@@ -0,0 +1,405 @@
+pub mod protocol;
+pub mod subscriptions;
+pub mod top_tokens;
+
+use actix_ws::{Message, Session};
+use futures::{Stream, StreamExt};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinSet;
+use tracing::{error, info, warn};
+
+use crate::db::candlesticks::{Candlestick, CandlestickInterval};
+use crate::db::ClickhouseDb;
+use crate::redis_subscriber::RedisSubscriber;
+use protocol::{Channel, ClientMessage, ServerMessage, Subscription};
+use subscriptions::{Subscriptions, WILDCARD};
+use top_tokens::TopTokensFeed;
+
+/// Messages queued for a connection before new ones are dropped, so a slow
+/// client never holds up the shared broadcast
+pub const OUTBOX_CAPACITY: usize = 256;
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// Connections that do not answer pings (or send anything) for this long
+/// are closed
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+/// Snapshots are only sent for the first mints of a subscription
+pub const MAX_SNAPSHOT_MINTS: usize = 20;
+pub const SNAPSHOT_CANDLES: usize = 100;
+/// Snapshot queries a single subscription runs at once
+pub const SNAPSHOT_CONCURRENCY: usize = 4;
+
+/// Latest candle of each candle snapshot, the live candles continue from it
+type CandleSeeds = Vec<(String, CandlestickInterval, Candlestick)>;
+
+pub struct AppState {
+    pub redis_subscriber: Arc<RedisSubscriber>,
+}
+
+/// Queues messages for the writer task, dropping them when the client does
+/// not keep up and telling it how many were lost once there is room again
+struct Outbox {
+    tx: mpsc::Sender<String>,
+    dropped: u64,
+}
+
+impl Outbox {
+    /// Returns false once the writer is gone
+    fn send(&mut self, msg: String) -> bool {
+        if self.dropped > 0 {
+            let notice = ServerMessage::Dropped {
+                count: self.dropped,
+            };
+            match self.tx.try_send(notice.to_json()) {
+                Ok(()) => self.dropped = 0,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped += 1;
+                    return true;
+                }
+                Err(TrySendError::Closed(_)) => return false,
+            }
+        }
+        match self.tx.try_send(msg) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+pub async fn handle_ws_connection(
+    mut session: Session,
+    mut msg_stream: impl Stream<Item = Result<Message, actix_ws::ProtocolError>> + Unpin,
+    redis_subscriber: Arc<RedisSubscriber>,
+    top_tokens_feed: Arc<TopTokensFeed>,
+    clickhouse_db: Arc<ClickhouseDb>,
+) {
+    info!("WebSocket connection established");
+
+    let mut trades_rx = redis_subscriber.subscribe();
+    let mut top_tokens_rx = top_tokens_feed.subscribe();
+
+    let (tx, mut rx) = mpsc::channel::<String>(OUTBOX_CAPACITY);
+    let mut outbox = Outbox { tx, dropped: 0 };
+    let mut writer_session = session.clone();
+    let writer = actix_web::rt::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if let Err(e) = writer_session.text(msg).await {
+                error!("Failed to send message: {}", e);
+                break;
+            }
+        }
+    });
+
+    let mut subscriptions = Subscriptions::default();
+    // snapshots are fetched off the loop, dropping the set on disconnect
+    // cancels the ones still running
+    let mut snapshots = JoinSet::new();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    loop {
+        tokio::select! {
+            msg = msg_stream.next() => {
+                let Some(Ok(msg)) = msg else {
+                    break;
+                };
+                last_seen = Instant::now();
+                match msg {
+                    Message::Close(reason) => {
+                        info!("WebSocket connection closed: {:?}", reason);
+                        break;
+                    }
+                    Message::Ping(bytes) => {
+                        if let Err(e) = session.pong(&bytes).await {
+                            error!("Failed to send pong: {}", e);
+                            break;
+                        }
+                    }
+                    Message::Text(text) => {
+                        let sent = match serde_json::from_str::<ClientMessage>(&text) {
+                            Ok(msg) => {
+                                handle_client_message(
+                                    msg,
+                                    &mut subscriptions,
+                                    &mut outbox,
+                                    &mut snapshots,
+                                    &top_tokens_feed,
+                                    &clickhouse_db,
+                                )
+                                .await
+                            }
+                            Err(e) => outbox.send(
+                                ServerMessage::error(format!("Invalid message format: {}", e))
+                                    .to_json(),
+                            ),
+                        };
+                        if !sent {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            update = trades_rx.recv() => match update {
+                Ok(update) => {
+                    let messages = subscriptions.on_trade(&update);
+                    if !messages.into_iter().all(|msg| outbox.send(msg)) {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(n)) => {
+                    warn!("WebSocket connection lagged behind by {} updates", n);
+                    outbox.dropped += n;
+                }
+                Err(RecvError::Closed) => break,
+            },
+
+            top_tokens = top_tokens_rx.recv() => match top_tokens {
+                Ok(msg) => {
+                    if subscriptions.top_tokens() && !outbox.send(msg.to_string()) {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+
+            Some(seeds) = snapshots.join_next() => {
+                for (mint, interval, candle) in seeds.unwrap_or_default() {
+                    subscriptions.seed_candle(&mint, interval, candle);
+                }
+            }
+
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > CLIENT_TIMEOUT {
+                    info!("WebSocket client timed out");
+                    break;
+                }
+                if session.ping(b"").await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    writer.abort();
+    let _ = session.close(None).await;
+}
+
+/// Applies a subscription change, queues the acknowledgement and starts
+/// fetching the snapshots. Returns false once the connection is gone.
+async fn handle_client_message(
+    msg: ClientMessage,
+    subscriptions: &mut Subscriptions,
+    outbox: &mut Outbox,
+    snapshots: &mut JoinSet<CandleSeeds>,
+    top_tokens_feed: &TopTokensFeed,
+    clickhouse_db: &Arc<ClickhouseDb>,
+) -> bool {
+    let (subscribe, subscription) = match msg {
+        ClientMessage::Subscribe(subscription) => (true, subscription),
+        ClientMessage::Unsubscribe(subscription) => (false, subscription),
+    };
+    let Subscription {
+        channel,
+        ref mints,
+        interval,
+        ref filters,
+        ..
+    } = subscription;
+    let legacy = channel.is_none();
+    let channel = channel.unwrap_or(Channel::Trades);
+
+    if subscribe {
+        if let Some(mint) = subscription.invalid_mint() {
+            return outbox.send(ServerMessage::error(format!("Invalid mint: {}", mint)).to_json());
+        }
+    }
+
+    let result = match (subscribe, channel) {
+        (true, Channel::Trades) => subscriptions.subscribe_trades(mints, filters, legacy),
+        (false, Channel::Trades) => {
+            subscriptions.unsubscribe_trades(mints);
+            Ok(())
+        }
+        (true, Channel::Candles) => match interval {
+            Some(interval) => subscriptions.subscribe_candles(mints, interval),
+            None => Err("Candle subscriptions require an interval".to_string()),
+        },
+        (false, Channel::Candles) => {
+            subscriptions.unsubscribe_candles(mints, interval);
+            Ok(())
+        }
+        (subscribe, Channel::TopTokens) => {
+            subscriptions.set_top_tokens(subscribe);
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        return outbox.send(ServerMessage::error(e).to_json());
+    }
+
+    // the original protocol had no acknowledgements
+    if !legacy {
+        let ack = match subscribe {
+            true => ServerMessage::Subscribed {
+                channel,
+                mints: mints.clone(),
+                interval: interval.map(|i| i.as_str()),
+            },
+            false => ServerMessage::Unsubscribed {
+                channel,
+                mints: mints.clone(),
+                interval: interval.map(|i| i.as_str()),
+            },
+        };
+        if !outbox.send(ack.to_json()) {
+            return false;
+        }
+    }
+
+    if !subscribe || !subscription.wants_snapshot() {
+        return true;
+    }
+
+    let snapshot_mints = mints
+        .iter()
+        .filter(|m| *m != WILDCARD)
+        .take(MAX_SNAPSHOT_MINTS)
+        .cloned()
+        .collect::<Vec<_>>();
+    match channel {
+        // candle subscriptions without an interval were refused above
+        Channel::Candles if interval.is_none() => {}
+        Channel::Trades | Channel::Candles => {
+            snapshots.spawn(send_snapshots(
+                channel,
+                interval,
+                snapshot_mints,
+                clickhouse_db.clone(),
+                outbox.tx.clone(),
+            ));
+        }
+        Channel::TopTokens => {
+            if let Some(latest) = top_tokens_feed.latest().await {
+                return outbox.send(latest.to_string());
+            }
+        }
+    }
+    true
+}
+
+/// Fetches the trades or candles snapshots of the mints a few at a time and
+/// queues them in order, waiting for room instead of dropping them
+async fn send_snapshots(
+    channel: Channel,
+    interval: Option<CandlestickInterval>,
+    mints: Vec<String>,
+    clickhouse_db: Arc<ClickhouseDb>,
+    tx: mpsc::Sender<String>,
+) -> CandleSeeds {
+    let mut seeds = CandleSeeds::new();
+    let mut messages = futures::stream::iter(mints)
+        .map(|mint| {
+            let clickhouse_db = clickhouse_db.clone();
+            async move {
+                let msg = snapshot(channel, interval, &mint, &clickhouse_db).await;
+                (mint, msg)
+            }
+        })
+        .buffered(SNAPSHOT_CONCURRENCY);
+    while let Some((mint, (msg, latest))) = messages.next().await {
+        if let (Some(interval), Some(latest)) = (interval, latest) {
+            seeds.push((mint, interval, latest));
+        }
+        if tx.send(msg.to_json()).await.is_err() {
+            break;
+        }
+    }
+    seeds
+}
+
+/// The snapshot message of the mint, and the latest candle of a candles
+/// snapshot
+async fn snapshot(
+    channel: Channel,
+    interval: Option<CandlestickInterval>,
+    mint: &str,
+    clickhouse_db: &ClickhouseDb,
+) -> (ServerMessage, Option<Candlestick>) {
+    match (channel, interval) {
+        (Channel::Candles, Some(interval)) => {
+            match clickhouse_db
+                .get_candlesticks(mint, &interval, None, None, Some(SNAPSHOT_CANDLES))
+                .await
+            {
+                Ok(candles) => {
+                    let latest = candles.last().cloned();
+                    let msg = ServerMessage::Snapshot {
+                        channel,
+                        mint: Some(mint.to_string()),
+                        interval: Some(interval.as_str()),
+                        data: serde_json::to_value(candles).unwrap_or_default(),
+                    };
+                    (msg, latest)
+                }
+                Err(e) => {
+                    error!("Failed to fetch candles snapshot: {}", e);
+                    let msg = ServerMessage::error(format!("Failed to fetch candles of {}", mint));
+                    (msg, None)
+                }
+            }
+        }
+        _ => {
+            let msg = match clickhouse_db.get_by_mint(mint).await {
+                Ok(trades) => ServerMessage::Snapshot {
+                    channel,
+                    mint: Some(mint.to_string()),
+                    interval: None,
+                    data: serde_json::to_value(trades).unwrap_or_default(),
+                },
+                Err(e) => {
+                    error!("Failed to fetch trades snapshot: {}", e);
+                    ServerMessage::error(format!("Failed to fetch trades of {}", mint))
+                }
+            };
+            (msg, None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_outbox_drops_when_full() {
+        let (tx, mut rx) = mpsc::channel(2);
+        let mut outbox = Outbox { tx, dropped: 0 };
+        assert!(outbox.send("1".to_string()));
+        assert!(outbox.send("2".to_string()));
+        assert!(outbox.send("3".to_string()));
+        assert_eq!(outbox.dropped, 1);
+
+        assert_eq!(rx.recv().await.unwrap(), "1");
+        assert_eq!(rx.recv().await.unwrap(), "2");
+
+        // the notice goes out before the next message
+        assert!(outbox.send("4".to_string()));
+        assert_eq!(outbox.dropped, 0);
+        let notice: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(notice["type"], "dropped");
+        assert_eq!(notice["count"], 1);
+        assert_eq!(rx.recv().await.unwrap(), "4");
+
+        drop(rx);
+        assert!(!outbox.send("5".to_string()));
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+use super::subscriptions::WILDCARD;
+use crate::db::wallets::is_valid_address;
+use crate::db::{candlesticks::Candlestick, candlesticks::CandlestickInterval, PriceUpdate};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    /// every swap of the subscribed mints
+    Trades,
+    /// live candles built from the swaps, at a given interval
+    Candles,
+    /// the top tokens by volume, refreshed periodically
+    TopTokens,
+}
+
+/// Server-side filters for the trades channel
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TradeFilters {
+    pub min_swap_usd: Option<f64>,
+    pub is_buy: Option<bool>,
+    pub min_market_cap: Option<f64>,
+    pub max_market_cap: Option<f64>,
+}
+
+impl TradeFilters {
+    pub fn matches(&self, update: &PriceUpdate) -> bool {
+        self.min_swap_usd
+            .is_none_or(|min| update.swap_amount >= min)
+            && self.is_buy.is_none_or(|is_buy| update.is_buy == is_buy)
+            && self
+                .min_market_cap
+                .is_none_or(|min| update.market_cap >= min)
+            && self
+                .max_market_cap
+                .is_none_or(|max| update.market_cap <= max)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Subscription {
+    /// subscriptions without a channel are the original protocol: trades,
+    /// replacing whatever was subscribed before
+    pub channel: Option<Channel>,
+    #[serde(default)]
+    pub mints: Vec<String>,
+    pub interval: Option<CandlestickInterval>,
+    #[serde(default)]
+    pub filters: TradeFilters,
+    /// defaults to true for channel subscriptions
+    pub snapshot: Option<bool>,
+}
+
+impl Subscription {
+    pub fn wants_snapshot(&self) -> bool {
+        self.snapshot.unwrap_or(self.channel.is_some())
+    }
+
+    /// The first mint that is neither the wildcard nor a valid address
+    pub fn invalid_mint(&self) -> Option<&str> {
+        self.mints
+            .iter()
+            .map(String::as_str)
+            .find(|mint| *mint != WILDCARD && !is_valid_address(mint))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Subscribe(Subscription),
+    Unsubscribe(Subscription),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Subscribed {
+        channel: Channel,
+        mints: Vec<String>,
+        interval: Option<&'static str>,
+    },
+    Unsubscribed {
+        channel: Channel,
+        mints: Vec<String>,
+        interval: Option<&'static str>,
+    },
+    Snapshot {
+        channel: Channel,
+        mint: Option<String>,
+        interval: Option<&'static str>,
+        data: serde_json::Value,
+    },
+    Candle {
+        mint: String,
+        interval: &'static str,
+        candle: Candlestick,
+    },
+    /// messages that were not delivered since the last notice because the
+    /// connection could not keep up
+    Dropped {
+        count: u64,
+    },
+    Error {
+        error: String,
+    },
+}
+
+impl ServerMessage {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("server messages serialize")
+    }
+
+    pub fn error(error: impl Into<String>) -> Self {
+        ServerMessage::Error {
+            error: error.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price_update(swap_amount: f64, market_cap: f64, is_buy: bool) -> PriceUpdate {
+        PriceUpdate {
+            name: "Test".to_string(),
+            pubkey: "mint".to_string(),
+            price: 1.0,
+            market_cap,
+            timestamp: 1_700_000_000,
+            slot: 1,
+            swap_amount,
+            owner: "owner".to_string(),
+            signature: "sig".to_string(),
+            multi_hop: false,
+            is_buy,
+            is_pump: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_legacy_subscribe() {
+        let msg: ClientMessage =
+            serde_json::from_str(r#"{"action": "subscribe", "mints": ["a", "b"]}"#).unwrap();
+        let ClientMessage::Subscribe(subscription) = msg else {
+            panic!("expected subscribe");
+        };
+        assert_eq!(subscription.channel, None);
+        assert_eq!(subscription.mints, vec!["a", "b"]);
+        assert!(!subscription.wants_snapshot());
+    }
+
+    #[test]
+    fn test_parse_channel_subscribe() {
+        let msg: ClientMessage = serde_json::from_str(
+            r#"{"action": "subscribe", "channel": "candles", "mints": ["a"], "interval": "5m"}"#,
+        )
+        .unwrap();
+        let ClientMessage::Subscribe(subscription) = msg else {
+            panic!("expected subscribe");
+        };
+        assert_eq!(subscription.channel, Some(Channel::Candles));
+        assert_eq!(
+            subscription.interval,
+            Some(CandlestickInterval::FiveMinutes)
+        );
+        assert!(subscription.wants_snapshot());
+
+        let msg: ClientMessage =
+            serde_json::from_str(r#"{"action": "unsubscribe", "channel": "top_tokens"}"#).unwrap();
+        assert!(matches!(msg, ClientMessage::Unsubscribe(_)));
+
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"action": "delete"}"#).is_err());
+    }
+
+    #[test]
+    fn test_invalid_mint() {
+        let msg: ClientMessage = serde_json::from_str(
+            r#"{"action": "subscribe", "channel": "trades", "mints": ["*", "9BB6NFEcjBCtnNLFko2FqVQBq8HHM13kCyYcdQbgpump"]}"#,
+        )
+        .unwrap();
+        let ClientMessage::Subscribe(subscription) = msg else {
+            panic!("expected subscribe");
+        };
+        assert_eq!(subscription.invalid_mint(), None);
+
+        let msg: ClientMessage = serde_json::from_str(
+            r#"{"action": "subscribe", "channel": "trades", "mints": ["x' OR 1=1 --"]}"#,
+        )
+        .unwrap();
+        let ClientMessage::Subscribe(subscription) = msg else {
+            panic!("expected subscribe");
+        };
+        assert_eq!(subscription.invalid_mint(), Some("x' OR 1=1 --"));
+    }
+
+    #[test]
+    fn test_trade_filters() {
+        let filters = TradeFilters {
+            min_swap_usd: Some(100.0),
+            is_buy: Some(true),
+            min_market_cap: Some(10_000.0),
+            max_market_cap: Some(1_000_000.0),
+        };
+        assert!(filters.matches(&price_update(150.0, 50_000.0, true)));
+        assert!(!filters.matches(&price_update(50.0, 50_000.0, true)));
+        assert!(!filters.matches(&price_update(150.0, 50_000.0, false)));
+        assert!(!filters.matches(&price_update(150.0, 5_000.0, true)));
+        assert!(!filters.matches(&price_update(150.0, 5_000_000.0, true)));
+        assert!(TradeFilters::default().matches(&price_update(0.0, 0.0, false)));
+    }
+
+    #[test]
+    fn test_server_message_is_tagged() {
+        let json: serde_json::Value =
+            serde_json::from_str(&ServerMessage::Dropped { count: 3 }.to_json()).unwrap();
+        assert_eq!(json["type"], "dropped");
+        assert_eq!(json["count"], 3);
+    }
+}
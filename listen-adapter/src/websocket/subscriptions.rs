@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use super::protocol::{ServerMessage, TradeFilters};
+use crate::db::candlesticks::{Candlestick, CandlestickInterval};
+use crate::db::PriceUpdate;
+use crate::redis_subscriber::TradeUpdate;
+
+/// Subscribes to the trades of every mint
+pub const WILDCARD: &str = "*";
+
+/// Upper bound on the mints (or mint and interval pairs) a single connection
+/// can subscribe to per channel
+pub const MAX_SUBSCRIPTIONS: usize = 100;
+
+/// What a single connection is subscribed to
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    trades: HashMap<String, TradeFilters>,
+    /// the candle being built for each pair, None until the first trade or
+    /// the snapshot
+    candles: HashMap<(String, CandlestickInterval), Option<Candlestick>>,
+    top_tokens: bool,
+}
+
+impl Subscriptions {
+    pub fn subscribe_trades(
+        &mut self,
+        mints: &[String],
+        filters: &TradeFilters,
+        replace: bool,
+    ) -> Result<(), String> {
+        if replace {
+            self.trades.clear();
+        }
+        let new = mints
+            .iter()
+            .filter(|m| !self.trades.contains_key(*m))
+            .count();
+        if self.trades.len() + new > MAX_SUBSCRIPTIONS {
+            return Err(format!(
+                "Cannot subscribe to more than {} mints",
+                MAX_SUBSCRIPTIONS
+            ));
+        }
+        for mint in mints {
+            self.trades.insert(mint.clone(), filters.clone());
+        }
+        Ok(())
+    }
+
+    /// Unsubscribes from the given mints, or from all of them if empty
+    pub fn unsubscribe_trades(&mut self, mints: &[String]) {
+        if mints.is_empty() {
+            self.trades.clear();
+        }
+        for mint in mints {
+            self.trades.remove(mint);
+        }
+    }
+
+    pub fn subscribe_candles(
+        &mut self,
+        mints: &[String],
+        interval: CandlestickInterval,
+    ) -> Result<(), String> {
+        if mints.iter().any(|m| m == WILDCARD) {
+            return Err("Candles cannot be subscribed for all mints".to_string());
+        }
+        let new = mints
+            .iter()
+            .filter(|m| !self.candles.contains_key(&((*m).clone(), interval)))
+            .count();
+        if self.candles.len() + new > MAX_SUBSCRIPTIONS {
+            return Err(format!(
+                "Cannot subscribe to more than {} candle series",
+                MAX_SUBSCRIPTIONS
+            ));
+        }
+        for mint in mints {
+            self.candles.entry((mint.clone(), interval)).or_insert(None);
+        }
+        Ok(())
+    }
+
+    /// Unsubscribes from the given mints at the interval, or at every
+    /// interval if none is given; no mints means all of them
+    pub fn unsubscribe_candles(&mut self, mints: &[String], interval: Option<CandlestickInterval>) {
+        self.candles.retain(|(mint, candle_interval), _| {
+            let mint_matches = mints.is_empty() || mints.contains(mint);
+            let interval_matches = interval.is_none_or(|i| i == *candle_interval);
+            !(mint_matches && interval_matches)
+        });
+    }
+
+    /// Continues the live candle from the latest candle of the snapshot
+    pub fn seed_candle(&mut self, mint: &str, interval: CandlestickInterval, candle: Candlestick) {
+        if let Some(current) = self.candles.get_mut(&(mint.to_string(), interval)) {
+            // trades that came in while the snapshot was fetched win
+            if current
+                .as_ref()
+                .is_none_or(|c| c.timestamp < candle.timestamp)
+            {
+                *current = Some(candle);
+            }
+        }
+    }
+
+    pub fn set_top_tokens(&mut self, subscribed: bool) {
+        self.top_tokens = subscribed;
+    }
+
+    pub fn top_tokens(&self) -> bool {
+        self.top_tokens
+    }
+
+    /// Messages to send for a trade
+    pub fn on_trade(&mut self, update: &TradeUpdate) -> Vec<String> {
+        let price_update = &update.price_update;
+        let mut messages = Vec::new();
+
+        let filters = self
+            .trades
+            .get(&price_update.pubkey)
+            .or_else(|| self.trades.get(WILDCARD));
+        if filters.is_some_and(|filters| filters.matches(price_update)) {
+            messages.push(update.json.clone());
+        }
+
+        for ((mint, interval), candle) in self.candles.iter_mut() {
+            if *mint != price_update.pubkey {
+                continue;
+            }
+            if let Some(candle) = apply_trade(candle, *interval, price_update) {
+                messages.push(
+                    ServerMessage::Candle {
+                        mint: mint.clone(),
+                        interval: interval.as_str(),
+                        candle: candle.clone(),
+                    }
+                    .to_json(),
+                );
+            }
+        }
+
+        messages
+    }
+}
+
+/// Updates the live candle with a trade, starting a new one when the trade
+/// falls into the next bucket. Returns None for trades older than the
+/// current candle.
+fn apply_trade<'a>(
+    candle: &'a mut Option<Candlestick>,
+    interval: CandlestickInterval,
+    update: &PriceUpdate,
+) -> Option<&'a Candlestick> {
+    let bucket = update.timestamp - update.timestamp % interval.seconds();
+    match candle {
+        Some(current) if current.timestamp == bucket => {
+            current.high = current.high.max(update.price);
+            current.low = current.low.min(update.price);
+            current.close = update.price;
+            current.volume += update.swap_amount;
+        }
+        Some(current) if current.timestamp > bucket => return None,
+        _ => {
+            *candle = Some(Candlestick {
+                timestamp: bucket,
+                open: update.price,
+                high: update.price,
+                low: update.price,
+                close: update.price,
+                volume: update.swap_amount,
+            });
+        }
+    }
+    candle.as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(mint: &str, price: f64, swap_amount: f64, timestamp: u64) -> TradeUpdate {
+        let json = format!(
+            r#"{{"name":"Test","pubkey":"{mint}","price":{price},"market_cap":1000.0,"timestamp":{timestamp},"slot":1,"swap_amount":{swap_amount},"owner":"owner","signature":"sig","multi_hop":false,"is_buy":true,"is_pump":false}}"#
+        );
+        TradeUpdate::from_payload(&json).unwrap()
+    }
+
+    fn candle(messages: &[String]) -> Candlestick {
+        let json: serde_json::Value = serde_json::from_str(messages.last().unwrap()).unwrap();
+        assert_eq!(json["type"], "candle");
+        serde_json::from_value(json["candle"].clone()).unwrap()
+    }
+
+    #[test]
+    fn test_trades_routing() {
+        let mut subscriptions = Subscriptions::default();
+        assert!(subscriptions
+            .on_trade(&trade("a", 1.0, 10.0, 60))
+            .is_empty());
+
+        subscriptions
+            .subscribe_trades(&["a".to_string()], &TradeFilters::default(), false)
+            .unwrap();
+        assert_eq!(subscriptions.on_trade(&trade("a", 1.0, 10.0, 60)).len(), 1);
+        assert!(subscriptions
+            .on_trade(&trade("b", 1.0, 10.0, 60))
+            .is_empty());
+
+        let filters = TradeFilters {
+            min_swap_usd: Some(100.0),
+            ..Default::default()
+        };
+        subscriptions
+            .subscribe_trades(&[WILDCARD.to_string()], &filters, false)
+            .unwrap();
+        assert!(subscriptions
+            .on_trade(&trade("b", 1.0, 10.0, 60))
+            .is_empty());
+        assert_eq!(subscriptions.on_trade(&trade("b", 1.0, 500.0, 60)).len(), 1);
+        // the mint's own filters take precedence over the wildcard
+        assert_eq!(subscriptions.on_trade(&trade("a", 1.0, 10.0, 60)).len(), 1);
+
+        subscriptions.unsubscribe_trades(&["a".to_string()]);
+        assert!(subscriptions
+            .on_trade(&trade("a", 1.0, 10.0, 60))
+            .is_empty());
+
+        // the legacy protocol replaces the subscription
+        subscriptions
+            .subscribe_trades(&["c".to_string()], &TradeFilters::default(), true)
+            .unwrap();
+        assert!(subscriptions
+            .on_trade(&trade("b", 1.0, 500.0, 60))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_subscription_limit() {
+        let mut subscriptions = Subscriptions::default();
+        let mints: Vec<String> = (0..MAX_SUBSCRIPTIONS).map(|i| i.to_string()).collect();
+        subscriptions
+            .subscribe_trades(&mints, &TradeFilters::default(), false)
+            .unwrap();
+        assert!(subscriptions
+            .subscribe_trades(&["new".to_string()], &TradeFilters::default(), false)
+            .is_err());
+        // already subscribed mints do not count twice
+        assert!(subscriptions
+            .subscribe_trades(&mints[..1], &TradeFilters::default(), false)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_live_candles() {
+        let mut subscriptions = Subscriptions::default();
+        subscriptions
+            .subscribe_candles(&["a".to_string()], CandlestickInterval::OneMinute)
+            .unwrap();
+
+        let first = candle(&subscriptions.on_trade(&trade("a", 2.0, 10.0, 65)));
+        assert_eq!(first.timestamp, 60);
+        assert_eq!(first.open, 2.0);
+
+        subscriptions.on_trade(&trade("a", 3.0, 5.0, 70));
+        let updated = candle(&subscriptions.on_trade(&trade("a", 1.0, 5.0, 110)));
+        assert_eq!(
+            updated,
+            Candlestick {
+                timestamp: 60,
+                open: 2.0,
+                high: 3.0,
+                low: 1.0,
+                close: 1.0,
+                volume: 20.0,
+            }
+        );
+
+        let next = candle(&subscriptions.on_trade(&trade("a", 4.0, 1.0, 125)));
+        assert_eq!(next.timestamp, 120);
+        assert_eq!(next.volume, 1.0);
+
+        // late trades do not reopen a closed candle
+        assert!(subscriptions
+            .on_trade(&trade("a", 9.0, 1.0, 100))
+            .is_empty());
+
+        subscriptions.unsubscribe_candles(&[], None);
+        assert!(subscriptions
+            .on_trade(&trade("a", 4.0, 1.0, 130))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_seed_candle() {
+        let mut subscriptions = Subscriptions::default();
+        subscriptions
+            .subscribe_candles(&["a".to_string()], CandlestickInterval::OneMinute)
+            .unwrap();
+        subscriptions.seed_candle(
+            "a",
+            CandlestickInterval::OneMinute,
+            Candlestick {
+                timestamp: 60,
+                open: 1.0,
+                high: 5.0,
+                low: 1.0,
+                close: 2.0,
+                volume: 100.0,
+            },
+        );
+        let updated = candle(&subscriptions.on_trade(&trade("a", 3.0, 10.0, 90)));
+        assert_eq!(updated.open, 1.0);
+        assert_eq!(updated.high, 5.0);
+        assert_eq!(updated.close, 3.0);
+        assert_eq!(updated.volume, 110.0);
+
+        assert!(subscriptions
+            .subscribe_candles(&[WILDCARD.to_string()], CandlestickInterval::OneMinute)
+            .is_err());
+    }
+}
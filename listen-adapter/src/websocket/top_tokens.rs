@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::{broadcast, RwLock};
+use tracing::error;
+
+use crate::db::{top_tokens::TopToken, ClickhouseDb};
+
+pub const TOP_TOKENS_REFRESH_SECS: u64 = 30;
+pub const TOP_TOKENS_LIMIT: usize = 20;
+
+#[derive(Serialize)]
+struct TopTokensMessage<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    tokens: &'a [TopToken],
+}
+
+/// Periodically refreshed top tokens, serialized once and shared between
+/// the websocket connections
+pub struct TopTokensFeed {
+    tx: broadcast::Sender<Arc<String>>,
+    latest: RwLock<Option<Arc<String>>>,
+}
+
+impl Default for TopTokensFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TopTokensFeed {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(16);
+        Self {
+            tx,
+            latest: RwLock::new(None),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<String>> {
+        self.tx.subscribe()
+    }
+
+    /// The last published message, sent as the snapshot on subscribe
+    pub async fn latest(&self) -> Option<Arc<String>> {
+        self.latest.read().await.clone()
+    }
+
+    pub async fn publish(&self, tokens: &[TopToken]) -> anyhow::Result<()> {
+        let message = Arc::new(serde_json::to_string(&TopTokensMessage {
+            kind: "top_tokens",
+            tokens,
+        })?);
+        *self.latest.write().await = Some(message.clone());
+        let _ = self.tx.send(message);
+        Ok(())
+    }
+
+    pub fn start(self: &Arc<Self>, clickhouse_db: Arc<ClickhouseDb>) {
+        let feed = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(TOP_TOKENS_REFRESH_SECS));
+            loop {
+                interval.tick().await;
+                let tokens = match clickhouse_db
+                    .get_top_tokens(TOP_TOKENS_LIMIT, None, None, None, None, true)
+                    .await
+                {
+                    Ok(tokens) => tokens,
+                    Err(e) => {
+                        error!("Failed to refresh top tokens: {}", e);
+                        continue;
+                    }
+                };
+                if let Err(e) = feed.publish(&tokens).await {
+                    error!("Failed to publish top tokens: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_publish_top_tokens() {
+        let feed = TopTokensFeed::new();
+        assert!(feed.latest().await.is_none());
+
+        let mut rx = feed.subscribe();
+        let tokens = vec![TopToken {
+            name: "Test".to_string(),
+            pubkey: "mint".to_string(),
+            price: 1.0,
+            market_cap: 1000.0,
+            volume_24h: 100.0,
+            price_change_24h: 5.0,
+        }];
+        feed.publish(&tokens).await.unwrap();
+
+        let message = rx.recv().await.unwrap();
+        assert_eq!(feed.latest().await, Some(message.clone()));
+        let json: serde_json::Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(json["type"], "top_tokens");
+        assert_eq!(json["tokens"][0]["pubkey"], "mint");
+    }
+}
@@ -0,0 +1,38 @@
+#!/bin/bash
+
+# Migrates an existing price_updates table to the ReplacingMergeTree layout
+# keyed on the swap instruction (signature, instruction index and stack
+# height), deduplicating existing rows. Rows written before the instruction
+# columns existed have both set to 0. listen-data runs the same migration on
+# startup, this script is for doing it ahead of a deploy.
+# Usage: CLICKHOUSE_URL=... CLICKHOUSE_USER=... CLICKHOUSE_PASSWORD=... ./scripts/migrate-price-updates.sh
+
+set -e
+
+query() {
+    curl -sS --fail \
+        --user "${CLICKHOUSE_USER:-default}:${CLICKHOUSE_PASSWORD:-}" \
+        --data-binary "$1" \
+        "${CLICKHOUSE_URL:-http://localhost:8123}"
+}
+
+query "ALTER TABLE price_updates
+ADD COLUMN IF NOT EXISTS instruction_index UInt32 DEFAULT 0 AFTER signature,
+ADD COLUMN IF NOT EXISTS stack_height UInt32 DEFAULT 0 AFTER instruction_index"
+
+query "CREATE TABLE IF NOT EXISTS price_updates_dedup AS price_updates
+ENGINE = ReplacingMergeTree(timestamp)
+ORDER BY (name, pubkey, signature, instruction_index, stack_height)"
+
+# CREATE TABLE ... AS copies the columns but not the data skipping indexes
+query "ALTER TABLE price_updates_dedup
+ADD INDEX IF NOT EXISTS idx_mints (name, pubkey) TYPE minmax GRANULARITY 1,
+ADD INDEX IF NOT EXISTS idx_timestamp timestamp TYPE minmax GRANULARITY 1"
+
+query "INSERT INTO price_updates_dedup SELECT * FROM price_updates"
+
+query "OPTIMIZE TABLE price_updates_dedup FINAL"
+
+query "EXCHANGE TABLES price_updates AND price_updates_dedup"
+
+echo "Migration complete, previous table kept as price_updates_dedup"
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use clap::Parser;
+use listen_data::{
+    holders::{
+        snapshot_holders, DEFAULT_HOLDER_SNAPSHOT_INTERVAL_SECS,
+        DEFAULT_HOLDER_SNAPSHOT_LIMIT,
+    },
+    util::{make_db, make_rpc_client},
+};
+use std::{collections::HashSet, time::Duration};
+use tracing::{error, info};
+
+/// Periodically stores the top holders of the most traded mints
+#[derive(Parser)]
+pub struct Args {
+    /// Mints to snapshot in addition to the most traded ones
+    #[arg(long, value_delimiter = ',')]
+    mints: Vec<String>,
+    /// How many of the most traded mints of the last day to snapshot
+    #[arg(long, default_value_t = 50)]
+    top: usize,
+    /// Holders kept per snapshot
+    #[arg(long, default_value_t = DEFAULT_HOLDER_SNAPSHOT_LIMIT)]
+    limit: usize,
+    #[arg(long, default_value_t = DEFAULT_HOLDER_SNAPSHOT_INTERVAL_SECS)]
+    interval_secs: u64,
+    /// Take a single round of snapshots and exit
+    #[arg(long)]
+    once: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    listen_tracing::setup_tracing();
+    if std::env::var("IS_SYSTEMD_SERVICE").is_err() {
+        dotenv::dotenv().expect("Failed to load .env file");
+    }
+    let args = Args::parse();
+    info!("Starting holder snapshots...");
+
+    let db = make_db().await?;
+    let rpc_client = make_rpc_client()?;
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(args.interval_secs));
+    loop {
+        interval.tick().await;
+
+        let since = chrono::Utc::now().timestamp() as u64 - 86400;
+        let mut mints = args.mints.clone();
+        match db.get_most_traded_mints(since, args.top).await {
+            Ok(traded) => mints.extend(traded),
+            Err(e) => error!("Failed to get most traded mints: {}", e),
+        }
+        let mut seen = HashSet::new();
+        mints.retain(|mint| seen.insert(mint.clone()));
+
+        // getProgramAccounts is heavy on the RPC, mints go one at a time
+        for mint in &mints {
+            let holders =
+                match snapshot_holders(&rpc_client, mint, args.limit).await {
+                    Ok(holders) => holders,
+                    Err(e) => {
+                        error!(mint, "Failed to snapshot holders: {}", e);
+                        continue;
+                    }
+                };
+            if let Err(e) = db.insert_holder_snapshot(&holders).await {
+                error!(mint, "Failed to insert holder snapshot: {}", e);
+                continue;
+            }
+            info!(mint, holders = holders.len(), "holder snapshot");
+        }
+
+        if args.once {
+            return Ok(());
+        }
+    }
+}
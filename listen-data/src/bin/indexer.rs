@@ -1,12 +1,16 @@
 use anyhow::Result;
 use clap::Parser;
 use listen_data::{
+    db::DEFAULT_CANDLESTICK_ROLLUP_SECS,
     geyser::make_geyser_pipeline,
+    handler::LiquidityHandler,
     metrics::SwapMetrics,
-    sol_price_stream::SolPriceCache,
-    util::{make_db, make_kv_store, make_message_queue},
+    util::{
+        make_db, make_kv_store, make_message_queue, make_rpc_client,
+        make_sinks, make_sol_price_cache,
+    },
 };
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tracing::{error, info};
 
 #[derive(Parser)]
@@ -22,16 +26,38 @@ async fn main() -> Result<()> {
 
     let db = make_db().await?;
     let kv_store = make_kv_store().await?;
+    tokio::spawn(db.clone().run_candlestick_rollup(
+        Duration::from_secs(DEFAULT_CANDLESTICK_ROLLUP_SECS),
+        kv_store.clone(),
+    ));
     let message_queue = make_message_queue().await?;
     let swap_metrics = Arc::new(SwapMetrics::new());
     let price_cache =
-        SolPriceCache::new(Some(kv_store.clone()), Some(message_queue.clone()));
+        make_sol_price_cache(kv_store.clone(), message_queue.clone())?;
     let price_cache = Arc::new(price_cache);
 
     info!("Solana price: {}", price_cache.get_price().await);
 
-    let mut pipeline =
-        make_geyser_pipeline(kv_store, message_queue, db, swap_metrics)?;
+    let sinks = make_sinks(message_queue).await?;
+
+    // pool account updates are a lot of extra traffic, opt-in for now
+    let liquidity_handler = match std::env::var("TRACK_LIQUIDITY") {
+        Ok(_) => Some(Arc::new(LiquidityHandler::new(
+            kv_store.clone(),
+            sinks.clone(),
+            Arc::new(make_rpc_client()?),
+            swap_metrics.clone(),
+        ))),
+        Err(_) => None,
+    };
+
+    let mut pipeline = make_geyser_pipeline(
+        kv_store,
+        sinks,
+        db,
+        swap_metrics,
+        liquidity_handler,
+    )?;
 
     tokio::spawn(async move {
         if let Err(e) = price_cache.start_price_stream().await {
@@ -4,7 +4,7 @@ use std::sync::Arc;
 
 #[derive(Parser)]
 pub enum Command {
-    RaydiumAccountsRpc,
+    PoolAccountsRpc,
     RaydiumInstructionsRpc,
 }
 
@@ -12,13 +12,16 @@ pub enum Command {
 #[tokio::main]
 async fn main() -> Result<()> {
     use listen_data::{
+        handler::LiquidityHandler,
         metrics::SwapMetrics,
         rpc::{
-            account_pipeline::make_raydium_rpc_accounts_pipeline,
+            account_pipeline::make_pool_rpc_accounts_pipeline,
             instruction_pipeline::make_raydium_rpc_instruction_pipeline,
         },
-        sol_price_stream::SolPriceCache,
-        util::{make_db, make_kv_store, make_message_queue},
+        util::{
+            make_db, make_kv_store, make_message_queue, make_rpc_client,
+            make_sinks, make_sol_price_cache,
+        },
     };
     use listen_tracing::setup_tracing;
     use tracing::{error, info};
@@ -36,7 +39,7 @@ async fn main() -> Result<()> {
 
     // Initialize price cache for cold starts
     let price_cache =
-        SolPriceCache::new(Some(kv_store.clone()), Some(message_queue.clone()));
+        make_sol_price_cache(kv_store.clone(), message_queue.clone())?;
     let price_cache = Arc::new(price_cache);
 
     info!("Solana price: {}", price_cache.get_price().await);
@@ -44,11 +47,18 @@ async fn main() -> Result<()> {
     let command = Command::parse();
 
     let mut pipeline = match command {
-        Command::RaydiumAccountsRpc => make_raydium_rpc_accounts_pipeline()?,
+        Command::PoolAccountsRpc => {
+            make_pool_rpc_accounts_pipeline(Arc::new(LiquidityHandler::new(
+                kv_store,
+                make_sinks(message_queue).await?,
+                Arc::new(make_rpc_client()?),
+                metrics,
+            )))?
+        }
         Command::RaydiumInstructionsRpc => {
             make_raydium_rpc_instruction_pipeline(
                 kv_store,
-                message_queue,
+                make_sinks(message_queue).await?,
                 db,
                 metrics,
             )?
@@ -59,6 +59,12 @@ pub const PUMP_SWAP_PROGRAM_ID: Pubkey =
 pub const PUMP_SWAP_PROGRAM_ID_STR: &str =
     "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA";
 
+pub const PUMP_FUN_PROGRAM_ID: Pubkey =
+    pubkey!("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P");
+
+pub const PUMP_FUN_PROGRAM_ID_STR: &str =
+    "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+
 // hardcoded program ids
 pub const TOKEN_PROGRAM_ID: Pubkey =
     pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
@@ -1,11 +1,15 @@
 use std::{sync::Arc, time::Duration};
 
+use crate::holders::HolderSnapshot;
+use crate::kv_store::RedisKVStore;
 use crate::price::PriceUpdate;
 use anyhow::{Context, Result};
 use clickhouse::inserter::Inserter;
 use clickhouse::Client;
+use serde::Serialize;
 use tokio::sync::RwLock;
-use tracing::{debug, info};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info};
 
 #[async_trait::async_trait]
 pub trait Database {
@@ -24,6 +28,142 @@ pub trait Database {
     async fn insert_price(&self, price: &PriceUpdate) -> Result<()>;
 }
 
+/// Candlestick intervals as table suffix, bucket size in seconds and the
+/// interval they are rolled up from, the shortest one is rolled up from
+/// price_updates; sources come before the intervals built from them.
+/// listen-adapter reads `candlesticks_{suffix}`
+pub const CANDLESTICK_INTERVALS: [(&str, u64, Option<&str>); 9] = [
+    ("15s", 15, None),
+    ("30s", 30, Some("15s")),
+    ("1m", 60, Some("15s")),
+    ("5m", 300, Some("1m")),
+    ("15m", 900, Some("1m")),
+    ("30m", 1800, Some("1m")),
+    ("1h", 3600, Some("1m")),
+    ("4h", 14400, Some("1h")),
+    ("1d", 86400, Some("1h")),
+];
+
+pub const DEFAULT_CANDLESTICK_ROLLUP_SECS: u64 = 15;
+
+/// How far back every rollup recomputes, has to cover the inserter period
+/// so rows that were still buffered during the last rollup are counted
+pub const CANDLESTICK_ROLLUP_LOOKBACK_SECS: u64 = 120;
+
+/// Channel every finished rollup is announced on, listen-adapter drops its
+/// cached candles of the mints that traded before `covers_until_ms`
+pub const CANDLESTICK_ROLLUPS_CHANNEL: &str = "candlestick_rollups";
+
+/// Longest a price update waits in the inserter buffer
+const INSERTER_PERIOD: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Serialize)]
+pub struct CandlestickRollup {
+    /// swaps published before this are counted in the candles
+    pub covers_until_ms: u64,
+}
+
+/// Swaps keyed on the instruction (signature, instruction index and stack
+/// height), a swap emitted twice collapses into one row on merge
+fn price_updates_table_sql(table: &str) -> String {
+    format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {table} (
+            name String,
+            pubkey String,
+            price Float64,
+            market_cap Float64,
+            timestamp UInt64,
+            slot UInt64,
+            swap_amount Float64,
+            owner String,
+            signature String,
+            instruction_index UInt32 DEFAULT 0,
+            stack_height UInt32 DEFAULT 0,
+            multi_hop Bool,
+            is_buy Bool,
+            is_pump Bool,
+            INDEX idx_mints (name, pubkey) TYPE minmax GRANULARITY 1,
+            INDEX idx_timestamp timestamp TYPE minmax GRANULARITY 1
+        )
+        ENGINE = ReplacingMergeTree(timestamp)
+        ORDER BY ({PRICE_UPDATES_KEY})
+        "#
+    )
+}
+
+const PRICE_UPDATES_KEY: &str =
+    "name, pubkey, signature, instruction_index, stack_height";
+
+/// OHLCV buckets per mint. Buckets are recomputed from deduplicated rows
+/// and replace the previous version, readers query with FINAL.
+fn candlestick_table_sql(table: &str) -> String {
+    format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {table} (
+            pubkey String,
+            interval_timestamp UInt64,
+            open Float64,
+            high Float64,
+            low Float64,
+            close Float64,
+            volume Float64,
+            updated_at UInt64
+        )
+        ENGINE = ReplacingMergeTree(updated_at)
+        ORDER BY (pubkey, interval_timestamp)
+        "#
+    )
+}
+
+/// Recomputes the buckets from the given start on, from price_updates FINAL
+/// so swaps written twice are counted once, or from the source interval's
+/// candles. The bucket is aliased so it does not shadow the columns the
+/// open and close are ordered by.
+fn candlestick_rollup_sql(
+    table: &str,
+    seconds: u64,
+    source: Option<&str>,
+) -> String {
+    let select = match source {
+        None => format!(
+            r#"
+            SELECT
+                pubkey,
+                intDiv(timestamp, {seconds}) * {seconds} AS bucket,
+                argMin(price, timestamp),
+                max(price),
+                min(price),
+                argMax(price, timestamp),
+                sum(swap_amount),
+                ?
+            FROM price_updates FINAL
+            WHERE timestamp >= ?
+            "#
+        ),
+        Some(source) => format!(
+            r#"
+            SELECT
+                pubkey,
+                intDiv(interval_timestamp, {seconds}) * {seconds} AS bucket,
+                argMin(open, interval_timestamp),
+                max(high),
+                min(low),
+                argMax(close, interval_timestamp),
+                sum(volume),
+                ?
+            FROM candlesticks_{source} FINAL
+            WHERE interval_timestamp >= ?
+            "#
+        ),
+    };
+    format!(
+        "INSERT INTO {table} \
+         (pubkey, interval_timestamp, open, high, low, close, volume, updated_at) \
+         {select} GROUP BY pubkey, bucket"
+    )
+}
+
 pub struct ClickhouseDb {
     client: Client,
     inserter: Option<Arc<RwLock<Inserter<PriceUpdate>>>>,
@@ -43,7 +183,233 @@ impl ClickhouseDb {
             )
             .with_max_rows(self.max_rows)
             .with_max_bytes(1_000_000) // price update is roughly ~200 bytes
-            .with_period(Some(Duration::from_secs(15))))
+            .with_period(Some(INSERTER_PERIOD)))
+    }
+}
+
+impl ClickhouseDb {
+    async fn price_updates_migrated(&self) -> Result<bool> {
+        let migrated = self
+            .client
+            .query(
+                r#"
+                SELECT count() FROM system.tables
+                WHERE database = currentDatabase()
+                    AND name = 'price_updates'
+                    AND engine = 'ReplacingMergeTree'
+                    AND sorting_key = ?
+                "#,
+            )
+            .bind(PRICE_UPDATES_KEY)
+            .fetch_one::<u64>()
+            .await
+            .context("Failed to check the price_updates table")?;
+        Ok(migrated > 0)
+    }
+
+    /// Copies price_updates into a table of the current layout and swaps
+    /// them, what scripts/migrate-price-updates.sh does by hand; the
+    /// previous table is kept as price_updates_next
+    async fn migrate_price_updates(&self) -> Result<()> {
+        info!("migrating price_updates to the instruction key");
+        let queries = [
+            "DROP TABLE IF EXISTS price_updates_next".to_string(),
+            price_updates_table_sql("price_updates_next"),
+            r#"
+            INSERT INTO price_updates_next
+            SELECT name, pubkey, price, market_cap, timestamp, slot,
+                swap_amount, owner, signature, instruction_index,
+                stack_height, multi_hop, is_buy, is_pump
+            FROM price_updates
+            "#
+            .to_string(),
+            "EXCHANGE TABLES price_updates AND price_updates_next".to_string(),
+        ];
+        for query in queries {
+            self.client
+                .query(&query)
+                .execute()
+                .await
+                .context("Failed to migrate price_updates")?;
+        }
+        info!(
+            "migrated price_updates, previous table kept as price_updates_next"
+        );
+        Ok(())
+    }
+
+    /// Creates the candlestick table, replacing the insert-triggered
+    /// aggregating view and table of earlier versions; candles are derived
+    /// data and are rebuilt by the backfill
+    async fn create_candlestick_table(&self, suffix: &str) -> Result<()> {
+        let table = format!("candlesticks_{}", suffix);
+        let legacy_view = format!("{}_mv", table);
+
+        let legacy = self
+            .client
+            .query("EXISTS TABLE ?")
+            .bind(clickhouse::sql::Identifier(&legacy_view))
+            .fetch_one::<u8>()
+            .await
+            .with_context(|| format!("Failed to check {}", legacy_view))?;
+        if legacy == 1 {
+            info!("replacing {} materialized view with rollups", table);
+            self.client
+                .query("DROP VIEW IF EXISTS ?")
+                .bind(clickhouse::sql::Identifier(&legacy_view))
+                .execute()
+                .await
+                .with_context(|| format!("Failed to drop {}", legacy_view))?;
+            self.client
+                .query("DROP TABLE IF EXISTS ?")
+                .bind(clickhouse::sql::Identifier(&table))
+                .execute()
+                .await
+                .with_context(|| format!("Failed to drop {}", table))?;
+        }
+
+        self.client
+            .query(&candlestick_table_sql(&table))
+            .execute()
+            .await
+            .with_context(|| format!("Failed to create {}", table))?;
+        Ok(())
+    }
+
+    /// Recomputes the candles of every interval from `since` on, each
+    /// interval from the start of the bucket `since` falls into
+    pub async fn rollup_candlesticks(&self, since: u64) -> Result<()> {
+        let updated_at = chrono::Utc::now().timestamp() as u64;
+        for (suffix, seconds, source) in CANDLESTICK_INTERVALS {
+            let table = format!("candlesticks_{}", suffix);
+            self.client
+                .query(&candlestick_rollup_sql(&table, seconds, source))
+                .bind(updated_at)
+                .bind(since / seconds * seconds)
+                .execute()
+                .await
+                .with_context(|| format!("Failed to roll up {}", table))?;
+        }
+        Ok(())
+    }
+
+    /// Builds the candles from all of price_updates unless a previous
+    /// backfill finished, the marker is only written once it has
+    pub async fn backfill_candlesticks(&self) -> Result<()> {
+        let backfilled = self
+            .client
+            .query("SELECT count() FROM candlestick_backfills FINAL")
+            .fetch_one::<u64>()
+            .await
+            .context("Failed to check candlestick backfill")?;
+        if backfilled as usize == CANDLESTICK_INTERVALS.len() {
+            return Ok(());
+        }
+
+        info!("backfilling candlesticks");
+        self.rollup_candlesticks(0).await?;
+
+        let backfilled_at = chrono::Utc::now().timestamp() as u64;
+        for (suffix, _, _) in CANDLESTICK_INTERVALS {
+            self.client
+                .query(
+                    "INSERT INTO candlestick_backfills (name, backfilled_at) \
+                     VALUES (?, ?)",
+                )
+                .bind(format!("candlesticks_{}", suffix))
+                .bind(backfilled_at)
+                .execute()
+                .await
+                .context("Failed to mark candlestick backfill")?;
+        }
+        info!("backfilled candlesticks");
+        Ok(())
+    }
+
+    /// Backfills if needed, then keeps recomputing the recent candles every
+    /// `period` and announces each rollup; several indexers can run it, the
+    /// rollups are idempotent
+    pub async fn run_candlestick_rollup(
+        self: Arc<Self>,
+        period: Duration,
+        kv_store: Arc<RedisKVStore>,
+    ) {
+        while let Err(e) = self.backfill_candlesticks().await {
+            error!(?e, "candlestick backfill failed");
+            tokio::time::sleep(period).await;
+        }
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started_ms = chrono::Utc::now().timestamp_millis() as u64;
+            let since = (started_ms / 1000)
+                .saturating_sub(CANDLESTICK_ROLLUP_LOOKBACK_SECS);
+            if let Err(e) = self.rollup_candlesticks(since).await {
+                error!(?e, "candlestick rollup failed");
+                continue;
+            }
+            // swaps still buffered by the inserters are left for the next one
+            let rollup = CandlestickRollup {
+                covers_until_ms: started_ms
+                    .saturating_sub(INSERTER_PERIOD.as_millis() as u64),
+            };
+            if let Err(e) =
+                kv_store.publish(CANDLESTICK_ROLLUPS_CHANNEL, &rollup).await
+            {
+                error!(?e, "failed to announce candlestick rollup");
+            }
+        }
+    }
+}
+
+impl ClickhouseDb {
+    /// Snapshots are small, a few hundred rows per mint, and are written
+    /// in one insert
+    pub async fn insert_holder_snapshot(
+        &self,
+        holders: &[HolderSnapshot],
+    ) -> Result<()> {
+        let mut insert = self
+            .client
+            .insert::<HolderSnapshot>("holder_snapshots")
+            .context("failed to prepare holder snapshot insert")?;
+        for holder in holders {
+            insert
+                .write(holder)
+                .await
+                .context("failed to write holder snapshot")?;
+        }
+        insert
+            .end()
+            .await
+            .context("failed to insert holder snapshot")?;
+        Ok(())
+    }
+
+    /// Mints by swap volume since the given time, for picking the mints
+    /// worth snapshotting
+    pub async fn get_most_traded_mints(
+        &self,
+        since: u64,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        self.client
+            .query(
+                r#"
+                SELECT pubkey
+                FROM price_updates
+                WHERE timestamp >= ?
+                GROUP BY pubkey
+                ORDER BY sum(swap_amount) DESC
+                LIMIT ?
+                "#,
+            )
+            .bind(since)
+            .bind(limit)
+            .fetch_all::<String>()
+            .await
+            .context("Failed to get most traded mints")
     }
 }
 
@@ -80,33 +446,78 @@ impl Database for ClickhouseDb {
         Ok(())
     }
 
+    /// price_updates is a ReplacingMergeTree keyed on the swap instruction
+    /// (signature, instruction index and stack height), a swap emitted twice
+    /// collapses into one row on merge; readers that sum volume should query
+    /// with FINAL to not count the unmerged duplicates
     async fn initialize(&mut self) -> Result<()> {
         debug!("initializing clickhouse");
+        self.client
+            .query(&price_updates_table_sql("price_updates"))
+            .execute()
+            .await
+            .context("Failed to create price_updates table")?;
+
         self.client
             .query(
                 r#"
-                CREATE TABLE IF NOT EXISTS price_updates (
-                    name String,
-                    pubkey String,
-                    price Float64,
-                    market_cap Float64,
+                ALTER TABLE price_updates
+                ADD COLUMN IF NOT EXISTS instruction_index UInt32 DEFAULT 0 AFTER signature,
+                ADD COLUMN IF NOT EXISTS stack_height UInt32 DEFAULT 0 AFTER instruction_index
+                "#,
+            )
+            .execute()
+            .await
+            .context("Failed to add instruction columns to price_updates")?;
+
+        // readers query price_updates with FINAL, which tables created
+        // before the instruction key do not support
+        if !self.price_updates_migrated().await? {
+            self.migrate_price_updates().await?;
+        }
+
+        // top holders of a mint over time, written by the holder-snapshots
+        // job and read by listen-adapter
+        self.client
+            .query(
+                r#"
+                CREATE TABLE IF NOT EXISTS holder_snapshots (
+                    mint String,
                     timestamp UInt64,
-                    slot UInt64,
-                    swap_amount Float64,
+                    rank UInt32,
                     owner String,
-                    signature String,
-                    multi_hop Bool,
-                    is_buy Bool,
-                    is_pump Bool,
-                    INDEX idx_mints (name, pubkey) TYPE minmax GRANULARITY 1
-                ) 
-                ENGINE = MergeTree()
-                ORDER BY (name, pubkey, timestamp)
+                    amount Float64,
+                    percentage Float64,
+                    holder_count UInt64
+                )
+                ENGINE = MergeTree
+                ORDER BY (mint, timestamp, rank)
+                TTL toDateTime(timestamp) + INTERVAL 90 DAY
                 "#,
             )
             .execute()
             .await
-            .context("Failed to create price_updates table")?;
+            .context("Failed to create holder_snapshots table")?;
+
+        // candlestick tables whose backfill finished
+        self.client
+            .query(
+                r#"
+                CREATE TABLE IF NOT EXISTS candlestick_backfills (
+                    name String,
+                    backfilled_at UInt64
+                )
+                ENGINE = ReplacingMergeTree(backfilled_at)
+                ORDER BY name
+                "#,
+            )
+            .execute()
+            .await
+            .context("Failed to create candlestick_backfills table")?;
+
+        for (suffix, _, _) in CANDLESTICK_INTERVALS {
+            self.create_candlestick_table(suffix).await?;
+        }
 
         self.inserter = Some(Arc::new(RwLock::new(self.create_inserter()?)));
         self.is_initialized = true;
@@ -148,6 +559,37 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_candlestick_rollup_sql() {
+        let sql = candlestick_rollup_sql("candlesticks_15s", 15, None);
+        assert!(sql.contains("intDiv(timestamp, 15) * 15 AS bucket"));
+        assert!(sql.contains("FROM price_updates FINAL"));
+        // the bucket alias must not shadow the columns open and close use
+        assert!(sql.contains("argMin(price, timestamp)"));
+        assert!(!sql.contains("AS timestamp"));
+
+        let sql = candlestick_rollup_sql("candlesticks_1d", 86400, Some("1h"));
+        assert!(sql.contains("FROM candlesticks_1h FINAL"));
+        assert!(sql.contains("argMax(close, interval_timestamp)"));
+        assert!(!sql.contains("AS interval_timestamp"));
+    }
+
+    #[test]
+    fn test_candlestick_sources_come_first() {
+        for (i, (_, seconds, source)) in
+            CANDLESTICK_INTERVALS.iter().enumerate()
+        {
+            let Some(source) = source else {
+                continue;
+            };
+            let (_, source_seconds, _) = CANDLESTICK_INTERVALS[..i]
+                .iter()
+                .find(|(suffix, _, _)| suffix == source)
+                .expect("source rolled up before the interval");
+            assert_eq!(seconds % source_seconds, 0);
+        }
+    }
+
     #[tokio::test]
     async fn test_health_check() {
         let db = make_db().await.unwrap();
@@ -0,0 +1,252 @@
+use crate::kv_store::RedisKVStore;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+pub const DEFAULT_DEDUPE_CAPACITY: usize = 100_000;
+pub const DEFAULT_DEDUPE_TTL_SECS: u64 = 60 * 60;
+
+/// Identifies a single swap instruction within a transaction.
+///
+/// A transaction can carry several swaps (multi-hop routes), so the signature
+/// alone is not enough. Carbon's instruction index is only unique within one
+/// stack height of one outer instruction, so the pool vaults the swap moves
+/// funds through are part of the key as well. All of it stays the same
+/// whichever processor picks the instruction up and across geyser reconnects.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SwapKey {
+    pub signature: String,
+    pub instruction: String,
+}
+
+impl SwapKey {
+    pub fn new(
+        signature: &str,
+        index: u32,
+        stack_height: u32,
+        vaults: &HashSet<String>,
+    ) -> Self {
+        let mut vaults = vaults.iter().map(String::as_str).collect::<Vec<_>>();
+        vaults.sort_unstable();
+        Self {
+            signature: signature.to_string(),
+            instruction: format!(
+                "{}:{}:{}",
+                index,
+                stack_height,
+                vaults.join(",")
+            ),
+        }
+    }
+
+    fn as_redis_key(&self) -> String {
+        format!("solana:swap:{}:{}", self.signature, self.instruction)
+    }
+}
+
+/// Bounded LRU set of recently seen keys.
+///
+/// Entries are stamped with a generation on every touch; stale queue entries
+/// are skipped on eviction so a hit only costs a push to the back.
+#[derive(Debug)]
+struct LruSet {
+    capacity: usize,
+    generation: u64,
+    entries: HashMap<SwapKey, u64>,
+    order: VecDeque<(SwapKey, u64)>,
+}
+
+impl LruSet {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            generation: 0,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Returns true if the key was already present
+    fn touch(&mut self, key: &SwapKey) -> bool {
+        self.generation += 1;
+        let existed =
+            self.entries.insert(key.clone(), self.generation).is_some();
+        self.order.push_back((key.clone(), self.generation));
+
+        while self.entries.len() > self.capacity {
+            self.evict_oldest();
+        }
+
+        // hits leave stale entries behind in the queue, compact once they
+        // start to dominate
+        if self.order.len() > self.capacity * 2 {
+            let entries = &self.entries;
+            self.order.retain(|(k, g)| entries.get(k) == Some(g));
+        }
+
+        existed
+    }
+
+    /// Stale queue entries of the key are skipped on eviction
+    fn remove(&mut self, key: &SwapKey) {
+        self.entries.remove(key);
+    }
+
+    fn evict_oldest(&mut self) {
+        while let Some((key, generation)) = self.order.pop_front() {
+            if self.entries.get(&key) == Some(&generation) {
+                self.entries.remove(&key);
+                return;
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Drops swaps that were already emitted, so the same instruction is written
+/// to ClickHouse, pub/sub and the KV store at most once.
+///
+/// The in-process LRU catches duplicates within a single indexer (several
+/// processors matching the same instruction), Redis SETNX catches the ones
+/// that survive a restart or geyser reconnect.
+///
+/// Keys are claimed before the write, a swap whose write fails is released
+/// again so that a replay of it is not dropped.
+pub struct SwapDeduplicator {
+    recent: Mutex<LruSet>,
+    kv_store: Option<Arc<RedisKVStore>>,
+    ttl_secs: u64,
+}
+
+impl SwapDeduplicator {
+    pub fn new(
+        kv_store: Option<Arc<RedisKVStore>>,
+        capacity: usize,
+        ttl_secs: u64,
+    ) -> Self {
+        Self {
+            recent: Mutex::new(LruSet::new(capacity)),
+            kv_store,
+            ttl_secs,
+        }
+    }
+
+    /// Cheap local check, returns true if the key was seen by this process
+    pub fn seen_locally(&self, key: &SwapKey) -> bool {
+        self.recent.lock().expect("dedupe lock poisoned").touch(key)
+    }
+
+    /// Claims the key in Redis, returns true if another writer already did
+    ///
+    /// Redis errors are treated as "not seen", a duplicate row is preferable
+    /// to dropping a swap
+    pub async fn seen_globally(&self, key: &SwapKey) -> bool {
+        let Some(kv_store) = &self.kv_store else {
+            return false;
+        };
+        match kv_store.set_nx(&key.as_redis_key(), self.ttl_secs).await {
+            Ok(claimed) => !claimed,
+            Err(e) => {
+                warn!(?e, signature = %key.signature, "dedupe setnx failed");
+                false
+            }
+        }
+    }
+
+    /// Gives the claim on a swap that failed to be written back, so that a
+    /// replay of it is written instead of skipped
+    pub async fn release(&self, key: &SwapKey) {
+        self.recent
+            .lock()
+            .expect("dedupe lock poisoned")
+            .remove(key);
+        let Some(kv_store) = &self.kv_store else {
+            return;
+        };
+        if let Err(e) = kv_store.delete(&key.as_redis_key()).await {
+            warn!(?e, signature = %key.signature, "dedupe release failed");
+        }
+    }
+
+    /// Returns true if the swap is a duplicate and should be skipped
+    pub async fn is_duplicate(&self, key: &SwapKey) -> bool {
+        self.seen_locally(key) || self.seen_globally(key).await
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.lock().expect("dedupe lock poisoned").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vaults(a: &str, b: &str) -> HashSet<String> {
+        HashSet::from([a.to_string(), b.to_string()])
+    }
+
+    #[test]
+    fn test_swap_key_is_order_independent() {
+        let a = SwapKey::new("sig", 1, 2, &vaults("v1", "v2"));
+        let b = SwapKey::new("sig", 1, 2, &vaults("v2", "v1"));
+        assert_eq!(a, b);
+
+        let other_pool = SwapKey::new("sig", 1, 2, &vaults("v3", "v4"));
+        assert_ne!(a, other_pool);
+    }
+
+    #[tokio::test]
+    async fn test_local_dedupe() {
+        let dedupe = SwapDeduplicator::new(None, 10, DEFAULT_DEDUPE_TTL_SECS);
+        let key = SwapKey::new("sig", 1, 1, &vaults("v1", "v2"));
+        assert!(!dedupe.is_duplicate(&key).await);
+        assert!(dedupe.is_duplicate(&key).await);
+    }
+
+    #[tokio::test]
+    async fn test_release() {
+        let dedupe = SwapDeduplicator::new(None, 10, DEFAULT_DEDUPE_TTL_SECS);
+        let key = SwapKey::new("sig", 1, 1, &vaults("v1", "v2"));
+        assert!(!dedupe.is_duplicate(&key).await);
+        dedupe.release(&key).await;
+        assert!(!dedupe.is_duplicate(&key).await);
+        assert!(dedupe.is_duplicate(&key).await);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut lru = LruSet::new(2);
+        let k1 = SwapKey::new("1", 1, 1, &vaults("a", "b"));
+        let k2 = SwapKey::new("2", 1, 1, &vaults("a", "b"));
+        let k3 = SwapKey::new("3", 1, 1, &vaults("a", "b"));
+
+        assert!(!lru.touch(&k1));
+        assert!(!lru.touch(&k2));
+        // refresh k1 so k2 becomes the least recently used
+        assert!(lru.touch(&k1));
+        assert!(!lru.touch(&k3));
+
+        assert_eq!(lru.len(), 2);
+        assert!(lru.touch(&k1));
+        assert!(!lru.touch(&k2), "k2 should have been evicted");
+    }
+
+    #[test]
+    fn test_lru_compacts_stale_entries() {
+        let mut lru = LruSet::new(4);
+        let k = SwapKey::new("1", 1, 1, &vaults("a", "b"));
+        for _ in 0..100 {
+            lru.touch(&k);
+        }
+        assert_eq!(lru.len(), 1);
+        assert!(lru.order.len() <= 8);
+    }
+}
@@ -4,6 +4,7 @@ use carbon_log_metrics::LogMetrics;
 use carbon_meteora_dlmm_decoder::MeteoraDlmmDecoder;
 use carbon_orca_whirlpool_decoder::OrcaWhirlpoolDecoder;
 use carbon_pump_swap_decoder::PumpSwapDecoder;
+use carbon_pumpfun_decoder::PumpfunDecoder;
 use carbon_raydium_amm_v4_decoder::RaydiumAmmV4Decoder;
 use carbon_raydium_clmm_decoder::RaydiumClmmDecoder;
 use carbon_raydium_cpmm_decoder::RaydiumCpmmDecoder;
@@ -14,36 +15,121 @@ use std::{
 };
 use tokio::sync::RwLock;
 use yellowstone_grpc_proto::geyser::{
-    CommitmentLevel, SubscribeRequestFilterAccounts,
+    subscribe_request_filter_accounts_filter::Filter,
+    subscribe_request_filter_accounts_filter_memcmp::Data, CommitmentLevel,
+    SubscribeRequestFilterAccounts, SubscribeRequestFilterAccountsFilter,
+    SubscribeRequestFilterAccountsFilterMemcmp,
     SubscribeRequestFilterTransactions,
 };
 
 use crate::{
     constants::{
+        METEORA_DLMM_PROGRAM_ID,
+        PUMP_SWAP_PROGRAM_ID,
+        RAYDIUM_AMM_V4_PROGRAM_ID,
+        RAYDIUM_CLMM_PROGRAM_ID,
+        RAYDIUM_CPMM_PROGRAM_ID,
         TOKEN_PROGRAM_ID,
+        WHIRLPOOLS_PROGRAM_ID,
         // TOKEN_2022_PROGRAM_ID,
-        // METEORA_DLMM_PROGRAM_ID, PUMP_SWAP_PROGRAM_ID,
-        // RAYDIUM_AMM_V4_PROGRAM_ID, RAYDIUM_CLMM_PROGRAM_ID,
-        // RAYDIUM_CPMM_PROGRAM_ID,  WHIRLPOOLS_PROGRAM_ID,
     },
     db::ClickhouseDb,
-    handler::TokenSwapHandler,
+    handler::{LiquidityHandler, TokenSwapHandler},
     kv_store::RedisKVStore,
-    message_queue::RedisMessageQueue,
+    message_queue::FanoutSink,
     metrics::SwapMetrics,
     processor::{
-        MeteoraDlmmInstructionProcessor, OcraWhirlpoolInstructionProcessor,
-        PumpAmmInstructionProcessor, RaydiumAmmV4InstructionProcessor,
-        RaydiumClmmInstructionProcessor, RaydiumCpmmInstructionProcessor,
+        MeteoraDlmmAccountProcessor, MeteoraDlmmInstructionProcessor,
+        OcraWhirlpoolAccountProcessor, OcraWhirlpoolInstructionProcessor,
+        PumpAmmAccountProcessor, PumpAmmInstructionProcessor,
+        PumpFunInstructionProcessor, RaydiumAmmV4AccountProcessor,
+        RaydiumAmmV4InstructionProcessor, RaydiumClmmAccountProcessor,
+        RaydiumClmmInstructionProcessor, RaydiumCpmmAccountProcessor,
+        RaydiumCpmmInstructionProcessor,
     },
     util::must_get_env,
 };
 
+// anchor account discriminators of the pool accounts
+const RAYDIUM_POOL_STATE_DISCRIMINATOR: [u8; 8] =
+    [0xf7, 0xed, 0xe3, 0xf5, 0xd7, 0xc3, 0xde, 0x46];
+const WHIRLPOOL_DISCRIMINATOR: [u8; 8] =
+    [0x3f, 0x95, 0xd1, 0x0c, 0xe1, 0x80, 0x63, 0x09];
+const LB_PAIR_DISCRIMINATOR: [u8; 8] =
+    [0x21, 0x0b, 0x31, 0x62, 0xb5, 0x65, 0xb1, 0x0d];
+const PUMP_SWAP_POOL_DISCRIMINATOR: [u8; 8] =
+    [0xf1, 0x9a, 0x6d, 0x04, 0x11, 0xb1, 0x6d, 0xbc];
+// raydium amm v4 is not an anchor program, AmmInfo is matched on size
+const RAYDIUM_AMM_V4_AMM_INFO_SIZE: u64 = 752;
+
+fn discriminator_filter(
+    discriminator: [u8; 8],
+) -> SubscribeRequestFilterAccountsFilter {
+    SubscribeRequestFilterAccountsFilter {
+        filter: Some(Filter::Memcmp(
+            SubscribeRequestFilterAccountsFilterMemcmp {
+                offset: 0,
+                data: Some(Data::Bytes(discriminator.to_vec())),
+            },
+        )),
+    }
+}
+
+/// Subscribes to the pool accounts only, the programs own plenty of other
+/// accounts (positions, tick arrays, bins) that change far more often
+fn make_pool_account_filters() -> HashMap<String, SubscribeRequestFilterAccounts>
+{
+    [
+        (
+            RAYDIUM_AMM_V4_PROGRAM_ID,
+            SubscribeRequestFilterAccountsFilter {
+                filter: Some(Filter::Datasize(RAYDIUM_AMM_V4_AMM_INFO_SIZE)),
+            },
+        ),
+        (
+            RAYDIUM_CPMM_PROGRAM_ID,
+            discriminator_filter(RAYDIUM_POOL_STATE_DISCRIMINATOR),
+        ),
+        (
+            RAYDIUM_CLMM_PROGRAM_ID,
+            discriminator_filter(RAYDIUM_POOL_STATE_DISCRIMINATOR),
+        ),
+        (
+            WHIRLPOOLS_PROGRAM_ID,
+            discriminator_filter(WHIRLPOOL_DISCRIMINATOR),
+        ),
+        (
+            METEORA_DLMM_PROGRAM_ID,
+            discriminator_filter(LB_PAIR_DISCRIMINATOR),
+        ),
+        (
+            PUMP_SWAP_PROGRAM_ID,
+            discriminator_filter(PUMP_SWAP_POOL_DISCRIMINATOR),
+        ),
+    ]
+    .into_iter()
+    .map(|(program_id, filter)| {
+        (
+            format!("pool_account_filter_{}", program_id),
+            SubscribeRequestFilterAccounts {
+                account: vec![],
+                owner: vec![program_id.to_string()],
+                filters: vec![filter],
+                nonempty_txn_signature: None,
+            },
+        )
+    })
+    .collect()
+}
+
+/// Pool account updates are only subscribed to when a liquidity handler is
+/// passed in
 pub fn make_geyser_pipeline(
     kv_store: Arc<RedisKVStore>,
-    message_queue: Arc<RedisMessageQueue>,
+    message_queue: Arc<FanoutSink>,
     db: Arc<ClickhouseDb>,
     metrics: Arc<SwapMetrics>,
+    liquidity_handler: Option<Arc<LiquidityHandler>>,
 ) -> Result<Pipeline> {
     let mut transaction_filters = HashMap::new();
     // TODO support TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb (other token program)
@@ -71,11 +157,12 @@ pub fn make_geyser_pipeline(
     let token_swap_handler =
         Arc::new(TokenSwapHandler::new(kv_store, message_queue, db, metrics));
 
-    // Create empty account filters since we only care about transactions
-    let account_filters: HashMap<String, SubscribeRequestFilterAccounts> =
-        HashMap::new();
+    let account_filters = match liquidity_handler {
+        Some(_) => make_pool_account_filters(),
+        None => HashMap::new(),
+    };
 
-    let pipeline = Pipeline::builder()
+    let mut builder = Pipeline::builder()
         .datasource(YellowstoneGrpcGeyserClient::new(
             must_get_env("GEYSER_URL"),
             Some(must_get_env("GEYSER_X_TOKEN")),
@@ -110,7 +197,40 @@ pub fn make_geyser_pipeline(
             PumpSwapDecoder,
             PumpAmmInstructionProcessor::new(token_swap_handler.clone()),
         )
-        .build()?;
+        .instruction(
+            PumpfunDecoder,
+            PumpFunInstructionProcessor::new(token_swap_handler.clone()),
+        );
+
+    if let Some(liquidity_handler) = liquidity_handler {
+        builder = builder
+            .account(
+                RaydiumAmmV4Decoder,
+                RaydiumAmmV4AccountProcessor::new(liquidity_handler.clone()),
+            )
+            .account(
+                RaydiumCpmmDecoder,
+                RaydiumCpmmAccountProcessor::new(liquidity_handler.clone()),
+            )
+            .account(
+                RaydiumClmmDecoder,
+                RaydiumClmmAccountProcessor::new(liquidity_handler.clone()),
+            )
+            .account(
+                OrcaWhirlpoolDecoder,
+                OcraWhirlpoolAccountProcessor::new(liquidity_handler.clone()),
+            )
+            .account(
+                MeteoraDlmmDecoder,
+                MeteoraDlmmAccountProcessor::new(liquidity_handler.clone()),
+            )
+            .account(
+                PumpSwapDecoder,
+                PumpAmmAccountProcessor::new(liquidity_handler.clone()),
+            );
+    }
+
+    let pipeline = builder.build()?;
 
     Ok(pipeline)
 }
@@ -0,0 +1,286 @@
+use crate::{
+    kv_store::RedisKVStore,
+    liquidity::{
+        is_quote, is_stable, price_a_in_b, quote_price_usd, to_ui_amount,
+        LiquidityUpdate, PoolState,
+    },
+    message_queue::{FanoutSink, MessageQueue},
+    metadata::get_token_metadata,
+    metrics::SwapMetrics,
+    sol_price_stream::get_sol_price,
+};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as Token};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{debug, error};
+
+pub const DEFAULT_LIQUIDITY_INTERVAL_SECS: u64 = 30;
+
+/// Turns pool account updates into liquidity updates.
+///
+/// Pool accounts of busy pools change with every swap, so updates are
+/// throttled per pool; the vault balances are fetched over RPC since the
+/// reserves are not part of the pool state for most programs.
+pub struct LiquidityHandler<M = FanoutSink>
+where
+    M: MessageQueue,
+{
+    pub kv_store: Arc<RedisKVStore>,
+    pub message_queue: Arc<M>,
+    pub rpc_client: Arc<RpcClient>,
+    pub metrics: Arc<SwapMetrics>,
+    min_interval: Duration,
+    throttle: Mutex<Throttle>,
+}
+
+/// When each pool was last updated; entries older than the throttle window
+/// no longer throttle anything and are pruned once per window, so the map
+/// only holds the pools that changed recently
+struct Throttle {
+    last_update: HashMap<Pubkey, Instant>,
+    last_prune: Instant,
+}
+
+impl Throttle {
+    fn new() -> Self {
+        Self {
+            last_update: HashMap::new(),
+            last_prune: Instant::now(),
+        }
+    }
+
+    /// Returns true if the pool was updated less than `min_interval` ago,
+    /// otherwise records the update
+    fn check(
+        &mut self,
+        pool: &Pubkey,
+        now: Instant,
+        min_interval: Duration,
+    ) -> bool {
+        if now.duration_since(self.last_prune) >= min_interval {
+            self.last_update
+                .retain(|_, at| now.duration_since(*at) < min_interval);
+            self.last_prune = now;
+        }
+        match self.last_update.get(pool) {
+            Some(at) if now.duration_since(*at) < min_interval => true,
+            _ => {
+                self.last_update.insert(*pool, now);
+                false
+            }
+        }
+    }
+}
+
+impl<M> LiquidityHandler<M>
+where
+    M: MessageQueue,
+{
+    pub fn new(
+        kv_store: Arc<RedisKVStore>,
+        message_queue: Arc<M>,
+        rpc_client: Arc<RpcClient>,
+        metrics: Arc<SwapMetrics>,
+    ) -> Self {
+        Self {
+            kv_store,
+            message_queue,
+            rpc_client,
+            metrics,
+            min_interval: Duration::from_secs(DEFAULT_LIQUIDITY_INTERVAL_SECS),
+            throttle: Mutex::new(Throttle::new()),
+        }
+    }
+
+    pub fn with_min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// Returns true if the pool was updated less than `min_interval` ago
+    fn is_throttled(&self, pool: &Pubkey) -> bool {
+        self.throttle
+            .lock()
+            .expect("liquidity lock poisoned")
+            .check(pool, Instant::now(), self.min_interval)
+    }
+
+    pub fn spawn_liquidity_update(&self, pool: PoolState, slot: u64) {
+        // pools without a SOL or stable side can't be priced
+        if !is_quote(&pool.mint_a.to_string())
+            && !is_quote(&pool.mint_b.to_string())
+        {
+            self.metrics.increment_skipped_liquidity_no_quote();
+            return;
+        }
+        if self.is_throttled(&pool.pool) {
+            return;
+        }
+
+        let kv_store = self.kv_store.clone();
+        let message_queue = self.message_queue.clone();
+        let rpc_client = self.rpc_client.clone();
+        let metrics = self.metrics.clone();
+
+        tokio::spawn(async move {
+            match process_liquidity_update(
+                &pool,
+                slot,
+                &rpc_client,
+                &kv_store,
+                message_queue.as_ref(),
+            )
+            .await
+            {
+                Ok(_) => metrics.increment_liquidity_updates(),
+                Err(e) => {
+                    metrics.increment_liquidity_update_failures();
+                    error!(
+                        ?e,
+                        pool = %pool.pool,
+                        dex = pool.dex,
+                        "liquidity update failed"
+                    );
+                }
+            }
+        });
+    }
+}
+
+fn token_amount(account: Option<&Account>, vault: &Pubkey) -> Result<u64> {
+    let account =
+        account.ok_or_else(|| anyhow!("vault {} not found", vault))?;
+    // token-2022 accounts share the base layout with spl-token ones
+    let token = StateWithExtensions::<Token>::unpack(&account.data)
+        .with_context(|| format!("failed to unpack vault {}", vault))?;
+    Ok(token.base.amount)
+}
+
+async fn get_decimals(
+    kv_store: &Arc<RedisKVStore>,
+    mint: &Pubkey,
+) -> Result<u8> {
+    let metadata = get_token_metadata(kv_store, &mint.to_string())
+        .await?
+        .ok_or_else(|| anyhow!("no metadata for {}", mint))?;
+    Ok(metadata.spl.decimals)
+}
+
+pub async fn make_liquidity_update(
+    pool: &PoolState,
+    slot: u64,
+    rpc_client: &RpcClient,
+    kv_store: &Arc<RedisKVStore>,
+) -> Result<LiquidityUpdate> {
+    let vaults = rpc_client
+        .get_multiple_accounts(&[pool.vault_a, pool.vault_b])
+        .await
+        .context("failed to fetch pool vaults")?;
+    let amount_a = token_amount(vaults[0].as_ref(), &pool.vault_a)?
+        .saturating_sub(pool.excluded_a);
+    let amount_b = token_amount(vaults[1].as_ref(), &pool.vault_b)?
+        .saturating_sub(pool.excluded_b);
+
+    let decimals_a = get_decimals(kv_store, &pool.mint_a).await?;
+    let decimals_b = get_decimals(kv_store, &pool.mint_b).await?;
+    let reserve_a = to_ui_amount(amount_a, decimals_a);
+    let reserve_b = to_ui_amount(amount_b, decimals_b);
+
+    let price_a_in_b = price_a_in_b(
+        pool.spot_price,
+        reserve_a,
+        reserve_b,
+        decimals_a,
+        decimals_b,
+    )
+    .ok_or_else(|| anyhow!("pool {} has no price", pool.pool))?;
+
+    let sol_price = get_sol_price().await;
+    let mint_a = pool.mint_a.to_string();
+    let mint_b = pool.mint_b.to_string();
+
+    let b_is_quote = match (
+        quote_price_usd(&mint_a, sol_price),
+        quote_price_usd(&mint_b, sol_price),
+    ) {
+        (_, None) => false,
+        (None, Some(_)) => true,
+        // SOL/stable pools price SOL
+        (Some(_), Some(_)) => is_stable(&mint_b),
+    };
+    let (mint, quote_mint, base_reserve, quote_reserve, price_in_quote) =
+        match b_is_quote {
+            true => (mint_a, mint_b, reserve_a, reserve_b, price_a_in_b),
+            false => (mint_b, mint_a, reserve_b, reserve_a, 1.0 / price_a_in_b),
+        };
+    let quote_usd = quote_price_usd(&quote_mint, sol_price)
+        .ok_or_else(|| anyhow!("pool {} has no quote token", pool.pool))?;
+
+    let price = price_in_quote * quote_usd;
+    Ok(LiquidityUpdate {
+        pool: pool.pool.to_string(),
+        dex: pool.dex.to_string(),
+        mint,
+        quote_mint,
+        base_reserve,
+        quote_reserve,
+        price,
+        tvl_usd: base_reserve * price + quote_reserve * quote_usd,
+        slot,
+        timestamp: Utc::now().timestamp() as u64,
+    })
+}
+
+async fn process_liquidity_update<M: MessageQueue>(
+    pool: &PoolState,
+    slot: u64,
+    rpc_client: &RpcClient,
+    kv_store: &Arc<RedisKVStore>,
+    message_queue: &M,
+) -> Result<()> {
+    let update =
+        make_liquidity_update(pool, slot, rpc_client, kv_store).await?;
+    debug!(?update, "liquidity update");
+
+    kv_store
+        .insert_liquidity(&update)
+        .await
+        .context("failed to insert liquidity")?;
+    message_queue
+        .publish_liquidity_update(update)
+        .await
+        .context("failed to publish liquidity update")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_prunes_expired_pools() {
+        let interval = Duration::from_secs(30);
+        let start = Instant::now();
+        let mut throttle = Throttle::new();
+        let (pool_a, pool_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert!(!throttle.check(&pool_a, start, interval));
+        assert!(throttle.check(
+            &pool_a,
+            start + Duration::from_secs(10),
+            interval
+        ));
+
+        let later = start + Duration::from_secs(31);
+        assert!(!throttle.check(&pool_b, later, interval));
+        assert_eq!(throttle.last_update.len(), 1);
+        assert!(!throttle.check(&pool_a, later, interval));
+    }
+}
@@ -1,2 +1,4 @@
+pub mod liquidity_handler;
 pub mod token_swap_handler;
+pub use liquidity_handler::LiquidityHandler;
 pub use token_swap_handler::TokenSwapHandler;
@@ -1,8 +1,20 @@
 use crate::{
-    db::ClickhouseDb, kv_store::RedisKVStore, message_queue::RedisMessageQueue,
-    metrics::SwapMetrics, process_swap::process_swap,
+    db::{ClickhouseDb, Database},
+    dedupe::{
+        SwapDeduplicator, SwapKey, DEFAULT_DEDUPE_CAPACITY,
+        DEFAULT_DEDUPE_TTL_SECS,
+    },
+    kv_store::RedisKVStore,
+    message_queue::{FanoutSink, MessageQueue},
+    metrics::SwapMetrics,
+    process_pump::{process_bonding_curve_trade, process_graduation},
+    process_swap::process_swap,
+    pump::{Graduated, GraduationVenue},
 };
 use carbon_core::instruction::{InstructionMetadata, NestedInstruction};
+use carbon_pumpfun_decoder::instructions::trade_event::TradeEvent;
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
 use std::{collections::HashSet, sync::Arc};
 use tracing::{debug, error};
 
@@ -14,27 +26,47 @@ pub enum Dex {
     MeteoraDlmm,
     Whirlpools,
     PumpSwap,
+    PumpFun,
 }
 
-pub struct TokenSwapHandler {
+/// Turns decoded swap instructions into price updates and writes them to the
+/// configured sinks. Generic over the message queue and the database so that
+/// downstream consumers can be fed without going through Redis; the defaults
+/// are what the indexer binaries run with.
+pub struct TokenSwapHandler<M = FanoutSink, D = ClickhouseDb>
+where
+    M: MessageQueue,
+    D: Database + Send + Sync + 'static,
+{
     pub kv_store: Arc<RedisKVStore>,
-    pub message_queue: Arc<RedisMessageQueue>,
-    pub db: Arc<ClickhouseDb>,
+    pub message_queue: Arc<M>,
+    pub db: Arc<D>,
     pub metrics: Arc<SwapMetrics>,
+    pub dedupe: Arc<SwapDeduplicator>,
 }
 
-impl TokenSwapHandler {
+impl<M, D> TokenSwapHandler<M, D>
+where
+    M: MessageQueue,
+    D: Database + Send + Sync + 'static,
+{
     pub fn new(
         kv_store: Arc<RedisKVStore>,
-        message_queue: Arc<RedisMessageQueue>,
-        db: Arc<ClickhouseDb>,
+        message_queue: Arc<M>,
+        db: Arc<D>,
         metrics: Arc<SwapMetrics>,
     ) -> Self {
+        let dedupe = Arc::new(SwapDeduplicator::new(
+            Some(kv_store.clone()),
+            DEFAULT_DEDUPE_CAPACITY,
+            DEFAULT_DEDUPE_TTL_SECS,
+        ));
         Self {
             kv_store,
             message_queue,
             db,
             metrics,
+            dedupe,
         }
     }
 
@@ -55,22 +87,42 @@ impl TokenSwapHandler {
         let kv_store = self.kv_store.clone();
         let db = self.db.clone();
         let metrics = self.metrics.clone();
+        let dedupe = self.dedupe.clone();
+
+        let key = SwapKey::new(
+            &meta.transaction_metadata.signature.to_string(),
+            meta.index,
+            meta.stack_height,
+            vaults,
+        );
+        // the same instruction can be matched by several processors
+        if !self.claim_locally(&key, &dex) {
+            return;
+        }
 
         let vaults = vaults.clone();
         let fee_adas = fee_adas.cloned();
-        let tx_meta = meta.transaction_metadata.clone();
+        let meta = meta.clone();
         let nested_instructions = nested_instructions.to_vec();
 
         metrics.increment_total_swaps();
         metrics.increment_pending_swaps();
 
         tokio::spawn(async move {
+            // replays after a reconnect or restart are caught by redis
+            if dedupe.seen_globally(&key).await {
+                debug!(signature = %key.signature, "skipping replayed swap");
+                metrics.increment_skipped_duplicate_swaps();
+                metrics.decrement_pending_swaps();
+                return;
+            }
+
             match process_swap(
                 &vaults,
                 fee_adas.as_ref(),
-                &tx_meta,
+                &meta,
                 &nested_instructions,
-                &message_queue,
+                message_queue.as_ref(),
                 &kv_store,
                 &db,
                 &metrics,
@@ -80,21 +132,142 @@ impl TokenSwapHandler {
                 Ok(_) => {
                     // println!(
                     //     "successful swap for {:?}: https://solscan.io/tx/{}",
-                    //     dex, tx_meta.signature
+                    //     dex, meta.transaction_metadata.signature
                     // );
                     metrics.increment_successful_swaps();
                 }
                 Err(e) => {
                     metrics.increment_failed_swaps();
+                    dedupe.release(&key).await;
+                    error!(
+                        ?e,
+                        "Transaction: https://solscan.io/tx/{}",
+                        meta.transaction_metadata.signature
+                    );
+                }
+            }
+        });
+    }
+
+    /// Claims the instruction for processing, false if it was seen before
+    fn claim_locally(&self, key: &SwapKey, dex: &Dex) -> bool {
+        if self.dedupe.seen_locally(key) {
+            debug!(?dex, signature = %key.signature, "skipping duplicate swap");
+            self.metrics.increment_skipped_duplicate_swaps();
+            return false;
+        }
+        true
+    }
+
+    pub fn spawn_bonding_curve_trade(
+        &self,
+        trade: &TradeEvent,
+        meta: &InstructionMetadata,
+    ) {
+        let key = SwapKey::new(
+            &meta.transaction_metadata.signature.to_string(),
+            meta.index,
+            meta.stack_height,
+            &HashSet::from([trade.mint.to_string()]),
+        );
+        if !self.claim_locally(&key, &Dex::PumpFun) {
+            return;
+        }
+
+        let message_queue = self.message_queue.clone();
+        let kv_store = self.kv_store.clone();
+        let db = self.db.clone();
+        let metrics = self.metrics.clone();
+        let dedupe = self.dedupe.clone();
+        let trade = trade.clone();
+        let meta = meta.clone();
+
+        metrics.increment_total_swaps();
+        metrics.increment_pending_swaps();
+
+        tokio::spawn(async move {
+            if dedupe.seen_globally(&key).await {
+                debug!(signature = %key.signature, "skipping replayed swap");
+                metrics.increment_skipped_duplicate_swaps();
+                metrics.decrement_pending_swaps();
+                return;
+            }
+
+            match process_bonding_curve_trade(
+                &trade,
+                &meta,
+                message_queue.as_ref(),
+                &kv_store,
+                &db,
+                &metrics,
+            )
+            .await
+            {
+                Ok(_) => metrics.increment_successful_swaps(),
+                Err(e) => {
+                    metrics.increment_failed_swaps();
+                    dedupe.release(&key).await;
                     error!(
                         ?e,
                         "Transaction: https://solscan.io/tx/{}",
-                        tx_meta.signature
+                        meta.transaction_metadata.signature
                     );
                 }
             }
         });
     }
+
+    pub fn spawn_graduation(
+        &self,
+        mint: &Pubkey,
+        venue: GraduationVenue,
+        pool: Option<&Pubkey>,
+        meta: &InstructionMetadata,
+    ) {
+        let key = SwapKey::new(
+            &meta.transaction_metadata.signature.to_string(),
+            meta.index,
+            meta.stack_height,
+            &HashSet::from([mint.to_string()]),
+        );
+        if !self.claim_locally(&key, &Dex::PumpFun) {
+            return;
+        }
+
+        let graduated = Graduated {
+            mint: mint.to_string(),
+            venue,
+            pool: pool.map(|pool| pool.to_string()),
+            slot: meta.transaction_metadata.slot,
+            timestamp: Utc::now().timestamp() as u64,
+            signature: key.signature.clone(),
+        };
+        let message_queue = self.message_queue.clone();
+        let kv_store = self.kv_store.clone();
+        let metrics = self.metrics.clone();
+        let dedupe = self.dedupe.clone();
+
+        tokio::spawn(async move {
+            if dedupe.seen_globally(&key).await {
+                metrics.increment_skipped_duplicate_swaps();
+                return;
+            }
+            if let Err(e) = process_graduation(
+                graduated,
+                message_queue.as_ref(),
+                &kv_store,
+                &metrics,
+            )
+            .await
+            {
+                dedupe.release(&key).await;
+                error!(
+                    ?e,
+                    "Graduation: https://solscan.io/tx/{}", key.signature
+                );
+            }
+        });
+    }
 }
 
 #[cfg(test)]
@@ -107,7 +280,7 @@ pub mod test_swaps {
         },
         handler::TokenSwapHandler,
         kv_store::RedisKVStore,
-        message_queue::RedisMessageQueue,
+        message_queue::{FanoutSink, RedisMessageQueue},
         metrics::SwapMetrics,
         util::{make_db, make_kv_store, make_message_queue, make_rpc_client},
     };
@@ -156,6 +329,8 @@ pub mod test_swaps {
 
     pub async fn get_token_swap_handler() -> Arc<TokenSwapHandler> {
         let (kv_store, message_queue, db) = get_storages().await;
+        let message_queue =
+            Arc::new(FanoutSink::new().with_sink("redis", message_queue));
         let metrics = Arc::new(SwapMetrics::new());
         Arc::new(TokenSwapHandler::new(kv_store, message_queue, db, metrics))
     }
@@ -0,0 +1,214 @@
+use crate::liquidity::to_ui_amount;
+use anyhow::{anyhow, Context, Result};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as Token};
+use std::collections::HashMap;
+
+pub const DEFAULT_HOLDER_SNAPSHOT_LIMIT: usize = 100;
+pub const DEFAULT_HOLDER_SNAPSHOT_INTERVAL_SECS: u64 = 3600;
+
+/// spl-token account size, token-2022 accounts with extensions are larger
+const TOKEN_ACCOUNT_LEN: u64 = 165;
+
+/// One of the top holders of a mint at the time of the snapshot; owners
+/// holding through several token accounts are summed up, pools and other
+/// program accounts are left out
+#[derive(Debug, Serialize, Deserialize, Clone, Row, PartialEq)]
+pub struct HolderSnapshot {
+    pub mint: String,
+    pub timestamp: u64,
+    /// 1-based, by amount
+    pub rank: u32,
+    pub owner: String,
+    pub amount: f64,
+    /// share of the supply, 0 to 1
+    pub percentage: f64,
+    /// number of owners with a balance, pools and programs left out; same
+    /// across the snapshot
+    pub holder_count: u64,
+}
+
+/// Owner and amount of a token account of either token program
+pub fn parse_token_account(data: &[u8]) -> Option<(Pubkey, u64)> {
+    let token = StateWithExtensions::<Token>::unpack(data).ok()?;
+    Some((token.base.owner, token.base.amount))
+}
+
+/// Wallets are keypairs, the vaults of AMM pools, bonding curves and other
+/// programs are owned by program derived addresses, which are off the curve
+fn is_wallet(owner: &Pubkey) -> bool {
+    owner.is_on_curve()
+}
+
+/// Ranks the wallets by their summed balance and keeps the top `limit`
+pub fn top_holders(
+    mint: &str,
+    accounts: impl IntoIterator<Item = (Pubkey, u64)>,
+    supply: u64,
+    decimals: u8,
+    limit: usize,
+    timestamp: u64,
+) -> Vec<HolderSnapshot> {
+    let mut balances: HashMap<Pubkey, u64> = HashMap::new();
+    for (owner, amount) in accounts {
+        if amount > 0 && is_wallet(&owner) {
+            *balances.entry(owner).or_default() += amount;
+        }
+    }
+    let holder_count = balances.len() as u64;
+
+    let mut balances: Vec<(Pubkey, u64)> = balances.into_iter().collect();
+    // ties by owner so snapshots are stable
+    balances.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    balances
+        .into_iter()
+        .take(limit)
+        .enumerate()
+        .map(|(i, (owner, amount))| HolderSnapshot {
+            mint: mint.to_string(),
+            timestamp,
+            rank: i as u32 + 1,
+            owner: owner.to_string(),
+            amount: to_ui_amount(amount, decimals),
+            percentage: match supply {
+                0 => 0.0,
+                supply => amount as f64 / supply as f64,
+            },
+            holder_count,
+        })
+        .collect()
+}
+
+/// Reads all token accounts of the mint with getProgramAccounts, the mint
+/// is at offset 0 of the account for both token programs
+async fn get_token_accounts(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Vec<(Pubkey, Account)>> {
+    let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+        0,
+        mint.as_ref(),
+    ))];
+    if *token_program == spl_token::id() {
+        filters.push(RpcFilterType::DataSize(TOKEN_ACCOUNT_LEN));
+    }
+
+    rpc_client
+        .get_program_accounts_with_config(
+            token_program,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .with_context(|| format!("failed to get token accounts of {}", mint))
+}
+
+pub async fn snapshot_holders(
+    rpc_client: &RpcClient,
+    mint: &str,
+    limit: usize,
+) -> Result<Vec<HolderSnapshot>> {
+    let mint_pubkey: Pubkey = mint.parse()?;
+    let token_program = rpc_client
+        .get_account(&mint_pubkey)
+        .await
+        .with_context(|| format!("failed to get mint {}", mint))?
+        .owner;
+    if token_program != spl_token::id() && token_program != spl_token_2022::id()
+    {
+        return Err(anyhow!("{} is not a token mint", mint));
+    }
+
+    let supply = rpc_client
+        .get_token_supply(&mint_pubkey)
+        .await
+        .with_context(|| format!("failed to get supply of {}", mint))?;
+    let amount: u64 = supply.amount.parse()?;
+
+    let accounts =
+        get_token_accounts(rpc_client, &mint_pubkey, &token_program).await?;
+    let timestamp = chrono::Utc::now().timestamp() as u64;
+
+    Ok(top_holders(
+        mint,
+        accounts
+            .iter()
+            .filter_map(|(_, account)| parse_token_account(&account.data)),
+        amount,
+        supply.decimals,
+        limit,
+        timestamp,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_holders() {
+        use solana_sdk::signature::{Keypair, Signer};
+
+        let wallet = || Keypair::new().pubkey();
+        let (a, b, c, d) = (wallet(), wallet(), wallet(), wallet());
+        // the vault authority of a pool holds the most but is no holder
+        let (pool, _) =
+            Pubkey::find_program_address(&[b"pool"], &Pubkey::new_unique());
+        let accounts = vec![
+            (pool, 600_000),
+            (a, 100_000),
+            (b, 300_000),
+            // second account of the same owner
+            (a, 250_000),
+            (c, 50_000),
+            // closed out accounts are not holders
+            (d, 0),
+        ];
+
+        let holders = top_holders("mint", accounts, 1_000_000, 3, 2, 42);
+        assert_eq!(holders.len(), 2);
+        assert_eq!(holders[0].owner, a.to_string());
+        assert_eq!(holders[0].rank, 1);
+        assert_eq!(holders[0].amount, 350.0);
+        assert_eq!(holders[0].percentage, 0.35);
+        assert_eq!(holders[1].owner, b.to_string());
+        assert_eq!(holders[1].rank, 2);
+        assert!(holders.iter().all(|h| h.holder_count == 3));
+        assert!(holders.iter().all(|h| h.timestamp == 42));
+    }
+
+    #[test]
+    fn test_parse_token_account() {
+        use solana_sdk::program_pack::Pack;
+
+        let owner = Pubkey::new_unique();
+        let account = spl_token::state::Account {
+            mint: Pubkey::new_unique(),
+            owner,
+            amount: 1234,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account::pack(account, &mut data).unwrap();
+
+        assert_eq!(parse_token_account(&data), Some((owner, 1234)));
+        assert_eq!(parse_token_account(&data[..64]), None);
+    }
+}
@@ -3,8 +3,10 @@ use bb8_redis::{bb8, redis::cmd, RedisConnectionManager};
 use serde::{de::DeserializeOwned, Serialize};
 use tracing::{debug, info};
 
+use crate::liquidity::LiquidityUpdate;
 use crate::metadata::TokenMetadata;
 use crate::price::PriceUpdate;
+use crate::pump::{BondingCurveUpdate, Graduated};
 use crate::util::create_redis_pool;
 
 #[derive(Debug, Clone)]
@@ -83,6 +85,60 @@ impl RedisKVStore {
         Ok(exists)
     }
 
+    /// SET NX with an expiry, returns true if the key was newly set
+    pub async fn set_nx(&self, key: &str, ttl_secs: u64) -> Result<bool> {
+        let mut conn = self.pool.get().await.context(format!(
+            "Failed to get Redis connection: {:#?}",
+            self.pool.state().statistics
+        ))?;
+        let reply: Option<String> = cmd("SET")
+            .arg(key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to set nx for key: {}", key))?;
+        let claimed = reply.is_some();
+        debug!(key, claimed, "redis set nx ok");
+        Ok(claimed)
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        let mut conn = self.pool.get().await.context(format!(
+            "Failed to get Redis connection: {:#?}",
+            self.pool.state().statistics
+        ))?;
+        let _: () = cmd("DEL")
+            .arg(key)
+            .query_async(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to delete key: {}", key))?;
+        debug!(key, "redis del ok");
+        Ok(())
+    }
+
+    pub async fn publish<T: Serialize + Send + Sync>(
+        &self,
+        channel: &str,
+        value: &T,
+    ) -> Result<()> {
+        let mut conn = self.pool.get().await.context(format!(
+            "Failed to get Redis connection: {:#?}",
+            self.pool.state().statistics
+        ))?;
+        let payload = serde_json::to_string(value)?;
+        let _: () = cmd("PUBLISH")
+            .arg(channel)
+            .arg(payload)
+            .query_async(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to publish to: {}", channel))?;
+        debug!(channel, "redis publish ok");
+        Ok(())
+    }
+
     fn make_price_key(&self, mint: &str) -> String {
         format!("solana:price:{}", mint)
     }
@@ -91,6 +147,18 @@ impl RedisKVStore {
         format!("solana:metadata:{}", mint)
     }
 
+    fn make_liquidity_key(&self, mint: &str) -> String {
+        format!("solana:liquidity:{}", mint)
+    }
+
+    fn make_curve_key(&self, mint: &str) -> String {
+        format!("solana:curve:{}", mint)
+    }
+
+    fn make_graduated_key(&self, mint: &str) -> String {
+        format!("solana:graduated:{}", mint)
+    }
+
     pub async fn insert_price(&self, price: &PriceUpdate) -> Result<()> {
         let key = self.make_price_key(&price.pubkey);
         self.set(&key, price).await
@@ -121,4 +189,73 @@ impl RedisKVStore {
         let key = self.make_metadata_key(mint);
         self.exists(&key).await
     }
+
+    /// Liquidity is kept per pool in a hash keyed on the token mint
+    pub async fn insert_liquidity(
+        &self,
+        liquidity: &LiquidityUpdate,
+    ) -> Result<()> {
+        let key = self.make_liquidity_key(&liquidity.mint);
+        let mut conn = self.pool.get().await.context(format!(
+            "Failed to get Redis connection: {:#?}",
+            self.pool.state().statistics
+        ))?;
+        let json_str = serde_json::to_string(liquidity)?;
+        let _: () = cmd("HSET")
+            .arg(&key)
+            .arg(&liquidity.pool)
+            .arg(json_str)
+            .query_async(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to hset key: {}", key))?;
+        debug!(key, pool = liquidity.pool, "redis hset ok");
+        Ok(())
+    }
+
+    pub async fn get_liquidity(
+        &self,
+        mint: &str,
+    ) -> Result<Vec<LiquidityUpdate>> {
+        let key = self.make_liquidity_key(mint);
+        let mut conn = self.pool.get().await.context(format!(
+            "Failed to get Redis connection: {:#?}",
+            self.pool.state().statistics
+        ))?;
+        let pools: Vec<String> = cmd("HVALS")
+            .arg(&key)
+            .query_async(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to hvals key: {}", key))?;
+        pools
+            .iter()
+            .map(|json_str| {
+                serde_json::from_str(json_str).with_context(|| {
+                    format!("Failed to deserialize value for key: {}", key)
+                })
+            })
+            .collect()
+    }
+
+    pub async fn insert_curve(&self, curve: &BondingCurveUpdate) -> Result<()> {
+        let key = self.make_curve_key(&curve.mint);
+        self.set(&key, curve).await
+    }
+
+    pub async fn get_curve(
+        &self,
+        mint: &str,
+    ) -> Result<Option<BondingCurveUpdate>> {
+        let key = self.make_curve_key(mint);
+        self.get(&key).await
+    }
+
+    pub async fn insert_graduated(&self, graduated: &Graduated) -> Result<()> {
+        let key = self.make_graduated_key(&graduated.mint);
+        self.set(&key, graduated).await
+    }
+
+    pub async fn get_graduated(&self, mint: &str) -> Result<Option<Graduated>> {
+        let key = self.make_graduated_key(mint);
+        self.get(&key).await
+    }
 }
@@ -18,12 +18,17 @@ pub mod rpc;
 pub mod geyser;
 
 pub mod db;
+pub mod dedupe;
+pub mod holders;
 pub mod kv_store;
+pub mod liquidity;
 pub mod message_queue;
 pub mod metadata;
 pub mod metrics;
 pub mod price;
+pub mod process_pump;
 pub mod process_swap;
+pub mod pump;
 pub mod sol_price_stream;
 pub mod util;
 
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::constants::{
+    USDC_MINT_KEY_STR, USDT_MINT_KEY_STR, WSOL_MINT_KEY_STR,
+};
+
+/// Liquidity snapshot of a single pool, denominated in the non-quote token.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LiquidityUpdate {
+    pub pool: String,
+    pub dex: String,
+    pub mint: String,
+    pub quote_mint: String,
+    pub base_reserve: f64,
+    pub quote_reserve: f64,
+    pub price: f64,   // spot price of `mint`, denoted as usd
+    pub tvl_usd: f64, // both sides of the pool, denoted as usd
+    pub slot: u64,
+    pub timestamp: u64,
+}
+
+/// How the spot price is derived for a given pool type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpotPrice {
+    /// constant product pools, price is the ratio of the reserves
+    Reserves,
+    /// concentrated liquidity pools (Raydium CLMM, Orca Whirlpool)
+    SqrtPriceX64(u128),
+    /// Meteora DLMM, price of the active bin
+    ActiveBin { active_id: i32, bin_step: u16 },
+}
+
+/// Pool state as read from a pool account, before the vault balances are
+/// known. Token a/b follow the order the program stores them in.
+#[derive(Debug, Clone)]
+pub struct PoolState {
+    pub pool: Pubkey,
+    pub dex: &'static str,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub vault_a: Pubkey,
+    pub vault_b: Pubkey,
+    pub spot_price: SpotPrice,
+    /// amounts held in the vaults that are not part of the reserves
+    /// (accrued protocol/fund fees, pnl not taken yet)
+    pub excluded_a: u64,
+    pub excluded_b: u64,
+}
+
+/// Returns the usd value of one unit of a quote token, if the mint is one
+pub fn quote_price_usd(mint: &str, sol_price: f64) -> Option<f64> {
+    match mint {
+        WSOL_MINT_KEY_STR => Some(sol_price),
+        USDC_MINT_KEY_STR | USDT_MINT_KEY_STR => Some(1.0),
+        _ => None,
+    }
+}
+
+pub fn is_quote(mint: &str) -> bool {
+    mint == WSOL_MINT_KEY_STR || is_stable(mint)
+}
+
+pub fn is_stable(mint: &str) -> bool {
+    matches!(mint, USDC_MINT_KEY_STR | USDT_MINT_KEY_STR)
+}
+
+pub fn to_ui_amount(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+/// Price of token a denoted in token b from a Q64.64 sqrt price
+pub fn price_from_sqrt_price_x64(
+    sqrt_price_x64: u128,
+    decimals_a: u8,
+    decimals_b: u8,
+) -> f64 {
+    let sqrt_price = sqrt_price_x64 as f64 / 2f64.powi(64);
+    sqrt_price * sqrt_price * 10f64.powi(decimals_a as i32 - decimals_b as i32)
+}
+
+/// Price of token x denoted in token y for the active bin of a DLMM pair
+pub fn price_from_active_bin(
+    active_id: i32,
+    bin_step: u16,
+    decimals_x: u8,
+    decimals_y: u8,
+) -> f64 {
+    (1.0 + bin_step as f64 / 10_000.0).powi(active_id)
+        * 10f64.powi(decimals_x as i32 - decimals_y as i32)
+}
+
+/// Price of token a denoted in token b
+pub fn price_a_in_b(
+    spot_price: SpotPrice,
+    reserve_a: f64,
+    reserve_b: f64,
+    decimals_a: u8,
+    decimals_b: u8,
+) -> Option<f64> {
+    let price = match spot_price {
+        SpotPrice::Reserves => {
+            if reserve_a <= 0.0 {
+                return None;
+            }
+            reserve_b / reserve_a
+        }
+        SpotPrice::SqrtPriceX64(sqrt_price_x64) => {
+            price_from_sqrt_price_x64(sqrt_price_x64, decimals_a, decimals_b)
+        }
+        SpotPrice::ActiveBin {
+            active_id,
+            bin_step,
+        } => price_from_active_bin(active_id, bin_step, decimals_a, decimals_b),
+    };
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::round_to_decimals;
+
+    #[test]
+    fn test_price_from_sqrt_price_x64() {
+        // sqrt(1) in Q64.64 with equal decimals is a price of 1
+        let one = 1u128 << 64;
+        assert_eq!(price_from_sqrt_price_x64(one, 6, 6), 1.0);
+
+        // SOL/USDC whirlpool, sqrt price observed at ~150 USDC per SOL
+        let price = price_from_sqrt_price_x64(7_144_424_337_818_599_424, 9, 6);
+        assert_eq!(round_to_decimals(price, 2), 150.0);
+    }
+
+    #[test]
+    fn test_price_from_active_bin() {
+        assert_eq!(price_from_active_bin(0, 10, 6, 6), 1.0);
+        let price = price_from_active_bin(100, 25, 6, 6);
+        assert_eq!(round_to_decimals(price, 4), 1.2836);
+        // negative bins price below 1
+        assert!(price_from_active_bin(-100, 25, 6, 6) < 1.0);
+    }
+
+    #[test]
+    fn test_price_from_reserves() {
+        let price =
+            price_a_in_b(SpotPrice::Reserves, 1_000_000.0, 10.0, 6, 9).unwrap();
+        assert_eq!(price, 0.00001);
+        assert!(price_a_in_b(SpotPrice::Reserves, 0.0, 10.0, 6, 9).is_none());
+    }
+
+    #[test]
+    fn test_quote_price_usd() {
+        assert_eq!(quote_price_usd(WSOL_MINT_KEY_STR, 150.0), Some(150.0));
+        assert_eq!(quote_price_usd(USDC_MINT_KEY_STR, 150.0), Some(1.0));
+        assert_eq!(quote_price_usd("mint", 150.0), None);
+    }
+}
@@ -9,7 +9,10 @@ fn main() {
     println!("   RPC-based crawler for Raydium data");
     println!("   Usage: cargo run --bin rpc-crawler [COMMAND]");
     println!("   Commands:");
-    println!("     - raydium-accounts-rpc");
+    println!("     - pool-accounts-rpc");
     println!("     - raydium-instrutions-rpc");
+    println!("\n3. holder-snapshots");
+    println!("   Periodic top holder snapshots of the most traded tokens");
+    println!("   Usage: cargo run --bin holder-snapshots [--mints MINTS]");
     println!("\nFor more details, run any command with --help");
 }
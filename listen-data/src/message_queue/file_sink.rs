@@ -0,0 +1,252 @@
+use super::{MessageQueue, SinkError};
+use crate::{liquidity::LiquidityUpdate, price::PriceUpdate, pump::PumpEvent};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
+use tracing::info;
+
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 256 * 1024 * 1024;
+
+struct OpenFile {
+    file: File,
+    path: PathBuf,
+    bytes_written: u64,
+    hour: String,
+}
+
+/// A new file is started every hour and whenever the current one reaches
+/// `max_bytes`, files are named `<prefix>-<yyyymmdd-hh>-<seq>.ndjson`
+struct RollingFile {
+    dir: PathBuf,
+    prefix: String,
+    max_bytes: u64,
+    current: Mutex<Option<OpenFile>>,
+}
+
+impl RollingFile {
+    fn new(dir: &Path, prefix: &str, max_bytes: u64) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            prefix: prefix.to_string(),
+            max_bytes,
+            current: Mutex::new(None),
+        }
+    }
+
+    fn hour_bucket(now: DateTime<Utc>) -> String {
+        now.format("%Y%m%d-%H").to_string()
+    }
+
+    async fn open_next(&self, hour: &str) -> Result<OpenFile, SinkError> {
+        // pick the first sequence number that is not taken yet, so that a
+        // restart within the same hour does not append to a rolled file
+        let mut seq = 0;
+        let path = loop {
+            let path = self
+                .dir
+                .join(format!("{}-{}-{:04}.ndjson", self.prefix, hour, seq));
+            if !tokio::fs::try_exists(&path).await? {
+                break path;
+            }
+            seq += 1;
+        };
+
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        info!("Rolled {} file to {}", self.prefix, path.display());
+        Ok(OpenFile {
+            file,
+            path,
+            bytes_written: 0,
+            hour: hour.to_string(),
+        })
+    }
+
+    async fn append<T: Serialize>(&self, value: &T) -> Result<(), SinkError> {
+        let mut line = serde_json::to_vec(value)?;
+        line.push(b'\n');
+
+        let hour = Self::hour_bucket(Utc::now());
+        let mut current = self.current.lock().await;
+
+        let needs_roll = match current.as_ref() {
+            Some(open) => {
+                open.hour != hour
+                    || open.bytes_written + line.len() as u64 > self.max_bytes
+            }
+            None => true,
+        };
+
+        if needs_roll {
+            if let Some(mut previous) = current.take() {
+                previous.file.flush().await?;
+            }
+            *current = Some(self.open_next(&hour).await?);
+        }
+
+        let open = current.as_mut().expect("file opened above");
+        open.file.write_all(&line).await?;
+        open.file.flush().await?;
+        open.bytes_written += line.len() as u64;
+
+        Ok(())
+    }
+
+    async fn current_path(&self) -> Option<PathBuf> {
+        self.current.lock().await.as_ref().map(|f| f.path.clone())
+    }
+}
+
+/// Appends price, liquidity and pump.fun updates as newline-delimited JSON to
+/// rolling files, `price_updates-*.ndjson`, `liquidity_updates-*.ndjson`
+/// and `pump_events-*.ndjson`
+pub struct NdjsonFileSink {
+    prices: RollingFile,
+    liquidity: RollingFile,
+    pump: RollingFile,
+}
+
+impl NdjsonFileSink {
+    pub async fn new(
+        dir: impl AsRef<Path>,
+        max_bytes: u64,
+    ) -> Result<Self, SinkError> {
+        let dir = dir.as_ref();
+        tokio::fs::create_dir_all(dir).await?;
+        info!("Writing price updates to {}", dir.display());
+        Ok(Self {
+            prices: RollingFile::new(dir, "price_updates", max_bytes),
+            liquidity: RollingFile::new(dir, "liquidity_updates", max_bytes),
+            pump: RollingFile::new(dir, "pump_events", max_bytes),
+        })
+    }
+
+    pub async fn current_path(&self) -> Option<PathBuf> {
+        self.prices.current_path().await
+    }
+
+    pub async fn current_liquidity_path(&self) -> Option<PathBuf> {
+        self.liquidity.current_path().await
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageQueue for NdjsonFileSink {
+    type Error = SinkError;
+
+    async fn publish_price_update(
+        &self,
+        price_update: PriceUpdate,
+    ) -> Result<(), Self::Error> {
+        self.prices.append(&price_update).await
+    }
+
+    async fn publish_liquidity_update(
+        &self,
+        liquidity_update: LiquidityUpdate,
+    ) -> Result<(), Self::Error> {
+        self.liquidity.append(&liquidity_update).await
+    }
+
+    async fn publish_pump_event(
+        &self,
+        pump_event: PumpEvent,
+    ) -> Result<(), Self::Error> {
+        self.pump.append(&pump_event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_queue::tests::{
+        test_liquidity_update, test_price_update,
+    };
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "listen-data-{}-{}",
+            name,
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_writes_ndjson_lines() {
+        let dir = temp_dir("ndjson");
+        let sink = NdjsonFileSink::new(&dir, 1024 * 1024).await.unwrap();
+
+        sink.publish_price_update(test_price_update("a"))
+            .await
+            .unwrap();
+        sink.publish_price_update(test_price_update("b"))
+            .await
+            .unwrap();
+
+        let path = sink.current_path().await.unwrap();
+        let contents = tokio::fs::read_to_string(&path).await.unwrap();
+        let updates = contents
+            .lines()
+            .map(|l| serde_json::from_str::<PriceUpdate>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].signature, "a");
+        assert_eq!(updates[1].signature, "b");
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rolls_on_size() {
+        let dir = temp_dir("ndjson-roll");
+        // small enough that every update gets its own file
+        let sink = NdjsonFileSink::new(&dir, 10).await.unwrap();
+
+        sink.publish_price_update(test_price_update("a"))
+            .await
+            .unwrap();
+        let first = sink.current_path().await.unwrap();
+        sink.publish_price_update(test_price_update("b"))
+            .await
+            .unwrap();
+        let second = sink.current_path().await.unwrap();
+
+        assert_ne!(first, second);
+        let mut entries = tokio::fs::read_dir(&dir).await.unwrap();
+        let mut count = 0;
+        while entries.next_entry().await.unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 2);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_liquidity_goes_to_own_file() {
+        let dir = temp_dir("ndjson-liquidity");
+        let sink = NdjsonFileSink::new(&dir, 1024 * 1024).await.unwrap();
+
+        sink.publish_price_update(test_price_update("a"))
+            .await
+            .unwrap();
+        sink.publish_liquidity_update(test_liquidity_update("pool"))
+            .await
+            .unwrap();
+
+        let prices = sink.current_path().await.unwrap();
+        let liquidity = sink.current_liquidity_path().await.unwrap();
+        assert_ne!(prices, liquidity);
+
+        let contents = tokio::fs::read_to_string(&liquidity).await.unwrap();
+        let update: LiquidityUpdate =
+            serde_json::from_str(contents.trim()).unwrap();
+        assert_eq!(update.pool, "pool");
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+pub mod file_sink;
+pub mod nats_sink;
+pub mod redis_message_queue;
+
+pub use file_sink::NdjsonFileSink;
+pub use nats_sink::NatsSink;
+pub use redis_message_queue::RedisMessageQueue;
+
+use crate::{liquidity::LiquidityUpdate, price::PriceUpdate, pump::PumpEvent};
+use futures_util::future::join_all;
+use std::sync::Arc;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error("redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("nats error: {0}")]
+    Nats(String),
+
+    #[error("{failed} of {total} sinks failed, first error: {first}")]
+    Fanout {
+        failed: usize,
+        total: usize,
+        first: Box<SinkError>,
+    },
+}
+
+#[async_trait::async_trait]
+pub trait MessageQueue: Send + Sync + 'static {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    async fn publish_price_update(
+        &self,
+        price_update: PriceUpdate,
+    ) -> Result<(), Self::Error>;
+
+    /// Sinks that only carry prices can leave this out
+    async fn publish_liquidity_update(
+        &self,
+        _liquidity_update: LiquidityUpdate,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Bonding curve progress and graduations of pump.fun tokens
+    async fn publish_pump_event(
+        &self,
+        _pump_event: PumpEvent,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+pub type DynMessageQueue = Arc<dyn MessageQueue<Error = SinkError>>;
+
+/// Publishes every update to all of the configured sinks.
+///
+/// Sinks are published to concurrently; a failing sink does not prevent the
+/// others from receiving the update, the error is reported after all of them
+/// were tried.
+#[derive(Default)]
+pub struct FanoutSink {
+    sinks: Vec<(String, DynMessageQueue)>,
+}
+
+impl FanoutSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_sink(
+        mut self,
+        name: impl Into<String>,
+        sink: DynMessageQueue,
+    ) -> Self {
+        self.sinks.push((name.into(), sink));
+        self
+    }
+
+    pub fn sink_names(&self) -> Vec<&str> {
+        self.sinks.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    fn collect_errors(
+        &self,
+        results: Vec<Result<(), SinkError>>,
+    ) -> Result<(), SinkError> {
+        let total = results.len();
+        let mut errors = results
+            .into_iter()
+            .zip(self.sinks.iter())
+            .filter_map(|(result, (name, _))| {
+                result
+                    .map_err(|e| {
+                        tracing::warn!(sink = name, ?e, "sink publish failed");
+                        e
+                    })
+                    .err()
+            })
+            .collect::<Vec<_>>();
+
+        match errors.len() {
+            0 => Ok(()),
+            failed => Err(SinkError::Fanout {
+                failed,
+                total,
+                first: Box::new(errors.swap_remove(0)),
+            }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageQueue for FanoutSink {
+    type Error = SinkError;
+
+    async fn publish_price_update(
+        &self,
+        price_update: PriceUpdate,
+    ) -> Result<(), Self::Error> {
+        let results =
+            join_all(self.sinks.iter().map(|(_, sink)| {
+                sink.publish_price_update(price_update.clone())
+            }))
+            .await;
+        self.collect_errors(results)
+    }
+
+    async fn publish_liquidity_update(
+        &self,
+        liquidity_update: LiquidityUpdate,
+    ) -> Result<(), Self::Error> {
+        let results = join_all(self.sinks.iter().map(|(_, sink)| {
+            sink.publish_liquidity_update(liquidity_update.clone())
+        }))
+        .await;
+        self.collect_errors(results)
+    }
+
+    async fn publish_pump_event(
+        &self,
+        pump_event: PumpEvent,
+    ) -> Result<(), Self::Error> {
+        let results = join_all(
+            self.sinks
+                .iter()
+                .map(|(_, sink)| sink.publish_pump_event(pump_event.clone())),
+        )
+        .await;
+        self.collect_errors(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    pub fn test_price_update(signature: &str) -> PriceUpdate {
+        PriceUpdate {
+            name: "Test".to_string(),
+            pubkey: "mint".to_string(),
+            price: 1.0,
+            market_cap: 1_000_000.0,
+            timestamp: 1_700_000_000,
+            slot: 1,
+            swap_amount: 100.0,
+            owner: "owner".to_string(),
+            signature: signature.to_string(),
+            instruction_index: 0,
+            stack_height: 0,
+            multi_hop: false,
+            is_buy: true,
+            is_pump: false,
+        }
+    }
+
+    pub fn test_liquidity_update(pool: &str) -> LiquidityUpdate {
+        LiquidityUpdate {
+            pool: pool.to_string(),
+            dex: "raydium_cpmm".to_string(),
+            mint: "mint".to_string(),
+            quote_mint: "So11111111111111111111111111111111111111112"
+                .to_string(),
+            base_reserve: 1_000_000.0,
+            quote_reserve: 10.0,
+            price: 0.0015,
+            tvl_usd: 3000.0,
+            slot: 1,
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingSink {
+        count: AtomicU64,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageQueue for CountingSink {
+        type Error = SinkError;
+
+        async fn publish_price_update(
+            &self,
+            _price_update: PriceUpdate,
+        ) -> Result<(), Self::Error> {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            if self.fail {
+                return Err(SinkError::Nats("down".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fanout_publishes_to_all_sinks() {
+        let ok = Arc::new(CountingSink::default());
+        let failing = Arc::new(CountingSink {
+            fail: true,
+            ..Default::default()
+        });
+        let fanout = FanoutSink::new()
+            .with_sink("ok", ok.clone())
+            .with_sink("failing", failing.clone());
+
+        let result =
+            fanout.publish_price_update(test_price_update("sig")).await;
+
+        assert_eq!(ok.count.load(Ordering::Relaxed), 1);
+        assert_eq!(failing.count.load(Ordering::Relaxed), 1);
+        assert!(matches!(
+            result,
+            Err(SinkError::Fanout {
+                failed: 1,
+                total: 2,
+                ..
+            })
+        ));
+    }
+}
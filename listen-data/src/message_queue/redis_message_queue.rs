@@ -3,17 +3,8 @@ use anyhow::{Context, Result};
 use bb8_redis::{bb8, RedisConnectionManager};
 use tracing::info;
 
-use crate::price::PriceUpdate;
-
-#[async_trait::async_trait]
-pub trait MessageQueue: Send + Sync + 'static {
-    type Error: std::error::Error + Send + Sync + 'static;
-
-    async fn publish_price_update(
-        &self,
-        price_update: PriceUpdate,
-    ) -> Result<(), Self::Error>;
-}
+use super::{MessageQueue, SinkError};
+use crate::{liquidity::LiquidityUpdate, price::PriceUpdate, pump::PumpEvent};
 
 // Redis implementation of MessageQueue
 #[derive(Debug)]
@@ -27,16 +18,12 @@ impl RedisMessageQueue {
         info!("Connected to Redis message queue at {}", redis_url);
         Ok(Self { pool })
     }
-}
 
-#[async_trait::async_trait]
-impl MessageQueue for RedisMessageQueue {
-    type Error = redis::RedisError;
-
-    async fn publish_price_update(
+    async fn publish(
         &self,
-        price_update: PriceUpdate,
-    ) -> Result<(), Self::Error> {
+        channel: &str,
+        payload: String,
+    ) -> Result<(), SinkError> {
         let mut conn = self
             .pool
             .get()
@@ -52,18 +39,41 @@ impl MessageQueue for RedisMessageQueue {
                     e.to_string(),
                 ))
             })?;
-        let payload = serde_json::to_string(&price_update).map_err(|e| {
-            redis::RedisError::from((
-                redis::ErrorKind::IoError,
-                "Serialization error",
-                e.to_string(),
-            ))
-        })?;
 
-        redis::cmd("PUBLISH")
-            .arg("price_updates")
+        let _: () = redis::cmd("PUBLISH")
+            .arg(channel)
             .arg(payload)
             .query_async(&mut *conn)
-            .await
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageQueue for RedisMessageQueue {
+    type Error = SinkError;
+
+    async fn publish_price_update(
+        &self,
+        price_update: PriceUpdate,
+    ) -> Result<(), Self::Error> {
+        let payload = serde_json::to_string(&price_update)?;
+        self.publish("price_updates", payload).await
+    }
+
+    async fn publish_liquidity_update(
+        &self,
+        liquidity_update: LiquidityUpdate,
+    ) -> Result<(), Self::Error> {
+        let payload = serde_json::to_string(&liquidity_update)?;
+        self.publish("liquidity_updates", payload).await
+    }
+
+    async fn publish_pump_event(
+        &self,
+        pump_event: PumpEvent,
+    ) -> Result<(), Self::Error> {
+        let payload = serde_json::to_string(&pump_event)?;
+        self.publish("pump_events", payload).await
     }
 }
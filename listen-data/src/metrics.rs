@@ -1,4 +1,11 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use chrono::Utc;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
 use tracing::info;
 
 #[derive(Debug, Default)]
@@ -11,6 +18,7 @@ pub struct SwapMetrics {
     pub skipped_unexpected_number_of_tokens: AtomicU64,
     pub skipped_no_metadata: AtomicU64,
     pub skipped_non_wsol: AtomicU64,
+    pub skipped_duplicate_swaps: AtomicU64,
     pub message_send_success: AtomicU64,
     pub message_send_failure: AtomicU64,
     pub db_insert_success: AtomicU64,
@@ -26,6 +34,11 @@ pub struct SwapMetrics {
     pub meteora_dlmm_swaps: AtomicU64,
     pub whirlpools_swaps: AtomicU64,
     pub pump_swaps: AtomicU64,
+    pub liquidity_updates: AtomicU64,
+    pub liquidity_update_failures: AtomicU64,
+    pub skipped_liquidity_no_quote: AtomicU64,
+    pub pump_fun_trades: AtomicU64,
+    pub graduations: AtomicU64,
 }
 
 impl SwapMetrics {
@@ -56,6 +69,14 @@ impl SwapMetrics {
         self.pump_swaps.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn increment_pump_fun_trades(&self) {
+        self.pump_fun_trades.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_graduations(&self) {
+        self.graduations.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn increment_total_swaps(&self) {
         let count = self.total_swaps_processed.fetch_add(1, Ordering::Relaxed);
         // println!("total swaps processed: {}", count);
@@ -93,6 +114,10 @@ impl SwapMetrics {
         self.skipped_non_wsol.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn increment_skipped_duplicate_swaps(&self) {
+        self.skipped_duplicate_swaps.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn increment_db_insert_success(&self) {
         self.db_insert_success.fetch_add(1, Ordering::Relaxed);
     }
@@ -129,6 +154,20 @@ impl SwapMetrics {
         self.pending_swaps.fetch_sub(1, Ordering::Relaxed);
     }
 
+    pub fn increment_liquidity_updates(&self) {
+        self.liquidity_updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_liquidity_update_failures(&self) {
+        self.liquidity_update_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_skipped_liquidity_no_quote(&self) {
+        self.skipped_liquidity_no_quote
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn set_latest_update_slot(&self, slot: u64) {
         self.latest_update_slot.store(slot, Ordering::Relaxed);
     }
@@ -141,6 +180,8 @@ impl SwapMetrics {
         let raydium_clmm = self.raydium_clmm_swaps.load(Ordering::Relaxed);
         let whirlpools = self.whirlpools_swaps.load(Ordering::Relaxed);
         let pump = self.pump_swaps.load(Ordering::Relaxed);
+        let pump_fun = self.pump_fun_trades.load(Ordering::Relaxed);
+        let graduations = self.graduations.load(Ordering::Relaxed);
         let pending = self.pending_swaps.load(Ordering::Relaxed);
         let successful = self.successful_swaps.load(Ordering::Relaxed);
         let failed = self.failed_swaps.load(Ordering::Relaxed);
@@ -151,6 +192,7 @@ impl SwapMetrics {
             .load(Ordering::Relaxed);
         let non_wsol = self.skipped_non_wsol.load(Ordering::Relaxed);
         let no_metadata = self.skipped_no_metadata.load(Ordering::Relaxed);
+        let duplicate = self.skipped_duplicate_swaps.load(Ordering::Relaxed);
         let message_send_success =
             self.message_send_success.load(Ordering::Relaxed);
         let message_send_failure =
@@ -160,6 +202,11 @@ impl SwapMetrics {
         let multi_hop = self.multi_hop_swap.load(Ordering::Relaxed);
         let kv_insert_success = self.kv_insert_success.load(Ordering::Relaxed);
         let kv_insert_failure = self.kv_insert_failure.load(Ordering::Relaxed);
+        let liquidity_updates = self.liquidity_updates.load(Ordering::Relaxed);
+        let liquidity_update_failures =
+            self.liquidity_update_failures.load(Ordering::Relaxed);
+        let liquidity_no_quote =
+            self.skipped_liquidity_no_quote.load(Ordering::Relaxed);
 
         let success_rate = if total > 0 {
             (successful as f64 / total as f64) * 100.0
@@ -179,6 +226,8 @@ impl SwapMetrics {
              Meteora DLMM: {}\n\
              Whirlpools: {}\n\
              PumpSwap: {}\n\
+             Pump.fun: {}\n\
+             Graduations: {}\n\
              Pending: {}\n\
              Successful: {} ({:.1}%)\n\
              Failed: {}\n\
@@ -187,6 +236,7 @@ impl SwapMetrics {
              Skipped (unexpected tokens): {}\n\
              Skipped (non-wSOL): {}\n\
              Skipped (no metadata): {}\n\
+             Skipped (duplicate): {}\n\
              Message Send Success: {}\n\
              Message Send Failure: {}\n\
              DB Insert Success: {}\n\
@@ -194,6 +244,9 @@ impl SwapMetrics {
              Multi-hop Swaps: {}\n\
              KV Insert Success: {}\n\
              KV Insert Failure: {}\n\
+             Liquidity Updates: {}\n\
+             Liquidity Update Failures: {}\n\
+             Liquidity Skipped (no quote): {}\n\
              Latest Update Slot: {}",
             total,
             raydium_amm_v4,
@@ -202,6 +255,8 @@ impl SwapMetrics {
             meteora_dlmm,
             whirlpools,
             pump,
+            pump_fun,
+            graduations,
             pending,
             successful,
             success_rate,
@@ -211,6 +266,7 @@ impl SwapMetrics {
             unexpected,
             non_wsol,
             no_metadata,
+            duplicate,
             message_send_success,
             message_send_failure,
             db_insert_success,
@@ -218,7 +274,111 @@ impl SwapMetrics {
             multi_hop,
             kv_insert_success,
             kv_insert_failure,
+            liquidity_updates,
+            liquidity_update_failures,
+            liquidity_no_quote,
             latest_update_slot,
         );
     }
 }
+
+/// Freshness of the SOL/USD reference price and its sources
+#[derive(Debug, Default)]
+pub struct SolPriceMetrics {
+    pub updates: AtomicU64,
+    pub stale: AtomicU64,
+    pub rest_fallbacks: AtomicU64,
+    pub fresh_sources: AtomicU64,
+    pub last_update_ms: AtomicU64,
+    source_last_update_ms: Mutex<HashMap<&'static str, u64>>,
+}
+
+impl SolPriceMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn increment_updates(&self) {
+        self.updates.fetch_add(1, Ordering::Relaxed);
+        self.last_update_ms
+            .store(Utc::now().timestamp_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn increment_stale(&self) {
+        self.stale.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_rest_fallbacks(&self) {
+        self.rest_fallbacks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_fresh_sources(&self, count: u64) {
+        self.fresh_sources.store(count, Ordering::Relaxed);
+    }
+
+    pub fn record_source_update(&self, source: &'static str) {
+        self.source_last_update_ms
+            .lock()
+            .expect("metrics lock poisoned")
+            .insert(source, Utc::now().timestamp_millis() as u64);
+    }
+
+    pub fn stale(&self) -> u64 {
+        self.stale.load(Ordering::Relaxed)
+    }
+
+    pub fn rest_fallbacks(&self) -> u64 {
+        self.rest_fallbacks.load(Ordering::Relaxed)
+    }
+
+    pub fn fresh_sources(&self) -> u64 {
+        self.fresh_sources.load(Ordering::Relaxed)
+    }
+
+    /// Milliseconds since each source last reported a price
+    pub fn source_ages_ms(&self) -> Vec<(&'static str, u64)> {
+        let now = Utc::now().timestamp_millis() as u64;
+        let mut ages = self
+            .source_last_update_ms
+            .lock()
+            .expect("metrics lock poisoned")
+            .iter()
+            .map(|(source, at)| (*source, now.saturating_sub(*at)))
+            .collect::<Vec<_>>();
+        ages.sort();
+        ages
+    }
+
+    pub fn log_metrics(&self) {
+        let updates = self.updates.load(Ordering::Relaxed);
+        let last_update_ms = self.last_update_ms.load(Ordering::Relaxed);
+        let age_ms = match last_update_ms {
+            0 => None,
+            at => {
+                Some((Utc::now().timestamp_millis() as u64).saturating_sub(at))
+            }
+        };
+        let sources = self
+            .source_ages_ms()
+            .iter()
+            .map(|(source, age)| format!("{}: {}ms", source, age))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        info!(
+            "SOL Price Metrics:\n\
+             Updates: {}\n\
+             Last Update Age: {:?}ms\n\
+             Fresh Sources: {}\n\
+             Stale: {}\n\
+             REST Fallbacks: {}\n\
+             Source Ages: {}",
+            updates,
+            age_ms,
+            self.fresh_sources(),
+            self.stale(),
+            self.rest_fallbacks(),
+            sources,
+        );
+    }
+}
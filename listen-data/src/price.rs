@@ -21,6 +21,12 @@ pub struct PriceUpdate {
     pub swap_amount: f64, // denoted as usd
     pub owner: String,
     pub signature: String,
+    /// Position of the swap instruction in the transaction, a transaction
+    /// can swap the same mint more than once
+    #[serde(default)]
+    pub instruction_index: u32,
+    #[serde(default)]
+    pub stack_height: u32,
     pub multi_hop: bool,
     pub is_buy: bool,
     pub is_pump: bool,
@@ -0,0 +1,168 @@
+use crate::{
+    db::Database,
+    kv_store::RedisKVStore,
+    message_queue::MessageQueue,
+    metadata::get_token_metadata,
+    metrics::SwapMetrics,
+    price::PriceUpdate,
+    process_swap::PendingSwapGuard,
+    pump::{
+        curve_price_in_sol, curve_progress, BondingCurveUpdate, Graduated,
+        PumpEvent, SOL_DECIMALS,
+    },
+    sol_price_stream::get_sol_price,
+};
+use anyhow::{Context, Result};
+use carbon_core::instruction::InstructionMetadata;
+use carbon_pumpfun_decoder::instructions::trade_event::TradeEvent;
+use chrono::Utc;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Emits a price update and the curve state for a bonding curve trade.
+///
+/// The trade event carries the reserves after the trade, so the price comes
+/// straight from the curve instead of the token transfers.
+pub async fn process_bonding_curve_trade<M: MessageQueue, D: Database>(
+    trade: &TradeEvent,
+    instruction_metadata: &InstructionMetadata,
+    message_queue: &M,
+    kv_store: &Arc<RedisKVStore>,
+    db: &Arc<D>,
+    metrics: &SwapMetrics,
+) -> Result<()> {
+    let transaction_metadata = &instruction_metadata.transaction_metadata;
+    let _pending_guard = PendingSwapGuard(metrics);
+
+    if trade.sol_amount == 0 || trade.token_amount == 0 {
+        debug!("skipping zero bonding curve trade");
+        metrics.increment_skipped_zero_swaps();
+        return Ok(());
+    }
+
+    let Some(price_in_sol) = curve_price_in_sol(
+        trade.virtual_sol_reserves,
+        trade.virtual_token_reserves,
+    ) else {
+        metrics.increment_skipped_zero_swaps();
+        return Ok(());
+    };
+
+    let mint = trade.mint.to_string();
+    let token_metadata = match get_token_metadata(kv_store, &mint).await {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => {
+            metrics.increment_skipped_no_metadata();
+            return Ok(());
+        }
+        Err(e) => {
+            warn!(
+                "https://solscan.io/tx/{} failed to get token metadata: {}",
+                transaction_metadata.signature, e
+            );
+            metrics.increment_skipped_no_metadata();
+            return Ok(());
+        }
+    };
+
+    let sol_price = get_sol_price().await;
+    let price = price_in_sol * sol_price;
+    let market_cap = {
+        let supply = token_metadata.spl.supply as f64;
+        let adjusted_supply =
+            supply / (10_f64.powi(token_metadata.spl.decimals as i32));
+        price * adjusted_supply
+    };
+    let swap_amount =
+        trade.sol_amount as f64 / 10_f64.powi(SOL_DECIMALS as i32) * sol_price;
+    let timestamp = Utc::now().timestamp() as u64;
+    let signature = transaction_metadata.signature.to_string();
+
+    let price_update = PriceUpdate {
+        name: token_metadata.mpl.name,
+        pubkey: mint.clone(),
+        price,
+        market_cap,
+        timestamp,
+        slot: transaction_metadata.slot,
+        swap_amount,
+        owner: trade.user.to_string(),
+        signature: signature.clone(),
+        instruction_index: instruction_metadata.index,
+        stack_height: instruction_metadata.stack_height,
+        multi_hop: false,
+        is_buy: trade.is_buy,
+        is_pump: true,
+    };
+
+    let curve = BondingCurveUpdate {
+        mint,
+        progress: curve_progress(trade.real_token_reserves),
+        price,
+        market_cap,
+        virtual_sol_reserves: trade.virtual_sol_reserves,
+        virtual_token_reserves: trade.virtual_token_reserves,
+        real_sol_reserves: trade.real_sol_reserves,
+        real_token_reserves: trade.real_token_reserves,
+        slot: transaction_metadata.slot,
+        timestamp,
+        signature,
+    };
+
+    metrics.set_latest_update_slot(transaction_metadata.slot);
+
+    let curve_event = PumpEvent::BondingCurve(curve.clone());
+    let (db_result, mq_result, kv_result, curve_mq, curve_kv) = tokio::join!(
+        db.insert_price(&price_update),
+        message_queue.publish_price_update(price_update.clone()),
+        kv_store.insert_price(&price_update),
+        message_queue.publish_pump_event(curve_event),
+        kv_store.insert_curve(&curve),
+    );
+
+    match db_result {
+        Ok(_) => metrics.increment_db_insert_success(),
+        Err(e) => {
+            metrics.increment_db_insert_failure();
+            return Err(e);
+        }
+    }
+
+    match mq_result.and(curve_mq) {
+        Ok(_) => metrics.increment_message_send_success(),
+        Err(e) => {
+            metrics.increment_message_send_failure();
+            return Err(e.into());
+        }
+    }
+
+    match kv_result.and(curve_kv) {
+        Ok(_) => metrics.increment_kv_insert_success(),
+        Err(e) => {
+            metrics.increment_kv_insert_failure();
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn process_graduation<M: MessageQueue>(
+    graduated: Graduated,
+    message_queue: &M,
+    kv_store: &Arc<RedisKVStore>,
+    metrics: &SwapMetrics,
+) -> Result<()> {
+    debug!(?graduated, "token graduated");
+    metrics.increment_graduations();
+
+    kv_store
+        .insert_graduated(&graduated)
+        .await
+        .context("failed to insert graduation")?;
+    message_queue
+        .publish_pump_event(PumpEvent::Graduated(graduated))
+        .await
+        .context("failed to publish graduation")?;
+    Ok(())
+}
@@ -3,17 +3,16 @@ use crate::diffs::{
     DiffsResult, TokenTransferDetails, SPL_TOKEN_TRANSFER_PROCESSOR,
 };
 use crate::{
-    db::{ClickhouseDb, Database},
+    db::Database,
     kv_store::RedisKVStore,
-    message_queue::{MessageQueue, RedisMessageQueue},
+    message_queue::MessageQueue,
     metadata::get_token_metadata,
     metrics::SwapMetrics,
     price::PriceUpdate,
-    sol_price_stream::get_sol_price,
+    sol_price_stream::{get_sol_price, SOL_USDC_TWAP},
 };
 use anyhow::{Context, Result};
-use carbon_core::instruction::NestedInstruction;
-use carbon_core::transaction::TransactionMetadata;
+use carbon_core::instruction::{InstructionMetadata, NestedInstruction};
 use chrono::Utc;
 use std::collections::HashSet;
 use std::sync::Arc;
@@ -44,16 +43,18 @@ pub fn is_valid_vault_transfer(
     vaults.contains(&transfer.destination) || vaults.contains(&transfer.source)
 }
 
-pub async fn process_swap(
+#[allow(clippy::too_many_arguments)]
+pub async fn process_swap<M: MessageQueue, D: Database>(
     vaults: &HashSet<String>,
     fee_adas: Option<&HashSet<String>>,
-    transaction_metadata: &TransactionMetadata,
+    instruction_metadata: &InstructionMetadata,
     nested_instructions: &[NestedInstruction],
-    message_queue: &RedisMessageQueue,
+    message_queue: &M,
     kv_store: &Arc<RedisKVStore>,
-    db: &Arc<ClickhouseDb>,
+    db: &Arc<D>,
     metrics: &SwapMetrics,
 ) -> Result<()> {
+    let transaction_metadata = &instruction_metadata.transaction_metadata;
     // Decrement pending swaps when this function exits
     let _pending_guard = PendingSwapGuard(metrics);
 
@@ -96,7 +97,7 @@ pub async fn process_swap(
     process_two_token_swap(
         vaults,
         &transfers,
-        transaction_metadata,
+        instruction_metadata,
         message_queue,
         kv_store,
         db,
@@ -110,17 +111,21 @@ pub async fn process_swap(
 
 // Helper function to process a single two-token swap
 #[allow(clippy::too_many_arguments)]
-async fn process_two_token_swap(
+async fn process_two_token_swap<M: MessageQueue, D: Database>(
     vaults: &HashSet<String>,
     transfers: &[TokenTransferDetails],
-    transaction_metadata: &TransactionMetadata,
-    message_queue: &RedisMessageQueue,
+    instruction_metadata: &InstructionMetadata,
+    message_queue: &M,
     kv_store: &Arc<RedisKVStore>,
-    db: &Arc<ClickhouseDb>,
+    db: &Arc<D>,
     metrics: &SwapMetrics,
     sol_price: f64,
     multi_hop: bool,
 ) -> Result<()> {
+    let transaction_metadata = &instruction_metadata.transaction_metadata;
+    // SOL/USDC swaps double as an on-chain SOL price source
+    SOL_USDC_TWAP.record_swap(transfers);
+
     let DiffsResult {
         price,
         swap_amount,
@@ -189,6 +194,8 @@ async fn process_two_token_swap(
         swap_amount,
         owner: transaction_metadata.fee_payer.to_string(),
         signature: transaction_metadata.signature.to_string(),
+        instruction_index: instruction_metadata.index,
+        stack_height: instruction_metadata.stack_height,
         multi_hop,
         is_buy,
         is_pump,
@@ -243,7 +250,7 @@ async fn process_two_token_swap(
 }
 
 // Helper struct to decrement pending swaps when dropped
-struct PendingSwapGuard<'a>(&'a SwapMetrics);
+pub(crate) struct PendingSwapGuard<'a>(pub(crate) &'a SwapMetrics);
 
 impl Drop for PendingSwapGuard<'_> {
     fn drop(&mut self) {
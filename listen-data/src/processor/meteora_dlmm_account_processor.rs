@@ -0,0 +1,54 @@
+use crate::{
+    handler::LiquidityHandler,
+    liquidity::{PoolState, SpotPrice},
+};
+use carbon_core::{
+    account::AccountProcessorInputType, error::CarbonResult,
+    metrics::MetricsCollection, processor::Processor,
+};
+use carbon_meteora_dlmm_decoder::accounts::MeteoraDlmmAccount;
+use std::sync::Arc;
+
+pub struct MeteoraDlmmAccountProcessor {
+    liquidity_handler: Arc<LiquidityHandler>,
+}
+
+impl MeteoraDlmmAccountProcessor {
+    pub fn new(liquidity_handler: Arc<LiquidityHandler>) -> Self {
+        Self { liquidity_handler }
+    }
+}
+
+#[async_trait::async_trait]
+impl Processor for MeteoraDlmmAccountProcessor {
+    type InputType = AccountProcessorInputType<MeteoraDlmmAccount>;
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (meta, account) = data;
+        if let MeteoraDlmmAccount::LbPair(pool) = &account.data {
+            self.liquidity_handler.spawn_liquidity_update(
+                PoolState {
+                    pool: meta.pubkey,
+                    dex: "meteora_dlmm",
+                    mint_a: pool.token_x_mint,
+                    mint_b: pool.token_y_mint,
+                    vault_a: pool.reserve_x,
+                    vault_b: pool.reserve_y,
+                    spot_price: SpotPrice::ActiveBin {
+                        active_id: pool.active_id,
+                        bin_step: pool.bin_step,
+                    },
+                    excluded_a: 0,
+                    excluded_b: 0,
+                },
+                meta.slot,
+            );
+        };
+
+        Ok(())
+    }
+}
@@ -1,18 +1,30 @@
+mod meteora_dlmm_account_processor;
 mod meteora_dlmm_instruction_processor;
+mod ocra_whirlpool_account_processor;
 mod ocra_whirlpool_instruction_processor;
+mod pump_amm_account_processor;
 mod pump_amm_instruction_processor;
+mod pump_fun_instruction_processor;
 mod raydium_amm_v4_account_processor;
 mod raydium_amm_v4_instruction_processor;
+mod raydium_clmm_account_processor;
 mod raydium_clmm_instruction_processor;
+mod raydium_cpmm_account_processor;
 mod raydium_cpmm_instruction_processor;
 
 // instruction	processor
 pub use meteora_dlmm_instruction_processor::MeteoraDlmmInstructionProcessor;
 pub use ocra_whirlpool_instruction_processor::OcraWhirlpoolInstructionProcessor;
 pub use pump_amm_instruction_processor::PumpAmmInstructionProcessor;
+pub use pump_fun_instruction_processor::PumpFunInstructionProcessor;
 pub use raydium_amm_v4_instruction_processor::RaydiumAmmV4InstructionProcessor;
 pub use raydium_clmm_instruction_processor::RaydiumClmmInstructionProcessor;
 pub use raydium_cpmm_instruction_processor::RaydiumCpmmInstructionProcessor;
 
 // account processor
+pub use meteora_dlmm_account_processor::MeteoraDlmmAccountProcessor;
+pub use ocra_whirlpool_account_processor::OcraWhirlpoolAccountProcessor;
+pub use pump_amm_account_processor::PumpAmmAccountProcessor;
 pub use raydium_amm_v4_account_processor::RaydiumAmmV4AccountProcessor;
+pub use raydium_clmm_account_processor::RaydiumClmmAccountProcessor;
+pub use raydium_cpmm_account_processor::RaydiumCpmmAccountProcessor;
@@ -0,0 +1,52 @@
+use crate::{
+    handler::LiquidityHandler,
+    liquidity::{PoolState, SpotPrice},
+};
+use carbon_core::{
+    account::AccountProcessorInputType, error::CarbonResult,
+    metrics::MetricsCollection, processor::Processor,
+};
+use carbon_orca_whirlpool_decoder::accounts::OrcaWhirlpoolAccount;
+use std::sync::Arc;
+
+pub struct OcraWhirlpoolAccountProcessor {
+    liquidity_handler: Arc<LiquidityHandler>,
+}
+
+impl OcraWhirlpoolAccountProcessor {
+    pub fn new(liquidity_handler: Arc<LiquidityHandler>) -> Self {
+        Self { liquidity_handler }
+    }
+}
+
+#[async_trait::async_trait]
+impl Processor for OcraWhirlpoolAccountProcessor {
+    type InputType = AccountProcessorInputType<OrcaWhirlpoolAccount>;
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (meta, account) = data;
+        if let OrcaWhirlpoolAccount::Whirlpool(pool) = &account.data {
+            self.liquidity_handler.spawn_liquidity_update(
+                PoolState {
+                    pool: meta.pubkey,
+                    dex: "whirlpools",
+                    mint_a: pool.token_mint_a,
+                    mint_b: pool.token_mint_b,
+                    vault_a: pool.token_vault_a,
+                    vault_b: pool.token_vault_b,
+                    spot_price: SpotPrice::SqrtPriceX64(pool.sqrt_price),
+                    // protocol fees accrue in the vaults until collected
+                    excluded_a: pool.protocol_fee_owed_a,
+                    excluded_b: pool.protocol_fee_owed_b,
+                },
+                meta.slot,
+            );
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{
+    handler::LiquidityHandler,
+    liquidity::{PoolState, SpotPrice},
+};
+use carbon_core::{
+    account::AccountProcessorInputType, error::CarbonResult,
+    metrics::MetricsCollection, processor::Processor,
+};
+use carbon_pump_swap_decoder::accounts::PumpSwapAccount;
+use std::sync::Arc;
+
+pub struct PumpAmmAccountProcessor {
+    liquidity_handler: Arc<LiquidityHandler>,
+}
+
+impl PumpAmmAccountProcessor {
+    pub fn new(liquidity_handler: Arc<LiquidityHandler>) -> Self {
+        Self { liquidity_handler }
+    }
+}
+
+#[async_trait::async_trait]
+impl Processor for PumpAmmAccountProcessor {
+    type InputType = AccountProcessorInputType<PumpSwapAccount>;
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (meta, account) = data;
+        if let PumpSwapAccount::Pool(pool) = &account.data {
+            self.liquidity_handler.spawn_liquidity_update(
+                PoolState {
+                    pool: meta.pubkey,
+                    dex: "pump_swap",
+                    mint_a: pool.base_mint,
+                    mint_b: pool.quote_mint,
+                    vault_a: pool.pool_base_token_account,
+                    vault_b: pool.pool_quote_token_account,
+                    spot_price: SpotPrice::Reserves,
+                    excluded_a: 0,
+                    excluded_b: 0,
+                },
+                meta.slot,
+            );
+        };
+
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
-use crate::handler::{token_swap_handler::Dex, TokenSwapHandler};
+use crate::{
+    handler::{token_swap_handler::Dex, TokenSwapHandler},
+    pump::{pool_authority, GraduationVenue},
+};
 use carbon_core::{
     deserialize::ArrangeAccounts, error::CarbonResult,
     instruction::InstructionProcessorInputType, metrics::MetricsCollection,
     processor::Processor,
 };
 use carbon_pump_swap_decoder::instructions::{
-    buy::Buy, sell::Sell, PumpSwapInstruction,
+    buy::Buy, create_pool::CreatePool, sell::Sell, PumpSwapInstruction,
 };
 use std::{collections::HashSet, sync::Arc};
 
@@ -72,6 +75,21 @@ impl Processor for PumpAmmInstructionProcessor {
                     );
                 }
             }
+            // pump.fun migrations create the pool from a per-mint PDA
+            PumpSwapInstruction::CreatePool(_) => {
+                let accounts =
+                    CreatePool::arrange_accounts(&instruction.accounts);
+                if let Some(accounts) = accounts {
+                    if accounts.creator == pool_authority(&accounts.base_mint) {
+                        self.swap_handler.spawn_graduation(
+                            &accounts.base_mint,
+                            GraduationVenue::PumpSwap,
+                            Some(&accounts.pool),
+                            &meta,
+                        );
+                    }
+                }
+            }
             _ => {}
         }
 
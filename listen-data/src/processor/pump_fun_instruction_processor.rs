@@ -0,0 +1,65 @@
+use crate::{handler::TokenSwapHandler, pump::GraduationVenue};
+use carbon_core::{
+    deserialize::ArrangeAccounts, error::CarbonResult,
+    instruction::InstructionProcessorInputType, metrics::MetricsCollection,
+    processor::Processor,
+};
+use carbon_pumpfun_decoder::instructions::{
+    withdraw::Withdraw, PumpfunInstruction,
+};
+use std::sync::Arc;
+use tracing::debug;
+
+/// Prices pump.fun tokens while they are still on the bonding curve.
+///
+/// Buys and sells emit a `TradeEvent` self-CPI with the curve reserves after
+/// the trade, which is all that is needed for the price and curve progress.
+/// `Withdraw` is the legacy migration that moves the liquidity to Raydium.
+pub struct PumpFunInstructionProcessor {
+    swap_handler: Arc<TokenSwapHandler>,
+}
+
+impl PumpFunInstructionProcessor {
+    pub fn new(swap_handler: Arc<TokenSwapHandler>) -> Self {
+        Self { swap_handler }
+    }
+}
+
+#[async_trait::async_trait]
+impl Processor for PumpFunInstructionProcessor {
+    type InputType = InstructionProcessorInputType<PumpfunInstruction>;
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (meta, instruction, _nested_instructions) = data;
+
+        match &instruction.data {
+            PumpfunInstruction::TradeEvent(trade) => {
+                self.swap_handler.metrics.increment_pump_fun_trades();
+                self.swap_handler.spawn_bonding_curve_trade(trade, &meta);
+            }
+            PumpfunInstruction::CompleteEvent(complete) => {
+                // migration follows in a separate transaction
+                debug!(mint = %complete.mint, "bonding curve complete");
+            }
+            PumpfunInstruction::Withdraw(_) => {
+                if let Some(accounts) =
+                    Withdraw::arrange_accounts(&instruction.accounts)
+                {
+                    self.swap_handler.spawn_graduation(
+                        &accounts.mint,
+                        GraduationVenue::Raydium,
+                        None,
+                        &meta,
+                    );
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
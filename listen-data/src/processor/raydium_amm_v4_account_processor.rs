@@ -1,22 +1,21 @@
+use crate::{
+    handler::LiquidityHandler,
+    liquidity::{PoolState, SpotPrice},
+};
 use carbon_core::{
     account::AccountProcessorInputType, error::CarbonResult,
     metrics::MetricsCollection, processor::Processor,
 };
 use carbon_raydium_amm_v4_decoder::accounts::RaydiumAmmV4Account;
 use std::sync::Arc;
-use tracing::info;
-
-pub struct RaydiumAmmV4AccountProcessor {}
 
-impl Default for RaydiumAmmV4AccountProcessor {
-    fn default() -> Self {
-        Self::new()
-    }
+pub struct RaydiumAmmV4AccountProcessor {
+    liquidity_handler: Arc<LiquidityHandler>,
 }
 
 impl RaydiumAmmV4AccountProcessor {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(liquidity_handler: Arc<LiquidityHandler>) -> Self {
+        Self { liquidity_handler }
     }
 }
 
@@ -29,9 +28,23 @@ impl Processor for RaydiumAmmV4AccountProcessor {
         data: Self::InputType,
         _metrics: Arc<MetricsCollection>,
     ) -> CarbonResult<()> {
-        let (_meta, account) = data;
+        let (meta, account) = data;
         if let RaydiumAmmV4Account::AmmInfo(pool) = &account.data {
-            info!("pool: {:#?}", pool);
+            self.liquidity_handler.spawn_liquidity_update(
+                PoolState {
+                    pool: meta.pubkey,
+                    dex: "raydium_amm_v4",
+                    mint_a: pool.coin_mint,
+                    mint_b: pool.pc_mint,
+                    vault_a: pool.token_coin,
+                    vault_b: pool.token_pc,
+                    spot_price: SpotPrice::Reserves,
+                    // pnl owed to the protocol sits in the vaults until taken
+                    excluded_a: pool.out_put.need_take_pnl_coin,
+                    excluded_b: pool.out_put.need_take_pnl_pc,
+                },
+                meta.slot,
+            );
         };
 
         Ok(())
@@ -0,0 +1,54 @@
+use crate::{
+    handler::LiquidityHandler,
+    liquidity::{PoolState, SpotPrice},
+};
+use carbon_core::{
+    account::AccountProcessorInputType, error::CarbonResult,
+    metrics::MetricsCollection, processor::Processor,
+};
+use carbon_raydium_clmm_decoder::accounts::RaydiumClmmAccount;
+use std::sync::Arc;
+
+pub struct RaydiumClmmAccountProcessor {
+    liquidity_handler: Arc<LiquidityHandler>,
+}
+
+impl RaydiumClmmAccountProcessor {
+    pub fn new(liquidity_handler: Arc<LiquidityHandler>) -> Self {
+        Self { liquidity_handler }
+    }
+}
+
+#[async_trait::async_trait]
+impl Processor for RaydiumClmmAccountProcessor {
+    type InputType = AccountProcessorInputType<RaydiumClmmAccount>;
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (meta, account) = data;
+        if let RaydiumClmmAccount::PoolState(pool) = &account.data {
+            self.liquidity_handler.spawn_liquidity_update(
+                PoolState {
+                    pool: meta.pubkey,
+                    dex: "raydium_clmm",
+                    mint_a: pool.token_mint0,
+                    mint_b: pool.token_mint1,
+                    vault_a: pool.token_vault0,
+                    vault_b: pool.token_vault1,
+                    spot_price: SpotPrice::SqrtPriceX64(pool.sqrt_price_x64),
+                    // fees accrue in the vaults until collected
+                    excluded_a: pool.protocol_fees_token0
+                        + pool.fund_fees_token0,
+                    excluded_b: pool.protocol_fees_token1
+                        + pool.fund_fees_token1,
+                },
+                meta.slot,
+            );
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    handler::LiquidityHandler,
+    liquidity::{PoolState, SpotPrice},
+};
+use carbon_core::{
+    account::AccountProcessorInputType, error::CarbonResult,
+    metrics::MetricsCollection, processor::Processor,
+};
+use carbon_raydium_cpmm_decoder::accounts::RaydiumCpmmAccount;
+use std::sync::Arc;
+
+pub struct RaydiumCpmmAccountProcessor {
+    liquidity_handler: Arc<LiquidityHandler>,
+}
+
+impl RaydiumCpmmAccountProcessor {
+    pub fn new(liquidity_handler: Arc<LiquidityHandler>) -> Self {
+        Self { liquidity_handler }
+    }
+}
+
+#[async_trait::async_trait]
+impl Processor for RaydiumCpmmAccountProcessor {
+    type InputType = AccountProcessorInputType<RaydiumCpmmAccount>;
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (meta, account) = data;
+        if let RaydiumCpmmAccount::PoolState(pool) = &account.data {
+            self.liquidity_handler.spawn_liquidity_update(
+                PoolState {
+                    pool: meta.pubkey,
+                    dex: "raydium_cpmm",
+                    mint_a: pool.token0_mint,
+                    mint_b: pool.token1_mint,
+                    vault_a: pool.token0_vault,
+                    vault_b: pool.token1_vault,
+                    spot_price: SpotPrice::Reserves,
+                    // fees accrue in the vaults until collected
+                    excluded_a: pool.protocol_fees_token0
+                        + pool.fund_fees_token0,
+                    excluded_b: pool.protocol_fees_token1
+                        + pool.fund_fees_token1,
+                },
+                meta.slot,
+            );
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use crate::constants::PUMP_FUN_PROGRAM_ID;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+pub const PUMP_TOKEN_DECIMALS: u8 = 6;
+pub const SOL_DECIMALS: u8 = 9;
+
+/// Tokens sold through the curve before it completes, the remaining supply
+/// is deposited into the AMM on migration
+pub const INITIAL_REAL_TOKEN_RESERVES: u64 = 793_100_000_000_000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GraduationVenue {
+    PumpSwap,
+    Raydium,
+}
+
+/// State of a bonding curve after a trade
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BondingCurveUpdate {
+    pub mint: String,
+    pub progress: f64, // percentage of the curve sold, 0-100
+    pub price: f64,    // denoted as usd
+    pub market_cap: f64,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    pub slot: u64,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+/// Emitted once the token leaves the bonding curve for an AMM pool
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Graduated {
+    pub mint: String,
+    pub venue: GraduationVenue,
+    pub pool: Option<String>,
+    pub slot: u64,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PumpEvent {
+    BondingCurve(BondingCurveUpdate),
+    Graduated(Graduated),
+}
+
+impl PumpEvent {
+    pub fn mint(&self) -> &str {
+        match self {
+            PumpEvent::BondingCurve(update) => &update.mint,
+            PumpEvent::Graduated(graduated) => &graduated.mint,
+        }
+    }
+}
+
+/// Percentage of the sellable supply bought out of the curve
+pub fn curve_progress(real_token_reserves: u64) -> f64 {
+    let sold =
+        INITIAL_REAL_TOKEN_RESERVES.saturating_sub(real_token_reserves) as f64;
+    (sold / INITIAL_REAL_TOKEN_RESERVES as f64 * 100.0).clamp(0.0, 100.0)
+}
+
+/// Spot price of the token in SOL, from the virtual reserves
+pub fn curve_price_in_sol(
+    virtual_sol_reserves: u64,
+    virtual_token_reserves: u64,
+) -> Option<f64> {
+    if virtual_token_reserves == 0 {
+        return None;
+    }
+    let sol = virtual_sol_reserves as f64 / 10f64.powi(SOL_DECIMALS as i32);
+    let tokens =
+        virtual_token_reserves as f64 / 10f64.powi(PUMP_TOKEN_DECIMALS as i32);
+    Some(sol / tokens)
+}
+
+/// pump.fun migrates to PumpSwap with a per-mint PDA as the pool creator,
+/// pools created by anyone else are not graduations
+pub fn pool_authority(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"pool-authority", mint.as_ref()],
+        &PUMP_FUN_PROGRAM_ID,
+    )
+    .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::round_to_decimals;
+
+    #[test]
+    fn test_curve_progress() {
+        assert_eq!(curve_progress(INITIAL_REAL_TOKEN_RESERVES), 0.0);
+        assert_eq!(curve_progress(0), 100.0);
+        assert_eq!(curve_progress(INITIAL_REAL_TOKEN_RESERVES / 2), 50.0);
+        // reserves above the initial amount do not go negative
+        assert_eq!(curve_progress(INITIAL_REAL_TOKEN_RESERVES + 1), 0.0);
+    }
+
+    #[test]
+    fn test_curve_price_in_sol() {
+        // initial virtual reserves, 30 SOL and 1.073B tokens
+        let price =
+            curve_price_in_sol(30_000_000_000, 1_073_000_000_000_000).unwrap();
+        assert_eq!(round_to_decimals(price * 1e9, 2), 27.96);
+        assert!(curve_price_in_sol(30_000_000_000, 0).is_none());
+    }
+
+    #[test]
+    fn test_pump_event_is_tagged() {
+        let event = PumpEvent::Graduated(Graduated {
+            mint: "mint".to_string(),
+            venue: GraduationVenue::PumpSwap,
+            pool: Some("pool".to_string()),
+            slot: 1,
+            timestamp: 1_700_000_000,
+            signature: "sig".to_string(),
+        });
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "graduated");
+        assert_eq!(json["venue"], "pump_swap");
+        assert_eq!(event.mint(), "mint");
+    }
+}
@@ -1,35 +1,85 @@
 use crate::{
-    constants::RAYDIUM_AMM_V4_PROGRAM_ID,
-    processor::RaydiumAmmV4AccountProcessor, util::must_get_env,
+    constants::{
+        METEORA_DLMM_PROGRAM_ID, PUMP_SWAP_PROGRAM_ID,
+        RAYDIUM_AMM_V4_PROGRAM_ID, RAYDIUM_CLMM_PROGRAM_ID,
+        RAYDIUM_CPMM_PROGRAM_ID, WHIRLPOOLS_PROGRAM_ID,
+    },
+    handler::LiquidityHandler,
+    processor::{
+        MeteoraDlmmAccountProcessor, OcraWhirlpoolAccountProcessor,
+        PumpAmmAccountProcessor, RaydiumAmmV4AccountProcessor,
+        RaydiumClmmAccountProcessor, RaydiumCpmmAccountProcessor,
+    },
+    util::must_get_env,
 };
 use anyhow::Result;
 use carbon_core::pipeline::{Pipeline, ShutdownStrategy};
 use carbon_log_metrics::LogMetrics;
+use carbon_meteora_dlmm_decoder::MeteoraDlmmDecoder;
+use carbon_orca_whirlpool_decoder::OrcaWhirlpoolDecoder;
+use carbon_pump_swap_decoder::PumpSwapDecoder;
 use carbon_raydium_amm_v4_decoder::RaydiumAmmV4Decoder;
+use carbon_raydium_clmm_decoder::RaydiumClmmDecoder;
+use carbon_raydium_cpmm_decoder::RaydiumCpmmDecoder;
 use carbon_rpc_program_subscribe_datasource::{Filters, RpcProgramSubscribe};
 use solana_account_decoder::UiAccountEncoding;
 use solana_client::rpc_config::{
     RpcAccountInfoConfig, RpcProgramAccountsConfig,
 };
+use solana_sdk::pubkey::Pubkey;
 use std::sync::Arc;
 
-pub fn make_raydium_rpc_accounts_pipeline() -> Result<Pipeline> {
-    let pipeline = Pipeline::builder()
-        .datasource(RpcProgramSubscribe::new(
-            must_get_env("WS_URL"),
-            Filters::new(
-                RAYDIUM_AMM_V4_PROGRAM_ID,
-                Some(RpcProgramAccountsConfig {
-                    filters: None,
-                    account_config: RpcAccountInfoConfig {
-                        encoding: Some(UiAccountEncoding::Base64),
-                        ..Default::default()
-                    },
+fn program_subscribe(program_id: Pubkey) -> RpcProgramSubscribe {
+    RpcProgramSubscribe::new(
+        must_get_env("WS_URL"),
+        Filters::new(
+            program_id,
+            Some(RpcProgramAccountsConfig {
+                filters: None,
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
                     ..Default::default()
-                }),
-            ),
-        ))
-        .account(RaydiumAmmV4Decoder, RaydiumAmmV4AccountProcessor::new())
+                },
+                ..Default::default()
+            }),
+        ),
+    )
+}
+
+pub fn make_pool_rpc_accounts_pipeline(
+    liquidity_handler: Arc<LiquidityHandler>,
+) -> Result<Pipeline> {
+    let pipeline = Pipeline::builder()
+        .datasource(program_subscribe(RAYDIUM_AMM_V4_PROGRAM_ID))
+        .datasource(program_subscribe(RAYDIUM_CPMM_PROGRAM_ID))
+        .datasource(program_subscribe(RAYDIUM_CLMM_PROGRAM_ID))
+        .datasource(program_subscribe(WHIRLPOOLS_PROGRAM_ID))
+        .datasource(program_subscribe(METEORA_DLMM_PROGRAM_ID))
+        .datasource(program_subscribe(PUMP_SWAP_PROGRAM_ID))
+        .account(
+            RaydiumAmmV4Decoder,
+            RaydiumAmmV4AccountProcessor::new(liquidity_handler.clone()),
+        )
+        .account(
+            RaydiumCpmmDecoder,
+            RaydiumCpmmAccountProcessor::new(liquidity_handler.clone()),
+        )
+        .account(
+            RaydiumClmmDecoder,
+            RaydiumClmmAccountProcessor::new(liquidity_handler.clone()),
+        )
+        .account(
+            OrcaWhirlpoolDecoder,
+            OcraWhirlpoolAccountProcessor::new(liquidity_handler.clone()),
+        )
+        .account(
+            MeteoraDlmmDecoder,
+            MeteoraDlmmAccountProcessor::new(liquidity_handler.clone()),
+        )
+        .account(
+            PumpSwapDecoder,
+            PumpAmmAccountProcessor::new(liquidity_handler),
+        )
         .shutdown_strategy(ShutdownStrategy::Immediate)
         .metrics(Arc::new(LogMetrics::new()))
         .build()?;
@@ -10,13 +10,13 @@ use std::{sync::Arc, time::Duration};
 use crate::{
     constants::RAYDIUM_AMM_V4_PROGRAM_ID, db::ClickhouseDb,
     handler::TokenSwapHandler, kv_store::RedisKVStore,
-    message_queue::RedisMessageQueue, metrics::SwapMetrics,
+    message_queue::FanoutSink, metrics::SwapMetrics,
     processor::RaydiumAmmV4InstructionProcessor,
 };
 
 pub fn make_raydium_rpc_instruction_pipeline(
     kv_store: Arc<RedisKVStore>,
-    message_queue: Arc<RedisMessageQueue>,
+    message_queue: Arc<FanoutSink>,
     db: Arc<ClickhouseDb>,
     metrics: Arc<SwapMetrics>,
 ) -> Result<Pipeline> {
@@ -0,0 +1,91 @@
+use super::{stream_websocket, PriceSource};
+use anyhow::Result;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+pub const BINANCE_WS_URL: &str =
+    "wss://fstream.binance.com/ws/solusdt@aggTrade";
+pub const BINANCE_REST_URL: &str =
+    "https://api.binance.com/api/v3/ticker/price?symbol=SOLUSDT";
+
+#[derive(Debug, Deserialize)]
+struct TradeData {
+    p: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinancePrice {
+    price: String,
+}
+
+/// Binance futures SOL/USDT aggregated trades
+pub struct BinanceSource {
+    ws_url: String,
+    rest_url: String,
+}
+
+impl BinanceSource {
+    pub fn new(ws_url: &str, rest_url: &str) -> Self {
+        Self {
+            ws_url: ws_url.to_string(),
+            rest_url: rest_url.to_string(),
+        }
+    }
+}
+
+impl Default for BinanceSource {
+    fn default() -> Self {
+        Self::new(BINANCE_WS_URL, BINANCE_REST_URL)
+    }
+}
+
+fn parse_trade(text: &str) -> Option<f64> {
+    let trade = serde_json::from_str::<TradeData>(text).ok()?;
+    trade.p.parse::<f64>().ok()
+}
+
+#[async_trait::async_trait]
+impl PriceSource for BinanceSource {
+    fn name(&self) -> &'static str {
+        "binance"
+    }
+
+    async fn stream(&self, tx: mpsc::UnboundedSender<f64>) -> Result<()> {
+        stream_websocket(&self.ws_url, None, &tx, parse_trade).await
+    }
+
+    async fn fetch(&self) -> Result<f64> {
+        let response = reqwest::get(&self.rest_url).await?;
+        let price_data: BinancePrice = response.json().await?;
+        price_data.price.parse::<f64>().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sol_price_stream::tests::mock_ws_server;
+
+    #[tokio::test]
+    async fn test_binance_stream() {
+        let (url, _server) = mock_ws_server(
+            vec![
+                r#"{"e":"aggTrade","s":"SOLUSDT","p":"150.25","q":"1.2"}"#
+                    .to_string(),
+                r#"{"result":null,"id":1}"#.to_string(),
+                r#"{"e":"aggTrade","s":"SOLUSDT","p":"150.30","q":"0.4"}"#
+                    .to_string(),
+            ],
+            false,
+        )
+        .await;
+
+        let source = BinanceSource::new(&url, BINANCE_REST_URL);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move { source.stream(tx).await });
+
+        assert_eq!(rx.recv().await, Some(150.25));
+        // messages without a price are skipped
+        assert_eq!(rx.recv().await, Some(150.30));
+    }
+}
@@ -0,0 +1,110 @@
+use super::{stream_websocket, PriceSource};
+use anyhow::Result;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+pub const COINBASE_WS_URL: &str = "wss://ws-feed.exchange.coinbase.com";
+pub const COINBASE_REST_URL: &str =
+    "https://api.exchange.coinbase.com/products/SOL-USD/ticker";
+
+/// Ticker messages and the REST ticker share the price field
+#[derive(Debug, Deserialize)]
+struct Ticker {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    price: String,
+}
+
+/// Coinbase Exchange SOL-USD ticker
+pub struct CoinbaseSource {
+    ws_url: String,
+    rest_url: String,
+}
+
+impl CoinbaseSource {
+    pub fn new(ws_url: &str, rest_url: &str) -> Self {
+        Self {
+            ws_url: ws_url.to_string(),
+            rest_url: rest_url.to_string(),
+        }
+    }
+}
+
+impl Default for CoinbaseSource {
+    fn default() -> Self {
+        Self::new(COINBASE_WS_URL, COINBASE_REST_URL)
+    }
+}
+
+fn parse_ticker(text: &str) -> Option<f64> {
+    let ticker = serde_json::from_str::<Ticker>(text).ok()?;
+    if ticker.kind.as_deref() != Some("ticker") {
+        return None;
+    }
+    ticker.price.parse::<f64>().ok()
+}
+
+#[async_trait::async_trait]
+impl PriceSource for CoinbaseSource {
+    fn name(&self) -> &'static str {
+        "coinbase"
+    }
+
+    async fn stream(&self, tx: mpsc::UnboundedSender<f64>) -> Result<()> {
+        let subscribe = serde_json::json!({
+            "type": "subscribe",
+            "product_ids": ["SOL-USD"],
+            "channels": ["ticker"],
+        });
+        stream_websocket(
+            &self.ws_url,
+            Some(subscribe.to_string()),
+            &tx,
+            parse_ticker,
+        )
+        .await
+    }
+
+    async fn fetch(&self) -> Result<f64> {
+        // the exchange api rejects requests without a user agent
+        let response = reqwest::Client::new()
+            .get(&self.rest_url)
+            .header("User-Agent", "listen-data")
+            .send()
+            .await?;
+        let ticker: Ticker = response.json().await?;
+        ticker.price.parse::<f64>().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sol_price_stream::tests::mock_ws_server;
+
+    #[tokio::test]
+    async fn test_coinbase_stream() {
+        let (url, server) = mock_ws_server(
+            vec![
+                r#"{"type":"subscriptions","channels":[]}"#.to_string(),
+                r#"{"type":"ticker","product_id":"SOL-USD","price":"149.87"}"#
+                    .to_string(),
+            ],
+            true,
+        )
+        .await;
+
+        let source = CoinbaseSource::new(&url, COINBASE_REST_URL);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let stream = tokio::spawn(async move { source.stream(tx).await });
+
+        assert_eq!(rx.recv().await, Some(149.87));
+
+        stream.abort();
+        let subscribe = server.await.unwrap().unwrap();
+        let subscribe: serde_json::Value =
+            serde_json::from_str(&subscribe).unwrap();
+        assert_eq!(subscribe["type"], "subscribe");
+        assert_eq!(subscribe["product_ids"][0], "SOL-USD");
+    }
+}
@@ -0,0 +1,134 @@
+use super::{stream_websocket, PriceSource};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+pub const PYTH_WS_URL: &str = "wss://hermes.pyth.network/ws";
+pub const PYTH_REST_URL: &str =
+    "https://hermes.pyth.network/v2/updates/price/latest";
+pub const PYTH_SOL_USD_FEED_ID: &str =
+    "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
+
+#[derive(Debug, Deserialize)]
+struct PythPrice {
+    price: String,
+    expo: i32,
+}
+
+impl PythPrice {
+    fn to_f64(&self) -> Option<f64> {
+        let price = self.price.parse::<i64>().ok()?;
+        Some(price as f64 * 10f64.powi(self.expo))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PriceFeed {
+    price: PythPrice,
+}
+
+#[derive(Debug, Deserialize)]
+struct PriceUpdateMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    price_feed: Option<PriceFeed>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LatestPrices {
+    parsed: Vec<PriceFeed>,
+}
+
+/// Pyth SOL/USD feed through the Hermes price service
+pub struct PythSource {
+    ws_url: String,
+    rest_url: String,
+}
+
+impl PythSource {
+    pub fn new(ws_url: &str, rest_url: &str) -> Self {
+        Self {
+            ws_url: ws_url.to_string(),
+            rest_url: rest_url.to_string(),
+        }
+    }
+}
+
+impl Default for PythSource {
+    fn default() -> Self {
+        Self::new(PYTH_WS_URL, PYTH_REST_URL)
+    }
+}
+
+fn parse_price_update(text: &str) -> Option<f64> {
+    let message = serde_json::from_str::<PriceUpdateMessage>(text).ok()?;
+    if message.kind != "price_update" {
+        return None;
+    }
+    message.price_feed?.price.to_f64()
+}
+
+#[async_trait::async_trait]
+impl PriceSource for PythSource {
+    fn name(&self) -> &'static str {
+        "pyth"
+    }
+
+    async fn stream(&self, tx: mpsc::UnboundedSender<f64>) -> Result<()> {
+        let subscribe = serde_json::json!({
+            "type": "subscribe",
+            "ids": [PYTH_SOL_USD_FEED_ID],
+        });
+        stream_websocket(
+            &self.ws_url,
+            Some(subscribe.to_string()),
+            &tx,
+            parse_price_update,
+        )
+        .await
+    }
+
+    async fn fetch(&self) -> Result<f64> {
+        let response = reqwest::Client::new()
+            .get(&self.rest_url)
+            .query(&[("ids[]", PYTH_SOL_USD_FEED_ID)])
+            .send()
+            .await?;
+        let latest: LatestPrices = response.json().await?;
+        latest
+            .parsed
+            .first()
+            .and_then(|feed| feed.price.to_f64())
+            .ok_or_else(|| anyhow!("no price in pyth response"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sol_price_stream::tests::mock_ws_server;
+
+    #[tokio::test]
+    async fn test_pyth_stream() {
+        let (url, server) = mock_ws_server(
+            vec![
+                r#"{"type":"response","status":"success"}"#.to_string(),
+                r#"{"type":"price_update","price_feed":{"id":"ef0d8b6f","price":{"price":"15012345678","conf":"1234","expo":-8,"publish_time":1700000000}}}"#
+                    .to_string(),
+            ],
+            true,
+        )
+        .await;
+
+        let source = PythSource::new(&url, PYTH_REST_URL);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let stream = tokio::spawn(async move { source.stream(tx).await });
+
+        let price = rx.recv().await.unwrap();
+        assert!((price - 150.12345678).abs() < 1e-9);
+
+        stream.abort();
+        let subscribe = server.await.unwrap().unwrap();
+        assert!(subscribe.contains(PYTH_SOL_USD_FEED_ID));
+    }
+}
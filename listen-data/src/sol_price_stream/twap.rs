@@ -0,0 +1,221 @@
+use super::PriceSource;
+use crate::{
+    constants::{USDC_MINT_KEY_STR, WSOL_MINT_KEY_STR},
+    diffs::TokenTransferDetails,
+};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, Notify};
+
+pub const DEFAULT_TWAP_WINDOW_SECS: u64 = 300;
+
+/// Swaps smaller than this are too cheap to move the price with
+pub const MIN_TWAP_SWAP_SOL: f64 = 1.0;
+
+/// SOL/USDC swaps seen by the indexer
+pub static SOL_USDC_TWAP: Lazy<Arc<PoolTwap>> = Lazy::new(|| {
+    Arc::new(PoolTwap::new(Duration::from_secs(DEFAULT_TWAP_WINDOW_SECS)))
+});
+
+/// Time weighted average of the SOL/USDC price of indexed swaps
+pub struct PoolTwap {
+    window: Duration,
+    observations: Mutex<VecDeque<(Instant, f64)>>,
+    notify: Notify,
+}
+
+impl PoolTwap {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            observations: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Records the price of a swap if it is between SOL and USDC
+    pub fn record_swap(&self, transfers: &[TokenTransferDetails]) {
+        if let Some(price) = sol_usdc_price(transfers) {
+            self.record(price);
+        }
+    }
+
+    pub fn record(&self, price: f64) {
+        self.record_at(Instant::now(), price);
+        self.notify.notify_waiters();
+    }
+
+    fn record_at(&self, at: Instant, price: f64) {
+        let mut observations =
+            self.observations.lock().expect("twap lock poisoned");
+        observations.push_back((at, price));
+        // the newest observation before the window still covers its start
+        let window_start = at.checked_sub(self.window).unwrap_or(at);
+        while observations.len() > 1 && observations[1].0 <= window_start {
+            observations.pop_front();
+        }
+    }
+
+    pub fn twap(&self) -> Option<f64> {
+        self.twap_at(Instant::now())
+    }
+
+    fn twap_at(&self, now: Instant) -> Option<f64> {
+        let observations =
+            self.observations.lock().expect("twap lock poisoned");
+        let window_start = now.checked_sub(self.window).unwrap_or(now);
+
+        let mut weighted = 0.0;
+        let mut total = 0.0;
+        for (i, (at, price)) in observations.iter().enumerate() {
+            let start = (*at).max(window_start);
+            let end = observations.get(i + 1).map_or(now, |(next, _)| *next);
+            let weight = end.saturating_duration_since(start).as_secs_f64();
+            weighted += price * weight;
+            total += weight;
+        }
+        match total > 0.0 {
+            true => Some(weighted / total),
+            // a single observation that was just made
+            false => observations.back().map(|(_, price)| *price),
+        }
+    }
+}
+
+/// USDC per SOL of a two token swap, if it swapped SOL for USDC
+pub fn sol_usdc_price(transfers: &[TokenTransferDetails]) -> Option<f64> {
+    let [a, b] = transfers else {
+        return None;
+    };
+    let (sol, usdc) = match (a.mint.as_str(), b.mint.as_str()) {
+        (WSOL_MINT_KEY_STR, USDC_MINT_KEY_STR) => (a, b),
+        (USDC_MINT_KEY_STR, WSOL_MINT_KEY_STR) => (b, a),
+        _ => return None,
+    };
+    if sol.ui_amount < MIN_TWAP_SWAP_SOL {
+        return None;
+    }
+    Some(usdc.ui_amount / sol.ui_amount)
+}
+
+/// Reports the TWAP whenever a new swap comes in, so the quote goes stale
+/// when no SOL/USDC swaps are indexed
+pub struct PoolTwapSource {
+    twap: Arc<PoolTwap>,
+}
+
+impl PoolTwapSource {
+    pub fn new(twap: Arc<PoolTwap>) -> Self {
+        Self { twap }
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceSource for PoolTwapSource {
+    fn name(&self) -> &'static str {
+        "pool_twap"
+    }
+
+    async fn stream(&self, tx: mpsc::UnboundedSender<f64>) -> Result<()> {
+        loop {
+            self.twap.notify.notified().await;
+            if let Some(price) = self.twap.twap() {
+                tx.send(price)?;
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<f64> {
+        self.twap
+            .twap()
+            .ok_or_else(|| anyhow!("no SOL/USDC swaps indexed yet"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(mint: &str, ui_amount: f64) -> TokenTransferDetails {
+        TokenTransferDetails {
+            program_id: String::new(),
+            source: String::new(),
+            destination: String::new(),
+            mint: mint.to_string(),
+            authority: String::new(),
+            decimals: 6,
+            amount: 0,
+            ui_amount,
+        }
+    }
+
+    #[test]
+    fn test_twap_is_time_weighted() {
+        let twap = PoolTwap::new(Duration::from_secs(100));
+        let start = Instant::now();
+        twap.record_at(start, 100.0);
+        twap.record_at(start + Duration::from_secs(75), 200.0);
+        // 75s at 100 and 25s at 200
+        let price = twap.twap_at(start + Duration::from_secs(100)).unwrap();
+        assert_eq!(price, 125.0);
+    }
+
+    #[test]
+    fn test_twap_window() {
+        let twap = PoolTwap::new(Duration::from_secs(100));
+        let start = Instant::now();
+        twap.record_at(start, 100.0);
+        twap.record_at(start + Duration::from_secs(150), 200.0);
+        twap.record_at(start + Duration::from_secs(300), 300.0);
+        // the first observation fell out of the window entirely
+        assert_eq!(twap.observations.lock().unwrap().len(), 2);
+        let price = twap.twap_at(start + Duration::from_secs(350)).unwrap();
+        assert_eq!(price, 250.0);
+    }
+
+    #[test]
+    fn test_twap_single_observation() {
+        let twap = PoolTwap::new(Duration::from_secs(100));
+        assert!(twap.twap().is_none());
+        let now = Instant::now();
+        twap.record_at(now, 150.0);
+        assert_eq!(twap.twap_at(now), Some(150.0));
+    }
+
+    #[test]
+    fn test_sol_usdc_price() {
+        let price = sol_usdc_price(&[
+            transfer(USDC_MINT_KEY_STR, 1500.0),
+            transfer(WSOL_MINT_KEY_STR, 10.0),
+        ]);
+        assert_eq!(price, Some(150.0));
+        // too small to count
+        assert!(sol_usdc_price(&[
+            transfer(WSOL_MINT_KEY_STR, 0.5),
+            transfer(USDC_MINT_KEY_STR, 75.0),
+        ])
+        .is_none());
+        assert!(sol_usdc_price(&[
+            transfer(WSOL_MINT_KEY_STR, 10.0),
+            transfer("mint", 1500.0),
+        ])
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_twap_source_streams_on_swaps() {
+        let twap = Arc::new(PoolTwap::new(Duration::from_secs(100)));
+        let source = PoolTwapSource::new(twap.clone());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move { source.stream(tx).await });
+        tokio::task::yield_now().await;
+
+        twap.record(150.0);
+        assert_eq!(rx.recv().await, Some(150.0));
+    }
+}
@@ -5,12 +5,13 @@ use crate::data::evm_fallback_tools::{
 use crate::evm::tools::{GetErc20Balance, GetEthBalance};
 use crate::solana::tools::{
     DeployPumpFunToken, GetCurrentTime, GetSolBalance, GetSplTokenBalance,
+    PreviewSwap,
 };
 
 use crate::agents::listen::create_deep_research_agent_openrouter;
 use crate::agents::research::ViewImage;
 use crate::common::{openrouter_agent_builder, OpenRouterAgent};
-use crate::cross_chain::tools::{GetQuote, Swap};
+use crate::cross_chain::tools::{GetMultichainPortfolio, GetQuote, Swap};
 use crate::data::{
     AnalyzePageContent, FetchPriceActionAnalysis, FetchTopTokens, FetchXPost,
     GetToken, ResearchXProfile, SearchTweets, SearchWeb,
@@ -65,6 +66,7 @@ pub fn equip_with_tools<M: StreamingCompletionModel>(
         .tool(GetQuote)
         .tool(GetSolBalance)
         .tool(GetSplTokenBalance)
+        .tool(GetMultichainPortfolio)
         .tool(SearchOnDexScreener)
         .tool(FetchTopTokens)
         .tool(DeployPumpFunToken)
@@ -115,7 +117,7 @@ pub fn equip_with_worldchain_tools<M: StreamingCompletionModel>(
 pub fn equip_with_autonomous_tools<M: StreamingCompletionModel>(
     agent_builder: AgentBuilder<M>,
 ) -> AgentBuilder<M> {
-    agent_builder.tool(Swap) // .tool(CreateAdvancedOrder)
+    agent_builder.tool(PreviewSwap).tool(Swap) // .tool(CreateAdvancedOrder)
 }
 
 // TODO ensure that the reserach trader agent has evm tools too
@@ -123,6 +125,21 @@ pub fn create_listen_agent(
     preamble: Option<String>,
     features: Features,
     locale: String,
+) -> OpenRouterAgent {
+    create_listen_agent_with_model(
+        preamble,
+        features,
+        locale,
+        "google/gemini-2.5-flash-preview".to_string(),
+    )
+}
+
+/// `model` is an OpenRouter model, see `model_to_versioned_model`
+pub fn create_listen_agent_with_model(
+    preamble: Option<String>,
+    features: Features,
+    locale: String,
+    model: String,
 ) -> OpenRouterAgent {
     if features.worldchain {
         return create_worldchain_agent(preamble);
@@ -133,10 +150,9 @@ pub fn create_listen_agent(
     if features.deep_research {
         return create_deep_research_agent_openrouter(locale, None);
     }
-    let model = Some("google/gemini-2.5-flash-preview".to_string());
 
     let mut agent = equip_with_evm_tools(equip_with_tools(
-        openrouter_agent_builder(model),
+        openrouter_agent_builder(Some(model)),
     ))
     .preamble(&preamble);
 
@@ -1,17 +1,17 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::{
     common::spawn_with_signer,
-    reasoning_loop::{Model, ReasoningLoop, StreamResponse},
+    reasoning_loop::{Model, ReasoningLoop, StreamResponse, RUN_CANCELLED},
     signer::TransactionSigner,
 };
 use privy::util::base64encode;
 
-// FIXME this has to break as the signal is sent to cancel the request!
-
-/// Delegate a task to a specific agent and handle the response
+/// Delegate a task to a specific agent and handle the response, the agent
+/// is cancelled together with the run that delegated to it and counts
+/// towards its limits
 pub async fn delegate_to_agent(
     prompt: String,
     agent: Model,
@@ -20,7 +20,16 @@ pub async fn delegate_to_agent(
     with_stdout: bool,
     user_id: String,
 ) -> Result<String> {
-    let reasoning_loop = ReasoningLoop::new(agent).with_stdout(with_stdout);
+    let cancel = ReasoningLoop::current_cancellation()
+        .map(|parent| parent.child_token())
+        .unwrap_or_default();
+    let mut reasoning_loop = ReasoningLoop::new(agent)
+        .with_stdout(with_stdout)
+        .with_cancellation(cancel.clone());
+    if let Some(tracker) = ReasoningLoop::current_tracker() {
+        reasoning_loop = reasoning_loop.with_tracker(tracker);
+    }
+    let tracker = reasoning_loop.tracker();
 
     // Get the parent agent's stream channel from the task-local variable
     let parent_tx = ReasoningLoop::get_current_stream_channel().await;
@@ -34,8 +43,13 @@ pub async fn delegate_to_agent(
         while let Some(response) = rx.recv().await {
             res_ptr.write().await.push_str(&response.render());
 
-            // Forward to parent if available, as a NestedAgentOutput
+            // Forward to parent if available, as a NestedAgentOutput,
+            // approvals as they are so the client can answer them
             if let Some(parent_tx) = &parent_tx {
+                if let StreamResponse::ApprovalRequired { .. } = response {
+                    let _ = parent_tx.send(response).await;
+                    continue;
+                }
                 let nested_output = StreamResponse::NestedAgentOutput {
                     agent_type: agent_type.clone(),
                     content: format!(
@@ -66,6 +80,12 @@ pub async fn delegate_to_agent(
     .await;
 
     let _ = tokio::try_join!(reader_handle, loop_handle);
+    if cancel.is_cancelled() {
+        return Err(anyhow!(RUN_CANCELLED));
+    }
+    if let Some(limit) = tracker.report().limit {
+        return Err(anyhow!(limit));
+    }
 
     let response = res.read().await.to_string();
 
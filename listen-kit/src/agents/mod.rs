@@ -3,6 +3,7 @@ pub mod delegate;
 pub mod listen;
 pub mod research;
 pub mod suggester;
+pub mod summarizer;
 pub mod trader;
 pub mod x;
 
@@ -0,0 +1,36 @@
+use crate::common::{gemini_agent_builder, messages_to_string};
+use anyhow::Result;
+use rig::{completion::Prompt, message::Message};
+
+const PROMPT: &str = r#"
+Summarize the earlier part of a conversation between a user and a crypto
+trading assistant, so that the assistant can continue it without the
+original messages.
+Keep every concrete fact: token names, mints and addresses, amounts, prices,
+transaction signatures, the user's preferences and any open requests.
+Drop greetings, chatter and tool call details that led nowhere.
+Write plain prose in the language of the conversation, at most 300 words.
+Provide only the summary, no other text."#;
+
+const MAX_CHARS: usize = 100_000;
+
+/// Folds `messages` into `previous`, the summary of the turns before them
+pub async fn summarize(
+    messages: &[Message],
+    previous: Option<&str>,
+) -> Result<String> {
+    let mut prompt =
+        format!("{}\n\n{}", PROMPT, messages_to_string(messages, MAX_CHARS));
+    if let Some(previous) = previous {
+        prompt = format!(
+            "summary of the conversation so far: {}\n\n{}",
+            previous, prompt
+        );
+    }
+
+    let agent = gemini_agent_builder().build();
+
+    let summary = agent.prompt(Message::user(prompt)).await?;
+
+    Ok(summary.trim().to_string())
+}
@@ -1,6 +1,7 @@
 use crate::{
     agents::delegate::delegate_to_agent,
     common::{gemini_agent_builder, GeminiAgent},
+    cross_chain::tools::GetMultichainPortfolio,
     data::{
         evm_fallback_tools::{
             FetchPriceActionAnalysisEvm, FetchTokenMetadataEvm,
@@ -15,7 +16,7 @@ use crate::{
         advanced_orders::CreateAdvancedOrder,
         tools::{
             AnalyzeRisk, DeployPumpFunToken, GetQuote, GetSolBalance,
-            GetSplTokenBalance, Swap,
+            GetSplTokenBalance, PreviewSwap, Swap,
         },
     },
 };
@@ -52,6 +53,7 @@ pub fn create_trader_agent(locale: String) -> GeminiAgent {
         .tool(GetQuote)
         .tool(DeployPumpFunToken)
         .tool(CreateAdvancedOrder)
+        .tool(PreviewSwap)
         .tool(Swap)
         .tool(FetchTokenMetadata)
         .tool(GetSolBalance)
@@ -61,6 +63,7 @@ pub fn create_trader_agent(locale: String) -> GeminiAgent {
         .tool(AnalyzeRisk)
         .tool(GetEthBalance)
         .tool(GetErc20Balance)
+        .tool(GetMultichainPortfolio)
         .tool(FetchTokenMetadataEvm)
         .tool(FetchPriceActionAnalysisEvm)
         .build()
@@ -1,6 +1,8 @@
 use crate::{
     common::{claude_agent_builder, ClaudeAgent},
-    cross_chain::tools::{ApproveToken, CheckApproval, GetQuote, Swap},
+    cross_chain::tools::{
+        ApproveToken, CheckApproval, GetMultichainPortfolio, GetQuote, Swap,
+    },
     data::{FetchPriceActionAnalysis, FetchTopTokens},
     dexscreener::tools::SearchOnDexScreener,
 };
@@ -15,6 +17,7 @@ pub fn create_cross_chain_agent(preamble: Option<String>) -> ClaudeAgent {
         .tool(Swap)
         .tool(ApproveToken)
         .tool(CheckApproval)
+        .tool(GetMultichainPortfolio)
         .tool(FetchPriceActionAnalysis)
         .tool(FetchTopTokens);
     agent_builder.build()
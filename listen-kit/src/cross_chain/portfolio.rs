@@ -0,0 +1,480 @@
+//! Holdings of a user across Solana and the EVM chains, valued in USD
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+
+use crate::evm::abi::IERC20;
+use crate::evm::transaction::native_symbol;
+use crate::evm::util::make_provider;
+
+use lifi::LiFi;
+
+/// LiFi's id for Solana
+pub const SOLANA_CHAIN_ID: u64 = 1151111081099710;
+
+/// Chains `chain_id_to_rpc_url` has an RPC for
+pub const DEFAULT_EVM_CHAINS: [u64; 5] = [1, 56, 8453, 42161, 480];
+
+/// Addresses LiFi lists native tokens under
+const LIFI_NATIVE_ADDRESSES: [&str; 2] = [
+    "0x0000000000000000000000000000000000000000",
+    "11111111111111111111111111111111",
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Portfolio {
+    /// Sum of the holdings with a known price
+    pub total_usd: f64,
+    /// Most valuable first
+    pub holdings: Vec<PortfolioHolding>,
+    /// Chains that could not be read, left out of the total
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PortfolioHolding {
+    /// "solana" or the CAIP-2 id of the EVM chain
+    pub chain: String,
+    /// Mint or contract address, "native" for SOL and ETH
+    pub address: String,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub decimals: u8,
+    pub raw_amount: String,
+    pub amount: f64,
+    pub price_usd: Option<f64>,
+    pub value_usd: Option<f64>,
+}
+
+/// Balance as read from the chain, before it is valued
+#[derive(Debug, Clone, Default)]
+pub struct Balance {
+    pub chain: String,
+    pub address: String,
+    pub decimals: u8,
+    pub raw_amount: u128,
+    pub symbol: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenInfo {
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+    pub price_usd: Option<f64>,
+}
+
+/// Token metadata and prices by chain and address, EVM addresses
+/// lowercased
+#[derive(Debug, Clone, Default)]
+pub struct TokenList {
+    tokens: HashMap<(String, String), TokenInfo>,
+}
+
+pub fn chain_name(chain_id: u64) -> String {
+    if chain_id == SOLANA_CHAIN_ID {
+        "solana".to_string()
+    } else {
+        format!("eip155:{}", chain_id)
+    }
+}
+
+fn normalize(address: &str) -> String {
+    if LIFI_NATIVE_ADDRESSES.contains(&address) {
+        "native".to_string()
+    } else if address.starts_with("0x") {
+        address.to_lowercase()
+    } else {
+        address.to_string()
+    }
+}
+
+impl TokenList {
+    /// LiFi's token list for the chains, with a USD price for most tokens
+    pub async fn fetch(chain_ids: &[u64]) -> Result<Self> {
+        let lifi = LiFi::new(
+            std::env::var("LIFI_API_KEY").ok(),
+            Some("listen".to_string()),
+        );
+        let chains = chain_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let response =
+            lifi.get_tokens(&chains, None, None).await.map_err(|e| {
+                anyhow!("Failed to fetch the LiFi token list: {}", e)
+            })?;
+
+        let mut list = Self::default();
+        for (chain_id, tokens) in response.tokens {
+            let chain = chain_name(chain_id.parse()?);
+            for token in tokens {
+                list.insert(
+                    &chain,
+                    &token.address,
+                    TokenInfo {
+                        symbol: token.symbol,
+                        name: token.name,
+                        decimals: token.decimals.as_u64().unwrap_or_default()
+                            as u8,
+                        price_usd: token
+                            .price_usd
+                            .and_then(|p| p.parse::<f64>().ok()),
+                    },
+                );
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn insert(&mut self, chain: &str, address: &str, info: TokenInfo) {
+        self.tokens
+            .insert((chain.to_string(), normalize(address)), info);
+    }
+
+    pub fn get(&self, chain: &str, address: &str) -> Option<&TokenInfo> {
+        self.tokens.get(&(chain.to_string(), normalize(address)))
+    }
+}
+
+/// Values the balances, `solana_prices` are listen-adapter prices by mint
+/// and take precedence over the token list for Solana tokens
+pub fn value_balances(
+    balances: Vec<Balance>,
+    tokens: &TokenList,
+    solana_prices: &HashMap<String, f64>,
+) -> Portfolio {
+    let mut holdings = balances
+        .into_iter()
+        .filter(|b| b.raw_amount > 0)
+        .map(|balance| {
+            let info = tokens.get(&balance.chain, &balance.address);
+            let price_usd = solana_prices
+                .get(&balance.address)
+                .copied()
+                .or_else(|| info.and_then(|i| i.price_usd));
+            let amount = balance.raw_amount as f64
+                / 10f64.powi(balance.decimals as i32);
+            PortfolioHolding {
+                symbol: balance
+                    .symbol
+                    .or_else(|| info.map(|i| i.symbol.clone())),
+                name: info.map(|i| i.name.clone()),
+                decimals: balance.decimals,
+                raw_amount: balance.raw_amount.to_string(),
+                amount,
+                price_usd,
+                value_usd: price_usd.map(|p| p * amount),
+                chain: balance.chain,
+                address: balance.address,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    holdings.sort_by(|a, b| {
+        b.value_usd
+            .unwrap_or(-1.0)
+            .total_cmp(&a.value_usd.unwrap_or(-1.0))
+    });
+
+    Portfolio {
+        total_usd: holdings.iter().filter_map(|h| h.value_usd).sum(),
+        holdings,
+        errors: Vec::new(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AlchemyTokenBalances {
+    token_balances: Vec<AlchemyTokenBalance>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AlchemyTokenBalance {
+    contract_address: String,
+    token_balance: Option<String>,
+}
+
+/// Native and ERC20 balances of the owner, the ERC20s are enumerated
+/// through Alchemy's `alchemy_getTokenBalances`
+pub async fn evm_balances(
+    chain_id: u64,
+    owner: Address,
+    tokens: &TokenList,
+) -> Result<Vec<Balance>> {
+    let chain = chain_name(chain_id);
+    let provider = make_provider(chain_id)?;
+
+    let native = provider.get_balance(owner).await?;
+    let mut balances = vec![Balance {
+        chain: chain.clone(),
+        address: "native".to_string(),
+        decimals: 18,
+        raw_amount: native.saturating_to::<u128>(),
+        symbol: Some(native_symbol(chain_id).to_string()),
+    }];
+
+    let response: AlchemyTokenBalances = provider
+        .raw_request("alchemy_getTokenBalances".into(), (owner, "erc20"))
+        .await?;
+    let held = response.token_balances.into_iter().filter_map(|b| {
+        let raw = U256::from_str(b.token_balance.as_deref()?).ok()?;
+        (!raw.is_zero()).then_some((b.contract_address, raw))
+    });
+
+    let balances_futures = held.map(|(address, raw)| {
+        let provider = &provider;
+        let chain = &chain;
+        async move {
+            let (decimals, symbol) = match tokens.get(chain, &address) {
+                Some(info) => (info.decimals, info.symbol.clone()),
+                None => {
+                    let erc20 = IERC20::new(
+                        Address::from_str(&address).ok()?,
+                        provider,
+                    );
+                    (
+                        erc20.decimals().call().await.ok()?._0,
+                        erc20.symbol().call().await.ok()?._0,
+                    )
+                }
+            };
+            Some(Balance {
+                chain: chain.clone(),
+                address,
+                decimals,
+                raw_amount: raw.saturating_to::<u128>(),
+                symbol: Some(symbol),
+            })
+        }
+    });
+    balances.extend(join_all(balances_futures).await.into_iter().flatten());
+
+    Ok(balances)
+}
+
+/// Native SOL and every SPL and Token-2022 holding of the owner
+#[cfg(feature = "solana")]
+pub async fn solana_balances(owner: &str) -> Result<Vec<Balance>> {
+    use solana_sdk::pubkey::Pubkey;
+
+    let owner = Pubkey::from_str(owner)?;
+    let rpc = crate::solana::util::make_rpc_client();
+    let native = rpc.get_balance(&owner).await?;
+    let holdings = crate::solana::balance::get_holdings(&rpc, &owner).await?;
+
+    let mut balances = vec![Balance {
+        chain: chain_name(SOLANA_CHAIN_ID),
+        address: "native".to_string(),
+        decimals: 9,
+        raw_amount: native as u128,
+        symbol: Some("SOL".to_string()),
+    }];
+    balances.extend(holdings.into_iter().map(|holding| Balance {
+        chain: chain_name(SOLANA_CHAIN_ID),
+        address: holding.mint,
+        decimals: holding.decimals,
+        raw_amount: holding.amount as u128,
+        symbol: None,
+    }));
+    Ok(balances)
+}
+
+/// listen-adapter prices and symbols of the Solana tokens, the adapter
+/// indexes the DEX trades most memecoins only trade on
+async fn solana_prices(
+    balances: &mut [Balance],
+    tokens: &TokenList,
+) -> HashMap<String, f64> {
+    use crate::data::listen_api_tools::LISTEN_API;
+
+    let lookups = balances
+        .iter_mut()
+        .filter(|b| b.chain == chain_name(SOLANA_CHAIN_ID))
+        .filter(|b| b.address != "native")
+        .map(|balance| async move {
+            let price = LISTEN_API.price(&balance.address).await.ok();
+            if balance.symbol.is_none()
+                && tokens.get(&balance.chain, &balance.address).is_none()
+            {
+                if let Ok(Some(metadata)) =
+                    LISTEN_API.metadata(&balance.address).await
+                {
+                    balance.symbol = Some(metadata.mpl.symbol);
+                }
+            }
+            price.map(|p| (balance.address.clone(), p.price))
+        });
+    join_all(lookups).await.into_iter().flatten().collect()
+}
+
+/// Configured through `PORTFOLIO_EVM_CHAINS`, comma separated chain ids
+pub fn evm_chains() -> Vec<u64> {
+    std::env::var("PORTFOLIO_EVM_CHAINS")
+        .ok()
+        .map(|chains| {
+            chains
+                .split(',')
+                .filter_map(|c| c.trim().parse::<u64>().ok())
+                .collect()
+        })
+        .unwrap_or_else(|| DEFAULT_EVM_CHAINS.to_vec())
+}
+
+/// Reads every chain the user has a wallet on, a chain that fails is
+/// reported in `errors` rather than failing the whole portfolio
+pub async fn get_portfolio(
+    solana_owner: Option<String>,
+    evm_owner: Option<String>,
+) -> Result<Portfolio> {
+    let evm_owner = evm_owner.map(|a| Address::from_str(&a)).transpose()?;
+    let evm_chains = if evm_owner.is_some() {
+        evm_chains()
+    } else {
+        Vec::new()
+    };
+
+    let mut chain_ids = evm_chains.clone();
+    if solana_owner.is_some() {
+        chain_ids.push(SOLANA_CHAIN_ID);
+    }
+    let tokens = TokenList::fetch(&chain_ids).await.unwrap_or_else(|e| {
+        tracing::warn!("{}", e);
+        TokenList::default()
+    });
+
+    let mut balances = Vec::new();
+    let mut errors = Vec::new();
+
+    if let Some(owner) = evm_owner {
+        let results = join_all(
+            evm_chains
+                .iter()
+                .map(|chain_id| evm_balances(*chain_id, owner, &tokens)),
+        )
+        .await;
+        for (chain_id, result) in evm_chains.iter().zip(results) {
+            match result {
+                Ok(b) => balances.extend(b),
+                Err(e) => {
+                    errors.push(format!("{}: {}", chain_name(*chain_id), e))
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "solana")]
+    {
+        if let Some(owner) = &solana_owner {
+            match solana_balances(owner).await {
+                Ok(b) => balances.extend(b),
+                Err(e) => errors.push(format!("solana: {}", e)),
+            }
+        }
+    }
+
+    let prices = solana_prices(&mut balances, &tokens).await;
+    let mut portfolio = value_balances(balances, &tokens, &prices);
+    portfolio.errors = errors;
+    Ok(portfolio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_balances() {
+        let mut tokens = TokenList::default();
+        tokens.insert(
+            "eip155:8453",
+            "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913",
+            TokenInfo {
+                symbol: "USDC".to_string(),
+                name: "USD Coin".to_string(),
+                decimals: 6,
+                price_usd: Some(1.0),
+            },
+        );
+        tokens.insert(
+            "solana",
+            "11111111111111111111111111111111",
+            TokenInfo {
+                symbol: "SOL".to_string(),
+                name: "SOL".to_string(),
+                decimals: 9,
+                price_usd: Some(150.0),
+            },
+        );
+        let bonk = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+        let prices = HashMap::from([(bonk.to_string(), 0.5)]);
+
+        let balances = vec![
+            Balance {
+                chain: "eip155:8453".to_string(),
+                address: "0x833589fcd6edb6e08f4c7c32d4f71b54bda02913"
+                    .to_string(),
+                decimals: 6,
+                raw_amount: 25_000_000,
+                symbol: None,
+            },
+            Balance {
+                chain: "solana".to_string(),
+                address: "native".to_string(),
+                decimals: 9,
+                raw_amount: 2_000_000_000,
+                symbol: Some("SOL".to_string()),
+            },
+            Balance {
+                chain: "solana".to_string(),
+                address: bonk.to_string(),
+                decimals: 5,
+                raw_amount: 4_000_000,
+                symbol: Some("Bonk".to_string()),
+            },
+            Balance {
+                chain: "solana".to_string(),
+                address: "unpriced".to_string(),
+                decimals: 0,
+                raw_amount: 5,
+                symbol: None,
+            },
+            Balance {
+                chain: "eip155:1".to_string(),
+                address: "native".to_string(),
+                decimals: 18,
+                raw_amount: 0,
+                symbol: Some("ETH".to_string()),
+            },
+        ];
+
+        let portfolio = value_balances(balances, &tokens, &prices);
+        let symbols = portfolio
+            .holdings
+            .iter()
+            .map(|h| h.symbol.as_deref().unwrap_or("?"))
+            .collect::<Vec<_>>();
+        assert_eq!(symbols, vec!["SOL", "USDC", "Bonk", "?"]);
+        assert_eq!(portfolio.holdings[1].name.as_deref(), Some("USD Coin"));
+        assert_eq!(portfolio.holdings[2].value_usd, Some(20.0));
+        assert_eq!(portfolio.holdings[3].value_usd, None);
+        assert_eq!(portfolio.total_usd, 345.0);
+    }
+
+    #[test]
+    fn test_evm_chains() {
+        std::env::set_var("PORTFOLIO_EVM_CHAINS", "8453, 42161");
+        assert_eq!(evm_chains(), vec![8453, 42161]);
+        std::env::remove_var("PORTFOLIO_EVM_CHAINS");
+        assert_eq!(evm_chains(), DEFAULT_EVM_CHAINS.to_vec());
+    }
+}
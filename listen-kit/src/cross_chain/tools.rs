@@ -1,7 +1,8 @@
 use std::str::FromStr;
 use std::sync::Arc;
 
-use alloy::primitives::FixedBytes;
+use alloy::network::TransactionBuilder;
+use alloy::primitives::{Address, Bytes, FixedBytes, U256};
 use alloy::providers::PendingTransactionConfig;
 use alloy::providers::Provider;
 use anyhow::{anyhow, Result};
@@ -10,9 +11,12 @@ use evm_approvals::MAX_APPROVAL_AMOUNT_0X;
 use rig_tool_macro::tool;
 
 use crate::common::wrap_unsafe;
+use crate::cross_chain::portfolio::{get_portfolio, Portfolio};
 use crate::ensure_evm_wallet_created;
 use crate::ensure_solana_wallet_created;
 use crate::evm::util::make_provider;
+use crate::signer::approval::TxIntent;
+use crate::signer::preview::TxPreview;
 use crate::signer::SignerContext;
 use crate::signer::TransactionSigner;
 
@@ -249,34 +253,83 @@ pub async fn swap(
             )
         })?;
 
+    let intent = TxIntent::new(format!(
+        "Swap {} of {} on chain {} for {} on chain {}",
+        amount, from_token_address, from_chain, to_token_address, to_chain
+    ))
+    .with_usd_value(
+        quote
+            .estimate
+            .from_amount_usd
+            .as_ref()
+            .and_then(|usd| usd.parse::<f64>().ok()),
+    )
+    .with_token(from_token_address.clone())
+    .with_token(to_token_address.clone());
+    let quote_summary = quote.summary();
+
     match quote.transaction_request {
         Some(transaction_request) => {
-            wrap_unsafe(move || async move {
-                if transaction_request.is_solana() {
-                    let latest_blockhash =
-                        BLOCKHASH_CACHE.get_blockhash().await?.to_string();
-                    let encoded_tx = inject_blockhash_into_encoded_tx(
-                        &transaction_request.data,
-                        &latest_blockhash,
-                    )?;
-                    signer
-                        .sign_and_send_encoded_solana_transaction(encoded_tx)
-                        .await
-                } else {
-                    ensure_lifi_router_approvals(
-                        signer.clone(),
-                        from_token_address,
-                        amount,
-                        from_chain.parse::<u64>().map_err(|e| anyhow!(e))?,
-                    )
-                    .await?;
-                    signer
-                        .sign_and_send_json_evm_transaction(
-                            transaction_request.to_json_rpc()?,
-                            None,
-                        )
-                        .await
+            let preview = simulate_lifi_transaction(
+                &transaction_request,
+                &quote.action.from_token.address,
+                signer.clone(),
+            )
+            .await?;
+            if let Some(preview) = &preview {
+                if !preview.success {
+                    return Err(anyhow!(
+                        "Transaction simulation failed, not sending it: {}",
+                        preview.summary()
+                    ));
                 }
+            }
+            let tx_preview = serde_json::json!({
+                "quote": quote_summary,
+                "transaction": transaction_request,
+                "preview": preview,
+            });
+            let intent = intent.with_preview(preview.as_ref());
+            signer.request_approval(intent.clone(), tx_preview).await?;
+
+            wrap_unsafe(move || {
+                intent.scope(async move {
+                    if transaction_request.is_solana() {
+                        let latest_blockhash = BLOCKHASH_CACHE
+                            .get_blockhash()
+                            .await?
+                            .to_string();
+                        let encoded_tx = inject_blockhash_into_encoded_tx(
+                            &transaction_request.data,
+                            &latest_blockhash,
+                        )?;
+                        signer
+                            .sign_and_send_encoded_solana_transaction(
+                                encoded_tx,
+                            )
+                            .await
+                    } else {
+                        ensure_lifi_router_approvals(
+                            signer.clone(),
+                            transaction_request
+                                .to
+                                .as_deref()
+                                .unwrap_or_default(),
+                            from_token_address,
+                            amount,
+                            from_chain
+                                .parse::<u64>()
+                                .map_err(|e| anyhow!(e))?,
+                        )
+                        .await?;
+                        signer
+                            .sign_and_send_json_evm_transaction(
+                                transaction_request.to_json_rpc()?,
+                                None,
+                            )
+                            .await
+                    }
+                })
             })
             .await
         }
@@ -284,6 +337,25 @@ pub async fn swap(
     }
 }
 
+#[tool(description = "
+Returns everything the user holds on Solana and on the supported EVM chains
+(mainnet, bsc, base, arbitrum, worldchain): native and token balances with
+their USD price and value, most valuable first, and the total USD value.
+
+Use this for questions about the whole portfolio, like what it is worth,
+instead of checking balances token by token and chain by chain
+")]
+pub async fn get_multichain_portfolio() -> Result<Portfolio> {
+    let signer = SignerContext::current().await;
+    let (pubkey, address) = (signer.pubkey(), signer.address());
+    if pubkey.is_none() && address.is_none() {
+        return Err(anyhow!("Wallet unavailable"));
+    }
+
+    wrap_unsafe(move || async move { get_portfolio(pubkey, address).await })
+        .await
+}
+
 #[tool(description = "
 Check if a token has enough approval for a spender.
 
@@ -338,26 +410,137 @@ pub async fn approve_token(
     )
     .await?;
 
-    wrap_unsafe(move || async move {
-        signer
-            .sign_and_send_json_evm_transaction(transaction, None)
-            .await
-            .map_err(|e| anyhow!(e.to_string()))
+    let intent = TxIntent::new(format!(
+        "Approve {} to spend {} on {}",
+        spender_address, token_address, from_chain_caip2
+    ))
+    .with_approval(spender_address.clone())
+    .with_token(token_address);
+    signer
+        .request_approval(
+            intent.clone(),
+            serde_json::json!({
+                "chain": from_chain_caip2,
+                "transaction": transaction,
+            }),
+        )
+        .await?;
+
+    wrap_unsafe(move || {
+        intent.scope(async move {
+            signer
+                .sign_and_send_json_evm_transaction(transaction, None)
+                .await
+                .map_err(|e| anyhow!(e.to_string()))
+        })
     })
     .await?;
 
     Ok("Approved".to_string())
 }
 
+/// Simulates the source chain transaction of a LiFi quote
+///
+/// EVM swaps from an ERC20 are not simulated, the router allowance they
+/// rely on is only set right before sending
+async fn simulate_lifi_transaction(
+    transaction_request: &lifi::quote::TransactionRequest,
+    from_token_address: &str,
+    signer: Arc<dyn TransactionSigner>,
+) -> Result<Option<TxPreview>> {
+    if std::env::var("SKIP_SIMULATION").is_ok() {
+        return Ok(None);
+    }
+
+    if transaction_request.is_solana() {
+        #[cfg(feature = "solana")]
+        {
+            use base64::prelude::BASE64_STANDARD;
+            use base64::Engine;
+            use solana_sdk::transaction::{
+                Transaction, VersionedTransaction,
+            };
+
+            let owner = solana_sdk::pubkey::Pubkey::from_str(
+                &signer
+                    .pubkey()
+                    .ok_or_else(|| anyhow!("Wallet unavailable"))?,
+            )?;
+            let bytes = BASE64_STANDARD.decode(&transaction_request.data)?;
+            let tx =
+                match bincode::deserialize::<VersionedTransaction>(&bytes) {
+                    Ok(tx) => tx,
+                    Err(_) => {
+                        bincode::deserialize::<Transaction>(&bytes)?.into()
+                    }
+                };
+            return wrap_unsafe(move || async move {
+                crate::solana::transaction::simulate_transaction(&tx, &owner)
+                    .await
+                    .map(Some)
+            })
+            .await;
+        }
+        #[cfg(not(feature = "solana"))]
+        return Ok(None);
+    }
+
+    if Address::from_str(from_token_address).ok() != Some(Address::ZERO) {
+        return Ok(None);
+    }
+    let chain_id = transaction_request
+        .chain_id
+        .as_ref()
+        .and_then(|c| c.as_u64())
+        .ok_or_else(|| anyhow!("No chain id in the transaction request"))?;
+    let owner = Address::from_str(
+        &signer
+            .address()
+            .ok_or_else(|| anyhow!("Wallet unavailable"))?,
+    )?;
+    let to = transaction_request
+        .to
+        .as_deref()
+        .ok_or_else(|| anyhow!("No recipient in the transaction request"))?;
+    let tx = alloy::rpc::types::TransactionRequest::default()
+        .with_from(owner)
+        .with_to(Address::from_str(to)?)
+        .with_input(Bytes::from_str(&transaction_request.data)?)
+        .with_value(U256::from_str(
+            transaction_request.value.as_deref().unwrap_or("0x0"),
+        )?)
+        .with_chain_id(chain_id);
+
+    wrap_unsafe(move || async move {
+        let provider = make_provider(chain_id)?;
+        crate::evm::transaction::preview_transaction(
+            &provider, &tx, owner, chain_id,
+        )
+        .await
+        .map(Some)
+    })
+    .await
+}
+
 pub const LIFI_DIAMOND_ADDRESS: &str =
     "0x1231DEB6f5749EF6cE6943a275A1D3E7486F4EaE";
 
+/// Approves the LiFi router to spend the token ahead of a swap the user
+/// already approved, without asking again, so `spender` (what the swap
+/// transaction calls) has to be the router
 pub async fn ensure_lifi_router_approvals(
     signer: Arc<dyn TransactionSigner>,
+    spender: &str,
     token_address: String,
     amount: String,
     chain_id: u64,
 ) -> Result<()> {
+    if !spender.eq_ignore_ascii_case(LIFI_DIAMOND_ADDRESS) {
+        return Err(anyhow!(
+            "The swap goes to {} instead of the LiFi router, not approving it",
+            spender
+        ));
+    }
     if signer.address().is_none() {
         return Err(anyhow!(
             "No address found, it is required for EVM actions!"
@@ -383,13 +566,21 @@ pub async fn ensure_lifi_router_approvals(
             &chain_id.to_string(),
         )
         .await?;
-        let tx_hash = signer
-            .sign_and_send_json_evm_transaction(
-                transaction,
-                Some(format!("eip155:{}", chain_id)),
-            )
-            .await
-            .map_err(|e| anyhow!(e.to_string()))?;
+        // signed under its own intent, so the user is asked about it
+        // separately and the swap it precedes is what counts towards the
+        // spending limits
+        let tx_hash = TxIntent::new(format!(
+            "Approve the LiFi router to spend {}",
+            token_address
+        ))
+        .with_approval(LIFI_DIAMOND_ADDRESS)
+        .with_token(token_address.clone())
+        .scope(signer.sign_and_send_json_evm_transaction(
+            transaction,
+            Some(format!("eip155:{}", chain_id)),
+        ))
+        .await
+        .map_err(|e| anyhow!(e.to_string()))?;
         let provider = make_provider(chain_id)?;
         provider
             .watch_pending_transaction(PendingTransactionConfig::new(
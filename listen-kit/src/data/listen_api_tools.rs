@@ -6,18 +6,14 @@ use crate::{
     common::spawn_with_signer_and_channel, solana::util::validate_mint,
 };
 use anyhow::{anyhow, Result};
+use listen_adapter_client::{
+    CandlestickParams, ListenAdapterClient, TopTokensParams,
+};
+use once_cell::sync::Lazy;
 use rig_tool_macro::tool;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Candlestick {
-    pub timestamp: u64,
-    pub open: f64,
-    pub high: f64,
-    pub low: f64,
-    pub close: f64,
-    pub volume: f64,
-}
+pub use listen_adapter_client::Candlestick;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PriceTick {
@@ -64,7 +60,9 @@ pub struct PriceActionAnalysisResponse {
     pub low: f64,
 }
 
-pub const LISTEN_API_BASE: &str = "https://api.listen-rs.com/v1/adapter";
+/// `LISTEN_ADAPTER_URL` overrides the hosted API
+pub static LISTEN_API: Lazy<ListenAdapterClient> =
+    Lazy::new(ListenAdapterClient::from_env);
 
 #[tool(description = "
 Fetch token metadata for any Solana token from the Listen API. This is the metadata that was
@@ -82,15 +80,13 @@ It returns metadata that includes:
 pub async fn fetch_token_metadata(mint: String) -> Result<serde_json::Value> {
     validate_mint(&mint)?;
 
-    let response =
-        reqwest::get(format!("{}/metadata?mint={}", LISTEN_API_BASE, mint))
-            .await
-            .map_err(|e| anyhow!("Failed to fetch token metadata: {}", e))?;
-
-    response
-        .json::<serde_json::Value>()
+    let metadata = LISTEN_API
+        .metadata(&mint)
         .await
-        .map_err(|e| anyhow!("Failed to parse JSON {}", e))
+        .map_err(|e| anyhow!("Failed to fetch token metadata: {}", e))?
+        .ok_or_else(|| anyhow!("No metadata found for token: {}", mint))?;
+
+    Ok(serde_json::to_value(metadata)?)
 }
 
 #[tool(description = "
@@ -104,22 +100,12 @@ Returns the price of the token in USD.
 pub async fn fetch_token_price(mint: String) -> Result<f64> {
     validate_mint(&mint)?;
 
-    let response =
-        reqwest::get(format!("{}/price?mint={}", LISTEN_API_BASE, mint))
-            .await
-            .map_err(|e| anyhow!("Failed to fetch token price: {}", e))?;
-
-    let data = response
-        .json::<serde_json::Value>()
+    let price = LISTEN_API
+        .price(&mint)
         .await
-        .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
-
-    let price = match data["price"].as_f64() {
-        Some(price) => price,
-        None => return Err(anyhow!("Failed to parse price: {}", data)),
-    };
+        .map_err(|e| anyhow!("Failed to fetch token price: {}", e))?;
 
-    Ok(price)
+    Ok(price.price)
 }
 
 #[tool(description = "
@@ -143,36 +129,38 @@ pub async fn fetch_top_tokens(
     max_market_cap: Option<String>,
     timeframe: Option<String>,
 ) -> Result<Vec<TopToken>> {
-    let limit = limit.unwrap_or("4".to_string());
-    let min_market_cap = min_market_cap.unwrap_or("1000000".to_string());
-    let max_market_cap = max_market_cap.unwrap_or("0".to_string());
-    let timeframe = timeframe.unwrap_or("7200".to_string());
-
-    let mut url = format!("{}/top-tokens", LISTEN_API_BASE);
-    let mut query_params = vec![];
-
-    query_params.push(format!("limit={}", limit));
-    query_params.push(format!("min_market_cap={}", min_market_cap));
-    if max_market_cap != "0" {
-        query_params.push(format!("max_market_cap={}", max_market_cap));
-    }
-    query_params.push(format!("timeframe={}", timeframe));
-    query_params.push("only_pumpfun_tokens=true".to_string());
-
-    if !query_params.is_empty() {
-        url = format!("{}?{}", url, query_params.join("&"));
-    }
+    let params = TopTokensParams {
+        limit: Some(limit.as_deref().unwrap_or("4").parse()?),
+        min_market_cap: Some(
+            min_market_cap.as_deref().unwrap_or("1000000").parse()?,
+        ),
+        max_market_cap: match max_market_cap.as_deref() {
+            None | Some("0") => None,
+            Some(max_market_cap) => Some(max_market_cap.parse()?),
+        },
+        timeframe: Some(timeframe.as_deref().unwrap_or("7200").parse()?),
+        only_pumpfun_tokens: Some(true),
+        ..Default::default()
+    };
 
-    let response = reqwest::get(&url)
+    let tokens = LISTEN_API
+        .top_tokens(&params)
         .await
         .map_err(|e| anyhow!("Failed to fetch top tokens: {}", e))?;
 
-    let tokens = response
-        .json::<Vec<TopToken>>()
-        .await
-        .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
-
-    Ok(tokens)
+    Ok(tokens
+        .into_iter()
+        .map(|token| TopToken {
+            name: token.name,
+            pubkey: token.pubkey,
+            price: token.price,
+            market_cap: token.market_cap,
+            volume_24h: token.volume_24h,
+            price_change_24h: token.price_change_24h,
+            chain_id: None,
+            pools: vec![],
+        })
+        .collect())
 }
 
 #[tool(description = "
@@ -305,17 +293,12 @@ pub async fn fetch_candlesticks(
     mint: String,
     interval: String,
 ) -> Result<Vec<Candlestick>> {
-    let url = format!(
-        "{}/candlesticks?mint={}&interval={}",
-        LISTEN_API_BASE, mint, interval
-    );
-
-    let response = reqwest::get(&url)
-        .await
-        .map_err(|e| anyhow!("Failed to fetch candlesticks: {}", e))?;
-
-    response
-        .json::<Vec<Candlestick>>()
+    LISTEN_API
+        .candlesticks(&CandlestickParams {
+            mint,
+            interval,
+            ..Default::default()
+        })
         .await
-        .map_err(|e| anyhow!("Failed to parse response: {}", e))
+        .map_err(|e| anyhow!("Failed to fetch candlesticks: {}", e))
 }
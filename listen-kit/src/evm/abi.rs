@@ -8,5 +8,15 @@ sol! {
         function approve(address spender, uint256 amount) external returns (bool);
         function balanceOf(address owner) external view returns (uint256);
         function decimals() external view returns (uint8);
+        function symbol() external view returns (string);
+
+        event Transfer(address indexed from, address indexed to, uint256 value);
+    }
+}
+
+sol! {
+    /// SwapRouter02's multicall that reverts once the deadline has passed
+    interface IMulticallExtended {
+        function multicall(uint256 deadline, bytes[] data) external payable returns (bytes[] memory results);
     }
 }
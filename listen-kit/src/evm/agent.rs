@@ -1,6 +1,7 @@
 use super::tools::{
-    ApproveTokenForRouterSpend, GetErc20Balance, GetEthBalance, Trade,
-    TransferErc20, TransferEth, VerifySwapRouterHasAllowance, WalletAddress,
+    ApproveTokenForRouterSpend, GetErc20Balance, GetEthBalance, PreviewTrade,
+    Trade, TransferErc20, TransferEth, VerifySwapRouterHasAllowance,
+    WalletAddress,
 };
 use crate::common::{claude_agent_builder, ClaudeAgent};
 
@@ -9,6 +10,7 @@ pub fn create_evm_agent(preamble: Option<String>) -> ClaudeAgent {
         preamble.unwrap_or("you are an ethereum trading agent".to_string());
     claude_agent_builder()
         .preamble(&preamble)
+        .tool(PreviewTrade)
         .tool(Trade)
         .tool(TransferEth)
         .tool(TransferErc20)
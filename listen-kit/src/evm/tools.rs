@@ -1,6 +1,5 @@
 use std::str::FromStr;
 
-use alloy::primitives::utils::parse_ether;
 use alloy::primitives::Address;
 use alloy::providers::Provider;
 use anyhow::{Context, Result};
@@ -9,10 +8,14 @@ use rig_tool_macro::tool;
 use uniswap_sdk_core::prelude::SWAP_ROUTER_02_ADDRESSES;
 
 use crate::common::wrap_unsafe;
+use crate::signer::approval::TxIntent;
 use crate::signer::SignerContext;
 
 use super::balance::{balance, token_balance};
-use super::trade::{check_allowance, create_approve_tx, create_trade_tx};
+use super::trade::{
+    check_allowance, create_approve_tx, create_trade_tx, TradeOptions,
+};
+use super::transaction::preview_transaction;
 use super::transfer::{create_transfer_erc20_tx, create_transfer_eth_tx};
 use super::util::{execute_evm_transaction, make_provider};
 use crate::ensure_evm_wallet_created;
@@ -66,22 +69,35 @@ pub async fn approve_token_for_router_spend(
     })
     .await?;
 
-    execute_evm_transaction(move |owner| async move {
-        create_approve_tx(
-            input_token_address,
-            router_address.to_string(),
-            owner.to_string(),
-            &provider,
-        )
+    let intent = TxIntent::new(format!(
+        "Approve the Uniswap router to spend {} on chain {}",
+        input_token_address, chain_id
+    ))
+    .with_approval(router_address.to_string())
+    .with_token(input_token_address.clone());
+
+    intent
+        .scope(execute_evm_transaction(move |owner| async move {
+            create_approve_tx(
+                input_token_address,
+                router_address.to_string(),
+                owner.to_string(),
+                &provider,
+            )
+            .await
+        }))
         .await
-    })
-    .await
 }
 
 #[tool(description = "
 Use this function to swap any tokens on EVM using Uniswap
 
-The function supports tokens that are on the same chain
+The function supports tokens that are on the same chain, the best route is
+picked across all fee tiers and two-hop paths through the wrapped native
+token or USDC
+
+input_amount is either a raw amount or a decimal one like "1.5", which is
+scaled by the input token's decimals
 ")]
 pub async fn trade(
     input_token_address: String,
@@ -89,22 +105,67 @@ pub async fn trade(
     output_token_address: String,
     chain_id: String,
 ) -> Result<String> {
-    let input_amount = if input_amount.contains('.') {
-        parse_ether(&input_amount)?.to_string()
-    } else {
-        input_amount
-    };
-    execute_evm_transaction(move |owner| async move {
-        create_trade_tx(
+    let intent = TxIntent::new(format!(
+        "Swap {} of {} for {} on chain {}",
+        input_amount, input_token_address, output_token_address, chain_id
+    ))
+    .with_token(input_token_address.clone())
+    .with_token(output_token_address.clone());
+
+    intent
+        .scope(execute_evm_transaction(move |owner| async move {
+            create_trade_tx(
+                input_token_address,
+                input_amount,
+                output_token_address,
+                &make_provider(chain_id.parse::<u64>()?)?,
+                owner,
+                TradeOptions::from_env(),
+            )
+            .await
+        }))
+        .await
+}
+
+#[tool(description = "
+Simulates a Uniswap trade without sending it, returning what the wallet
+would send and receive, the gas fee and a one line summary of it.
+
+Params are the same as for the Trade tool
+
+Use it to show the user what a trade will do before performing it, if the
+simulation fails the trade would fail too
+")]
+pub async fn preview_trade(
+    input_token_address: String,
+    input_amount: String,
+    output_token_address: String,
+    chain_id: String,
+) -> Result<serde_json::Value> {
+    let signer = SignerContext::current().await;
+    ensure_evm_wallet_created(signer.clone()).await?;
+    let owner = Address::from_str(&signer.address().unwrap())?;
+
+    let preview = wrap_unsafe(move || async move {
+        let chain_id = chain_id.parse::<u64>()?;
+        let provider = make_provider(chain_id)?;
+        let tx = create_trade_tx(
             input_token_address,
             input_amount,
             output_token_address,
-            &make_provider(chain_id.parse::<u64>()?)?,
+            &provider,
             owner,
+            TradeOptions::from_env(),
         )
-        .await
+        .await?;
+        preview_transaction(&provider, &tx, owner, chain_id).await
     })
-    .await
+    .await?;
+
+    Ok(serde_json::json!({
+        "summary": preview.summary(),
+        "preview": preview,
+    }))
 }
 
 #[tool(description = "
@@ -120,16 +181,23 @@ pub async fn transfer_eth(
     amount: String,
     chain_id: String,
 ) -> Result<String> {
-    execute_evm_transaction(move |owner| async move {
-        create_transfer_eth_tx(
-            recipient,
-            amount,
-            &make_provider(chain_id.parse::<u64>()?)?,
-            owner,
-        )
+    let intent = TxIntent::new(format!(
+        "Transfer {} ETH to {} on chain {}",
+        amount, recipient, chain_id
+    ))
+    .with_destination(recipient.clone());
+
+    intent
+        .scope(execute_evm_transaction(move |owner| async move {
+            create_transfer_eth_tx(
+                recipient,
+                amount,
+                &make_provider(chain_id.parse::<u64>()?)?,
+                owner,
+            )
+            .await
+        }))
         .await
-    })
-    .await
 }
 
 #[tool(description = "
@@ -146,17 +214,25 @@ pub async fn transfer_erc20(
     amount: String,
     chain_id: String,
 ) -> Result<String> {
-    execute_evm_transaction(move |owner| async move {
-        create_transfer_erc20_tx(
-            token_address,
-            recipient,
-            amount,
-            &make_provider(chain_id.parse::<u64>()?)?,
-            owner,
-        )
+    let intent = TxIntent::new(format!(
+        "Transfer {} of {} to {} on chain {}",
+        amount, token_address, recipient, chain_id
+    ))
+    .with_destination(recipient.clone())
+    .with_token(token_address.clone());
+
+    intent
+        .scope(execute_evm_transaction(move |owner| async move {
+            create_transfer_erc20_tx(
+                token_address,
+                recipient,
+                amount,
+                &make_provider(chain_id.parse::<u64>()?)?,
+                owner,
+            )
+            .await
+        }))
         .await
-    })
-    .await
 }
 
 #[tool(description = "
@@ -1,15 +1,19 @@
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
-use alloy::primitives::Address;
+use alloy::primitives::utils::parse_units;
+use alloy::primitives::{Address, Bytes};
+use alloy::sol_types::SolCall;
 use alloy::{
     network::TransactionBuilder, providers::Provider,
     rpc::types::TransactionRequest,
 };
 use anyhow::{anyhow, Context, Result};
+use futures::future::join_all;
 use uniswap_sdk_core::{prelude::*, token};
 use uniswap_v3_sdk::prelude::*;
 
-use super::abi::IERC20;
+use super::abi::{IMulticallExtended, IERC20};
 use super::util::EvmProvider;
 
 pub async fn check_allowance(
@@ -54,39 +58,316 @@ pub async fn create_approve_tx(
         .with_from(owner_addr)
         .with_to(input_addr)
         .with_call(&call)
-        .with_gas_price(gas_price);
+        .with_gas_price(gas_price)
+        .with_chain_id(provider.get_chain_id().await?);
 
     Ok(tx)
     // send_transaction(tx, provider, wallet).await?;
     // should probably wait for the tx here and verify approvals, but retries will handle this
 }
 
+/// Fee tiers the router probes, 0.01%, 0.05%, 0.3% and 1%
+pub const FEE_TIERS: [FeeAmount; 4] = [
+    FeeAmount::LOWEST,
+    FeeAmount::LOW,
+    FeeAmount::MEDIUM,
+    FeeAmount::HIGH,
+];
+
+pub const DEFAULT_SLIPPAGE_BPS: u64 = 50;
+pub const DEFAULT_DEADLINE_SECS: u64 = 1200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TradeOptions {
+    pub slippage_bps: u64,
+    /// The router reverts the swap if it lands later than this
+    pub deadline_secs: u64,
+}
+
+impl Default for TradeOptions {
+    fn default() -> Self {
+        Self {
+            slippage_bps: DEFAULT_SLIPPAGE_BPS,
+            deadline_secs: DEFAULT_DEADLINE_SECS,
+        }
+    }
+}
+
+impl TradeOptions {
+    /// Uses `EVM_SLIPPAGE_BPS` and `EVM_SWAP_DEADLINE_SECS` if set
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok())
+        };
+        let default = Self::default();
+        Self {
+            slippage_bps: var("EVM_SLIPPAGE_BPS")
+                .unwrap_or(default.slippage_bps),
+            deadline_secs: var("EVM_SWAP_DEADLINE_SECS")
+                .unwrap_or(default.deadline_secs),
+        }
+    }
+}
+
+/// Wrapped native token and USDC of the chain, two-hop routes go through
+/// them
+pub fn intermediate_tokens(chain_id: u64) -> Vec<Address> {
+    let addresses: &[&str] = match chain_id {
+        1 => &[
+            "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+            "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+        ],
+        56 => &[
+            "0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c",
+            "0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d",
+        ],
+        8453 => &[
+            "0x4200000000000000000000000000000000000006",
+            "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913",
+        ],
+        42161 => &[
+            "0x82aF49447D8a07e3bd95BD0d56f35241523fBab1",
+            "0xaf88d065e77c8cC2239327C5EDb3A432268e5831",
+        ],
+        480 => &[
+            "0x4200000000000000000000000000000000000006",
+            "0x79A02482A880bCE3F13e09Da970dC34db4CD24d1",
+        ],
+        _ => &[],
+    };
+    addresses
+        .iter()
+        .filter_map(|a| Address::from_str(a).ok())
+        .collect()
+}
+
+/// A pool of a route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hop {
+    pub token_in: Address,
+    pub token_out: Address,
+    pub fee: FeeAmount,
+}
+
+impl Hop {
+    /// Identifies the pool regardless of the direction of the swap
+    fn pool_key(&self) -> (Address, Address, FeeAmount) {
+        if self.token_in < self.token_out {
+            (self.token_in, self.token_out, self.fee)
+        } else {
+            (self.token_out, self.token_in, self.fee)
+        }
+    }
+}
+
+/// Direct routes on every fee tier, and two-hop routes through each of
+/// the intermediates on every pair of fee tiers
+pub fn candidate_routes(
+    input: Address,
+    output: Address,
+    intermediates: &[Address],
+) -> Vec<Vec<Hop>> {
+    let mut routes = FEE_TIERS
+        .iter()
+        .map(|&fee| {
+            vec![Hop {
+                token_in: input,
+                token_out: output,
+                fee,
+            }]
+        })
+        .collect::<Vec<_>>();
+    for &mid in intermediates {
+        if mid == input || mid == output {
+            continue;
+        }
+        for &fee_in in FEE_TIERS.iter() {
+            for &fee_out in FEE_TIERS.iter() {
+                routes.push(vec![
+                    Hop {
+                        token_in: input,
+                        token_out: mid,
+                        fee: fee_in,
+                    },
+                    Hop {
+                        token_in: mid,
+                        token_out: output,
+                        fee: fee_out,
+                    },
+                ]);
+            }
+        }
+    }
+    routes
+}
+
+/// Raw amounts are passed through, decimal ones are scaled by the
+/// token's decimals
+pub fn parse_amount(amount: &str, decimals: u8) -> Result<U256> {
+    if amount.contains('.') {
+        Ok(parse_units(amount, decimals)?.into())
+    } else {
+        Ok(U256::from_str(amount)?)
+    }
+}
+
+/// Wraps the router calls in the multicall that checks the deadline
+pub fn with_deadline(calldata: Bytes, deadline: U256) -> Bytes {
+    let data = decode_multicall::<Bytes, _>(&calldata)
+        .unwrap_or_else(|_| vec![calldata]);
+    IMulticallExtended::multicallCall { deadline, data }
+        .abi_encode()
+        .into()
+}
+
+pub async fn token_decimals(
+    token: Address,
+    provider: &EvmProvider,
+) -> Result<u8> {
+    Ok(IERC20::new(token, provider).decimals().call().await?._0)
+}
+
+type RouterPool = Pool<EphemeralTickMapDataProvider>;
+
+async fn fetch_pool(
+    chain_id: u64,
+    factory: Address,
+    key: (Address, Address, FeeAmount),
+    provider: &EvmProvider,
+) -> Result<RouterPool> {
+    let (token_a, token_b, fee) = key;
+    let pool = Pool::from_pool_key(
+        chain_id,
+        factory,
+        token_a,
+        token_b,
+        fee,
+        provider.clone(),
+        None,
+    )
+    .await?;
+    if pool.liquidity == 0 {
+        return Err(anyhow!("Pool has no liquidity"));
+    }
+    // the pool computes its address with the mainnet factory by default
+    let tick_data_provider = EphemeralTickMapDataProvider::new(
+        pool.address(None, Some(factory)),
+        provider.clone(),
+        None,
+        None,
+        None,
+    )
+    .await?;
+    Ok(Pool::new_with_tick_data_provider(
+        pool.token0,
+        pool.token1,
+        pool.fee,
+        pool.sqrt_ratio_x96,
+        pool.liquidity,
+        tick_data_provider,
+    )?)
+}
+
+/// Quotes every candidate route against the pools that exist and returns
+/// the one with the largest output
+pub async fn find_best_trade(
+    input_token: Token,
+    output_token: Token,
+    amount_in: U256,
+    provider: &EvmProvider,
+) -> Result<Trade<Token, Token, EphemeralTickMapDataProvider>> {
+    let chain_id = input_token.chain_id();
+    let factory = *V3_CORE_FACTORY_ADDRESSES
+        .get(&chain_id)
+        .context("Uniswap V3 is not deployed on this chain")?;
+
+    let routes = candidate_routes(
+        input_token.address(),
+        output_token.address(),
+        &intermediate_tokens(chain_id),
+    );
+    let keys = routes
+        .iter()
+        .flatten()
+        .map(Hop::pool_key)
+        .collect::<HashSet<_>>();
+    let pools = join_all(keys.into_iter().map(|key| async move {
+        (key, fetch_pool(chain_id, factory, key, provider).await)
+    }))
+    .await
+    .into_iter()
+    .filter_map(|(key, pool)| pool.ok().map(|pool| (key, pool)))
+    .collect::<HashMap<_, _>>();
+    tracing::info!(pools = pools.len(), "Fetched candidate pools");
+
+    let amount_in = CurrencyAmount::from_raw_amount(
+        input_token.clone(),
+        BigInt::from_str(&amount_in.to_string())?,
+    )
+    .context("Failed to create CurrencyAmount")?;
+
+    let mut best: Option<(BigInt, Trade<_, _, _>)> = None;
+    for route in routes {
+        let Some(route_pools) = route
+            .iter()
+            .map(|hop| pools.get(&hop.pool_key()).cloned())
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let route = Route::new(
+            route_pools,
+            input_token.clone(),
+            output_token.clone(),
+        );
+        let Ok(trade) = Trade::from_route(
+            route,
+            amount_in.clone(),
+            TradeType::ExactInput,
+        ) else {
+            continue;
+        };
+        let Ok(output) = trade.output_amount() else {
+            continue;
+        };
+        let output = output.quotient();
+        if best.as_ref().is_none_or(|(best, _)| output > *best) {
+            best = Some((output, trade));
+        }
+    }
+
+    best.map(|(_, trade)| trade)
+        .ok_or_else(|| anyhow!("No Uniswap route found for the pair"))
+}
+
 pub async fn create_trade_tx(
     input_token_address: String,
     input_amount: String,
     output_token_address: String,
     provider: &EvmProvider,
     owner: Address,
+    options: TradeOptions,
 ) -> Result<TransactionRequest> {
-    // Convert addresses from string to Address type
     let input_addr = Address::from_str(&input_token_address)?;
     let output_addr = Address::from_str(&output_token_address)?;
+    if input_addr == output_addr {
+        return Err(anyhow!("Input and output tokens are the same"));
+    }
 
-    // Create token instances
     let chain_id = provider.get_chain_id().await?;
-    let input_token = token!(chain_id, input_addr, 18);
-    let output_token = token!(chain_id, output_addr, 18);
-
-    // Parse input amount
-    let amount_in = CurrencyAmount::from_raw_amount(
-        input_token.clone(),
-        BigInt::from_str(&input_amount)?,
-    )
-    .context("Failed to create CurrencyAmount")?;
+    let input_decimals = token_decimals(input_addr, provider)
+        .await
+        .context("Failed to get input token decimals")?;
+    let output_decimals = token_decimals(output_addr, provider)
+        .await
+        .context("Failed to get output token decimals")?;
+    let input_token = token!(chain_id, input_addr, input_decimals);
+    let output_token = token!(chain_id, output_addr, output_decimals);
+    let amount_in = parse_amount(&input_amount, input_decimals)?;
 
     let router_address = *SWAP_ROUTER_02_ADDRESSES
         .get(&chain_id)
-        .expect("Swap router address not found");
+        .context("Swap router address not found")?;
 
     if !check_allowance(input_addr, owner, router_address, provider)
         .await
@@ -100,40 +381,36 @@ pub async fn create_trade_tx(
         .await
         .context("Failed to get gas price")?;
 
-    // Create pool instance
-    let pool = Pool::<EphemeralTickMapDataProvider>::from_pool_key_with_tick_data_provider(
-        chain_id,
-        FACTORY_ADDRESS,
-        input_addr,
-        output_addr,
-        FeeAmount::MEDIUM,
-        provider.clone(),
-        None,
-    )
-    .await
-    .context("Failed to create pool")?;
-
-    let route = Route::new(vec![pool], input_token, output_token);
-
     let trade =
-        Trade::from_route(route.clone(), amount_in, TradeType::ExactInput)
-            .context("Failed to create trade")?;
+        find_best_trade(input_token, output_token, amount_in, provider)
+            .await?;
+    tracing::info!(
+        hops = trade.swaps[0].route.pools.len(),
+        fees = ?trade.swaps[0].route.pools.iter().map(|p| p.fee).collect::<Vec<_>>(),
+        output = %trade.output_amount()?.to_exact(),
+        "Best route"
+    );
 
     let params = swap_call_parameters(
         &mut [trade],
         SwapOptions {
             recipient: owner,
+            slippage_tolerance: Percent::new(options.slippage_bps, 10_000),
             ..Default::default()
         },
     )
     .context("Failed to get swap parameters")?;
+    let deadline = U256::from(
+        chrono::Utc::now().timestamp() as u64 + options.deadline_secs,
+    );
 
     let request = TransactionRequest::default()
         .with_from(owner)
         .with_to(router_address)
-        .with_input(params.calldata)
+        .with_input(with_deadline(params.calldata, deadline))
         .with_value(params.value)
-        .with_gas_price(gas_price);
+        .with_gas_price(gas_price)
+        .with_chain_id(chain_id);
 
     Ok(request)
 }
@@ -192,6 +469,7 @@ mod tests {
                     output_token,
                     &provider,
                     owner,
+                    TradeOptions::default(),
                 )
                 .await
             },
@@ -199,4 +477,55 @@ mod tests {
         .await
         .unwrap();
     }
+
+    #[test]
+    fn test_candidate_routes() {
+        let input = Address::repeat_byte(1);
+        let output = Address::repeat_byte(2);
+        let mid = Address::repeat_byte(3);
+
+        let routes = candidate_routes(input, output, &[mid, input]);
+        assert_eq!(routes.len(), 4 + 16);
+        assert!(routes[..4].iter().all(|r| r.len() == 1));
+        assert!(routes[4..].iter().all(|r| {
+            r[0].token_in == input
+                && r[0].token_out == mid
+                && r[1].token_in == mid
+                && r[1].token_out == output
+        }));
+    }
+
+    #[test]
+    fn test_hop_pool_key_ignores_direction() {
+        let a = Address::repeat_byte(1);
+        let b = Address::repeat_byte(2);
+        let hop = |token_in, token_out| Hop {
+            token_in,
+            token_out,
+            fee: FeeAmount::LOW,
+        };
+        assert_eq!(hop(a, b).pool_key(), hop(b, a).pool_key());
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(
+            parse_amount("1000000", 6).unwrap(),
+            U256::from(1_000_000)
+        );
+        assert_eq!(parse_amount("1.5", 6).unwrap(), U256::from(1_500_000));
+        assert!(parse_amount("abc", 6).is_err());
+    }
+
+    #[test]
+    fn test_with_deadline() {
+        let call = Bytes::from(vec![0xde, 0xad]);
+        let deadline = U256::from(1_700_000_000u64);
+        let encoded = with_deadline(call.clone(), deadline);
+        let decoded =
+            IMulticallExtended::multicallCall::abi_decode(&encoded, true)
+                .unwrap();
+        assert_eq!(decoded.deadline, deadline);
+        assert_eq!(decoded.data, vec![call]);
+    }
 }
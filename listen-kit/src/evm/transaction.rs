@@ -1,11 +1,17 @@
 use alloy::network::{EthereumWallet, TransactionBuilder};
+use alloy::primitives::{Address, Bytes, B256, U256};
 use alloy::providers::Provider;
 use alloy::rpc::types::TransactionRequest;
+use alloy::sol_types::SolEvent;
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
 use std::time::Duration;
 use tokio::time::sleep;
 
+use super::abi::IERC20;
 use super::util::EvmProvider;
+use crate::signer::preview::{BalanceChange, TxPreview};
 
 pub async fn send_transaction(
     request: TransactionRequest,
@@ -77,3 +83,355 @@ async fn try_send_transaction(
 
     Ok(tx_hash.to_string())
 }
+
+/// Frame of the geth `callTracer` with `withLog` enabled
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    #[serde(rename = "type", default)]
+    pub call_type: String,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub value: Option<U256>,
+    pub error: Option<String>,
+    pub revert_reason: Option<String>,
+    #[serde(default)]
+    pub logs: Vec<CallLog>,
+    #[serde(default)]
+    pub calls: Vec<CallFrame>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CallLog {
+    pub address: Address,
+    #[serde(default)]
+    pub topics: Vec<B256>,
+    #[serde(default)]
+    pub data: Bytes,
+}
+
+/// The RPC calls the preview needs, mocked in tests
+#[async_trait]
+pub trait EvmSimulationRpc: Send + Sync {
+    /// `debug_traceCall` with the call tracer
+    async fn trace_call(&self, tx: &TransactionRequest) -> Result<CallFrame>;
+
+    async fn eth_call(&self, tx: &TransactionRequest) -> Result<Bytes>;
+
+    async fn eth_estimate_gas(&self, tx: &TransactionRequest) -> Result<u64>;
+
+    async fn gas_price(&self) -> Result<u128>;
+
+    /// Decimals and symbol of an ERC20
+    async fn token_info(&self, token: Address) -> Result<(u8, String)>;
+}
+
+#[async_trait]
+impl EvmSimulationRpc for EvmProvider {
+    async fn trace_call(&self, tx: &TransactionRequest) -> Result<CallFrame> {
+        Ok(self
+            .raw_request(
+                "debug_traceCall".into(),
+                (
+                    tx,
+                    "latest",
+                    serde_json::json!({
+                        "tracer": "callTracer",
+                        "tracerConfig": { "withLog": true },
+                    }),
+                ),
+            )
+            .await?)
+    }
+
+    async fn eth_call(&self, tx: &TransactionRequest) -> Result<Bytes> {
+        Ok(Provider::call(self, tx).await?)
+    }
+
+    async fn eth_estimate_gas(&self, tx: &TransactionRequest) -> Result<u64> {
+        Ok(Provider::estimate_gas(self, tx).await?)
+    }
+
+    async fn gas_price(&self) -> Result<u128> {
+        Ok(self.get_gas_price().await?)
+    }
+
+    async fn token_info(&self, token: Address) -> Result<(u8, String)> {
+        let erc20 = IERC20::new(token, self);
+        let decimals = erc20.decimals().call().await?._0;
+        let symbol = erc20.symbol().call().await?._0;
+        Ok((decimals, symbol))
+    }
+}
+
+pub fn native_symbol(chain_id: u64) -> &'static str {
+    match chain_id {
+        56 => "BNB",
+        _ => "ETH",
+    }
+}
+
+/// Simulates the transaction and decodes the native and ERC20 transfers
+/// of `owner` from the call trace
+///
+/// RPCs without the debug namespace only tell whether the call reverts,
+/// the preview then has the native value sent alone
+pub async fn preview_transaction<R: EvmSimulationRpc + ?Sized>(
+    rpc: &R,
+    tx: &TransactionRequest,
+    owner: Address,
+    chain_id: u64,
+) -> Result<TxPreview> {
+    let chain = format!("eip155:{}", chain_id);
+    let mut native_delta = 0i128;
+    let mut token_deltas: Vec<(Address, i128)> = Vec::new();
+
+    match rpc.trace_call(tx).await {
+        Ok(frame) => {
+            if let Some(error) =
+                frame.revert_reason.clone().or(frame.error.clone())
+            {
+                return Ok(TxPreview::failed(chain, error));
+            }
+            collect_deltas(
+                &frame,
+                owner,
+                &mut native_delta,
+                &mut token_deltas,
+            );
+        }
+        Err(e) => {
+            tracing::debug!(?e, "debug_traceCall unavailable");
+            if let Err(e) = rpc.eth_call(tx).await {
+                return Ok(TxPreview::failed(chain, e.to_string()));
+            }
+            native_delta = -to_i128(tx.value.unwrap_or_default());
+        }
+    }
+    token_deltas.retain(|(_, delta)| *delta != 0);
+
+    let gas = match rpc.eth_estimate_gas(tx).await {
+        Ok(gas) => gas,
+        Err(e) => return Ok(TxPreview::failed(chain, e.to_string())),
+    };
+    let gas_price = match tx.gas_price {
+        Some(gas_price) => gas_price,
+        None => rpc.gas_price().await?,
+    };
+
+    let native = |raw_delta| BalanceChange {
+        token: "native".to_string(),
+        symbol: Some(native_symbol(chain_id).to_string()),
+        decimals: 18,
+        raw_delta,
+    };
+    let mut balance_changes = Vec::new();
+    if native_delta != 0 {
+        balance_changes.push(native(native_delta));
+    }
+    for (token, raw_delta) in token_deltas {
+        let (decimals, symbol) = match rpc.token_info(token).await {
+            Ok((decimals, symbol)) => (decimals, Some(symbol)),
+            Err(_) => (0, None),
+        };
+        balance_changes.push(BalanceChange {
+            token: token.to_string(),
+            symbol,
+            decimals,
+            raw_delta,
+        });
+    }
+
+    Ok(TxPreview {
+        chain,
+        success: true,
+        error: None,
+        balance_changes,
+        fee: Some(native(-((gas as u128 * gas_price) as i128))),
+        logs: vec![],
+    })
+}
+
+fn to_i128(value: U256) -> i128 {
+    i128::try_from(value).unwrap_or(i128::MAX)
+}
+
+/// Walks the frames that did not revert, value moved by delegate and
+/// static calls is the parent's so it is not counted again
+fn collect_deltas(
+    frame: &CallFrame,
+    owner: Address,
+    native_delta: &mut i128,
+    token_deltas: &mut Vec<(Address, i128)>,
+) {
+    if frame.error.is_some() {
+        return;
+    }
+    if frame.call_type != "DELEGATECALL" && frame.call_type != "STATICCALL" {
+        let value = to_i128(frame.value.unwrap_or_default());
+        if frame.from == owner {
+            *native_delta -= value;
+        }
+        if frame.to == Some(owner) {
+            *native_delta += value;
+        }
+    }
+
+    for log in frame.logs.iter() {
+        if log.topics.len() != 3
+            || log.topics[0] != IERC20::Transfer::SIGNATURE_HASH
+            || log.data.len() < 32
+        {
+            continue;
+        }
+        let from = Address::from_word(log.topics[1]);
+        let to = Address::from_word(log.topics[2]);
+        let amount = to_i128(U256::from_be_slice(&log.data[..32]));
+        let delta = match (from == owner, to == owner) {
+            (true, false) => -amount,
+            (false, true) => amount,
+            _ => continue,
+        };
+        match token_deltas.iter_mut().find(|(t, _)| *t == log.address) {
+            Some((_, total)) => *total += delta,
+            None => token_deltas.push((log.address, delta)),
+        }
+    }
+
+    for call in frame.calls.iter() {
+        collect_deltas(call, owner, native_delta, token_deltas);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+    use anyhow::anyhow;
+
+    const OWNER: Address =
+        address!("1111111111111111111111111111111111111111");
+    const ROUTER: Address =
+        address!("68b3465833fb72A70ecDF485E0e4C7bD8665Fc45");
+    const POOL: Address =
+        address!("2222222222222222222222222222222222222222");
+    const USDC: Address =
+        address!("af88d065e77c8cC2239327C5EDb3A432268e5831");
+
+    struct MockRpc {
+        trace: Option<CallFrame>,
+    }
+
+    #[async_trait]
+    impl EvmSimulationRpc for MockRpc {
+        async fn trace_call(
+            &self,
+            _tx: &TransactionRequest,
+        ) -> Result<CallFrame> {
+            self.trace.clone().ok_or_else(|| {
+                anyhow!("the method debug_traceCall does not exist")
+            })
+        }
+
+        async fn eth_call(&self, _tx: &TransactionRequest) -> Result<Bytes> {
+            Ok(Bytes::new())
+        }
+
+        async fn eth_estimate_gas(
+            &self,
+            _tx: &TransactionRequest,
+        ) -> Result<u64> {
+            Ok(100_000)
+        }
+
+        async fn gas_price(&self) -> Result<u128> {
+            Ok(1_000_000_000)
+        }
+
+        async fn token_info(&self, _token: Address) -> Result<(u8, String)> {
+            Ok((6, "USDC".to_string()))
+        }
+    }
+
+    fn swap_trace(error: Option<&str>) -> CallFrame {
+        serde_json::from_value(serde_json::json!({
+            "type": "CALL",
+            "from": OWNER,
+            "to": ROUTER,
+            "value": "0xde0b6b3a7640000",
+            "error": error,
+            "calls": [{
+                "type": "CALL",
+                "from": ROUTER,
+                "to": POOL,
+                "value": "0x0",
+                "logs": [{
+                    "address": USDC,
+                    "topics": [
+                        IERC20::Transfer::SIGNATURE_HASH,
+                        POOL.into_word(),
+                        OWNER.into_word(),
+                    ],
+                    "data": Bytes::from(
+                        U256::from(2_000_000_000u64).to_be_bytes_vec()
+                    ),
+                }],
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_preview_swap() {
+        let rpc = MockRpc {
+            trace: Some(swap_trace(None)),
+        };
+        let preview = preview_transaction(
+            &rpc,
+            &TransactionRequest::default(),
+            OWNER,
+            42161,
+        )
+        .await
+        .unwrap();
+        assert!(preview.success);
+        assert_eq!(preview.chain, "eip155:42161");
+        assert_eq!(preview.balance_changes[0].delta(), -1.0);
+        assert_eq!(preview.balance_changes[1].token, USDC.to_string());
+        assert_eq!(preview.balance_changes[1].delta(), 2000.0);
+        assert_eq!(
+            preview.summary(),
+            "You will send 1 ETH and receive ~2,000 USDC, fee ~0.0001 ETH"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preview_revert() {
+        let rpc = MockRpc {
+            trace: Some(swap_trace(Some("execution reverted"))),
+        };
+        let preview = preview_transaction(
+            &rpc,
+            &TransactionRequest::default(),
+            OWNER,
+            1,
+        )
+        .await
+        .unwrap();
+        assert!(!preview.success);
+        assert_eq!(preview.error.as_deref(), Some("execution reverted"));
+    }
+
+    #[tokio::test]
+    async fn test_preview_without_trace() {
+        let rpc = MockRpc { trace: None };
+        let tx = TransactionRequest::default()
+            .with_from(OWNER)
+            .with_value(U256::from(10u64.pow(17)));
+        let preview =
+            preview_transaction(&rpc, &tx, OWNER, 8453).await.unwrap();
+        assert!(preview.success);
+        assert_eq!(preview.balance_changes.len(), 1);
+        assert_eq!(preview.balance_changes[0].delta(), -0.1);
+    }
+}
@@ -26,7 +26,8 @@ pub async fn create_transfer_eth_tx(
         .with_from(owner)
         .with_to(Address::from_str(&to)?)
         .with_value(U256::from_str(&amount)?)
-        .with_gas_price(gas_price);
+        .with_gas_price(gas_price)
+        .with_chain_id(provider.get_chain_id().await?);
 
     Ok(request)
 }
@@ -53,7 +54,8 @@ pub async fn create_transfer_erc20_tx(
         .with_from(owner)
         .with_to(Address::from_str(&token_address)?)
         .with_call(&call)
-        .with_gas_price(gas_price);
+        .with_gas_price(gas_price)
+        .with_chain_id(provider.get_chain_id().await?);
 
     Ok(request)
 }
@@ -12,6 +12,8 @@ use anyhow::{anyhow, Result};
 
 use crate::common::wrap_unsafe;
 use crate::ensure_evm_wallet_created;
+use crate::evm::transaction::preview_transaction;
+use crate::signer::approval::TxIntent;
 use crate::signer::evm::LocalEvmSigner;
 use crate::signer::SignerContext;
 
@@ -85,8 +87,45 @@ where
         .await
         .map_err(|e| anyhow!("{:#?}", e))?;
 
-    wrap_unsafe(move || async move {
-        signer.sign_and_send_evm_transaction(tx).await
+    // transactions without a chain id cannot be simulated, the tools set it
+    let preview = match tx.chain_id {
+        Some(chain_id) if std::env::var("SKIP_SIMULATION").is_err() => {
+            let tx = tx.clone();
+            let preview = wrap_unsafe(move || async move {
+                let provider = make_provider(chain_id)?;
+                preview_transaction(&provider, &tx, owner, chain_id).await
+            })
+            .await?;
+            if !preview.success {
+                return Err(anyhow!(
+                    "Transaction simulation failed, not sending it: {}",
+                    preview.summary()
+                ));
+            }
+            Some(preview)
+        }
+        _ => None,
+    };
+
+    let intent = TxIntent::current()
+        .unwrap_or_else(|| TxIntent::new("Sign an EVM transaction"))
+        .with_preview(preview.as_ref());
+    signer
+        .request_approval(
+            intent.clone(),
+            serde_json::json!({
+                "chain": "evm",
+                "transaction": tx,
+                "preview": preview,
+            }),
+        )
+        .await?;
+
+    // the signer sees the intent too, policies are enforced when signing
+    wrap_unsafe(move || {
+        intent.scope(
+            async move { signer.sign_and_send_evm_transaction(tx).await },
+        )
     })
     .await
     .map_err(|e| anyhow!("{:#?}", e))
@@ -1,7 +1,88 @@
-use crate::faster100x::types::{Faster100xData, Faster100xResponse};
+use crate::data::listen_api_tools::LISTEN_API;
+use crate::faster100x::types::{
+    Faster100xData, Faster100xResponse, FundGraphData, Holder,
+    InnerResponseData, Node, PercentageValue, ResponseData,
+};
 use anyhow::{anyhow, Result};
+use listen_adapter_client::HolderSnapshot;
 use reqwest::Client;
 
+/// Snapshots are taken hourly, older ones are of a job that stopped
+/// covering the token and are not used
+pub const MAX_HOLDER_SNAPSHOT_AGE_SECS: u64 = 2 * 3600;
+
+fn is_recent(snapshot: &HolderSnapshot, now: u64) -> bool {
+    now.saturating_sub(snapshot.timestamp) <= MAX_HOLDER_SNAPSHOT_AGE_SECS
+}
+
+impl From<HolderSnapshot> for Faster100xData {
+    /// The snapshots carry no funding graph, every holder is a node of its
+    /// own so the risk is computed on the distribution alone
+    fn from(snapshot: HolderSnapshot) -> Self {
+        let updated_at =
+            chrono::DateTime::from_timestamp(snapshot.timestamp as i64, 0)
+                .map(|t| t.to_rfc3339());
+        Faster100xData {
+            status: "success".to_string(),
+            message: None,
+            data: Some(ResponseData {
+                token_address: Some(snapshot.mint),
+                response: InnerResponseData {
+                    fund_graph_data: FundGraphData {
+                        nodes: snapshot
+                            .holders
+                            .iter()
+                            .map(|h| Node {
+                                id: h.owner.clone(),
+                            })
+                            .collect(),
+                        links: vec![],
+                    },
+                    top_nodes: snapshot
+                        .holders
+                        .iter()
+                        .map(|h| h.owner.clone())
+                        .collect(),
+                    data: snapshot
+                        .holders
+                        .into_iter()
+                        .map(|h| Holder {
+                            address: h.owner,
+                            amount_percentage: PercentageValue::Float(
+                                h.percentage,
+                            ),
+                        })
+                        .collect(),
+                },
+                updated_at,
+            }),
+        }
+    }
+}
+
+/// Latest holder snapshot indexed by listen-data, served by the adapter,
+/// if it is recent
+pub async fn get_holder_snapshot_data(
+    token_address: &str,
+) -> Result<Faster100xData> {
+    let snapshot = LISTEN_API
+        .token_holders(token_address, None, None)
+        .await
+        .map_err(|e| anyhow!("Failed to fetch holder snapshot: {}", e))?
+        .ok_or_else(|| anyhow!("No holder snapshot for {}", token_address))?;
+
+    let now = chrono::Utc::now().timestamp() as u64;
+    if !is_recent(&snapshot, now) {
+        return Err(anyhow!(
+            "Holder snapshot of {} is from {}",
+            token_address,
+            snapshot.timestamp
+        ));
+    }
+
+    Ok(snapshot.into())
+}
+
 pub async fn get_faster100x_data(
     token_address: &str,
 ) -> Result<Faster100xData> {
@@ -64,6 +145,36 @@ pub async fn get_faster100x_data(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::faster100x::risk::compute_holder_risk;
+
+    #[test]
+    fn test_holder_snapshot_into_faster100x_data() {
+        let snapshot: HolderSnapshot = serde_json::from_value(
+            serde_json::json!({
+                "mint": "mint",
+                "timestamp": 1700000000,
+                "holder_count": 3,
+                "holders": [
+                    {"rank": 1, "owner": "a", "amount": 500.0, "percentage": 0.5},
+                    {"rank": 2, "owner": "b", "amount": 300.0, "percentage": 0.3},
+                    {"rank": 3, "owner": "c", "amount": 200.0, "percentage": 0.2}
+                ]
+            }),
+        )
+        .unwrap();
+
+        assert!(is_recent(&snapshot, 1700000000 + 3600));
+        assert!(!is_recent(
+            &snapshot,
+            1700000000 + MAX_HOLDER_SNAPSHOT_AGE_SECS + 1
+        ));
+
+        let data: Faster100xData = snapshot.into();
+        let risk = compute_holder_risk(&data).unwrap();
+        assert_eq!(risk.isolated.num_wallets, 3);
+        assert_eq!(risk.linked.num_clusters, 0);
+        assert!((risk.isolated.top70_centralization - 100.0).abs() < 1e-9);
+    }
 
     #[tokio::test]
     async fn test_get_faster100x_data() {
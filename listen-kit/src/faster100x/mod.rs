@@ -3,7 +3,8 @@ use anyhow::{anyhow, Result};
 use rig_tool_macro::tool;
 
 use crate::faster100x::{
-    data::get_faster100x_data, format::format_wallet_analysis,
+    data::{get_faster100x_data, get_holder_snapshot_data},
+    format::format_wallet_analysis,
 };
 
 pub mod data;
@@ -26,6 +27,18 @@ Returns:
 pub async fn analyze_holder_distribution(
     token_address: String,
 ) -> Result<serde_json::Value> {
+    // own recent snapshots first, faster100x is rate-limited and flaky
+    match get_holder_snapshot_data(&token_address).await {
+        Ok(data) => return format_wallet_analysis(&data),
+        Err(e) => {
+            tracing::warn!(
+                "No recent holder snapshot for {}: {}",
+                token_address,
+                e
+            )
+        }
+    }
+
     let data = match get_faster100x_data(&token_address).await {
         Ok(data) => data,
         Err(e) => {
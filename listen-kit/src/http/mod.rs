@@ -1,7 +1,11 @@
 pub mod middleware;
 pub mod routes;
+pub mod runs;
+pub mod scheduler;
 pub mod serde;
 pub mod server;
+pub mod session;
 pub mod state;
+pub mod tasks;
 
 pub use server::run_server;
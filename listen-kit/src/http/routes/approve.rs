@@ -0,0 +1,45 @@
+use crate::http::middleware::verify_auth;
+use crate::http::state::AppState;
+use actix_web::{post, web, Error, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ApproveRequest {
+    id: String,
+    approved: bool,
+}
+
+/// Resolves an `ApprovalRequired` event of the user's stream
+#[post("/approve")]
+async fn approve(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<ApproveRequest>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Error: unauthorized: {}", e);
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "error": "unauthorized"
+            })));
+        }
+    };
+
+    if !state.approvals.resolve(
+        &user_session.user_id,
+        &body.id,
+        body.approved,
+    ) {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "error": "approval not found or expired",
+            "id": body.id
+        })));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": body.id,
+        "approved": body.approved
+    })))
+}
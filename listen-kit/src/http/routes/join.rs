@@ -52,6 +52,21 @@ pub fn join_responses(
                     content,
                 });
             }
+            StreamResponse::ApprovalRequired {
+                id,
+                summary,
+                tx_preview,
+            } => {
+                refresh_accumulated_message(
+                    &mut message_acc,
+                    &mut output_responses,
+                );
+                output_responses.push(StreamResponse::ApprovalRequired {
+                    id,
+                    summary,
+                    tx_preview,
+                });
+            }
             StreamResponse::ToolCall { id, name, params } => {
                 refresh_accumulated_message(
                     &mut message_acc,
@@ -81,6 +96,21 @@ pub fn join_responses(
                 );
                 output_responses.push(StreamResponse::Error(error));
             }
+            StreamResponse::RunStarted { run_id } => {
+                refresh_accumulated_message(
+                    &mut message_acc,
+                    &mut output_responses,
+                );
+                output_responses.push(StreamResponse::RunStarted { run_id });
+            }
+            StreamResponse::HistoryCompacted { chat_history } => {
+                refresh_accumulated_message(
+                    &mut message_acc,
+                    &mut output_responses,
+                );
+                output_responses
+                    .push(StreamResponse::HistoryCompacted { chat_history });
+            }
         }
     }
 
@@ -10,4 +10,22 @@ pub use auth::*;
 pub mod suggest;
 pub use suggest::*;
 
+pub mod approve;
+pub use approve::*;
+
+pub mod policy;
+pub use policy::*;
+
+pub mod portfolio;
+pub use portfolio::*;
+
+pub mod sessions;
+pub use sessions::*;
+
+pub mod runs;
+pub use runs::*;
+
+pub mod tasks;
+pub use tasks::*;
+
 pub mod join;
@@ -0,0 +1,74 @@
+use crate::http::middleware::verify_auth;
+use crate::http::state::{
+    AppState, UserSpendingPolicy, SPENDING_POLICIES_COLLECTION,
+};
+use crate::signer::policy::SpendingPolicy;
+use actix_web::{get, put, web, Error, HttpRequest, HttpResponse};
+use serde_json::json;
+
+/// Returns the spending policy the user's agent runs under
+#[get("/policy")]
+async fn get_policy(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Error: unauthorized: {}", e);
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "error": "unauthorized"
+            })));
+        }
+    };
+
+    match state.spending_policy(&user_session.user_id).await {
+        Ok(policy) => Ok(HttpResponse::Ok().json(policy)),
+        Err(e) => {
+            tracing::error!("Error: loading spending policy: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to load spending policy"
+            })))
+        }
+    }
+}
+
+/// Replaces the user's spending policy, applies to the next conversation
+#[put("/policy")]
+async fn put_policy(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<SpendingPolicy>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Error: unauthorized: {}", e);
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "error": "unauthorized"
+            })));
+        }
+    };
+
+    let policy = body.into_inner();
+    if let Err(e) = state
+        .mongo
+        .upsert_by(
+            SPENDING_POLICIES_COLLECTION,
+            "user_id",
+            &user_session.user_id,
+            UserSpendingPolicy {
+                user_id: user_session.user_id.clone(),
+                policy: policy.clone(),
+            },
+        )
+        .await
+    {
+        tracing::error!("Error: saving spending policy: {}", e);
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "error": "failed to save spending policy"
+        })));
+    }
+
+    Ok(HttpResponse::Ok().json(policy))
+}
@@ -0,0 +1,30 @@
+use crate::cross_chain::portfolio::get_portfolio;
+use crate::http::middleware::verify_auth;
+use actix_web::{get, Error, HttpRequest, HttpResponse};
+use serde_json::json;
+
+/// Holdings of the user's wallets on every supported chain, valued in USD
+#[get("/portfolio")]
+async fn portfolio(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Error: unauthorized: {}", e);
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "error": "unauthorized"
+            })));
+        }
+    };
+
+    match get_portfolio(user_session.pubkey, user_session.wallet_address)
+        .await
+    {
+        Ok(portfolio) => Ok(HttpResponse::Ok().json(portfolio)),
+        Err(e) => {
+            tracing::error!("Error: fetching portfolio: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": format!("failed to fetch portfolio: {}", e)
+            })))
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use crate::http::middleware::verify_auth;
+use crate::http::runs::RunStatus;
+use crate::http::state::AppState;
+use crate::reasoning_loop::StreamResponse;
+use actix_web::{
+    get, post, web, Error, HttpRequest, HttpResponse, Responder,
+};
+use actix_web_lab::sse;
+use serde::Deserialize;
+use serde_json::json;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    /// Index of the last event the client got, the `id` of the SSE event
+    #[serde(default)]
+    after: Option<usize>,
+}
+
+/// Stops the run, nested agents included, before its next model chunk or
+/// tool call, whichever instance streams it
+#[post("/runs/{run_id}/cancel")]
+async fn cancel_run(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Error: unauthorized: {}", e);
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "error": "unauthorized"
+            })));
+        }
+    };
+
+    if state.runs.cancel(&user_session.user_id, &path) {
+        return Ok(HttpResponse::Ok().json(json!({
+            "cancelled": path.into_inner()
+        })));
+    }
+
+    // the run might be streaming on another instance, which picks the
+    // request up from Redis
+    if let Some(events) = &state.run_events {
+        match events.info(&path).await {
+            Ok(Some(info))
+                if info.user_id == user_session.user_id
+                    && info.status == RunStatus::Running =>
+            {
+                if let Err(e) = events.request_cancel(&path).await {
+                    tracing::error!("Error: cancelling run: {}", e);
+                    return Ok(HttpResponse::InternalServerError().json(
+                        json!({
+                            "error": "failed to cancel run"
+                        }),
+                    ));
+                }
+                return Ok(HttpResponse::Ok().json(json!({
+                    "cancelled": path.into_inner()
+                })));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Error: loading run: {}", e);
+                return Ok(HttpResponse::InternalServerError().json(json!({
+                    "error": "failed to load run"
+                })));
+            }
+        }
+    }
+
+    Ok(HttpResponse::NotFound().json(json!({
+        "error": "run not found"
+    })))
+}
+
+/// Replays the events of a run after `after` and follows it until it is
+/// done, for clients that lost the `/stream` connection
+#[get("/runs/{run_id}/events")]
+async fn run_events(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Error: unauthorized: {}", e);
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "error": "unauthorized"
+            })));
+        }
+    };
+
+    let Some(events) = state.run_events.clone() else {
+        return Ok(HttpResponse::ServiceUnavailable().json(json!({
+            "error": "run events are not buffered"
+        })));
+    };
+
+    let run_id = path.into_inner();
+    match events.info(&run_id).await {
+        Ok(Some(info)) if info.user_id == user_session.user_id => {}
+        Ok(_) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "error": "run not found"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Error: loading run: {}", e);
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to load run"
+            })));
+        }
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<sse::Event>(64);
+    let mut next = query.after.map_or(0, |after| after + 1);
+    tokio::spawn(async move {
+        loop {
+            // read the status first so that no event pushed before the
+            // run finished is missed
+            let status = match events.info(&run_id).await {
+                Ok(info) => info.map(|i| i.status),
+                Err(e) => {
+                    tracing::error!("Error: loading run: {}", e);
+                    None
+                }
+            };
+            let batch = match events.events_from(&run_id, next).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    tracing::error!("Error: loading run events: {}", e);
+                    let _ = tx
+                        .send(sse::Event::Data(sse::Data::new(
+                            serde_json::to_string(&StreamResponse::Error(
+                                format!("Error loading run events: {}", e),
+                            ))
+                            .unwrap(),
+                        )))
+                        .await;
+                    return;
+                }
+            };
+            for event in batch {
+                next = event.index + 1;
+                let data = sse::Data::new(
+                    serde_json::to_string(&event.event).unwrap(),
+                )
+                .id(event.index.to_string());
+                if tx.send(sse::Event::Data(data)).await.is_err() {
+                    return;
+                }
+            }
+            if status != Some(RunStatus::Running) {
+                return;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+
+    Ok(sse::Sse::from_infallible_receiver(rx)
+        .respond_to(&req)
+        .map_into_boxed_body())
+}
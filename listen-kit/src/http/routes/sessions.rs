@@ -0,0 +1,111 @@
+use crate::http::middleware::verify_auth;
+use crate::http::session::Session;
+use crate::http::state::AppState;
+use actix_web::{delete, get, post, web, Error, HttpRequest, HttpResponse};
+use serde_json::json;
+
+fn unauthorized(e: impl std::fmt::Display) -> HttpResponse {
+    tracing::error!("Error: unauthorized: {}", e);
+    HttpResponse::Unauthorized().json(json!({
+        "error": "unauthorized"
+    }))
+}
+
+/// Starts an empty session, pass its `session_id` to `/stream`
+#[post("/sessions")]
+async fn create_session(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    let session = Session::new(user_session.user_id);
+    match session.save(&state.mongo).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(session.info())),
+        Err(e) => {
+            tracing::error!("Error: saving session: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to create session"
+            })))
+        }
+    }
+}
+
+/// The user's sessions, most recently updated first
+#[get("/sessions")]
+async fn list_sessions(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    match Session::list(&state.mongo, &user_session.user_id).await {
+        Ok(sessions) => Ok(HttpResponse::Ok().json(sessions)),
+        Err(e) => {
+            tracing::error!("Error: listing sessions: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to list sessions"
+            })))
+        }
+    }
+}
+
+/// The session with all of its turns, clients that lost the stream fetch
+/// this until the status is back to idle
+#[get("/sessions/{session_id}")]
+async fn get_session(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    match Session::load(&state.mongo, &user_session.user_id, &path).await {
+        Ok(Some(session)) => Ok(HttpResponse::Ok().json(session)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "error": "session not found"
+        }))),
+        Err(e) => {
+            tracing::error!("Error: loading session: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to load session"
+            })))
+        }
+    }
+}
+
+#[delete("/sessions/{session_id}")]
+async fn delete_session(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    match Session::delete(&state.mongo, &user_session.user_id, &path).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(json!({
+            "deleted": path.into_inner()
+        }))),
+        Ok(false) => Ok(HttpResponse::NotFound().json(json!({
+            "error": "session not found"
+        }))),
+        Err(e) => {
+            tracing::error!("Error: deleting session: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to delete session"
+            })))
+        }
+    }
+}
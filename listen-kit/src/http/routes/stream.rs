@@ -2,13 +2,18 @@ use crate::agent::create_listen_agent;
 use crate::agent::Features;
 use crate::common::spawn_with_signer;
 use crate::http::middleware::verify_auth;
+use crate::http::runs::{RunInfo, RunStatus};
 use crate::http::serde::deserialize_messages;
+use crate::http::session::{Session, SessionStatus};
 use crate::http::state::AppState;
 use crate::memory::add_user_specific_memories;
 use crate::memory::make_mem0_messages;
+use crate::reasoning_loop::usage::UsageReport;
 use crate::reasoning_loop::Model;
 use crate::reasoning_loop::ReasoningLoop;
 use crate::reasoning_loop::StreamResponse;
+use crate::signer::approval::ApprovalSigner;
+use crate::signer::policy::PolicySigner;
 use crate::signer::privy::PrivySigner;
 use crate::signer::TransactionSigner;
 use actix_web::{post, web, HttpRequest, Responder};
@@ -21,8 +26,13 @@ use std::sync::Arc;
 #[derive(Deserialize, Serialize, Clone)]
 pub struct ChatRequest {
     prompt: String,
-    #[serde(deserialize_with = "deserialize_messages")]
+    /// Ignored when the request belongs to a session, the server keeps
+    /// the history then
+    #[serde(default, deserialize_with = "deserialize_messages")]
     chat_history: Vec<Message>,
+    /// Created with `POST /sessions`
+    #[serde(default)]
+    session_id: Option<String>,
     #[serde(default)]
     chain: Option<String>,
     #[serde(default)]
@@ -42,6 +52,9 @@ pub struct Chat<'a> {
     pub chat_request: ChatRequest,
     #[serde(default)]
     pub responses: Vec<StreamResponse>,
+    /// Tokens per model and tool latencies of the run
+    #[serde(default)]
+    pub usage: Option<UsageReport>,
 }
 
 #[derive(Serialize)]
@@ -120,7 +133,59 @@ async fn stream(
     let with_memory = features.memory;
     let locale = request.locale.clone().unwrap_or("en".to_string());
     let prompt = request.prompt.clone();
-    let messages = request.chat_history.clone();
+
+    let session = match &request.session_id {
+        Some(session_id) => {
+            match Session::load(
+                &state.mongo,
+                &user_session.user_id,
+                session_id,
+            )
+            .await
+            {
+                Ok(Some(session)) => Some(session),
+                Ok(None) => {
+                    let error_event = sse::Event::Data(sse::Data::new(
+                        serde_json::to_string(&StreamResponse::Error(
+                            "Error: session not found".to_string(),
+                        ))
+                        .unwrap(),
+                    ));
+                    let _ = tx.send(error_event).await;
+                    return sse::Sse::from_infallible_receiver(rx);
+                }
+                Err(e) => {
+                    tracing::error!("Error: loading session: {}", e);
+                    let error_event = sse::Event::Data(sse::Data::new(
+                        serde_json::to_string(&StreamResponse::Error(
+                            format!("Error loading session: {}", e),
+                        ))
+                        .unwrap(),
+                    ));
+                    let _ = tx.send(error_event).await;
+                    return sse::Sse::from_infallible_receiver(rx);
+                }
+            }
+        }
+        None => None,
+    };
+
+    // the reasoning loop compacts the history if it got too long, the
+    // compacted one is stored once the run is done
+    let session = match session {
+        Some(mut session) => {
+            session.status = SessionStatus::Running;
+            if let Err(e) = session.save(&state.mongo).await {
+                tracing::error!("Error: saving session: {}", e);
+            }
+            Some(session)
+        }
+        None => None,
+    };
+    let messages = match &session {
+        Some(session) => session.history(),
+        None => request.chat_history.clone(),
+    };
 
     // Select the appropriate agent based on the chain parameter and preamble
     let model = Model::OpenRouter(Arc::new(create_listen_agent(
@@ -129,15 +194,43 @@ async fn stream(
         locale.clone(),
     )));
 
-    let signer: Arc<dyn TransactionSigner> = Arc::new(PrivySigner::new(
-        state.privy.clone(),
-        user_session.clone(),
-        locale,
+    let spending_policy =
+        match state.spending_policy(&user_session.user_id).await {
+            Ok(policy) => policy,
+            Err(e) => {
+                tracing::error!("Error: loading spending policy: {}", e);
+                let error_event = sse::Event::Data(sse::Data::new(
+                    serde_json::to_string(&StreamResponse::Error(format!(
+                        "Error loading spending policy: {}",
+                        e
+                    )))
+                    .unwrap(),
+                ));
+                let _ = tx.send(error_event).await;
+                return sse::Sse::from_infallible_receiver(rx);
+            }
+        };
+
+    // transactions the spending policy refuses never reach the user, the
+    // rest goes past them first unless the approval policy lets it through
+    let signer: Arc<dyn TransactionSigner> = Arc::new(PolicySigner::new(
+        Arc::new(ApprovalSigner::new(
+            Arc::new(PrivySigner::new(
+                state.privy.clone(),
+                user_session.clone(),
+                locale,
+            )),
+            state.approvals.clone(),
+            state.approval_policy,
+        )),
+        spending_policy,
+        state.spend_ledger.clone(),
     ));
 
     // Create a channel for collecting responses - this stays put
     let (response_tx, response_rx) =
         tokio::sync::mpsc::channel::<StreamResponse>(1024);
+    let (usage_tx, usage_rx) = tokio::sync::oneshot::channel::<UsageReport>();
 
     // Store all responses in this vec TODO move this to a separate method to be used by swarm leader calls
     // to collect the subresponses (potentailly, in case it'd be to run offline)
@@ -155,6 +248,7 @@ async fn stream(
             while let Some(response) = rx.recv().await {
                 collected_responses.push(response);
             }
+            let usage = usage_rx.await.ok();
 
             // Only save if we have responses
             if !collected_responses.is_empty() {
@@ -189,6 +283,7 @@ async fn stream(
                     pubkey: None,
                     chat_request,
                     responses,
+                    usage,
                 };
 
                 match collection.insert_one(chat, None).await {
@@ -215,28 +310,72 @@ async fn stream(
     // Process responses in the background - don't wait for it
     tokio::spawn(response_collector);
 
+    // the run can be cancelled through `/runs/{id}/cancel` and resumed
+    // through `/runs/{id}/events` by the id sent as the first event
+    let (run_id, cancel) = state.runs.start(&user_session.user_id);
+    let run_info = RunInfo {
+        run_id: run_id.clone(),
+        user_id: user_session.user_id.clone(),
+        session_id: request.session_id.clone(),
+        status: RunStatus::Running,
+        started_at: chrono::Utc::now().timestamp(),
+    };
+    if let Some(run_events) = &state.run_events {
+        if let Err(e) = run_events.set_info(&run_info).await {
+            tracing::error!("Error: saving run: {}", e);
+        }
+    }
+
     spawn_with_signer(signer, move || async move {
-        let reasoning_loop = ReasoningLoop::new(model).with_stdout(false);
+        let reasoning_loop = ReasoningLoop::new(model)
+            .with_stdout(false)
+            .with_cancellation(cancel.clone());
 
         // Create a channel for the reasoning loop to send responses
         let (internal_tx, mut internal_rx) =
             tokio::sync::mpsc::channel::<StreamResponse>(1024);
 
-        // Create a separate task to handle sending responses
+        // Create a separate task to handle sending responses, the run
+        // carries on if the client goes away so it can pick the result up
+        // from the session later
         let tx_clone = tx.clone();
         let response_tx_clone = response_tx.clone();
+        let keep_responses = session.is_some();
+        let run_events = state.run_events.clone();
+        let _run_id = run_id.clone();
         let send_task = tokio::spawn(async move {
+            let mut client_connected = true;
+            let mut responses = Vec::new();
+            let mut index = 0;
             while let Some(response) = internal_rx.recv().await {
+                // Buffer for clients that reconnect, the index is the id of
+                // the SSE event
+                if let Some(run_events) = &run_events {
+                    if let Err(e) = run_events.push(&_run_id, &response).await
+                    {
+                        tracing::error!("Error: buffering run event: {}", e);
+                    }
+                }
+
                 // Send to client
-                if tx_clone
-                    .send(sse::Event::Data(sse::Data::new(
-                        serde_json::to_string(&response).unwrap(),
-                    )))
-                    .await
-                    .is_err()
+                if client_connected
+                    && tx_clone
+                        .send(sse::Event::Data(
+                            sse::Data::new(
+                                serde_json::to_string(&response).unwrap(),
+                            )
+                            .id(index.to_string()),
+                        ))
+                        .await
+                        .is_err()
                 {
-                    tracing::error!("Error: failed to send response");
-                    break;
+                    tracing::warn!("Client disconnected, continuing the run");
+                    client_connected = false;
+                }
+                index += 1;
+
+                if keep_responses {
+                    responses.push(response.clone());
                 }
 
                 // Send to our storage channel
@@ -245,8 +384,15 @@ async fn stream(
 
             // Close the response channel to signal completion
             drop(response_tx_clone);
+            (responses, index)
         });
 
+        let _ = internal_tx
+            .send(StreamResponse::RunStarted {
+                run_id: run_id.clone(),
+            })
+            .await;
+
         // Make the current channel available in the global task context
         // so nested agents can access it
         ReasoningLoop::set_current_stream_channel(Some(internal_tx.clone()))
@@ -255,7 +401,7 @@ async fn stream(
         // Run the reasoning loop in the current task (with signer context)
         let loop_result = reasoning_loop
             .stream(
-                prompt,
+                prompt.clone(),
                 messages,
                 Some(internal_tx),
                 if with_memory {
@@ -268,19 +414,58 @@ async fn stream(
             .await;
 
         // Wait for the send task to complete
-        let _ = send_task.await;
+        let (responses, index) = send_task.await.unwrap_or_default();
+
+        let usage = reasoning_loop.tracker().report();
+        tracing::info!(
+            run_id = %run_id,
+            tokens = usage.total_tokens(),
+            tool_calls = usage.tool_calls,
+            wall_time_ms = usage.wall_time_ms,
+            "Run finished"
+        );
+        let _ = usage_tx.send(usage.clone());
+
+        if let Some(mut session) = session {
+            session.finish_turn(
+                prompt,
+                super::join::join_responses(responses),
+                loop_result.as_ref().ok().cloned(),
+                Some(usage),
+            );
+            if let Err(e) = session.save(&state.mongo).await {
+                tracing::error!("Error: saving session: {}", e);
+            }
+        }
 
         // Check if the reasoning loop completed successfully
-        if let Some(e) = loop_result.err() {
-            tracing::error!("Error: reasoning loop failed: {}", e);
-            let _ = tx
-                .send(sse::Event::Data(sse::Data::new(
-                    serde_json::to_string(&StreamResponse::Error(
-                        e.to_string(),
+        let status = match loop_result {
+            Ok(_) => RunStatus::Completed,
+            Err(_) if cancel.is_cancelled() => RunStatus::Cancelled,
+            Err(e) => {
+                tracing::error!("Error: reasoning loop failed: {}", e);
+                let error = StreamResponse::Error(e.to_string());
+                if let Some(run_events) = &state.run_events {
+                    let _ = run_events.push(&run_id, &error).await;
+                }
+                let _ = tx
+                    .send(sse::Event::Data(
+                        sse::Data::new(
+                            serde_json::to_string(&error).unwrap(),
+                        )
+                        .id(index.to_string()),
                     ))
-                    .unwrap(),
-                )))
-                .await;
+                    .await;
+                RunStatus::Failed
+            }
+        };
+
+        state.runs.finish(&run_id);
+        if let Some(run_events) = &state.run_events {
+            let info = RunInfo { status, ..run_info };
+            if let Err(e) = run_events.set_info(&info).await {
+                tracing::error!("Error: saving run: {}", e);
+            }
         }
 
         Ok(())
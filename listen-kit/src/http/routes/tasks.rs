@@ -0,0 +1,271 @@
+use crate::agent::Features;
+use crate::http::middleware::verify_auth;
+use crate::http::state::AppState;
+use crate::http::tasks::{validate_webhook_url, Task, TaskRun};
+use actix_web::{
+    delete, get, patch, post, web, Error, HttpRequest, HttpResponse,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+pub struct CreateTaskRequest {
+    prompt: String,
+    /// Cron expression in UTC, e.g. "0 * * * *" for every hour
+    schedule: String,
+    #[serde(default)]
+    model_type: Option<String>,
+    #[serde(default)]
+    features: Option<Features>,
+    #[serde(default)]
+    allowed_tools: Option<Vec<String>>,
+    #[serde(default)]
+    locale: Option<String>,
+    #[serde(default)]
+    webhook_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTaskRequest {
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    schedule: Option<String>,
+    #[serde(default)]
+    prompt: Option<String>,
+}
+
+fn unauthorized(e: impl std::fmt::Display) -> HttpResponse {
+    tracing::error!("Error: unauthorized: {}", e);
+    HttpResponse::Unauthorized().json(json!({
+        "error": "unauthorized"
+    }))
+}
+
+fn bad_request(e: impl std::fmt::Display) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": e.to_string()
+    }))
+}
+
+/// Schedules a prompt, it runs under the user's wallets without asking for
+/// approvals, trading only if `features.autonomous` is set, which needs a
+/// spending policy with per-transaction and daily limits
+#[post("/tasks")]
+async fn create_task(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<CreateTaskRequest>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    let body = body.into_inner();
+    if let Some(webhook_url) = &body.webhook_url {
+        if let Err(e) = validate_webhook_url(webhook_url).await {
+            return Ok(bad_request(e));
+        }
+    }
+
+    let features = body.features.unwrap_or_default();
+    if features.autonomous {
+        match state.spending_policy(&user_session.user_id).await {
+            Ok(policy) if policy.bounds_value() => {}
+            Ok(_) => {
+                return Ok(bad_request(
+                    "autonomous tasks need max_tx_usd and daily_limit_usd in the spending policy",
+                ))
+            }
+            Err(e) => {
+                tracing::error!("Error: loading spending policy: {}", e);
+                return Ok(HttpResponse::InternalServerError().json(json!({
+                    "error": "failed to load spending policy"
+                })));
+            }
+        }
+    }
+
+    let task = match Task::new(
+        user_session.user_id,
+        user_session.wallet_address,
+        user_session.pubkey,
+        body.prompt,
+        body.schedule,
+        body.model_type,
+        features,
+        body.allowed_tools,
+        body.locale.unwrap_or("en".to_string()),
+        body.webhook_url,
+    ) {
+        Ok(task) => task,
+        Err(e) => return Ok(bad_request(e)),
+    };
+
+    match task.save(&state.mongo).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(task)),
+        Err(e) => {
+            tracing::error!("Error: saving task: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to create task"
+            })))
+        }
+    }
+}
+
+/// The user's tasks, most recently created first
+#[get("/tasks")]
+async fn list_tasks(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    match Task::list(&state.mongo, &user_session.user_id).await {
+        Ok(tasks) => Ok(HttpResponse::Ok().json(tasks)),
+        Err(e) => {
+            tracing::error!("Error: listing tasks: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to list tasks"
+            })))
+        }
+    }
+}
+
+#[get("/tasks/{task_id}")]
+async fn get_task(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    match Task::load(&state.mongo, &user_session.user_id, &path).await {
+        Ok(Some(task)) => Ok(HttpResponse::Ok().json(task)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "error": "task not found"
+        }))),
+        Err(e) => {
+            tracing::error!("Error: loading task: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to load task"
+            })))
+        }
+    }
+}
+
+/// Pauses or resumes the task, or changes its prompt or schedule
+#[patch("/tasks/{task_id}")]
+async fn update_task(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<UpdateTaskRequest>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    let mut task =
+        match Task::load(&state.mongo, &user_session.user_id, &path).await {
+            Ok(Some(task)) => task,
+            Ok(None) => {
+                return Ok(HttpResponse::NotFound().json(json!({
+                    "error": "task not found"
+                })))
+            }
+            Err(e) => {
+                tracing::error!("Error: loading task: {}", e);
+                return Ok(HttpResponse::InternalServerError().json(json!({
+                    "error": "failed to load task"
+                })));
+            }
+        };
+
+    let body = body.into_inner();
+    if let Some(prompt) = body.prompt {
+        task.prompt = prompt;
+    }
+    // a resumed task does not catch up on the runs it missed
+    let resumed = body.enabled == Some(true) && !task.enabled;
+    if let Some(enabled) = body.enabled {
+        task.enabled = enabled;
+    }
+    let schedule = body
+        .schedule
+        .or_else(|| resumed.then(|| task.schedule.clone()));
+    if let Some(schedule) = schedule {
+        if let Err(e) = task.reschedule(schedule) {
+            return Ok(bad_request(e));
+        }
+    }
+    task.updated_at = chrono::Utc::now().timestamp();
+
+    match task.save(&state.mongo).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(task)),
+        Err(e) => {
+            tracing::error!("Error: saving task: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to update task"
+            })))
+        }
+    }
+}
+
+#[delete("/tasks/{task_id}")]
+async fn delete_task(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    match Task::delete(&state.mongo, &user_session.user_id, &path).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(json!({
+            "deleted": path.into_inner()
+        }))),
+        Ok(false) => Ok(HttpResponse::NotFound().json(json!({
+            "error": "task not found"
+        }))),
+        Err(e) => {
+            tracing::error!("Error: deleting task: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to delete task"
+            })))
+        }
+    }
+}
+
+/// Transcripts of the task's runs, most recent first
+#[get("/tasks/{task_id}/runs")]
+async fn list_task_runs(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_session = match verify_auth(&req).await {
+        Ok(s) => s,
+        Err(e) => return Ok(unauthorized(e)),
+    };
+
+    match TaskRun::list(&state.mongo, &user_session.user_id, &path).await {
+        Ok(runs) => Ok(HttpResponse::Ok().json(runs)),
+        Err(e) => {
+            tracing::error!("Error: listing task runs: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "failed to list task runs"
+            })))
+        }
+    }
+}
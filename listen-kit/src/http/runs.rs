@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::reasoning_loop::StreamResponse;
+
+/// How long the events of a run can be replayed after it was last written
+pub const DEFAULT_RUN_EVENTS_TTL_SECS: u64 = 60 * 60;
+
+/// How often the runs streaming on an instance are checked for
+/// cancellations requested through other instances
+pub const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunInfo {
+    pub run_id: String,
+    pub user_id: String,
+    pub session_id: Option<String>,
+    pub status: RunStatus,
+    pub started_at: i64,
+}
+
+/// An event of a run, `index` is its SSE id and what `after` refers to
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunEvent {
+    pub index: usize,
+    pub event: StreamResponse,
+}
+
+struct ActiveRun {
+    user_id: String,
+    cancel: CancellationToken,
+}
+
+/// The runs streaming on this instance, so that they can be cancelled,
+/// from other instances too through [`RunEvents::request_cancel`]
+#[derive(Default)]
+pub struct RunRegistry {
+    active: Mutex<HashMap<String, ActiveRun>>,
+}
+
+impl RunRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a run of the user, the token is what the run listens to
+    pub fn start(&self, user_id: &str) -> (String, CancellationToken) {
+        let run_id = format!("{:032x}", rand::random::<u128>());
+        let cancel = CancellationToken::new();
+        self.active.lock().unwrap().insert(
+            run_id.clone(),
+            ActiveRun {
+                user_id: user_id.to_string(),
+                cancel: cancel.clone(),
+            },
+        );
+        (run_id, cancel)
+    }
+
+    /// Returns false if the user has no such run in progress here
+    pub fn cancel(&self, user_id: &str, run_id: &str) -> bool {
+        match self.active.lock().unwrap().get(run_id) {
+            Some(run) if run.user_id == user_id => {
+                run.cancel.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn active_ids(&self) -> Vec<String> {
+        self.active.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Cancels the runs streaming here that were cancelled through another
+    /// instance, the user was checked there
+    pub async fn watch_cancellations(self: Arc<Self>, events: RunEvents) {
+        loop {
+            tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
+            let run_ids = self.active_ids();
+            if run_ids.is_empty() {
+                continue;
+            }
+            let cancelled = match events.cancel_requested(&run_ids).await {
+                Ok(cancelled) => cancelled,
+                Err(e) => {
+                    tracing::error!(
+                        "Error: checking run cancellations: {}",
+                        e
+                    );
+                    continue;
+                }
+            };
+            let active = self.active.lock().unwrap();
+            for run_id in cancelled {
+                if let Some(run) = active.get(&run_id) {
+                    run.cancel.cancel();
+                }
+            }
+        }
+    }
+
+    pub fn finish(&self, run_id: &str) {
+        self.active.lock().unwrap().remove(run_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.active.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn info_key(run_id: &str) -> String {
+    format!("run:{}:info", run_id)
+}
+
+fn events_key(run_id: &str) -> String {
+    format!("run:{}:events", run_id)
+}
+
+fn cancel_key(run_id: &str) -> String {
+    format!("run:{}:cancel", run_id)
+}
+
+/// Buffers what each run streamed in Redis, so that clients can pick a run
+/// up again from any instance after losing the connection
+#[derive(Clone)]
+pub struct RunEvents {
+    conn: MultiplexedConnection,
+    ttl_secs: u64,
+}
+
+impl RunEvents {
+    pub async fn new(redis_url: &str, ttl_secs: u64) -> Result<Self> {
+        let client = redis::Client::open(redis_url)?;
+        let conn = client.get_multiplexed_async_connection().await?;
+        Ok(Self { conn, ttl_secs })
+    }
+
+    /// Uses `REDIS_URL` and `RUN_EVENTS_TTL_SECS`, None if there is no
+    /// Redis configured
+    pub async fn from_env() -> Result<Option<Self>> {
+        let Ok(redis_url) = std::env::var("REDIS_URL") else {
+            return Ok(None);
+        };
+        let ttl_secs = std::env::var("RUN_EVENTS_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RUN_EVENTS_TTL_SECS);
+        Ok(Some(Self::new(&redis_url, ttl_secs).await?))
+    }
+
+    pub async fn set_info(&self, info: &RunInfo) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let _: () = conn
+            .set_ex(
+                info_key(&info.run_id),
+                serde_json::to_string(info)?,
+                self.ttl_secs,
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn info(&self, run_id: &str) -> Result<Option<RunInfo>> {
+        let mut conn = self.conn.clone();
+        let info: Option<String> = conn.get(info_key(run_id)).await?;
+        Ok(info.map(|i| serde_json::from_str(&i)).transpose()?)
+    }
+
+    /// Asks whichever instance streams the run to cancel it
+    pub async fn request_cancel(&self, run_id: &str) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let _: () = conn.set_ex(cancel_key(run_id), 1, self.ttl_secs).await?;
+        Ok(())
+    }
+
+    /// The runs among `run_ids` that were asked to cancel
+    pub async fn cancel_requested(
+        &self,
+        run_ids: &[String],
+    ) -> Result<Vec<String>> {
+        let mut conn = self.conn.clone();
+        let keys: Vec<String> =
+            run_ids.iter().map(|id| cancel_key(id)).collect();
+        let flags: Vec<Option<u8>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+        Ok(run_ids
+            .iter()
+            .zip(flags)
+            .filter(|(_, flag)| flag.is_some())
+            .map(|(run_id, _)| run_id.clone())
+            .collect())
+    }
+
+    /// Appends the event, events are indexed in the order they were pushed
+    pub async fn push(
+        &self,
+        run_id: &str,
+        event: &StreamResponse,
+    ) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let key = events_key(run_id);
+        let _: () = redis::pipe()
+            .rpush(&key, serde_json::to_string(event)?)
+            .ignore()
+            .expire(&key, self.ttl_secs as i64)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// The events with an index of at least `from`
+    pub async fn events_from(
+        &self,
+        run_id: &str,
+        from: usize,
+    ) -> Result<Vec<RunEvent>> {
+        let mut conn = self.conn.clone();
+        let events: Vec<String> =
+            conn.lrange(events_key(run_id), from as isize, -1).await?;
+        events
+            .into_iter()
+            .enumerate()
+            .map(|(i, event)| {
+                Ok(RunEvent {
+                    index: from + i,
+                    event: serde_json::from_str(&event)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_registry_cancel() {
+        let registry = RunRegistry::new();
+        let (run_id, cancel) = registry.start("user");
+
+        assert!(!registry.cancel("someone else", &run_id));
+        assert!(!cancel.is_cancelled());
+
+        assert_eq!(registry.active_ids(), vec![run_id.clone()]);
+        assert!(registry.cancel("user", &run_id));
+        assert!(cancel.is_cancelled());
+
+        registry.finish(&run_id);
+        assert!(registry.is_empty());
+        assert!(!registry.cancel("user", &run_id));
+    }
+}
@@ -0,0 +1,394 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use privy::auth::UserSession;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::agent::{
+    create_listen_agent_with_model, model_to_versioned_model,
+};
+use crate::common::spawn_with_signer;
+use crate::http::routes::join::join_responses;
+use crate::http::runs::{RunInfo, RunStatus};
+use crate::http::state::AppState;
+use crate::http::tasks::{validate_webhook_url, Task, TaskRun};
+use crate::reasoning_loop::usage::UsageReport;
+use crate::reasoning_loop::{
+    Model, ReasoningLoop, StreamResponse, RUN_CANCELLED,
+};
+use crate::signer::policy::PolicySigner;
+use crate::signer::privy::PrivySigner;
+use crate::signer::TransactionSigner;
+
+const PREAMBLE: &str = r#"
+You are running a task the user scheduled, the user is not there to answer
+questions or to approve anything. Do not ask for confirmation, carry the task
+out with the tools you have and end with a short report of what you found
+and did. If the task cannot be done, say why instead."#;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Which wallets scheduled tasks sign with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskSignerKind {
+    /// The Privy wallets of the user that created the task
+    Privy,
+    /// The keys of the server (`SOLANA_PRIVATE_KEY`,
+    /// `ETHEREUM_PRIVATE_KEY`), for single-user deployments, only the tasks
+    /// of the owner sign with them
+    Local,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub poll_interval: Duration,
+    pub max_concurrent_runs: usize,
+    pub signer: TaskSignerKind,
+    /// User whose tasks sign with the local keys, nobody's if None
+    pub owner_user_id: Option<String>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(30),
+            max_concurrent_runs: 4,
+            signer: TaskSignerKind::Privy,
+            owner_user_id: None,
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// Uses `SCHEDULER_POLL_SECS`, `SCHEDULER_MAX_CONCURRENT_RUNS`,
+    /// `SCHEDULER_SIGNER` ("privy" or "local") and `SCHEDULER_OWNER_USER_ID`
+    /// if set
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok())
+        };
+        let default = Self::default();
+        Self {
+            poll_interval: var("SCHEDULER_POLL_SECS")
+                .map_or(default.poll_interval, Duration::from_secs),
+            max_concurrent_runs: var("SCHEDULER_MAX_CONCURRENT_RUNS")
+                .map_or(default.max_concurrent_runs, |v| v as usize),
+            signer: match std::env::var("SCHEDULER_SIGNER").as_deref() {
+                Ok("local") => TaskSignerKind::Local,
+                _ => default.signer,
+            },
+            owner_user_id: std::env::var("SCHEDULER_OWNER_USER_ID").ok(),
+        }
+    }
+}
+
+/// Runs the tasks that are due, every instance can run one, a task is only
+/// picked up by the instance that claims it first
+pub struct Scheduler {
+    state: Arc<AppState>,
+    config: SchedulerConfig,
+    http: reqwest::Client,
+}
+
+impl Scheduler {
+    pub fn new(state: Arc<AppState>, config: SchedulerConfig) -> Self {
+        Self {
+            state,
+            config,
+            // a redirect could lead the webhook past the address checks
+            http: reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("failed to build http client"),
+        }
+    }
+
+    pub async fn run(self) {
+        let scheduler = Arc::new(self);
+        let permits =
+            Arc::new(Semaphore::new(scheduler.config.max_concurrent_runs));
+        tracing::info!(config = ?scheduler.config, "Scheduler started");
+        loop {
+            let now = chrono::Utc::now().timestamp();
+            let tasks = match Task::due(&scheduler.state.mongo, now).await {
+                Ok(tasks) => tasks,
+                Err(e) => {
+                    tracing::error!("Error: loading due tasks: {}", e);
+                    Vec::new()
+                }
+            };
+            for task in tasks {
+                // tasks that are not claimed stay due for the next poll
+                let Ok(permit) = permits.clone().try_acquire_owned() else {
+                    break;
+                };
+                match task.claim(&scheduler.state.mongo, now).await {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(e) => {
+                        tracing::error!(
+                            task_id = %task.task_id,
+                            "Error: claiming task: {}",
+                            e
+                        );
+                        continue;
+                    }
+                }
+                let scheduler = scheduler.clone();
+                tokio::spawn(async move {
+                    scheduler.run_task(task).await;
+                    drop(permit);
+                });
+            }
+            tokio::time::sleep(scheduler.config.poll_interval).await;
+        }
+    }
+
+    async fn signer(
+        &self,
+        task: &Task,
+    ) -> Result<Arc<dyn TransactionSigner>> {
+        let inner: Arc<dyn TransactionSigner> = match self.config.signer {
+            TaskSignerKind::Privy => Arc::new(PrivySigner::new(
+                self.state.privy.clone(),
+                UserSession {
+                    user_id: task.user_id.clone(),
+                    session_id: String::new(),
+                    wallet_address: task.wallet_address.clone(),
+                    pubkey: task.pubkey.clone(),
+                    email: None,
+                },
+                task.locale.clone(),
+            )),
+            TaskSignerKind::Local => {
+                if self.config.owner_user_id.as_deref()
+                    != Some(task.user_id.as_str())
+                {
+                    return Err(anyhow!(
+                        "the server keys only sign tasks of its owner"
+                    ));
+                }
+                local_signer()?
+            }
+        };
+        // nobody is there to approve, the spending policy is what bounds
+        // the task
+        let spending_policy =
+            self.state.spending_policy(&task.user_id).await?;
+        Ok(Arc::new(
+            PolicySigner::new(
+                inner,
+                spending_policy,
+                self.state.spend_ledger.clone(),
+            )
+            .unattended(),
+        ))
+    }
+
+    /// Runs the task once and stores the transcript, the run can be
+    /// cancelled and followed through `/runs/{id}` like a `/stream` run
+    async fn run_task(&self, task: Task) {
+        let (run_id, cancel) = self.state.runs.start(&task.user_id);
+        let started_at = chrono::Utc::now().timestamp();
+        tracing::info!(task_id = %task.task_id, run_id = %run_id, "Running task");
+
+        let run_info = RunInfo {
+            run_id: run_id.clone(),
+            user_id: task.user_id.clone(),
+            session_id: None,
+            status: RunStatus::Running,
+            started_at,
+        };
+        if let Some(run_events) = &self.state.run_events {
+            if let Err(e) = run_events.set_info(&run_info).await {
+                tracing::error!("Error: saving run: {}", e);
+            }
+        }
+
+        let (responses, usage, loop_result) = match self.signer(&task).await {
+            Ok(signer) => {
+                self.stream_task(&task, &run_id, cancel.clone(), signer)
+                    .await
+            }
+            Err(e) => (Vec::new(), None, Err(e)),
+        };
+
+        let (status, error) = match loop_result {
+            Ok(()) => (RunStatus::Completed, None),
+            Err(_) if cancel.is_cancelled() => {
+                (RunStatus::Cancelled, Some(RUN_CANCELLED.to_string()))
+            }
+            Err(e) => {
+                tracing::error!(
+                    task_id = %task.task_id,
+                    "Error: task run failed: {}",
+                    e
+                );
+                (RunStatus::Failed, Some(e.to_string()))
+            }
+        };
+
+        self.state.runs.finish(&run_id);
+        if let Some(run_events) = &self.state.run_events {
+            if let (RunStatus::Failed, Some(error)) = (status, &error) {
+                let error = StreamResponse::Error(error.clone());
+                let _ = run_events.push(&run_id, &error).await;
+            }
+            let info = RunInfo { status, ..run_info };
+            if let Err(e) = run_events.set_info(&info).await {
+                tracing::error!("Error: saving run: {}", e);
+            }
+        }
+
+        let task_run = TaskRun {
+            run_id,
+            task_id: task.task_id.clone(),
+            user_id: task.user_id.clone(),
+            prompt: task.prompt.clone(),
+            status,
+            responses: join_responses(responses),
+            error,
+            usage,
+            started_at,
+            finished_at: chrono::Utc::now().timestamp(),
+        };
+        if let Err(e) = task_run.save(&self.state.mongo).await {
+            tracing::error!("Error: saving task run: {}", e);
+        }
+
+        // reload, the user might have changed the task while it ran
+        match Task::load_any(&self.state.mongo, &task.task_id).await {
+            Ok(Some(mut task)) => {
+                task.last_run_at = Some(task_run.started_at);
+                task.last_status = Some(status);
+                if let Err(e) = task.save(&self.state.mongo).await {
+                    tracing::error!("Error: saving task: {}", e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!("Error: loading task: {}", e),
+        }
+
+        if let Some(webhook_url) = &task.webhook_url {
+            if let Err(e) = self.notify(webhook_url, &task_run).await {
+                tracing::error!(
+                    task_id = %task.task_id,
+                    "Error: notifying about task run: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    /// Streams the task into the run's event buffer, returns what it
+    /// streamed and used along with how the loop ended
+    async fn stream_task(
+        &self,
+        task: &Task,
+        run_id: &str,
+        cancel: CancellationToken,
+        signer: Arc<dyn TransactionSigner>,
+    ) -> (Vec<StreamResponse>, Option<UsageReport>, Result<()>) {
+        let model =
+            Model::OpenRouter(Arc::new(create_listen_agent_with_model(
+                Some(PREAMBLE.to_string()),
+                task.features.clone(),
+                task.locale.clone(),
+                model_to_versioned_model(
+                    task.model.clone().unwrap_or_default(),
+                ),
+            )));
+        let mut reasoning_loop = ReasoningLoop::new(model)
+            .with_stdout(false)
+            .with_cancellation(cancel);
+        if let Some(tools) = &task.allowed_tools {
+            reasoning_loop = reasoning_loop.with_allowed_tools(tools.clone());
+        }
+        let tracker = reasoning_loop.tracker();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<StreamResponse>(1024);
+        let collector = tokio::spawn({
+            let run_events = self.state.run_events.clone();
+            let run_id = run_id.to_string();
+            async move {
+                let mut responses = Vec::new();
+                while let Some(response) = rx.recv().await {
+                    if let Some(run_events) = &run_events {
+                        if let Err(e) =
+                            run_events.push(&run_id, &response).await
+                        {
+                            tracing::error!(
+                                "Error: buffering run event: {}",
+                                e
+                            );
+                        }
+                    }
+                    responses.push(response);
+                }
+                responses
+            }
+        });
+
+        let _ = tx
+            .send(StreamResponse::RunStarted {
+                run_id: run_id.to_string(),
+            })
+            .await;
+
+        let prompt = task.prompt.clone();
+        let user_id = task.user_id.clone();
+        let global_memory = task
+            .features
+            .memory
+            .then(|| self.state.global_memory.clone());
+        let loop_result = spawn_with_signer(signer, move || async move {
+            reasoning_loop
+                .stream(prompt, vec![], Some(tx), global_memory, user_id)
+                .await
+        })
+        .await
+        .await;
+        let loop_result = match loop_result {
+            Ok(result) => result.map(|_| ()),
+            Err(e) => Err(anyhow!("task run panicked: {}", e)),
+        };
+
+        let responses = collector.await.unwrap_or_default();
+        (responses, Some(tracker.report()), loop_result)
+    }
+
+    async fn notify(
+        &self,
+        webhook_url: &str,
+        task_run: &TaskRun,
+    ) -> Result<()> {
+        // checked again, what the host resolves to might have changed
+        validate_webhook_url(webhook_url).await?;
+        self.http
+            .post(webhook_url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(&task_run.notification())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+fn local_signer() -> Result<Arc<dyn TransactionSigner>> {
+    #[cfg(feature = "solana")]
+    if let Ok(private_key) = std::env::var("SOLANA_PRIVATE_KEY") {
+        return Ok(Arc::new(crate::signer::solana::LocalSolanaSigner::new(
+            private_key,
+        )));
+    }
+    #[cfg(feature = "evm")]
+    if let Ok(private_key) = std::env::var("ETHEREUM_PRIVATE_KEY") {
+        return Ok(Arc::new(crate::signer::evm::LocalEvmSigner::new(
+            private_key,
+        )));
+    }
+    Err(anyhow!("no local signer key is set"))
+}
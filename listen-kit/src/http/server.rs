@@ -3,7 +3,13 @@ use actix_web::middleware::{Compress, Logger};
 use actix_web::{web, App, HttpServer};
 use privy::Privy;
 
-use super::routes::{auth, healthz, stream, suggest};
+use super::routes::{
+    approve, auth, cancel_run, create_session, create_task, delete_session,
+    delete_task, get_policy, get_session, get_task, healthz, list_sessions,
+    list_task_runs, list_tasks, portfolio, put_policy, run_events, stream,
+    suggest, update_task,
+};
+use super::scheduler::{Scheduler, SchedulerConfig};
 use super::state::AppState;
 use listen_mongo::MongoClient;
 
@@ -16,6 +22,18 @@ pub async fn run_server(
             std::io::Error::new(std::io::ErrorKind::Other, e)
         })?);
 
+    if let Some(run_events) = state.run_events.clone() {
+        tokio::spawn(state.runs.clone().watch_cancellations(run_events));
+    }
+
+    tokio::spawn(
+        Scheduler::new(
+            state.clone().into_inner(),
+            SchedulerConfig::from_env(),
+        )
+        .run(),
+    );
+
     HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
@@ -32,6 +50,22 @@ pub async fn run_server(
             .service(stream)
             .service(auth)
             .service(suggest)
+            .service(approve)
+            .service(get_policy)
+            .service(put_policy)
+            .service(portfolio)
+            .service(create_session)
+            .service(list_sessions)
+            .service(get_session)
+            .service(delete_session)
+            .service(cancel_run)
+            .service(run_events)
+            .service(create_task)
+            .service(list_tasks)
+            .service(get_task)
+            .service(update_task)
+            .service(delete_task)
+            .service(list_task_runs)
     })
     .bind("0.0.0.0:6969")?
     .run()
@@ -0,0 +1,202 @@
+use anyhow::Result;
+use listen_mongo::MongoClient;
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::reasoning_loop::compaction::{split_summary, summary_exchange};
+use crate::reasoning_loop::usage::UsageReport;
+use crate::reasoning_loop::StreamResponse;
+
+pub const SESSIONS_COLLECTION: &str = "sessions";
+
+const TITLE_MAX_CHARS: usize = 60;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    Idle,
+    /// A run is in progress, the session can be re-fetched once it is done
+    /// even if the client disconnected
+    Running,
+}
+
+/// A prompt and everything that was streamed back for it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionTurn {
+    pub prompt: String,
+    pub responses: Vec<StreamResponse>,
+    #[serde(default)]
+    pub usage: Option<UsageReport>,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Session {
+    pub session_id: String,
+    pub user_id: String,
+    pub title: Option<String>,
+    pub status: SessionStatus,
+    /// Summary of the turns that were compacted out of `messages`
+    pub summary: Option<String>,
+    /// What the model sees on the next prompt, after the summary
+    pub messages: Vec<Message>,
+    /// Every turn as the client rendered it, never compacted
+    pub turns: Vec<SessionTurn>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// What `GET /sessions` returns for each session
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub title: Option<String>,
+    pub status: SessionStatus,
+    pub turns: usize,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Session {
+    pub fn new(user_id: String) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            session_id: format!("{:032x}", rand::random::<u128>()),
+            user_id,
+            title: None,
+            status: SessionStatus::Idle,
+            summary: None,
+            messages: Vec::new(),
+            turns: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn info(&self) -> SessionInfo {
+        SessionInfo {
+            session_id: self.session_id.clone(),
+            title: self.title.clone(),
+            status: self.status,
+            turns: self.turns.len(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// The chat history to run the next prompt with, the summary goes
+    /// first as an exchange of its own
+    pub fn history(&self) -> Vec<Message> {
+        let mut history = match &self.summary {
+            Some(summary) => summary_exchange(summary),
+            None => Vec::new(),
+        };
+        history.extend(self.messages.iter().cloned());
+        history
+    }
+
+    /// Records a finished run, `messages` is the history the reasoning loop
+    /// returned, compacted by it if it got too long
+    pub fn finish_turn(
+        &mut self,
+        prompt: String,
+        responses: Vec<StreamResponse>,
+        messages: Option<Vec<Message>>,
+        usage: Option<UsageReport>,
+    ) {
+        if let Some(messages) = messages {
+            (self.summary, self.messages) = split_summary(messages);
+        }
+        if self.title.is_none() {
+            self.title = Some(prompt.chars().take(TITLE_MAX_CHARS).collect());
+        }
+        let now = chrono::Utc::now().timestamp();
+        self.turns.push(SessionTurn {
+            prompt,
+            responses,
+            usage,
+            created_at: now,
+        });
+        self.status = SessionStatus::Idle;
+        self.updated_at = now;
+    }
+
+    /// Loads the session if it exists and belongs to the user
+    pub async fn load(
+        mongo: &MongoClient,
+        user_id: &str,
+        session_id: &str,
+    ) -> Result<Option<Self>> {
+        Ok(mongo
+            .find_one_by::<Session>(
+                SESSIONS_COLLECTION,
+                "session_id",
+                session_id,
+            )
+            .await?
+            .filter(|s| s.user_id == user_id))
+    }
+
+    /// Most recently updated first
+    pub async fn list(
+        mongo: &MongoClient,
+        user_id: &str,
+    ) -> Result<Vec<SessionInfo>> {
+        let mut sessions = mongo
+            .find_many_by::<Session>(SESSIONS_COLLECTION, "user_id", user_id)
+            .await?
+            .iter()
+            .map(Session::info)
+            .collect::<Vec<_>>();
+        sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(sessions)
+    }
+
+    pub async fn save(&self, mongo: &MongoClient) -> Result<()> {
+        mongo
+            .upsert_by(
+                SESSIONS_COLLECTION,
+                "session_id",
+                &self.session_id,
+                self.clone(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Returns whether there was such a session of the user
+    pub async fn delete(
+        mongo: &MongoClient,
+        user_id: &str,
+        session_id: &str,
+    ) -> Result<bool> {
+        if Self::load(mongo, user_id, session_id).await?.is_none() {
+            return Ok(false);
+        }
+        Ok(mongo
+            .delete_one_by(SESSIONS_COLLECTION, "session_id", session_id)
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_strips_summary_on_finish() {
+        let mut session = Session::new("user".to_string());
+        session.summary = Some("earlier".to_string());
+        session.messages = vec![Message::user("hi".to_string())];
+
+        let mut messages = session.history();
+        assert_eq!(messages.len(), 3);
+        messages.push(Message::assistant("hello".to_string()));
+
+        session.finish_turn("hi".to_string(), vec![], Some(messages), None);
+        assert_eq!(session.summary.as_deref(), Some("earlier"));
+        assert_eq!(session.messages.len(), 2);
+        assert_eq!(session.title.as_deref(), Some("hi"));
+        assert_eq!(session.status, SessionStatus::Idle);
+    }
+}
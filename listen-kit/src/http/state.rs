@@ -1,14 +1,36 @@
 use anyhow::Result;
 use privy::Privy;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use listen_memory::graph::GraphMemory;
 use listen_mongo::MongoClient;
 
+use crate::http::runs::{RunEvents, RunRegistry};
+use crate::signer::approval::{ApprovalPolicy, Approvals};
+use crate::signer::policy::{SpendLedger, SpendingPolicy};
+
+pub const SPENDING_POLICIES_COLLECTION: &str = "spending_policies";
+
+#[derive(Serialize, Deserialize)]
+pub struct UserSpendingPolicy {
+    pub user_id: String,
+    #[serde(flatten)]
+    pub policy: SpendingPolicy,
+}
+
 pub struct AppState {
     pub(crate) privy: Arc<Privy>,
     pub(crate) mongo: Arc<MongoClient>,
     pub(crate) global_memory: Arc<GraphMemory>,
+    pub(crate) approvals: Arc<Approvals>,
+    pub(crate) approval_policy: ApprovalPolicy,
+    pub(crate) spend_ledger: Arc<SpendLedger>,
+    /// Applies to users that did not set a policy of their own
+    pub(crate) default_spending_policy: SpendingPolicy,
+    pub(crate) runs: Arc<RunRegistry>,
+    /// Without Redis runs cannot be resumed through `/runs/{id}/events`
+    pub(crate) run_events: Option<RunEvents>,
 }
 
 impl AppState {
@@ -17,6 +39,28 @@ impl AppState {
             privy: Arc::new(privy),
             mongo: Arc::new(mongo),
             global_memory: Arc::new(GraphMemory::from_env().await?),
+            approvals: Arc::new(Approvals::new()),
+            approval_policy: ApprovalPolicy::from_env(),
+            spend_ledger: Arc::new(SpendLedger::from_env().await?),
+            default_spending_policy: SpendingPolicy::from_env(),
+            runs: Arc::new(RunRegistry::new()),
+            run_events: RunEvents::from_env().await?,
         })
     }
+
+    pub async fn spending_policy(
+        &self,
+        user_id: &str,
+    ) -> Result<SpendingPolicy> {
+        Ok(self
+            .mongo
+            .find_one_by::<UserSpendingPolicy>(
+                SPENDING_POLICIES_COLLECTION,
+                "user_id",
+                user_id,
+            )
+            .await?
+            .map(|p| p.policy)
+            .unwrap_or_else(|| self.default_spending_policy.clone()))
+    }
 }
@@ -0,0 +1,453 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use listen_mongo::MongoClient;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::Features;
+use crate::http::runs::RunStatus;
+use crate::reasoning_loop::usage::UsageReport;
+use crate::reasoning_loop::StreamResponse;
+
+pub const TASKS_COLLECTION: &str = "tasks";
+pub const TASK_RUNS_COLLECTION: &str = "task_runs";
+
+/// Schedules that fire more often than this are refused
+pub const MIN_TASK_INTERVAL_SECS: i64 = 5 * 60;
+
+/// Parses a cron expression, the standard five fields (minute, hour, day
+/// of month, month, day of week) or six with seconds first, in UTC
+pub fn parse_schedule(expression: &str) -> Result<Schedule> {
+    let expression = expression.trim();
+    let schedule = match expression.split_whitespace().count() {
+        5 => Schedule::from_str(&format!("0 {}", expression)),
+        6 => Schedule::from_str(expression),
+        _ => return Err(anyhow!("expected a cron expression of 5 fields")),
+    }
+    .map_err(|e| anyhow!("invalid schedule: {}", e))?;
+
+    let mut upcoming = schedule.upcoming(Utc);
+    let (Some(first), Some(second)) = (upcoming.next(), upcoming.next())
+    else {
+        return Err(anyhow!("schedule never fires"));
+    };
+    if (second - first).num_seconds() < MIN_TASK_INTERVAL_SECS {
+        return Err(anyhow!(
+            "schedule fires more often than every {} minutes",
+            MIN_TASK_INTERVAL_SECS / 60
+        ));
+    }
+    Ok(schedule)
+}
+
+/// Timestamp of the first time the schedule fires after `after`
+pub fn next_run_after(schedule: &Schedule, after: i64) -> Result<i64> {
+    let after = DateTime::<Utc>::from_timestamp(after, 0)
+        .ok_or_else(|| anyhow!("invalid timestamp: {}", after))?;
+    schedule
+        .after(&after)
+        .next()
+        .map(|next| next.timestamp())
+        .ok_or_else(|| anyhow!("schedule never fires again"))
+}
+
+/// Whether the address is reachable from the internet, webhooks are not
+/// posted to private, loopback or link-local ones
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // shared address space, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Webhooks have to be https and resolve to public addresses only, so that
+/// tasks cannot reach the network of the server
+pub async fn validate_webhook_url(webhook_url: &str) -> Result<()> {
+    let url = reqwest::Url::parse(webhook_url)
+        .map_err(|e| anyhow!("invalid webhook_url: {}", e))?;
+    if url.scheme() != "https" {
+        return Err(anyhow!("webhook_url has to be an https url"));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("webhook_url has no host"))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    // IPv6 hosts come in brackets
+    let ips = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| anyhow!("webhook_url does not resolve: {}", e))?
+            .map(|addr| addr.ip())
+            .collect(),
+    };
+    if ips.is_empty() || !ips.into_iter().all(is_public_ip) {
+        return Err(anyhow!("webhook_url has to be a public address"));
+    }
+    Ok(())
+}
+
+/// A prompt the scheduler runs on behalf of the user, without the user
+/// being there to answer questions or approve transactions
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Task {
+    pub task_id: String,
+    pub user_id: String,
+    /// Wallets of the user when the task was created, what the task signs
+    /// with through Privy
+    pub wallet_address: Option<String>,
+    pub pubkey: Option<String>,
+    pub prompt: String,
+    /// Cron expression, see `parse_schedule`
+    pub schedule: String,
+    /// Model type as `/stream` takes it, e.g. "gemini" or "claude"
+    pub model: Option<String>,
+    /// `autonomous` lets the task trade, bounded by the user's spending
+    /// policy
+    pub features: Features,
+    /// Tools the task may call, any the features equip if None
+    pub allowed_tools: Option<Vec<String>>,
+    pub locale: String,
+    /// Gets a `TaskNotification` once a run is done
+    pub webhook_url: Option<String>,
+    pub enabled: bool,
+    pub next_run_at: i64,
+    pub last_run_at: Option<i64>,
+    pub last_status: Option<RunStatus>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// What a run of a task streamed, stored once it is done
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TaskRun {
+    pub run_id: String,
+    pub task_id: String,
+    pub user_id: String,
+    pub prompt: String,
+    pub status: RunStatus,
+    pub responses: Vec<StreamResponse>,
+    pub error: Option<String>,
+    pub usage: Option<UsageReport>,
+    pub started_at: i64,
+    pub finished_at: i64,
+}
+
+/// Posted to the task's `webhook_url` once a run is done
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TaskNotification {
+    pub task_id: String,
+    pub run_id: String,
+    pub status: RunStatus,
+    pub prompt: String,
+    /// The final answer of the agent, or the error it failed with
+    pub output: String,
+    pub finished_at: i64,
+}
+
+impl TaskRun {
+    pub fn notification(&self) -> TaskNotification {
+        let output = match &self.error {
+            Some(error) => error.clone(),
+            None => self
+                .responses
+                .iter()
+                .rev()
+                .find_map(|response| match response {
+                    StreamResponse::Message(message) => Some(message.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default(),
+        };
+        TaskNotification {
+            task_id: self.task_id.clone(),
+            run_id: self.run_id.clone(),
+            status: self.status,
+            prompt: self.prompt.clone(),
+            output,
+            finished_at: self.finished_at,
+        }
+    }
+
+    pub async fn save(&self, mongo: &MongoClient) -> Result<()> {
+        mongo
+            .upsert_by(
+                TASK_RUNS_COLLECTION,
+                "run_id",
+                &self.run_id,
+                self.clone(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Most recent first
+    pub async fn list(
+        mongo: &MongoClient,
+        user_id: &str,
+        task_id: &str,
+    ) -> Result<Vec<TaskRun>> {
+        let mut runs = mongo
+            .find_many_by::<TaskRun>(TASK_RUNS_COLLECTION, "task_id", task_id)
+            .await?
+            .into_iter()
+            .filter(|r| r.user_id == user_id)
+            .collect::<Vec<_>>();
+        runs.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        Ok(runs)
+    }
+}
+
+impl Task {
+    /// Validates the schedule, the first run is the next time it fires
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        user_id: String,
+        wallet_address: Option<String>,
+        pubkey: Option<String>,
+        prompt: String,
+        schedule: String,
+        model: Option<String>,
+        features: Features,
+        allowed_tools: Option<Vec<String>>,
+        locale: String,
+        webhook_url: Option<String>,
+    ) -> Result<Self> {
+        let now = Utc::now().timestamp();
+        let next_run_at = next_run_after(&parse_schedule(&schedule)?, now)?;
+        Ok(Self {
+            task_id: format!("{:032x}", rand::random::<u128>()),
+            user_id,
+            wallet_address,
+            pubkey,
+            prompt,
+            schedule,
+            model,
+            features,
+            allowed_tools,
+            locale,
+            webhook_url,
+            enabled: true,
+            next_run_at,
+            last_run_at: None,
+            last_status: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Changes the schedule, the next run is the next time the new one
+    /// fires
+    pub fn reschedule(&mut self, schedule: String) -> Result<()> {
+        let now = Utc::now().timestamp();
+        self.next_run_at = next_run_after(&parse_schedule(&schedule)?, now)?;
+        self.schedule = schedule;
+        self.updated_at = now;
+        Ok(())
+    }
+
+    /// Loads the task if it exists and belongs to the user
+    pub async fn load(
+        mongo: &MongoClient,
+        user_id: &str,
+        task_id: &str,
+    ) -> Result<Option<Self>> {
+        Ok(Self::load_any(mongo, task_id)
+            .await?
+            .filter(|t| t.user_id == user_id))
+    }
+
+    pub(crate) async fn load_any(
+        mongo: &MongoClient,
+        task_id: &str,
+    ) -> Result<Option<Self>> {
+        Ok(mongo
+            .find_one_by::<Task>(TASKS_COLLECTION, "task_id", task_id)
+            .await?)
+    }
+
+    /// Most recently created first
+    pub async fn list(
+        mongo: &MongoClient,
+        user_id: &str,
+    ) -> Result<Vec<Self>> {
+        let mut tasks = mongo
+            .find_many_by::<Task>(TASKS_COLLECTION, "user_id", user_id)
+            .await?;
+        tasks.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(tasks)
+    }
+
+    /// Enabled tasks whose next run is due
+    pub async fn due(mongo: &MongoClient, now: i64) -> Result<Vec<Self>> {
+        Ok(mongo
+            .find_many_lte::<Task>(TASKS_COLLECTION, "next_run_at", now)
+            .await?
+            .into_iter()
+            .filter(|t| t.enabled)
+            .collect())
+    }
+
+    /// Moves the next run to the next time the schedule fires after `now`,
+    /// returns false if another instance got to the task first
+    pub async fn claim(&self, mongo: &MongoClient, now: i64) -> Result<bool> {
+        let next_run_at =
+            next_run_after(&parse_schedule(&self.schedule)?, now)?;
+        Ok(mongo
+            .compare_and_set(
+                TASKS_COLLECTION,
+                "task_id",
+                &self.task_id,
+                "next_run_at",
+                self.next_run_at,
+                next_run_at,
+            )
+            .await?)
+    }
+
+    pub async fn save(&self, mongo: &MongoClient) -> Result<()> {
+        mongo
+            .upsert_by(
+                TASKS_COLLECTION,
+                "task_id",
+                &self.task_id,
+                self.clone(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Returns whether there was such a task of the user
+    pub async fn delete(
+        mongo: &MongoClient,
+        user_id: &str,
+        task_id: &str,
+    ) -> Result<bool> {
+        if Self::load(mongo, user_id, task_id).await?.is_none() {
+            return Ok(false);
+        }
+        Ok(mongo
+            .delete_one_by(TASKS_COLLECTION, "task_id", task_id)
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_schedule() {
+        assert!(parse_schedule("0 * * * *").is_ok());
+        assert!(parse_schedule("0 30 9 * * Mon-Fri").is_ok());
+        assert!(parse_schedule("* * * * *").is_err());
+        assert!(parse_schedule("every hour").is_err());
+    }
+
+    #[test]
+    fn test_next_run_after() {
+        let schedule = parse_schedule("0 * * * *").unwrap();
+        // 2025-01-01 10:15:00 UTC
+        let after = 1_735_726_500;
+        assert_eq!(next_run_after(&schedule, after).unwrap(), 1_735_729_200);
+        assert_eq!(
+            next_run_after(&schedule, 1_735_729_200).unwrap(),
+            1_735_732_800
+        );
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert!(is_public_ip(ip("8.8.8.8")));
+        assert!(is_public_ip(ip("2606:4700:4700::1111")));
+        for private in [
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public_ip(ip(private)), "{}", private);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_webhook_url() {
+        assert!(validate_webhook_url("https://8.8.8.8/hook").await.is_ok());
+        assert!(validate_webhook_url("http://8.8.8.8/hook").await.is_err());
+        assert!(validate_webhook_url("https://169.254.169.254/latest")
+            .await
+            .is_err());
+        assert!(validate_webhook_url("https://[::1]/hook").await.is_err());
+        assert!(validate_webhook_url("https://localhost/hook")
+            .await
+            .is_err());
+        assert!(validate_webhook_url("not a url").await.is_err());
+    }
+
+    #[test]
+    fn test_notification_output() {
+        let mut run = TaskRun {
+            run_id: "run".to_string(),
+            task_id: "task".to_string(),
+            user_id: "user".to_string(),
+            prompt: "digest".to_string(),
+            status: RunStatus::Completed,
+            responses: vec![
+                StreamResponse::Message("looking".to_string()),
+                StreamResponse::ToolResult {
+                    id: "1".to_string(),
+                    name: "search_web".to_string(),
+                    result: "...".to_string(),
+                },
+                StreamResponse::Message("SOL is up 5%".to_string()),
+            ],
+            error: None,
+            usage: None,
+            started_at: 0,
+            finished_at: 1,
+        };
+        assert_eq!(run.notification().output, "SOL is up 5%");
+
+        run.status = RunStatus::Failed;
+        run.error = Some("run stopped after 50 tool calls".to_string());
+        assert_eq!(
+            run.notification().output,
+            "run stopped after 50 tool calls"
+        );
+    }
+}
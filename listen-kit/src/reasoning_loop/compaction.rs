@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use rig::message::{
+    AssistantContent, Message, ToolResult, ToolResultContent, UserContent,
+};
+use rig::OneOrMany;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::agents::summarizer::summarize;
+use crate::data::Candlestick;
+use crate::distiller::analyst::Analyst;
+use crate::signer::SignerContext;
+use crate::tokenizer::exceeds_token_limit;
+
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation: ";
+const SUMMARY_ACK: &str = "Understood, continuing from there.";
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompactionConfig {
+    /// Histories above this many (estimated) tokens get compacted
+    pub token_limit: usize,
+    /// Compaction stops once the history is below this
+    pub target_tokens: usize,
+    /// Turns that are kept verbatim as long as the rest can be compacted
+    pub keep_recent_turns: usize,
+    /// Larger tool results of the compacted turns are distilled or cut
+    pub max_tool_result_chars: usize,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            token_limit: 40_000,
+            target_tokens: 20_000,
+            keep_recent_turns: 4,
+            max_tool_result_chars: 4_000,
+        }
+    }
+}
+
+/// The history in the `{role, content}` shape `/stream` accepts as
+/// `chat_history`, so that clients can replace theirs with it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// The summary exchange that stands in for the compacted turns
+pub fn summary_exchange(summary: &str) -> Vec<Message> {
+    vec![
+        Message::user(format!("{}{}", SUMMARY_PREFIX, summary)),
+        Message::assistant(SUMMARY_ACK.to_string()),
+    ]
+}
+
+/// Splits the summary exchange off the front of the history
+pub fn split_summary(
+    mut messages: Vec<Message>,
+) -> (Option<String>, Vec<Message>) {
+    let summary = match messages.first() {
+        Some(Message::User { content }) => match content.first() {
+            UserContent::Text(text) => {
+                text.text.strip_prefix(SUMMARY_PREFIX).map(str::to_string)
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    match summary {
+        Some(summary) if messages.len() >= 2 => {
+            messages.drain(..2);
+            (Some(summary), messages)
+        }
+        _ => (None, messages),
+    }
+}
+
+/// Index of the first message of the `keep_turns`-th last turn, a turn
+/// starts at a user text message so tool calls stay with their results
+pub fn split_point(messages: &[Message], keep_turns: usize) -> usize {
+    let turn_starts = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| {
+            matches!(
+                m,
+                Message::User { content }
+                    if matches!(content.first(), UserContent::Text(_))
+            )
+        })
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if turn_starts.len() <= keep_turns {
+        return 0;
+    }
+    turn_starts[turn_starts.len() - keep_turns]
+}
+
+struct CallInfo {
+    name: String,
+    arguments: Value,
+}
+
+fn tool_calls(messages: &[Message]) -> HashMap<String, CallInfo> {
+    let mut calls = HashMap::new();
+    for message in messages {
+        if let Message::Assistant { content } = message {
+            for item in content.iter() {
+                if let AssistantContent::ToolCall(call) = item {
+                    calls.insert(
+                        call.id.clone(),
+                        CallInfo {
+                            name: call.function.name.clone(),
+                            arguments: call.function.arguments.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+    calls
+}
+
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let kept = text.chars().take(max_chars).collect::<String>();
+    format!("{}... [truncated]", kept)
+}
+
+/// Runs the result through the analyst that fits it, anything that is not
+/// a chart or tweets goes to the web analyst
+async fn distill(
+    analyst: &Analyst,
+    call: Option<&CallInfo>,
+    result: &str,
+) -> Result<String> {
+    let name = call.map(|c| c.name.as_str()).unwrap_or("tool");
+    if let Ok(candlesticks) = serde_json::from_str::<Vec<Candlestick>>(result)
+    {
+        let interval = call
+            .and_then(|c| c.arguments.get("interval"))
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        return Ok(analyst
+            .analyze_chart(&candlesticks, interval, None)
+            .await?);
+    }
+    if let Ok(value) = serde_json::from_str::<Value>(result) {
+        if value.get("tweets").is_some() || name.contains("tweet") {
+            let query = call.map(|c| c.arguments.to_string());
+            return Ok(analyst
+                .analyze_twitter(
+                    query.as_deref().unwrap_or(name),
+                    &value,
+                    None,
+                )
+                .await?);
+        }
+    }
+    Ok(analyst.analyze_web(name, result, None).await?)
+}
+
+impl CompactionConfig {
+    async fn shrink_result(
+        &self,
+        analyst: Option<&Analyst>,
+        call: Option<&CallInfo>,
+        result: String,
+    ) -> String {
+        if result.chars().count() <= self.max_tool_result_chars {
+            return result;
+        }
+        if let Some(analyst) = analyst {
+            match distill(analyst, call, &result).await {
+                Ok(distilled) => {
+                    return truncate(&distilled, self.max_tool_result_chars)
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to distill tool result: {}", e)
+                }
+            }
+        }
+        truncate(&result, self.max_tool_result_chars)
+    }
+
+    /// Distills or cuts the large tool results of `messages`
+    async fn shrink_tool_results(
+        &self,
+        messages: Vec<Message>,
+        analyst: Option<&Analyst>,
+    ) -> Result<Vec<Message>> {
+        let calls = tool_calls(&messages);
+        let calls = &calls;
+        let shrunk =
+            join_all(messages.into_iter().map(|message| async move {
+                let Message::User { content } = message else {
+                    return Ok(message);
+                };
+                let mut items = Vec::new();
+                for item in content.into_iter() {
+                    let UserContent::ToolResult(result) = item else {
+                        items.push(item);
+                        continue;
+                    };
+                    let mut parts = Vec::new();
+                    for part in result.content.into_iter() {
+                        parts.push(match part {
+                            ToolResultContent::Text(text) => {
+                                ToolResultContent::text(
+                                    self.shrink_result(
+                                        analyst,
+                                        calls.get(&result.id),
+                                        text.text,
+                                    )
+                                    .await,
+                                )
+                            }
+                            other => other,
+                        });
+                    }
+                    items.push(UserContent::ToolResult(ToolResult {
+                        id: result.id,
+                        content: OneOrMany::many(parts)
+                            .map_err(|e| anyhow!("{}", e))?,
+                    }));
+                }
+                Ok::<_, anyhow::Error>(Message::User {
+                    content: OneOrMany::many(items)
+                        .map_err(|e| anyhow!("{}", e))?,
+                })
+            }))
+            .await;
+        shrunk.into_iter().collect()
+    }
+
+    fn fits(&self, prompt: &str, messages: &[Message]) -> bool {
+        !exceeds_token_limit(prompt, messages, self.target_tokens)
+    }
+
+    /// Shrinks the history below `target_tokens` if it can. The older
+    /// turns go first, their tool results are distilled and then they are
+    /// summarized with a cheap model, the recent turns are only touched if
+    /// that was not enough
+    pub async fn compact(
+        &self,
+        prompt: &str,
+        mut messages: Vec<Message>,
+    ) -> Result<Vec<Message>> {
+        let analyst = SignerContext::try_current()
+            .map(|signer| signer.locale())
+            .and_then(|locale| Analyst::from_env_with_locale(locale).ok());
+
+        for keep_turns in [self.keep_recent_turns, 1] {
+            let split = split_point(&messages, keep_turns);
+            if split == 0 {
+                continue;
+            }
+            let recent = messages.split_off(split);
+            let older =
+                self.shrink_tool_results(messages, analyst.as_ref()).await?;
+            messages = [older, recent].concat();
+            if self.fits(prompt, &messages) {
+                return Ok(messages);
+            }
+
+            let recent = messages.split_off(split);
+            let (previous, older) = split_summary(messages);
+            let summary = summarize(&older, previous.as_deref()).await?;
+            tracing::info!(compacted = older.len(), "Summarized older turns");
+            messages = [summary_exchange(&summary), recent].concat();
+            if self.fits(prompt, &messages) {
+                return Ok(messages);
+            }
+        }
+
+        self.shrink_tool_results(messages, analyst.as_ref()).await
+    }
+}
+
+/// Inverse of `deserialize_messages`, tool calls and results are written
+/// in its parallel format
+pub fn to_history_messages(messages: &[Message]) -> Vec<HistoryMessage> {
+    let mut history = Vec::new();
+    for message in messages {
+        match message {
+            Message::User { content } => {
+                let mut tool_results = Vec::new();
+                for item in content.iter() {
+                    match item {
+                        UserContent::Text(text) => {
+                            history.push(HistoryMessage {
+                                role: "user".to_string(),
+                                content: text.text.clone(),
+                            })
+                        }
+                        UserContent::ToolResult(result) => {
+                            let text = result
+                                .content
+                                .iter()
+                                .filter_map(|part| match part {
+                                    ToolResultContent::Text(text) => {
+                                        Some(text.text.clone())
+                                    }
+                                    _ => None,
+                                })
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            tool_results.push(json!({
+                                "index": tool_results.len(),
+                                "id": result.id,
+                                "name": "",
+                                "result": text,
+                            }));
+                        }
+                        _ => {}
+                    }
+                }
+                if !tool_results.is_empty() {
+                    history.push(HistoryMessage {
+                        role: "user".to_string(),
+                        content: json!({ "tool_results": tool_results })
+                            .to_string(),
+                    });
+                }
+            }
+            Message::Assistant { content } => {
+                let mut tool_calls = Vec::new();
+                for item in content.iter() {
+                    match item {
+                        AssistantContent::Text(text) => {
+                            history.push(HistoryMessage {
+                                role: "assistant".to_string(),
+                                content: text.text.clone(),
+                            })
+                        }
+                        AssistantContent::ToolCall(call) => {
+                            tool_calls.push(json!({
+                                "id": call.id,
+                                "function": {
+                                    "name": call.function.name,
+                                    "arguments": call.function.arguments,
+                                },
+                            }));
+                        }
+                    }
+                }
+                if !tool_calls.is_empty() {
+                    history.push(HistoryMessage {
+                        role: "assistant".to_string(),
+                        content: json!({ "tool_calls": tool_calls })
+                            .to_string(),
+                    });
+                }
+            }
+        }
+    }
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_result(id: &str, result: &str) -> Message {
+        Message::User {
+            content: OneOrMany::one(UserContent::ToolResult(ToolResult {
+                id: id.to_string(),
+                content: OneOrMany::one(ToolResultContent::text(
+                    result.to_string(),
+                )),
+            })),
+        }
+    }
+
+    fn tool_call(id: &str) -> Message {
+        Message::Assistant {
+            content: OneOrMany::one(AssistantContent::tool_call(
+                id.to_string(),
+                "get_sol_balance".to_string(),
+                json!({}),
+            )),
+        }
+    }
+
+    #[test]
+    fn test_split_point_keeps_recent_turns() {
+        let messages = vec![
+            Message::user("first".to_string()),
+            Message::assistant("a".to_string()),
+            Message::user("second".to_string()),
+            tool_call("1"),
+            tool_result("1", "ok"),
+            Message::assistant("b".to_string()),
+            Message::user("third".to_string()),
+            Message::assistant("c".to_string()),
+        ];
+        assert_eq!(split_point(&messages, 2), 2);
+        assert_eq!(split_point(&messages, 1), 6);
+        assert_eq!(split_point(&messages, 3), 0);
+    }
+
+    #[test]
+    fn test_summary_exchange_round_trip() {
+        let mut messages = summary_exchange("earlier");
+        messages.push(Message::user("hi".to_string()));
+
+        let (summary, rest) = split_summary(messages);
+        assert_eq!(summary.as_deref(), Some("earlier"));
+        assert_eq!(rest.len(), 1);
+
+        let (summary, rest) = split_summary(rest);
+        assert!(summary.is_none());
+        assert_eq!(rest.len(), 1);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("ääääää", 2), "ää... [truncated]");
+    }
+
+    #[tokio::test]
+    async fn test_shrink_tool_results_without_analyst() {
+        let config = CompactionConfig {
+            max_tool_result_chars: 10,
+            ..Default::default()
+        };
+        let messages =
+            vec![tool_call("1"), tool_result("1", &"x".repeat(100))];
+
+        let shrunk =
+            config.shrink_tool_results(messages, None).await.unwrap();
+        let history = to_history_messages(&shrunk);
+        assert!(history[1].content.contains("... [truncated]"));
+        assert!(!history[1].content.contains(&"x".repeat(11)));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_history_messages_deserialize() {
+        let messages = vec![
+            Message::user("swap".to_string()),
+            tool_call("1"),
+            tool_result("1", "ok"),
+            Message::assistant("done".to_string()),
+        ];
+        let history =
+            serde_json::to_string(&to_history_messages(&messages)).unwrap();
+
+        let parsed = crate::http::serde::deserialize_messages(
+            &mut serde_json::Deserializer::from_str(&history),
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::to_value(&messages).unwrap()
+        );
+    }
+}
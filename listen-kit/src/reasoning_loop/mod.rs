@@ -5,6 +5,7 @@ use crate::common::OpenAIAgent;
 use crate::common::OpenRouterAgent;
 use crate::tokenizer::exceeds_token_limit;
 use anyhow::Result;
+use compaction::{to_history_messages, CompactionConfig, HistoryMessage};
 use listen_memory::graph::GraphMemory;
 use rig::completion::Message;
 use rig::message::ToolCall;
@@ -15,11 +16,15 @@ use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
 use tokio::task_local;
+use tokio_util::sync::CancellationToken;
+use usage::{repeated_call_error, RunLimits, RunTracker};
 
+pub mod compaction;
 pub mod debase64;
 pub mod model;
 pub mod stream_gemini;
 pub mod stream_generic;
+pub mod usage;
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
 pub struct SimpleToolResult {
@@ -55,6 +60,23 @@ pub enum StreamResponse {
         agent_type: String,
         content: String,
     },
+    /// The tool is suspended until the client posts the decision to
+    /// `/approve`
+    ApprovalRequired {
+        id: String,
+        summary: String,
+        tx_preview: serde_json::Value,
+    },
+    /// First event of every `/stream` run, the id to cancel or resume it
+    /// with
+    RunStarted {
+        run_id: String,
+    },
+    /// The history was too long and got compacted, clients that keep the
+    /// history themselves send this one from now on
+    HistoryCompacted {
+        chat_history: Vec<HistoryMessage>,
+    },
 }
 
 impl StreamResponse {
@@ -82,6 +104,10 @@ impl StreamResponse {
             // dont consume the nested output, this is only required by the frontend
             // to show the reasoning thoughts, it will be returned again in the tool result
             StreamResponse::NestedAgentOutput { .. } => "".to_string(),
+            // the outcome is in the tool result
+            StreamResponse::ApprovalRequired { .. } => "".to_string(),
+            StreamResponse::HistoryCompacted { .. } => "".to_string(),
+            StreamResponse::RunStarted { .. } => "".to_string(),
             StreamResponse::ParToolCall { .. } => {
                 todo!(
                     "deep research currently doesn't support par tool calls"
@@ -108,13 +134,24 @@ pub enum Model {
 pub struct ReasoningLoop {
     model: Model,
     stdout: bool,
+    compaction: CompactionConfig,
+    cancel: CancellationToken,
+    tracker: Arc<RunTracker>,
+    allowed_tools: Option<Vec<String>>,
 }
 
+/// What a run that got cancelled fails with
+pub const RUN_CANCELLED: &str = "run cancelled";
+
 impl ReasoningLoop {
     pub fn new(model: Model) -> Self {
         Self {
             model,
             stdout: true,
+            compaction: CompactionConfig::default(),
+            cancel: CancellationToken::new(),
+            tracker: Arc::new(RunTracker::new(RunLimits::from_env())),
+            allowed_tools: None,
         }
     }
 
@@ -131,13 +168,27 @@ impl ReasoningLoop {
         }
 
         // Simple character-based check for token limit
-        if exceeds_token_limit(&prompt, &messages, 40_000) {
-            return Err(anyhow::anyhow!(
-                "Ahoy! Context is getting long, please start a new conversation",
-            ));
-        }
+        let limit = self.compaction.token_limit;
+        let messages = if exceeds_token_limit(&prompt, &messages, limit) {
+            let messages = self.compaction.compact(&prompt, messages).await?;
+            if exceeds_token_limit(&prompt, &messages, limit) {
+                return Err(anyhow::anyhow!(
+                    "Ahoy! Context is getting long, please start a new conversation",
+                ));
+            }
+            if let Some(tx) = &tx {
+                let _ = tx
+                    .send(StreamResponse::HistoryCompacted {
+                        chat_history: to_history_messages(&messages),
+                    })
+                    .await;
+            }
+            messages
+        } else {
+            messages
+        };
 
-        Self::with_stream_channel(tx.clone(), || async {
+        let run = Self::with_stream_channel(tx.clone(), || async {
             match &self.model {
                 Model::Gemini(agent) => {
                     self.stream_gemini(agent, prompt, messages, tx).await
@@ -154,19 +205,109 @@ impl ReasoningLoop {
                     .await
                 }
             }
-        })
-        .await
+        });
+        let run = CURRENT_TRACKER.scope(self.tracker.clone(), run);
+        CURRENT_CANCELLATION.scope(self.cancel.clone(), run).await
     }
 
     pub fn with_stdout(mut self, enabled: bool) -> Self {
         self.stdout = enabled;
         self
     }
+
+    pub fn with_compaction(mut self, compaction: CompactionConfig) -> Self {
+        self.compaction = compaction;
+        self
+    }
+
+    /// Stops the loop at the next chunk or tool call once the token is
+    /// cancelled, nested agents started from its tools stop with it
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Limits of the run, a fresh tracker starts counting from now
+    pub fn with_limits(self, limits: RunLimits) -> Self {
+        self.with_tracker(Arc::new(RunTracker::new(limits)))
+    }
+
+    /// Shares the tracker, nested agents use the one of the loop that
+    /// started them so that their usage counts towards its limits
+    pub fn with_tracker(mut self, tracker: Arc<RunTracker>) -> Self {
+        self.tracker = tracker;
+        self
+    }
+
+    /// Calls to any other tool are not run, the model gets an error
+    /// result for them instead
+    pub fn with_allowed_tools(mut self, tools: Vec<String>) -> Self {
+        self.allowed_tools = Some(tools);
+        self
+    }
+
+    pub fn tracker(&self) -> Arc<RunTracker> {
+        self.tracker.clone()
+    }
+
+    fn ensure_not_cancelled(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(anyhow::anyhow!(RUN_CANCELLED));
+        }
+        Ok(())
+    }
+
+    /// Drops `f` if the run is cancelled or out of time before it completes
+    async fn until_cancelled<F: Future>(&self, f: F) -> Result<F::Output> {
+        tokio::select! {
+            output = f => Ok(output),
+            _ = self.cancel.cancelled() => {
+                Err(anyhow::anyhow!(RUN_CANCELLED))
+            }
+            error = self.tracker.deadline() => Err(error.into()),
+        }
+    }
+
+    /// Runs the tool call unless the run is out of tool calls (`Err`), the
+    /// call is a repeat or the tool is not allowed, the model gets an error
+    /// result for the last two
+    async fn call_tool_tracked<F, E>(
+        &self,
+        name: &str,
+        params: &str,
+        call: F,
+    ) -> Result<Result<String, String>>
+    where
+        F: Future<Output = Result<String, E>>,
+        E: std::fmt::Display,
+    {
+        if let Some(allowed) = &self.allowed_tools {
+            if !allowed.iter().any(|tool| tool == name) {
+                return Ok(Err(format!(
+                    "Error: {} is not allowed in this run",
+                    name
+                )));
+            }
+        }
+        if !self.tracker.start_tool_call(name, params)? {
+            return Ok(Err(repeated_call_error(name)));
+        }
+        let started = std::time::Instant::now();
+        let result = self.until_cancelled(call).await?;
+        self.tracker.record_tool_call(
+            name,
+            started.elapsed(),
+            result.is_ok(),
+        );
+        Ok(result.map_err(|e| e.to_string()))
+    }
 }
 
 // Define a task-local variable to hold the current stream channel
 task_local! {
     static CURRENT_STREAM_CHANNEL: RefCell<Option<Sender<StreamResponse>>>;
+    static CURRENT_CANCELLATION: CancellationToken;
+    static CURRENT_TRACKER: Arc<RunTracker>;
 }
 
 impl ReasoningLoop {
@@ -192,6 +333,16 @@ impl ReasoningLoop {
             .unwrap_or_default()
     }
 
+    /// Cancellation token of the loop whose tool is running
+    pub fn current_cancellation() -> Option<CancellationToken> {
+        CURRENT_CANCELLATION.try_with(|c| c.clone()).ok()
+    }
+
+    /// Tracker of the loop whose tool is running
+    pub fn current_tracker() -> Option<Arc<RunTracker>> {
+        CURRENT_TRACKER.try_with(|t| t.clone()).ok()
+    }
+
     // Set the current stream channel
     pub async fn set_current_stream_channel(
         channel: Option<Sender<StreamResponse>>,
@@ -9,6 +9,17 @@ use rig::{
 use crate::reasoning_loop::Model;
 
 impl Model {
+    /// What the usage of the model is reported under
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Claude(_) => "claude",
+            Model::Gemini(_) => "gemini",
+            Model::DeepSeek(_) => "deepseek",
+            Model::OpenAI(_) => "openai",
+            Model::OpenRouter(_) => "openrouter",
+        }
+    }
+
     pub async fn stream_completion(
         &self,
         prompt: Message,
@@ -13,6 +13,7 @@ use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
 
 use crate::common::GeminiAgent;
+use crate::tokenizer::{estimate_conversation_tokens, estimate_tokens};
 
 use super::{ReasoningLoop, StreamResponse};
 
@@ -95,8 +96,18 @@ impl ReasoningLoop {
         let mut is_first_iteration = true;
 
         'outer: loop {
+            self.ensure_not_cancelled()?;
             let mut current_response = String::new();
 
+            self.tracker.record_request(
+                self.model.name(),
+                estimate_conversation_tokens("", &current_messages)
+                    + estimate_conversation_tokens(
+                        "",
+                        std::slice::from_ref(&next_input),
+                    ),
+            )?;
+
             // Stream using the next input (original prompt or tool result)
             let mut stream = match agent
                 .stream_completion(
@@ -130,10 +141,16 @@ impl ReasoningLoop {
                 current_messages.push(next_input.clone());
             }
 
-            while let Some(chunk) = stream.next().await {
+            while let Some(chunk) =
+                self.until_cancelled(stream.next()).await?
+            {
                 match chunk? {
                     StreamingChoice::ParToolCall(_tool_call) => todo!(),
                     StreamingChoice::Message(text) => {
+                        self.tracker.record_output(
+                            self.model.name(),
+                            estimate_tokens(&text),
+                        )?;
                         if stdout {
                             print!("{}", text);
                             std::io::stdout().flush()?;
@@ -150,6 +167,11 @@ impl ReasoningLoop {
                         current_response.push_str(&text);
                     }
                     StreamingChoice::ToolCall(name, tool_id, params) => {
+                        self.tracker.record_output(
+                            self.model.name(),
+                            estimate_tokens(&params.to_string()),
+                        )?;
+
                         // Add the assistant's response up to this point with the tool call
                         if !current_response.is_empty() {
                             current_messages.push(Message::Assistant {
@@ -189,8 +211,13 @@ impl ReasoningLoop {
                         }
 
                         // Call the tool and get result
-                        let result =
-                            agent.tools.call(&name, params.to_string()).await;
+                        let result = self
+                            .call_tool_tracked(
+                                &name,
+                                &params.to_string(),
+                                agent.tools.call(&name, params.to_string()),
+                            )
+                            .await?;
 
                         if stdout {
                             println!("Tool result: {:?}", result);
@@ -14,6 +14,7 @@ use crate::memory::inject_memories;
 use crate::memory::remember_tool_output;
 use crate::reasoning_loop::Model;
 use crate::reasoning_loop::SimpleToolResult;
+use crate::tokenizer::{estimate_conversation_tokens, estimate_tokens};
 
 use super::{ReasoningLoop, StreamResponse};
 
@@ -35,6 +36,7 @@ impl ReasoningLoop {
         let mut is_first_iteration = true;
 
         'outer: loop {
+            self.ensure_not_cancelled()?;
             let mut current_response = String::new();
 
             let _prompt = if is_first_iteration {
@@ -50,6 +52,15 @@ impl ReasoningLoop {
                 next_input.clone()
             };
 
+            self.tracker.record_request(
+                model.name(),
+                estimate_conversation_tokens("", &current_messages)
+                    + estimate_conversation_tokens(
+                        "",
+                        std::slice::from_ref(&_prompt),
+                    ),
+            )?;
+
             // Stream using the next input (original prompt or tool result)
             let mut stream = match model
                 .stream_completion(_prompt.clone(), current_messages.clone())
@@ -78,9 +89,23 @@ impl ReasoningLoop {
                 current_messages.push(next_input.clone());
             }
 
-            while let Some(chunk) = stream.next().await {
+            while let Some(chunk) =
+                self.until_cancelled(stream.next()).await?
+            {
                 match chunk? {
                     StreamingChoice::ParToolCall(tool_calls) => {
+                        self.tracker.record_output(
+                            model.name(),
+                            tool_calls
+                                .values()
+                                .map(|call| {
+                                    estimate_tokens(
+                                        &call.function.arguments.to_string(),
+                                    )
+                                })
+                                .sum(),
+                        )?;
+
                         // Add the assistant's response up to this point with the tool calls
                         if !current_response.is_empty() {
                             current_messages.push(Message::Assistant {
@@ -146,30 +171,36 @@ impl ReasoningLoop {
                                 let id = tool_call.id.clone();
 
                                 async move {
-                                    let result = model
-                                        .call_tool(
-                                            name.clone(),
-                                            params.clone(),
+                                    let result = self
+                                        .call_tool_tracked(
+                                            &name,
+                                            &params,
+                                            model.call_tool(
+                                                name.clone(),
+                                                params.clone(),
+                                            ),
                                         )
-                                        .await;
+                                        .await?;
                                     let result_str = match &result {
                                         Ok(content) => content.to_string(),
                                         Err(err) => err.to_string(),
                                     };
 
-                                    SimpleToolResult {
+                                    Ok::<_, anyhow::Error>(SimpleToolResult {
                                         index: *index,
                                         id,
                                         name,
                                         params,
                                         result: result_str,
-                                    }
+                                    })
                                 }
                             });
 
                         // Wait for all tool calls to complete
-                        let mut results =
-                            futures::future::join_all(tasks).await;
+                        let mut results = futures::future::join_all(tasks)
+                            .await
+                            .into_iter()
+                            .collect::<Result<Vec<_>>>()?;
                         results.sort_by_key(|tool_result| tool_result.index);
 
                         let global_memory = global_memory.clone();
@@ -235,6 +266,10 @@ impl ReasoningLoop {
                         continue 'outer;
                     }
                     StreamingChoice::Message(text) => {
+                        self.tracker.record_output(
+                            model.name(),
+                            estimate_tokens(&text),
+                        )?;
                         if stdout {
                             print!("{}", text);
                             std::io::stdout().flush()?;
@@ -251,6 +286,11 @@ impl ReasoningLoop {
                         current_response.push_str(&text);
                     }
                     StreamingChoice::ToolCall(name, tool_id, params) => {
+                        self.tracker.record_output(
+                            model.name(),
+                            estimate_tokens(&params.to_string()),
+                        )?;
+
                         // Add the assistant's response up to this point with the tool call
                         if !current_response.is_empty() {
                             current_messages.push(Message::Assistant {
@@ -290,9 +330,16 @@ impl ReasoningLoop {
                         }
 
                         // Call the tool and get result
-                        let result = model
-                            .call_tool(name.to_string(), params.to_string())
-                            .await;
+                        let result = self
+                            .call_tool_tracked(
+                                &name,
+                                &params.to_string(),
+                                model.call_tool(
+                                    name.to_string(),
+                                    params.to_string(),
+                                ),
+                            )
+                            .await?;
 
                         if stdout {
                             println!("Tool result: {:?}", result);
@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+/// Per-run limits, shared by the loop and the agents it delegates to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunLimits {
+    pub max_tool_calls: usize,
+    pub max_wall_time: Duration,
+    /// Prompt and completion tokens over all requests, estimated
+    pub max_tokens: usize,
+    /// Identical calls (same tool, same params) past this are not run, the
+    /// model gets an error telling it to use the earlier result instead
+    pub max_identical_calls: usize,
+}
+
+impl Default for RunLimits {
+    fn default() -> Self {
+        Self {
+            max_tool_calls: 50,
+            max_wall_time: Duration::from_secs(10 * 60),
+            max_tokens: 1_000_000,
+            max_identical_calls: 2,
+        }
+    }
+}
+
+impl RunLimits {
+    /// Uses `RUN_MAX_TOOL_CALLS`, `RUN_MAX_WALL_TIME_SECS`,
+    /// `RUN_MAX_TOKENS` and `RUN_MAX_IDENTICAL_CALLS` if set
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok())
+        };
+        let default = Self::default();
+        Self {
+            max_tool_calls: var("RUN_MAX_TOOL_CALLS")
+                .map_or(default.max_tool_calls, |v| v as usize),
+            max_wall_time: var("RUN_MAX_WALL_TIME_SECS")
+                .map_or(default.max_wall_time, Duration::from_secs),
+            max_tokens: var("RUN_MAX_TOKENS")
+                .map_or(default.max_tokens, |v| v as usize),
+            max_identical_calls: var("RUN_MAX_IDENTICAL_CALLS")
+                .map_or(default.max_identical_calls, |v| v as usize),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RunLimitError {
+    #[error("run stopped after {0} tool calls")]
+    ToolCalls(usize),
+    #[error("run stopped after {0:?}")]
+    WallTime(Duration),
+    #[error("run stopped after using ~{0} tokens")]
+    Tokens(usize),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModelUsage {
+    pub requests: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ToolUsage {
+    pub calls: usize,
+    pub errors: usize,
+    /// Identical calls that were refused
+    pub repeated: usize,
+    pub total_ms: u64,
+    pub max_ms: u64,
+}
+
+/// What a run used, token counts are estimated from the characters sent
+/// and received
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsageReport {
+    pub models: BTreeMap<String, ModelUsage>,
+    pub tools: BTreeMap<String, ToolUsage>,
+    pub tool_calls: usize,
+    pub wall_time_ms: u64,
+    /// Set if the run was stopped by one of its limits
+    pub limit: Option<String>,
+}
+
+impl UsageReport {
+    pub fn total_tokens(&self) -> usize {
+        self.models
+            .values()
+            .map(|m| m.prompt_tokens + m.completion_tokens)
+            .sum()
+    }
+}
+
+/// Enforces the limits of a run and collects its usage
+pub struct RunTracker {
+    limits: RunLimits,
+    started: Instant,
+    report: Mutex<UsageReport>,
+    calls: Mutex<HashMap<(String, String), usize>>,
+}
+
+impl Default for RunTracker {
+    fn default() -> Self {
+        Self::new(RunLimits::default())
+    }
+}
+
+impl RunTracker {
+    pub fn new(limits: RunLimits) -> Self {
+        Self {
+            limits,
+            started: Instant::now(),
+            report: Mutex::new(UsageReport::default()),
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limits(&self) -> RunLimits {
+        self.limits
+    }
+
+    /// Completes once the run is out of time
+    pub async fn deadline(&self) -> RunLimitError {
+        tokio::time::sleep_until(self.started + self.limits.max_wall_time)
+            .await;
+        self.fail(RunLimitError::WallTime(self.limits.max_wall_time))
+    }
+
+    fn fail(&self, error: RunLimitError) -> RunLimitError {
+        self.report.lock().unwrap().limit = Some(error.to_string());
+        error
+    }
+
+    /// Records a model request, fails once the run used up its tokens
+    pub fn record_request(
+        &self,
+        model: &str,
+        prompt_tokens: usize,
+    ) -> Result<(), RunLimitError> {
+        self.record(model, |usage| {
+            usage.requests += 1;
+            usage.prompt_tokens += prompt_tokens;
+        })
+    }
+
+    /// Records streamed output, fails once the run used up its tokens
+    pub fn record_output(
+        &self,
+        model: &str,
+        completion_tokens: usize,
+    ) -> Result<(), RunLimitError> {
+        self.record(model, |usage| {
+            usage.completion_tokens += completion_tokens;
+        })
+    }
+
+    fn record(
+        &self,
+        model: &str,
+        f: impl FnOnce(&mut ModelUsage),
+    ) -> Result<(), RunLimitError> {
+        let total = {
+            let mut report = self.report.lock().unwrap();
+            f(report.models.entry(model.to_string()).or_default());
+            report.total_tokens()
+        };
+        if total > self.limits.max_tokens {
+            return Err(self.fail(RunLimitError::Tokens(total)));
+        }
+        Ok(())
+    }
+
+    /// Counts the call before it runs, fails once the run used up its tool
+    /// calls and returns false for a call that was made too many times
+    pub fn start_tool_call(
+        &self,
+        name: &str,
+        params: &str,
+    ) -> Result<bool, RunLimitError> {
+        let tool_calls = {
+            let mut report = self.report.lock().unwrap();
+            report.tool_calls += 1;
+            report.tool_calls
+        };
+        if tool_calls > self.limits.max_tool_calls {
+            return Err(self.fail(RunLimitError::ToolCalls(tool_calls - 1)));
+        }
+
+        let mut calls = self.calls.lock().unwrap();
+        let count = calls
+            .entry((name.to_string(), normalize_params(params)))
+            .or_default();
+        *count += 1;
+        if *count > self.limits.max_identical_calls {
+            tracing::warn!(tool = name, count, "Refusing repeated tool call");
+            let mut report = self.report.lock().unwrap();
+            report.tools.entry(name.to_string()).or_default().repeated += 1;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    pub fn record_tool_call(&self, name: &str, elapsed: Duration, ok: bool) {
+        let ms = elapsed.as_millis() as u64;
+        let mut report = self.report.lock().unwrap();
+        let usage = report.tools.entry(name.to_string()).or_default();
+        usage.calls += 1;
+        usage.total_ms += ms;
+        usage.max_ms = usage.max_ms.max(ms);
+        if !ok {
+            usage.errors += 1;
+        }
+    }
+
+    pub fn report(&self) -> UsageReport {
+        let mut report = self.report.lock().unwrap().clone();
+        report.wall_time_ms = self.started.elapsed().as_millis() as u64;
+        report
+    }
+}
+
+/// The result the model gets instead of running a repeated call
+pub fn repeated_call_error(name: &str) -> String {
+    format!(
+        "Error: {} was already called with these exact params, use the \
+         earlier result instead of calling it again",
+        name
+    )
+}
+
+/// Params that only differ in formatting count as identical
+fn normalize_params(params: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(params)
+        .map(|v| v.to_string())
+        .unwrap_or_else(|_| params.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refuses_identical_calls() {
+        let tracker = RunTracker::new(RunLimits {
+            max_identical_calls: 2,
+            ..Default::default()
+        });
+        let params = r#"{"mint": "abc", "interval": "1h"}"#;
+        assert_eq!(tracker.start_tool_call("chart", params), Ok(true));
+        assert_eq!(
+            tracker.start_tool_call(
+                "chart",
+                r#"{"mint":"abc","interval":"1h"}"#
+            ),
+            Ok(true)
+        );
+        assert_eq!(tracker.start_tool_call("chart", params), Ok(false));
+        assert_eq!(
+            tracker.start_tool_call("chart", r#"{"mint":"xyz"}"#),
+            Ok(true)
+        );
+        assert_eq!(tracker.report().tools["chart"].repeated, 1);
+    }
+
+    #[test]
+    fn test_tool_call_limit() {
+        let tracker = RunTracker::new(RunLimits {
+            max_tool_calls: 2,
+            ..Default::default()
+        });
+        assert!(tracker.start_tool_call("a", "{}").is_ok());
+        assert!(tracker.start_tool_call("b", "{}").is_ok());
+        assert_eq!(
+            tracker.start_tool_call("c", "{}"),
+            Err(RunLimitError::ToolCalls(2))
+        );
+        assert!(tracker.report().limit.is_some());
+    }
+
+    #[test]
+    fn test_token_limit_and_report() {
+        let tracker = RunTracker::new(RunLimits {
+            max_tokens: 100,
+            ..Default::default()
+        });
+        assert!(tracker.record_request("gemini", 40).is_ok());
+        assert!(tracker.record_output("gemini", 10).is_ok());
+        tracker.record_tool_call("chart", Duration::from_millis(30), true);
+        tracker.record_tool_call("chart", Duration::from_millis(10), false);
+        assert!(tracker.record_request("gemini", 40).is_ok());
+        assert_eq!(
+            tracker.record_output("gemini", 20),
+            Err(RunLimitError::Tokens(110))
+        );
+
+        let report = tracker.report();
+        assert_eq!(report.models["gemini"].requests, 2);
+        assert_eq!(report.models["gemini"].prompt_tokens, 80);
+        assert_eq!(report.tools["chart"].total_ms, 40);
+        assert_eq!(report.tools["chart"].max_ms, 30);
+        assert_eq!(report.tools["chart"].errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let tracker = RunTracker::new(RunLimits {
+            max_wall_time: Duration::from_millis(10),
+            ..Default::default()
+        });
+        assert_eq!(
+            tracker.deadline().await,
+            RunLimitError::WallTime(Duration::from_millis(10))
+        );
+        assert!(tracker.report().limit.is_some());
+    }
+}
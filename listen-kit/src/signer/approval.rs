@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+#[cfg(feature = "solana")]
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use super::preview::TxPreview;
+use super::TransactionSigner;
+use crate::reasoning_loop::{ReasoningLoop, StreamResponse};
+
+/// How long a tool waits for the user before the transaction is dropped
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// What a tool is about to do, shown to the user when approval is required
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxIntent {
+    pub summary: String,
+    /// Value moved by the transaction, None if it could not be estimated
+    pub usd_value: Option<f64>,
+    /// Address receiving the funds, for transfers, or the spender, for
+    /// token approvals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    /// Grants the destination an allowance, which moves no funds by
+    /// itself but lets the spender move them later
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_approval: bool,
+    /// Mints or token contracts the transaction sends or receives
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<String>,
+}
+
+tokio::task_local! {
+    static CURRENT_INTENT: TxIntent;
+}
+
+impl TxIntent {
+    pub fn new(summary: impl Into<String>) -> Self {
+        Self {
+            summary: summary.into(),
+            usd_value: None,
+            destination: None,
+            is_approval: false,
+            tokens: Vec::new(),
+        }
+    }
+
+    pub fn with_usd_value(mut self, usd_value: Option<f64>) -> Self {
+        self.usd_value = usd_value;
+        self
+    }
+
+    pub fn with_destination(
+        mut self,
+        destination: impl Into<String>,
+    ) -> Self {
+        self.destination = Some(destination.into());
+        self
+    }
+
+    /// Marks the transaction as a token approval for `spender`
+    pub fn with_approval(mut self, spender: impl Into<String>) -> Self {
+        self.destination = Some(spender.into());
+        self.is_approval = true;
+        self
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.tokens.push(token.into());
+        self
+    }
+
+    /// Adds what the simulation expects the transaction to do
+    pub fn with_preview(mut self, preview: Option<&TxPreview>) -> Self {
+        if let Some(preview) = preview {
+            self.summary = format!("{}\n{}", self.summary, preview.summary());
+        }
+        self
+    }
+
+    /// Runs `f` with the intent attached, any transaction signed by `f`
+    /// is described to the user by it
+    pub async fn scope<T>(self, f: impl Future<Output = T>) -> T {
+        CURRENT_INTENT.scope(self, f).await
+    }
+
+    pub fn current() -> Option<TxIntent> {
+        CURRENT_INTENT.try_with(|intent| intent.clone()).ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    /// Transactions worth at most this much are signed without asking,
+    /// ones of unknown value and token approvals always ask
+    pub auto_approve_max_usd: f64,
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        Self {
+            auto_approve_max_usd: 0.0,
+        }
+    }
+}
+
+impl ApprovalPolicy {
+    /// Uses `AUTO_APPROVE_MAX_USD` if set
+    pub fn from_env() -> Self {
+        std::env::var("AUTO_APPROVE_MAX_USD")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .map(|auto_approve_max_usd| Self {
+                auto_approve_max_usd,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn requires_approval(&self, intent: &TxIntent) -> bool {
+        if intent.is_approval {
+            return true;
+        }
+        match intent.usd_value {
+            Some(value) => value > self.auto_approve_max_usd,
+            None => true,
+        }
+    }
+}
+
+struct PendingApproval {
+    user_id: Option<String>,
+    tx: oneshot::Sender<bool>,
+}
+
+/// Transactions waiting for the user, resolved through `/approve`
+///
+/// Pending approvals live in memory, so the decision has to reach the
+/// instance that streams the conversation
+#[derive(Default)]
+pub struct Approvals {
+    pending: Mutex<HashMap<String, PendingApproval>>,
+}
+
+impl Approvals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn register(
+        &self,
+        user_id: Option<String>,
+    ) -> (String, oneshot::Receiver<bool>) {
+        let id = format!("{:032x}", rand::random::<u128>());
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(id.clone(), PendingApproval { user_id, tx });
+        (id, rx)
+    }
+
+    fn remove(&self, id: &str) {
+        self.pending.lock().unwrap().remove(id);
+    }
+
+    /// Returns false if there is no such approval pending for the user
+    pub fn resolve(&self, user_id: &str, id: &str, approved: bool) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.get(id) {
+            Some(approval)
+                if approval
+                    .user_id
+                    .as_deref()
+                    .is_none_or(|u| u == user_id) =>
+            {
+                let approval = pending.remove(id).unwrap();
+                approval.tx.send(approved).is_ok()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Signer that asks the user before signing anything the policy does not
+/// let through, delegates everything else to the inner signer
+///
+/// Consent is checked again when signing, a transaction signed under an
+/// intent the user has not approved asks for approval itself
+pub struct ApprovalSigner {
+    inner: Arc<dyn TransactionSigner>,
+    approvals: Arc<Approvals>,
+    policy: ApprovalPolicy,
+    timeout: Duration,
+    /// Intents the user approved that have not been signed yet
+    granted: Mutex<Vec<String>>,
+}
+
+impl ApprovalSigner {
+    pub fn new(
+        inner: Arc<dyn TransactionSigner>,
+        approvals: Arc<Approvals>,
+        policy: ApprovalPolicy,
+    ) -> Self {
+        Self {
+            inner,
+            approvals,
+            policy,
+            timeout: APPROVAL_TIMEOUT,
+            granted: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn grant_key(intent: &TxIntent) -> String {
+        serde_json::to_string(intent).unwrap_or_default()
+    }
+
+    /// Uses up one approval of `intent`, each approval signs once
+    fn take_grant(&self, intent: &TxIntent) -> bool {
+        let key = Self::grant_key(intent);
+        let mut granted = self.granted.lock().unwrap();
+        match granted.iter().position(|k| *k == key) {
+            Some(i) => {
+                granted.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Lets a transaction through only if the intent it is signed under
+    /// was approved, or passes the policy, asks the user otherwise
+    async fn ensure_approved(
+        &self,
+        tx_preview: impl FnOnce() -> serde_json::Value,
+    ) -> Result<()> {
+        let intent = TxIntent::current()
+            .unwrap_or_else(|| TxIntent::new("Sign a transaction"));
+        if !self.policy.requires_approval(&intent) || self.take_grant(&intent)
+        {
+            return Ok(());
+        }
+        self.request_approval(intent.clone(), tx_preview()).await?;
+        // approved here, not by an earlier request
+        self.take_grant(&intent);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for ApprovalSigner {
+    fn locale(&self) -> String {
+        self.inner.locale()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        self.inner.user_id()
+    }
+
+    fn address(&self) -> Option<String> {
+        self.inner.address()
+    }
+
+    fn pubkey(&self) -> Option<String> {
+        self.inner.pubkey()
+    }
+
+    async fn request_approval(
+        &self,
+        intent: TxIntent,
+        tx_preview: serde_json::Value,
+    ) -> Result<()> {
+        if !self.policy.requires_approval(&intent) {
+            tracing::info!(summary = %intent.summary, "auto-approved");
+            return Ok(());
+        }
+
+        let channel = ReasoningLoop::get_current_stream_channel()
+            .await
+            .ok_or_else(|| anyhow!("No client to ask for approval"))?;
+
+        let (id, rx) = self.approvals.register(self.user_id());
+        let event = StreamResponse::ApprovalRequired {
+            id: id.clone(),
+            summary: intent.summary.clone(),
+            tx_preview,
+        };
+        if channel.send(event).await.is_err() {
+            self.approvals.remove(&id);
+            return Err(anyhow!("Failed to ask the user for approval"));
+        }
+
+        let decision = tokio::time::timeout(self.timeout, rx).await;
+        self.approvals.remove(&id);
+        match decision {
+            Ok(Ok(true)) => {
+                self.granted.lock().unwrap().push(Self::grant_key(&intent));
+                Ok(())
+            }
+            Ok(Ok(false)) => Err(anyhow!(
+                "The user rejected the transaction: {}",
+                intent.summary
+            )),
+            Ok(Err(_)) => Err(anyhow!("Approval was dropped")),
+            Err(_) => Err(anyhow!(
+                "The user did not approve the transaction within {}s",
+                self.timeout.as_secs()
+            )),
+        }
+    }
+
+    #[cfg(feature = "solana")]
+    async fn sign_and_send_solana_transaction(
+        &self,
+        tx: &mut solana_sdk::transaction::VersionedTransaction,
+    ) -> Result<String> {
+        self.ensure_approved(|| {
+            serde_json::json!({
+                "chain": "solana",
+                "transaction": bincode::serialize(&*tx)
+                    .map(|tx| BASE64_STANDARD.encode(tx))
+                    .ok(),
+            })
+        })
+        .await?;
+        self.inner.sign_and_send_solana_transaction(tx).await
+    }
+
+    #[cfg(feature = "evm")]
+    async fn sign_and_send_evm_transaction(
+        &self,
+        tx: alloy::rpc::types::TransactionRequest,
+    ) -> Result<String> {
+        self.ensure_approved(|| serde_json::json!({ "transaction": tx }))
+            .await?;
+        self.inner.sign_and_send_evm_transaction(tx).await
+    }
+
+    async fn sign_and_send_encoded_solana_transaction(
+        &self,
+        tx: String,
+    ) -> Result<String> {
+        self.ensure_approved(
+            || serde_json::json!({ "chain": "solana", "transaction": tx }),
+        )
+        .await?;
+        self.inner
+            .sign_and_send_encoded_solana_transaction(tx)
+            .await
+    }
+
+    async fn sign_and_send_json_evm_transaction(
+        &self,
+        tx: serde_json::Value,
+        caip2: Option<String>,
+    ) -> Result<String> {
+        self.ensure_approved(
+            || serde_json::json!({ "chain": caip2, "transaction": tx }),
+        )
+        .await?;
+        self.inner
+            .sign_and_send_json_evm_transaction(tx, caip2)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopSigner;
+
+    #[async_trait]
+    impl TransactionSigner for NoopSigner {
+        fn user_id(&self) -> Option<String> {
+            Some("user".to_string())
+        }
+
+        async fn sign_and_send_encoded_solana_transaction(
+            &self,
+            _tx: String,
+        ) -> Result<String> {
+            Ok("signature".to_string())
+        }
+    }
+
+    fn signer(approvals: Arc<Approvals>, max_usd: f64) -> ApprovalSigner {
+        ApprovalSigner::new(
+            Arc::new(NoopSigner),
+            approvals,
+            ApprovalPolicy {
+                auto_approve_max_usd: max_usd,
+            },
+        )
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = ApprovalPolicy {
+            auto_approve_max_usd: 10.0,
+        };
+        let intent = TxIntent::new("swap");
+        assert!(policy.requires_approval(&intent));
+        assert!(!policy
+            .requires_approval(&intent.clone().with_usd_value(Some(5.0))));
+        assert!(policy.requires_approval(&intent.with_usd_value(Some(50.0))));
+
+        let approval = TxIntent::new("approve")
+            .with_approval("0xspender")
+            .with_usd_value(Some(0.0));
+        assert!(policy.requires_approval(&approval));
+    }
+
+    #[tokio::test]
+    async fn test_small_amount_auto_approves() {
+        let signer = signer(Arc::new(Approvals::new()), 10.0);
+        let intent = TxIntent::new("swap").with_usd_value(Some(1.0));
+        // no stream channel, so anything but an auto-approve would fail
+        assert!(signer
+            .request_approval(intent, serde_json::Value::Null)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_approve_and_reject() {
+        let approvals = Arc::new(Approvals::new());
+        let signer = Arc::new(signer(approvals.clone(), 0.0));
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+
+        for approved in [true, false] {
+            let handle = tokio::spawn({
+                let signer = signer.clone();
+                let tx = tx.clone();
+                ReasoningLoop::with_stream_channel(
+                    Some(tx),
+                    move || async move {
+                        signer
+                            .request_approval(
+                                TxIntent::new("transfer 1 SOL"),
+                                serde_json::Value::Null,
+                            )
+                            .await
+                    },
+                )
+            });
+
+            let id = match rx.recv().await.unwrap() {
+                StreamResponse::ApprovalRequired { id, summary, .. } => {
+                    assert_eq!(summary, "transfer 1 SOL");
+                    id
+                }
+                _ => panic!("expected an approval request"),
+            };
+            assert!(!approvals.resolve("someone else", &id, true));
+            assert!(approvals.resolve("user", &id, approved));
+            assert_eq!(handle.await.unwrap().is_ok(), approved);
+            assert!(approvals.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let approvals = Arc::new(Approvals::new());
+        let signer = signer(approvals.clone(), 0.0)
+            .with_timeout(Duration::from_millis(10));
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+
+        let result = ReasoningLoop::with_stream_channel(Some(tx), || {
+            signer.request_approval(
+                TxIntent::new("swap"),
+                serde_json::Value::Null,
+            )
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(approvals.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_signing_requires_approved_intent() {
+        let approvals = Arc::new(Approvals::new());
+        let signer = Arc::new(signer(approvals.clone(), 10.0));
+        let intent = TxIntent::new("approve 0xspender").with_approval("0x1");
+        let sign = |signer: Arc<ApprovalSigner>, intent: TxIntent| {
+            intent.scope(async move {
+                signer
+                    .sign_and_send_encoded_solana_transaction("tx".into())
+                    .await
+            })
+        };
+
+        // never approved, and there is no client to ask
+        assert!(sign(signer.clone(), intent.clone()).await.is_err());
+        assert!(signer
+            .sign_and_send_encoded_solana_transaction("tx".into())
+            .await
+            .is_err());
+        // under the policy, so it goes through without asking
+        let small = TxIntent::new("swap").with_usd_value(Some(1.0));
+        assert!(sign(signer.clone(), small).await.is_ok());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let handle = tokio::spawn({
+            let signer = signer.clone();
+            let intent = intent.clone();
+            ReasoningLoop::with_stream_channel(Some(tx), move || async move {
+                signer
+                    .request_approval(intent, serde_json::Value::Null)
+                    .await
+            })
+        });
+        let id = match rx.recv().await.unwrap() {
+            StreamResponse::ApprovalRequired { id, .. } => id,
+            _ => panic!("expected an approval request"),
+        };
+        assert!(approvals.resolve("user", &id, true));
+        assert!(handle.await.unwrap().is_ok());
+
+        // the approval signs once
+        assert!(sign(signer.clone(), intent.clone()).await.is_ok());
+        assert!(sign(signer, intent).await.is_err());
+    }
+}
@@ -1,5 +1,8 @@
+pub mod approval;
 #[cfg(feature = "evm")]
 pub mod evm;
+pub mod policy;
+pub mod preview;
 #[cfg(feature = "http")]
 pub mod privy;
 #[cfg(feature = "solana")]
@@ -11,6 +14,8 @@ use std::sync::Arc;
 use anyhow::Result;
 use async_trait::async_trait;
 
+use self::approval::TxIntent;
+
 #[cfg(feature = "evm")]
 use self::evm::LocalEvmSigner;
 #[cfg(feature = "http")]
@@ -52,6 +57,16 @@ pub trait TransactionSigner: Send + Sync {
         None
     }
 
+    /// Called before signing a transaction that moves funds, errors if
+    /// the transaction should not be sent
+    async fn request_approval(
+        &self,
+        _intent: TxIntent,
+        _tx_preview: serde_json::Value,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     #[cfg(feature = "solana")]
     async fn sign_and_send_solana_transaction(
         &self,
@@ -109,4 +124,9 @@ impl SignerContext {
     pub async fn current() -> Arc<dyn TransactionSigner> {
         CURRENT_SIGNER.get().clone()
     }
+
+    /// For code that also runs outside of `with_signer`
+    pub fn try_current() -> Option<Arc<dyn TransactionSigner>> {
+        CURRENT_SIGNER.try_with(|signer| signer.clone()).ok()
+    }
 }
@@ -0,0 +1,777 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::approval::TxIntent;
+use super::TransactionSigner;
+use crate::cross_chain::tools::LIFI_DIAMOND_ADDRESS;
+
+/// Native SOL, wrapped SOL and the addresses EVM aggregators use for the
+/// native token, always allowed since every trade goes through them
+const NATIVE_TOKENS: [&str; 4] = [
+    "native",
+    "So11111111111111111111111111111111111111112",
+    "0x0000000000000000000000000000000000000000",
+    "0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee",
+];
+
+/// Spenders the swap tools approve on their own, and only ahead of a swap
+/// the user approved, always allowed like the native tokens
+const ROUTER_SPENDERS: [&str; 1] = [LIFI_DIAMOND_ADDRESS];
+
+/// How long a day's spend is kept in Redis, past the end of the day
+#[cfg(feature = "redis")]
+const SPEND_TTL_SECS: i64 = 2 * 24 * 60 * 60;
+
+/// Limits on what an agent can do with a user's wallet, a None field
+/// means no limit
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SpendingPolicy {
+    /// USD the wallet can move per UTC day
+    #[serde(default)]
+    pub daily_limit_usd: Option<f64>,
+    #[serde(default)]
+    pub max_tx_usd: Option<f64>,
+    /// Addresses transfers can go to and token approvals can be granted to
+    #[serde(default)]
+    pub allowed_destinations: Option<Vec<String>>,
+    /// Mints and token contracts that can be sent or traded
+    #[serde(default)]
+    pub allowed_tokens: Option<Vec<String>>,
+}
+
+/// Why a transaction was refused, the message is meant for the agent to
+/// relay to the user
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PolicyViolation {
+    #[error(
+        "Transaction worth ${value:.2} exceeds the per-transaction limit of ${limit:.2}"
+    )]
+    MaxTransactionValue { value: f64, limit: f64 },
+    #[error(
+        "Transaction worth ${value:.2} exceeds the daily limit of ${limit:.2}, ${spent:.2} was already spent today"
+    )]
+    DailyLimit { value: f64, spent: f64, limit: f64 },
+    #[error("Destination {0} is not in the wallet's allowed destinations")]
+    DestinationNotAllowed(String),
+    #[error("Token {0} is not in the wallet's allowed tokens")]
+    TokenNotAllowed(String),
+    #[error(
+        "The value of the transaction could not be estimated and the wallet has spending limits"
+    )]
+    UnknownValue,
+    #[error(
+        "The transaction does not describe what it does and the wallet has a spending policy"
+    )]
+    UnknownTransaction,
+    #[error(
+        "Unattended runs can only sign with per-transaction and daily limits in the wallet's spending policy"
+    )]
+    Unbounded,
+    #[error(
+        "Unattended runs can only approve spenders in the wallet's allowed destinations, not {0}"
+    )]
+    UnattendedApproval(String),
+}
+
+fn same_address(a: &str, b: &str) -> bool {
+    // EVM addresses are case-insensitive, base58 ones are not
+    if a.starts_with("0x") && b.starts_with("0x") {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+fn contains_address(list: &[String], address: &str) -> bool {
+    list.iter().any(|a| same_address(a, address))
+}
+
+impl SpendingPolicy {
+    /// Uses `DAILY_SPEND_LIMIT_USD` and `MAX_TX_USD` if set, the
+    /// allowlists are per user only
+    pub fn from_env() -> Self {
+        let limit = |var: &str| {
+            std::env::var(var).ok().and_then(|v| v.parse::<f64>().ok())
+        };
+        Self {
+            daily_limit_usd: limit("DAILY_SPEND_LIMIT_USD"),
+            max_tx_usd: limit("MAX_TX_USD"),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Caps both single transactions and the day, what runs nobody
+    /// watches need to trade
+    pub fn bounds_value(&self) -> bool {
+        self.daily_limit_usd.is_some() && self.max_tx_usd.is_some()
+    }
+
+    /// Whether token approvals can be granted to `spender`, checked for
+    /// unattended runs even when the destinations are not restricted
+    fn allows_spender(&self, spender: &str) -> bool {
+        ROUTER_SPENDERS.iter().any(|r| same_address(r, spender))
+            || self
+                .allowed_destinations
+                .as_ref()
+                .is_some_and(|allowed| contains_address(allowed, spender))
+    }
+
+    fn has_value_limits(&self) -> bool {
+        self.daily_limit_usd.is_some() || self.max_tx_usd.is_some()
+    }
+
+    /// Checks the transaction against the policy, `spent` being what the
+    /// wallet already moved today
+    pub fn check(
+        &self,
+        intent: Option<&TxIntent>,
+        spent: f64,
+    ) -> Result<(), PolicyViolation> {
+        if self.is_unrestricted() {
+            return Ok(());
+        }
+        let intent = intent.ok_or(PolicyViolation::UnknownTransaction)?;
+
+        if let (Some(allowed), Some(destination)) =
+            (&self.allowed_destinations, &intent.destination)
+        {
+            let allowed_spender =
+                intent.is_approval && self.allows_spender(destination);
+            if !contains_address(allowed, destination) && !allowed_spender {
+                return Err(PolicyViolation::DestinationNotAllowed(
+                    destination.clone(),
+                ));
+            }
+        }
+
+        if let Some(allowed) = &self.allowed_tokens {
+            if let Some(token) = intent.tokens.iter().find(|token| {
+                !contains_address(allowed, token)
+                    && !NATIVE_TOKENS.iter().any(|n| same_address(n, token))
+            }) {
+                return Err(PolicyViolation::TokenNotAllowed(token.clone()));
+            }
+        }
+
+        // approvals move nothing by themselves, what the spender is allowed
+        // to be is bounded by the destinations above
+        if !self.has_value_limits() || intent.is_approval {
+            return Ok(());
+        }
+        let value = intent.usd_value.ok_or(PolicyViolation::UnknownValue)?;
+        if let Some(limit) = self.max_tx_usd {
+            if value > limit {
+                return Err(PolicyViolation::MaxTransactionValue {
+                    value,
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.daily_limit_usd {
+            if spent + value > limit {
+                return Err(PolicyViolation::DailyLimit {
+                    value,
+                    spent,
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// USD each user moved per UTC day
+///
+/// Kept in Redis when there is one, so the limits hold across instances
+/// and restarts, in memory otherwise
+pub struct SpendLedger {
+    store: SpendStore,
+}
+
+enum SpendStore {
+    Memory(Mutex<HashMap<String, (NaiveDate, f64)>>),
+    #[cfg(feature = "redis")]
+    Redis(redis::aio::MultiplexedConnection),
+}
+
+impl Default for SpendLedger {
+    fn default() -> Self {
+        Self {
+            store: SpendStore::Memory(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+fn spend_key(user_id: &str, day: NaiveDate) -> String {
+    format!("spend:{}:{}", user_id, day)
+}
+
+impl SpendLedger {
+    /// In memory, a restart forgets what was spent earlier in the day
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(feature = "redis")]
+    pub async fn redis(redis_url: &str) -> Result<Self> {
+        let client = redis::Client::open(redis_url)?;
+        let conn = client.get_multiplexed_async_connection().await?;
+        Ok(Self {
+            store: SpendStore::Redis(conn),
+        })
+    }
+
+    /// Uses `REDIS_URL` if set
+    pub async fn from_env() -> Result<Self> {
+        #[cfg(feature = "redis")]
+        if let Ok(redis_url) = std::env::var("REDIS_URL") {
+            return Self::redis(&redis_url).await;
+        }
+        Ok(Self::new())
+    }
+
+    pub async fn spent_today(&self, user_id: &str) -> Result<f64> {
+        self.spent_on(user_id, Utc::now().date_naive()).await
+    }
+
+    async fn spent_on(&self, user_id: &str, day: NaiveDate) -> Result<f64> {
+        match &self.store {
+            SpendStore::Memory(spent) => {
+                Ok(match spent.lock().unwrap().get(user_id) {
+                    Some((d, spent)) if *d == day => *spent,
+                    _ => 0.0,
+                })
+            }
+            #[cfg(feature = "redis")]
+            SpendStore::Redis(conn) => {
+                use redis::AsyncCommands;
+                let mut conn = conn.clone();
+                let spent: Option<f64> =
+                    conn.get(spend_key(user_id, day)).await?;
+                Ok(spent.unwrap_or_default())
+            }
+        }
+    }
+
+    /// Adds `value` to what the user spent on the day, atomically, returns
+    /// the new total
+    async fn add(
+        &self,
+        user_id: &str,
+        day: NaiveDate,
+        value: f64,
+    ) -> Result<f64> {
+        match &self.store {
+            SpendStore::Memory(spent) => {
+                let mut spent = spent.lock().unwrap();
+                let entry =
+                    spent.entry(user_id.to_string()).or_insert((day, 0.0));
+                // only the latest day is kept, earlier ones are over
+                if entry.0 > day {
+                    return Ok(value);
+                }
+                if entry.0 < day {
+                    *entry = (day, 0.0);
+                }
+                entry.1 = (entry.1 + value).max(0.0);
+                Ok(entry.1)
+            }
+            #[cfg(feature = "redis")]
+            SpendStore::Redis(conn) => {
+                let mut conn = conn.clone();
+                let key = spend_key(user_id, day);
+                let (total,): (f64,) = redis::pipe()
+                    .atomic()
+                    .incr(&key, value)
+                    .expire(&key, SPEND_TTL_SECS)
+                    .ignore()
+                    .query_async(&mut conn)
+                    .await?;
+                Ok(total)
+            }
+        }
+    }
+
+    /// Checks the transaction and counts its value as spent on the day if
+    /// it passes, returns the value counted
+    ///
+    /// The value is counted before the daily limit is checked, so
+    /// concurrent transactions cannot both fit under it
+    pub async fn reserve(
+        &self,
+        user_id: &str,
+        policy: &SpendingPolicy,
+        intent: Option<&TxIntent>,
+        day: NaiveDate,
+    ) -> Result<f64> {
+        if policy.is_unrestricted() {
+            return Ok(0.0);
+        }
+        let value = intent
+            .filter(|i| !i.is_approval)
+            .and_then(|i| i.usd_value)
+            .unwrap_or_default();
+        let total = self.add(user_id, day, value).await?;
+        if let Err(violation) = policy.check(intent, total - value) {
+            self.refund(user_id, day, value).await?;
+            return Err(violation.into());
+        }
+        Ok(value)
+    }
+
+    /// Gives back a reservation of a transaction that was not sent
+    pub async fn refund(
+        &self,
+        user_id: &str,
+        day: NaiveDate,
+        value: f64,
+    ) -> Result<()> {
+        if value > 0.0 {
+            self.add(user_id, day, -value).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Signer that refuses transactions the user's spending policy does not
+/// allow, delegates everything else to the inner signer
+///
+/// The transaction is described by the `TxIntent` in scope when signing
+pub struct PolicySigner {
+    inner: Arc<dyn TransactionSigner>,
+    policy: SpendingPolicy,
+    ledger: Arc<SpendLedger>,
+    unattended: bool,
+}
+
+impl PolicySigner {
+    pub fn new(
+        inner: Arc<dyn TransactionSigner>,
+        policy: SpendingPolicy,
+        ledger: Arc<SpendLedger>,
+    ) -> Self {
+        Self {
+            inner,
+            policy,
+            ledger,
+            unattended: false,
+        }
+    }
+
+    /// Nobody is there to approve, so nothing is signed unless the policy
+    /// bounds what can be spent, and token approvals, which the limits do
+    /// not bound, only go to known spenders
+    pub fn unattended(mut self) -> Self {
+        self.unattended = true;
+        self
+    }
+
+    fn check_attended(
+        &self,
+        intent: Option<&TxIntent>,
+    ) -> Result<(), PolicyViolation> {
+        if !self.unattended {
+            return Ok(());
+        }
+        if !self.policy.bounds_value() {
+            return Err(PolicyViolation::Unbounded);
+        }
+        if let Some(intent) = intent.filter(|i| i.is_approval) {
+            match &intent.destination {
+                Some(spender) if self.policy.allows_spender(spender) => {}
+                spender => {
+                    return Err(PolicyViolation::UnattendedApproval(
+                        spender.clone().unwrap_or_default(),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn ledger_key(&self) -> String {
+        self.inner
+            .user_id()
+            .or_else(|| self.inner.address())
+            .or_else(|| self.inner.pubkey())
+            .unwrap_or_default()
+    }
+
+    async fn guarded<T>(
+        &self,
+        send: impl Future<Output = Result<T>> + Send,
+    ) -> Result<T> {
+        let intent = TxIntent::current();
+        self.check_attended(intent.as_ref())?;
+        let key = self.ledger_key();
+        let day = Utc::now().date_naive();
+        let value = self
+            .ledger
+            .reserve(&key, &self.policy, intent.as_ref(), day)
+            .await?;
+        let result = send.await;
+        if result.is_err() {
+            if let Err(e) = self.ledger.refund(&key, day, value).await {
+                tracing::error!("Error: refunding spend: {}", e);
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for PolicySigner {
+    fn locale(&self) -> String {
+        self.inner.locale()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        self.inner.user_id()
+    }
+
+    fn address(&self) -> Option<String> {
+        self.inner.address()
+    }
+
+    fn pubkey(&self) -> Option<String> {
+        self.inner.pubkey()
+    }
+
+    /// Refuses early so the user is not asked about a transaction that
+    /// would be refused anyway
+    async fn request_approval(
+        &self,
+        intent: TxIntent,
+        tx_preview: serde_json::Value,
+    ) -> Result<()> {
+        self.check_attended(Some(&intent))?;
+        if !self.policy.is_unrestricted() {
+            let spent = self.ledger.spent_today(&self.ledger_key()).await?;
+            self.policy.check(Some(&intent), spent)?;
+        }
+        self.inner.request_approval(intent, tx_preview).await
+    }
+
+    #[cfg(feature = "solana")]
+    async fn sign_and_send_solana_transaction(
+        &self,
+        tx: &mut solana_sdk::transaction::VersionedTransaction,
+    ) -> Result<String> {
+        self.guarded(self.inner.sign_and_send_solana_transaction(tx))
+            .await
+    }
+
+    #[cfg(feature = "evm")]
+    async fn sign_and_send_evm_transaction(
+        &self,
+        tx: alloy::rpc::types::TransactionRequest,
+    ) -> Result<String> {
+        self.guarded(self.inner.sign_and_send_evm_transaction(tx))
+            .await
+    }
+
+    async fn sign_and_send_encoded_solana_transaction(
+        &self,
+        tx: String,
+    ) -> Result<String> {
+        self.guarded(self.inner.sign_and_send_encoded_solana_transaction(tx))
+            .await
+    }
+
+    async fn sign_and_send_json_evm_transaction(
+        &self,
+        tx: serde_json::Value,
+        caip2: Option<String>,
+    ) -> Result<String> {
+        self.guarded(self.inner.sign_and_send_json_evm_transaction(tx, caip2))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopSigner;
+
+    #[async_trait]
+    impl TransactionSigner for NoopSigner {
+        fn user_id(&self) -> Option<String> {
+            Some("user".to_string())
+        }
+
+        async fn sign_and_send_encoded_solana_transaction(
+            &self,
+            _tx: String,
+        ) -> Result<String> {
+            Ok("signature".to_string())
+        }
+    }
+
+    fn policy() -> SpendingPolicy {
+        SpendingPolicy {
+            daily_limit_usd: Some(100.0),
+            max_tx_usd: Some(60.0),
+            allowed_destinations: Some(vec![
+                "0xAbC0000000000000000000000000000000000001".to_string(),
+            ]),
+            allowed_tokens: Some(vec![
+                "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263".to_string(),
+            ]),
+        }
+    }
+
+    fn swap(usd_value: f64) -> TxIntent {
+        TxIntent::new("swap")
+            .with_usd_value(Some(usd_value))
+            .with_token("So11111111111111111111111111111111111111112")
+            .with_token("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263")
+    }
+
+    #[test]
+    fn test_check() {
+        let policy = policy();
+        assert_eq!(policy.check(Some(&swap(50.0)), 0.0), Ok(()));
+        assert_eq!(
+            policy.check(Some(&swap(70.0)), 0.0),
+            Err(PolicyViolation::MaxTransactionValue {
+                value: 70.0,
+                limit: 60.0
+            })
+        );
+        assert_eq!(
+            policy.check(Some(&swap(50.0)), 60.0),
+            Err(PolicyViolation::DailyLimit {
+                value: 50.0,
+                spent: 60.0,
+                limit: 100.0
+            })
+        );
+        assert_eq!(
+            policy.check(Some(&swap(1.0).with_token("pump")), 0.0),
+            Err(PolicyViolation::TokenNotAllowed("pump".to_string()))
+        );
+        assert_eq!(
+            policy.check(Some(&TxIntent::new("swap")), 0.0),
+            Err(PolicyViolation::UnknownValue)
+        );
+        assert_eq!(
+            policy.check(None, 0.0),
+            Err(PolicyViolation::UnknownTransaction)
+        );
+        assert_eq!(SpendingPolicy::default().check(None, 1e9), Ok(()));
+    }
+
+    #[test]
+    fn test_destinations() {
+        let policy = SpendingPolicy {
+            allowed_destinations: policy().allowed_destinations,
+            ..Default::default()
+        };
+        let transfer = |to: &str| {
+            TxIntent::new("transfer").with_destination(to.to_string())
+        };
+        assert_eq!(
+            policy.check(
+                Some(&transfer("0xabc0000000000000000000000000000000000001")),
+                0.0
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            policy.check(Some(&transfer("attacker")), 0.0),
+            Err(PolicyViolation::DestinationNotAllowed(
+                "attacker".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_approvals() {
+        let policy = policy();
+        let approve =
+            |spender: &str| TxIntent::new("approve").with_approval(spender);
+        // the spender is a destination, approvals count for no value
+        assert_eq!(
+            policy.check(
+                Some(&approve("0xabc0000000000000000000000000000000000001")),
+                100.0
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            policy.check(Some(&approve("0xattacker")), 0.0),
+            Err(PolicyViolation::DestinationNotAllowed(
+                "0xattacker".to_string()
+            ))
+        );
+        assert_eq!(
+            policy.check(
+                Some(&approve(&LIFI_DIAMOND_ADDRESS.to_lowercase())),
+                0.0
+            ),
+            Ok(())
+        );
+        // the router is only trusted as a spender
+        assert!(policy
+            .check(
+                Some(&swap(1.0).with_destination(LIFI_DIAMOND_ADDRESS)),
+                0.0
+            )
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ledger_resets_daily() {
+        let ledger = SpendLedger::new();
+        let policy = policy();
+        let day = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let intent = swap(50.0);
+        for _ in 0..2 {
+            assert!(ledger
+                .reserve("user", &policy, Some(&intent), day)
+                .await
+                .is_ok());
+        }
+        assert!(ledger
+            .reserve("user", &policy, Some(&intent), day)
+            .await
+            .is_err());
+        assert_eq!(ledger.spent_on("user", day).await.unwrap(), 100.0);
+        ledger.refund("user", day, 30.0).await.unwrap();
+        assert_eq!(ledger.spent_on("user", day).await.unwrap(), 70.0);
+
+        let next_day = day.succ_opt().unwrap();
+        assert!(ledger
+            .reserve("user", &policy, Some(&intent), next_day)
+            .await
+            .is_ok());
+        // refunds of an earlier day leave the new one alone
+        ledger.refund("user", day, 30.0).await.unwrap();
+        assert_eq!(ledger.spent_on("user", next_day).await.unwrap(), 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_policy_signer() {
+        let ledger = Arc::new(SpendLedger::new());
+        let signer =
+            PolicySigner::new(Arc::new(NoopSigner), policy(), ledger.clone());
+
+        let sent =
+            swap(50.0)
+                .scope(signer.sign_and_send_encoded_solana_transaction(
+                    "tx".to_string(),
+                ))
+                .await;
+        assert_eq!(sent.unwrap(), "signature");
+        assert_eq!(ledger.spent_today("user").await.unwrap(), 50.0);
+
+        let refused =
+            swap(60.0)
+                .scope(signer.sign_and_send_encoded_solana_transaction(
+                    "tx".to_string(),
+                ))
+                .await
+                .unwrap_err();
+        assert!(matches!(
+            refused.downcast_ref::<PolicyViolation>(),
+            Some(PolicyViolation::DailyLimit { .. })
+        ));
+
+        // nothing is counted for transactions that fail to send
+        let failed = swap(10.0)
+            .scope(signer.sign_and_send_json_evm_transaction(
+                serde_json::Value::Null,
+                None,
+            ))
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(ledger.spent_today("user").await.unwrap(), 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_unattended_needs_limits() {
+        let ledger = Arc::new(SpendLedger::new());
+        let send = |policy: SpendingPolicy| {
+            let signer = PolicySigner::new(
+                Arc::new(NoopSigner),
+                policy,
+                ledger.clone(),
+            )
+            .unattended();
+            async move {
+                swap(10.0)
+                    .scope(signer.sign_and_send_encoded_solana_transaction(
+                        "tx".to_string(),
+                    ))
+                    .await
+            }
+        };
+
+        let refused = send(SpendingPolicy::default()).await.unwrap_err();
+        assert_eq!(
+            refused.downcast_ref::<PolicyViolation>(),
+            Some(&PolicyViolation::Unbounded)
+        );
+        let daily_only = SpendingPolicy {
+            daily_limit_usd: Some(100.0),
+            ..Default::default()
+        };
+        assert!(send(daily_only).await.is_err());
+        assert_eq!(send(policy()).await.unwrap(), "signature");
+    }
+
+    #[tokio::test]
+    async fn test_unattended_approvals() {
+        let limits_only = SpendingPolicy {
+            daily_limit_usd: Some(100.0),
+            max_tx_usd: Some(60.0),
+            ..Default::default()
+        };
+        let approve = |policy: SpendingPolicy, spender: &str| {
+            let signer = PolicySigner::new(
+                Arc::new(NoopSigner),
+                policy,
+                Arc::new(SpendLedger::new()),
+            )
+            .unattended();
+            let intent = TxIntent::new("approve").with_approval(spender);
+            async move {
+                intent
+                    .scope(signer.sign_and_send_encoded_solana_transaction(
+                        "tx".to_string(),
+                    ))
+                    .await
+            }
+        };
+
+        // the limits do not bound approvals, so any spender would pass them
+        let refused = approve(limits_only.clone(), "0xattacker")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            refused.downcast_ref::<PolicyViolation>(),
+            Some(&PolicyViolation::UnattendedApproval(
+                "0xattacker".to_string()
+            ))
+        );
+        assert!(approve(limits_only, LIFI_DIAMOND_ADDRESS).await.is_ok());
+        assert!(approve(
+            policy(),
+            "0xabc0000000000000000000000000000000000001"
+        )
+        .await
+        .is_ok());
+    }
+}
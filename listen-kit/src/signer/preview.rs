@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+
+/// Expected outcome of a transaction for its signer, decoded from a
+/// simulation
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TxPreview {
+    /// "solana" or the CAIP-2 id of the EVM chain
+    pub chain: String,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Net change of every token the signer sends or receives, the
+    /// native one included
+    pub balance_changes: Vec<BalanceChange>,
+    pub fee: Option<BalanceChange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BalanceChange {
+    /// Mint or contract address, "native" for SOL and ETH
+    pub token: String,
+    pub symbol: Option<String>,
+    pub decimals: u8,
+    pub raw_delta: i128,
+}
+
+impl BalanceChange {
+    pub fn delta(&self) -> f64 {
+        self.raw_delta as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    pub fn label(&self) -> String {
+        match &self.symbol {
+            Some(symbol) => symbol.clone(),
+            None if self.token.len() > 12 => format!(
+                "{}...{}",
+                &self.token[..4],
+                &self.token[self.token.len() - 4..]
+            ),
+            None => self.token.clone(),
+        }
+    }
+}
+
+impl TxPreview {
+    pub fn failed(
+        chain: impl Into<String>,
+        error: impl Into<String>,
+    ) -> Self {
+        Self {
+            chain: chain.into(),
+            success: false,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+
+    /// e.g. "You will send 1.2 SOL and receive ~34,000 BONK, fee ~0.000105
+    /// SOL"
+    pub fn summary(&self) -> String {
+        if !self.success {
+            return format!(
+                "Simulation failed: {}",
+                self.error.as_deref().unwrap_or("unknown error")
+            );
+        }
+
+        let describe = |changes: Vec<&BalanceChange>, approx: bool| {
+            changes
+                .iter()
+                .map(|c| {
+                    format!(
+                        "{}{} {}",
+                        if approx { "~" } else { "" },
+                        format_amount(c.delta().abs()),
+                        c.label()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let sent: Vec<_> = self
+            .balance_changes
+            .iter()
+            .filter(|c| c.raw_delta < 0)
+            .collect();
+        let received: Vec<_> = self
+            .balance_changes
+            .iter()
+            .filter(|c| c.raw_delta > 0)
+            .collect();
+
+        let mut parts = Vec::new();
+        if !sent.is_empty() {
+            parts.push(format!("send {}", describe(sent, false)));
+        }
+        if !received.is_empty() {
+            parts.push(format!("receive {}", describe(received, true)));
+        }
+        let mut summary = if parts.is_empty() {
+            "No balance changes".to_string()
+        } else {
+            format!("You will {}", parts.join(" and "))
+        };
+        if let Some(fee) = &self.fee {
+            summary.push_str(&format!(
+                ", fee ~{} {}",
+                format_amount(fee.delta().abs()),
+                fee.label()
+            ));
+        }
+        summary
+    }
+}
+
+/// Thousands separated above 1,000, six significant digits below
+pub fn format_amount(amount: f64) -> String {
+    if amount >= 1000.0 {
+        let digits = format!("{:.0}", amount);
+        let mut out = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                out.push(',');
+            }
+            out.push(c);
+        }
+        return out;
+    }
+    if amount == 0.0 {
+        return "0".to_string();
+    }
+    let precision = (5 - amount.log10().floor() as i32).max(0) as usize;
+    let formatted = format!("{:.*}", precision, amount);
+    if formatted.contains('.') {
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    } else {
+        formatted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(34000.4), "34,000");
+        assert_eq!(format_amount(1234567.0), "1,234,567");
+        assert_eq!(format_amount(1.2), "1.2");
+        assert_eq!(format_amount(0.000105), "0.000105");
+        assert_eq!(format_amount(0.0), "0");
+    }
+
+    #[test]
+    fn test_summary() {
+        let preview = TxPreview {
+            chain: "solana".to_string(),
+            success: true,
+            balance_changes: vec![
+                BalanceChange {
+                    token: "native".to_string(),
+                    symbol: Some("SOL".to_string()),
+                    decimals: 9,
+                    raw_delta: -1_200_000_000,
+                },
+                BalanceChange {
+                    token: "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263"
+                        .to_string(),
+                    symbol: Some("BONK".to_string()),
+                    decimals: 5,
+                    raw_delta: 3_400_000_000,
+                },
+            ],
+            fee: Some(BalanceChange {
+                token: "native".to_string(),
+                symbol: Some("SOL".to_string()),
+                decimals: 9,
+                raw_delta: -105_000,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            preview.summary(),
+            "You will send 1.2 SOL and receive ~34,000 BONK, fee ~0.000105 SOL"
+        );
+        assert_eq!(
+            TxPreview::failed("solana", "slippage").summary(),
+            "Simulation failed: slippage"
+        );
+    }
+}
@@ -9,11 +9,14 @@ use solana_client::rpc_request::TokenAccountsFilter;
 use solana_client::rpc_response::RpcKeyedAccount;
 use solana_sdk::pubkey::Pubkey;
 
+use super::constants::TOKEN_2022_PROGRAM;
+
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct Holding {
     pub mint: String,
     pub ata: String,
     pub amount: u64,
+    pub decimals: u8,
 }
 
 pub fn parse_holding(ata: RpcKeyedAccount) -> Result<Holding> {
@@ -27,6 +30,9 @@ pub fn parse_holding(ata: RpcKeyedAccount) -> Result<Holding> {
             .as_str()
             .expect("amount")
             .parse::<u64>()?;
+        let decimals = parsed["info"]["tokenAmount"]["decimals"]
+            .as_u64()
+            .unwrap_or_default() as u8;
         let mint =
             Pubkey::from_str(parsed["info"]["mint"].as_str().expect("mint"))?;
         let ata = Pubkey::from_str(&ata.pubkey)?;
@@ -34,22 +40,34 @@ pub fn parse_holding(ata: RpcKeyedAccount) -> Result<Holding> {
             mint: mint.to_string(),
             ata: ata.to_string(),
             amount,
+            decimals,
         })
     } else {
         Err(anyhow!("failed to parse holding"))
     }
 }
 
+/// Non-empty SPL and Token-2022 accounts of the owner
 pub async fn get_holdings(
     rpc_client: &RpcClient,
     owner: &Pubkey,
 ) -> Result<Vec<Holding>> {
-    let atas = rpc_client
+    let mut atas = rpc_client
         .get_token_accounts_by_owner(
             owner,
             TokenAccountsFilter::ProgramId(spl_token::id()),
         )
         .await?;
+    atas.extend(
+        rpc_client
+            .get_token_accounts_by_owner(
+                owner,
+                TokenAccountsFilter::ProgramId(Pubkey::from_str(
+                    TOKEN_2022_PROGRAM,
+                )?),
+            )
+            .await?,
+    );
     let holdings = atas
         .iter()
         .map(|ata| parse_holding(ata.clone()).expect("parse holding"))
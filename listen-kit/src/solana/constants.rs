@@ -19,6 +19,8 @@ pub const PUMP_SELL_METHOD: [u8; 8] =
 pub const PUMP_CREATE_METHOD: [u8; 8] =
     [0x18, 0x1e, 0xc8, 0x28, 0x05, 0x00, 0x00, 0x00];
 pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM: &str =
+    "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
 pub const RENT_PROGRAM: &str = "SysvarRent111111111111111111111111111111111";
 pub const ASSOCIATED_TOKEN_PROGRAM: &str =
     "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
@@ -30,7 +30,10 @@ pub async fn fetch_token_price(mint: String, client: &Client) -> Result<f64> {
         .await?;
     let data = res.json::<PriceResponse>().await?;
     tracing::debug!(?data, "fetch_token_price");
-    Ok(data.data.get(&mint).unwrap().price)
+    data.data
+        .get(&mint)
+        .map(|data| data.price)
+        .ok_or_else(|| anyhow!("No price found for {}", mint))
 }
 
 #[cfg(test)]
@@ -15,12 +15,15 @@ use std::str::FromStr;
 
 use crate::common::wrap_unsafe;
 use crate::ensure_solana_wallet_created;
+use crate::signer::approval::TxIntent;
 use crate::solana::data::PortfolioItem;
 
+use super::constants::WSOL;
 use super::data::holdings_to_portfolio;
 use super::deploy_token::create_deploy_token_tx;
 use super::trade::create_jupiter_swap_transaction;
 use super::trade_pump::{create_buy_pump_fun_tx, create_sell_pump_fun_tx};
+use super::transaction::simulate_transaction;
 use super::transfer::{create_transfer_sol_tx, create_transfer_spl_tx};
 use super::util::execute_solana_transaction;
 use crate::signer::SignerContext;
@@ -34,6 +37,33 @@ fn create_rpc() -> RpcClient {
     RpcClient::new(SOLANA_RPC_URL.to_string())
 }
 
+/// Describes a raw token amount for the approval summary, with its USD
+/// value if the decimals and the price are known
+async fn valuate(mint: &str, amount: u64) -> (String, Option<f64>) {
+    let decimals = match Pubkey::from_str(mint) {
+        Ok(pubkey) => create_rpc()
+            .get_token_supply(&pubkey)
+            .await
+            .ok()
+            .map(|supply| supply.decimals),
+        Err(_) => None,
+    };
+    let Some(decimals) = decimals else {
+        return (format!("{} (raw) of {}", amount, mint), None);
+    };
+    let ui_amount = amount as f64 / 10f64.powi(decimals as i32);
+    let price = crate::solana::price::fetch_token_price(
+        mint.to_string(),
+        &Client::new(),
+    )
+    .await
+    .ok();
+    (
+        format!("{} of {}", ui_amount, mint),
+        price.map(|price| price * ui_amount),
+    )
+}
+
 #[tool(description = "
 Runs risk checks for any Solana token.
 
@@ -99,21 +129,62 @@ pub async fn swap(
     amount: String,
     output_mint: String,
 ) -> Result<String> {
-    let _input_mint = input_mint.clone();
-    let _amount = amount.clone();
-    let _output_mint = output_mint.clone();
+    let (description, usd_value) =
+        valuate(&input_mint, amount.parse::<u64>()?).await;
+    let intent =
+        TxIntent::new(format!("Swap {} for {}", description, output_mint))
+            .with_usd_value(usd_value)
+            .with_token(input_mint.clone())
+            .with_token(output_mint.clone());
+
+    intent
+        .scope(execute_solana_transaction(move |owner| async move {
+            create_jupiter_swap_transaction(
+                input_mint.clone(),
+                amount.parse::<u64>()?,
+                output_mint.clone(),
+                &owner,
+            )
+            .await
+            // there would be a slippage error here
+        }))
+        .await
+}
+
+#[tool(description = "
+Simulates a swap without sending it, returning what the wallet would send
+and receive, the fee and a one line summary of it.
 
-    execute_solana_transaction(move |owner| async move {
-        create_jupiter_swap_transaction(
-            input_mint.clone(),
+Params are the same as for the Swap tool
+
+Use it to show the user what a swap will do before performing it, if the
+simulation fails the swap would fail too
+")]
+pub async fn preview_swap(
+    input_mint: String,
+    amount: String,
+    output_mint: String,
+) -> Result<serde_json::Value> {
+    let signer = SignerContext::current().await;
+    ensure_solana_wallet_created(signer.clone()).await?;
+    let owner = Pubkey::from_str(&signer.pubkey().unwrap())?;
+
+    let preview = wrap_unsafe(move || async move {
+        let tx = create_jupiter_swap_transaction(
+            input_mint,
             amount.parse::<u64>()?,
-            output_mint.clone(),
+            output_mint,
             &owner,
         )
-        .await
-        // there would be a slippage error here
+        .await?;
+        simulate_transaction(&tx, &owner).await
     })
-    .await
+    .await?;
+
+    Ok(serde_json::json!({
+        "summary": preview.summary(),
+        "preview": preview,
+    }))
 }
 
 #[tool(description = "
@@ -126,10 +197,17 @@ ALWAYS double check the to address with the user before calling this function
 amount is denoted in lamports, 1 SOL = 10^9 lamports
 ")]
 pub async fn transfer_sol(to: String, amount: u64) -> Result<String> {
-    execute_solana_transaction(move |owner| async move {
-        create_transfer_sol_tx(&Pubkey::from_str(&to)?, amount, &owner).await
-    })
-    .await
+    let (description, usd_value) = valuate(WSOL, amount).await;
+    let intent = TxIntent::new(format!("Transfer {} to {}", description, to))
+        .with_usd_value(usd_value)
+        .with_destination(to.clone());
+
+    intent
+        .scope(execute_solana_transaction(move |owner| async move {
+            create_transfer_sol_tx(&Pubkey::from_str(&to)?, amount, &owner)
+                .await
+        }))
+        .await
 }
 
 /// param amount is token amount, accounting for decimals
@@ -149,17 +227,24 @@ pub async fn transfer_spl_token(
     amount: u64,
     mint: String,
 ) -> Result<String> {
-    execute_solana_transaction(move |owner| async move {
-        create_transfer_spl_tx(
-            &Pubkey::from_str(&to)?,
-            amount,
-            &Pubkey::from_str(&mint)?,
-            &owner,
-            &create_rpc(),
-        )
+    let (description, usd_value) = valuate(&mint, amount).await;
+    let intent = TxIntent::new(format!("Transfer {} to {}", description, to))
+        .with_usd_value(usd_value)
+        .with_destination(to.clone())
+        .with_token(mint.clone());
+
+    intent
+        .scope(execute_solana_transaction(move |owner| async move {
+            create_transfer_spl_tx(
+                &Pubkey::from_str(&to)?,
+                amount,
+                &Pubkey::from_str(&mint)?,
+                &owner,
+                &create_rpc(),
+            )
+            .await
+        }))
         .await
-    })
-    .await
 }
 
 #[tool(description = "
@@ -263,23 +348,31 @@ pub async fn deploy_pump_fun_token(
     image_url: String,
     description: String,
 ) -> Result<String> {
-    execute_solana_transaction(move |owner| async move {
-        create_deploy_token_tx(
-            crate::solana::deploy_token::DeployTokenParams {
-                name,
-                symbol,
-                twitter: Some(twitter),
-                website: Some(website),
-                dev_buy: Some(dev_buy),
-                telegram: Some(telegram),
-                image_url: Some(image_url),
-                description,
-            },
-            &owner,
-        )
+    let (dev_buy_description, usd_value) = valuate(WSOL, dev_buy).await;
+    let intent = TxIntent::new(format!(
+        "Deploy {} (${}) on PumpFun, buying {}",
+        name, symbol, dev_buy_description
+    ))
+    .with_usd_value(usd_value);
+
+    intent
+        .scope(execute_solana_transaction(move |owner| async move {
+            create_deploy_token_tx(
+                crate::solana::deploy_token::DeployTokenParams {
+                    name,
+                    symbol,
+                    twitter: Some(twitter),
+                    website: Some(website),
+                    dev_buy: Some(dev_buy),
+                    telegram: Some(telegram),
+                    image_url: Some(image_url),
+                    description,
+                },
+                &owner,
+            )
+            .await
+        }))
         .await
-    })
-    .await
 }
 
 #[tool(description = "
@@ -303,17 +396,27 @@ pub async fn buy_pump_fun_token(
     sol_amount: f64,
     slippage_bps: u16,
 ) -> Result<String> {
-    execute_solana_transaction(move |owner| async move {
-        create_buy_pump_fun_tx(
-            mint,
-            sol_to_lamports(sol_amount),
-            slippage_bps,
-            &create_rpc(),
-            &owner,
-        )
+    let (description, usd_value) =
+        valuate(WSOL, sol_to_lamports(sol_amount)).await;
+    let intent = TxIntent::new(format!(
+        "Buy {} on PumpFun with {}",
+        mint, description
+    ))
+    .with_usd_value(usd_value)
+    .with_token(mint.clone());
+
+    intent
+        .scope(execute_solana_transaction(move |owner| async move {
+            create_buy_pump_fun_tx(
+                mint,
+                sol_to_lamports(sol_amount),
+                slippage_bps,
+                &create_rpc(),
+                &owner,
+            )
+            .await
+        }))
         .await
-    })
-    .await
 }
 
 #[tool(description = "
@@ -329,10 +432,16 @@ pub async fn sell_pump_fun_token(
     mint: String,
     token_amount: u64,
 ) -> Result<String> {
-    execute_solana_transaction(move |owner| async move {
-        create_sell_pump_fun_tx(mint, token_amount, &owner).await
-    })
-    .await
+    let (description, usd_value) = valuate(&mint, token_amount).await;
+    let intent = TxIntent::new(format!("Sell {} on PumpFun", description))
+        .with_usd_value(usd_value)
+        .with_token(mint.clone());
+
+    intent
+        .scope(execute_solana_transaction(move |owner| async move {
+            create_sell_pump_fun_tx(mint, token_amount, &owner).await
+        }))
+        .await
 }
 
 #[tool(description = "
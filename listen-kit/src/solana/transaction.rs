@@ -1,21 +1,34 @@
 use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use rand::rngs::ThreadRng;
 use rand::thread_rng;
 use rand::Rng;
 use serde::Deserialize;
 use serde_json::json;
+use solana_account_decoder::UiAccountEncoding;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_client::rpc_config::RpcSimulateTransactionAccountsConfig;
 use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::account::Account;
+use solana_sdk::address_lookup_table::state::AddressLookupTable;
+use solana_sdk::compute_budget;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::program_pack::Pack;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::transaction::VersionedTransaction;
 use solana_transaction_status::{
     Encodable, EncodedTransaction, UiTransactionEncoding,
 };
+use spl_token::state::{Account as TokenAccount, Mint};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::str::FromStr;
 use tracing::info;
 
+use crate::data::listen_api_tools::LISTEN_API;
+use crate::signer::preview::{BalanceChange, TxPreview};
+use crate::solana::constants::{TOKEN_2022_PROGRAM, TOKEN_PROGRAM, WSOL};
 use crate::solana::util::env;
 
 #[derive(Debug, Deserialize)]
@@ -133,8 +146,460 @@ pub fn get_jito_tip_pubkey() -> Pubkey {
     Pubkey::from_str(PUBKEYS[index as usize]).expect("parse tip pubkey")
 }
 
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// The RPC calls the preview needs, mocked in tests
+#[async_trait]
+pub trait SimulationRpc: Send + Sync {
+    async fn get_accounts(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>>;
+
+    /// Simulates the transaction, returning the state of `addresses`
+    /// after it
+    async fn simulate(
+        &self,
+        tx: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<Simulation>;
+}
+
+#[derive(Debug, Default)]
+pub struct Simulation {
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub accounts: Vec<Option<Account>>,
+}
+
+#[async_trait]
+impl SimulationRpc for RpcClient {
+    async fn get_accounts(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>> {
+        Ok(self.get_multiple_accounts(keys).await?)
+    }
+
+    async fn simulate(
+        &self,
+        tx: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<Simulation> {
+        let result = self
+            .simulate_transaction_with_config(
+                tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        addresses: addresses
+                            .iter()
+                            .map(|a| a.to_string())
+                            .collect(),
+                    }),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )
+            .await?
+            .value;
+
+        Ok(Simulation {
+            err: result.err.map(|e| e.to_string()),
+            logs: result.logs.unwrap_or_default(),
+            accounts: result
+                .accounts
+                .unwrap_or_default()
+                .into_iter()
+                .map(|account| account.and_then(|a| a.decode::<Account>()))
+                .collect(),
+        })
+    }
+}
+
+/// Simulates the transaction and decodes what it does to the balances of
+/// `owner`, the native change excludes the fee that is reported apart
+pub async fn preview_transaction<R: SimulationRpc + ?Sized>(
+    rpc: &R,
+    tx: &VersionedTransaction,
+    owner: &Pubkey,
+) -> Result<TxPreview> {
+    let keys = account_keys(rpc, &tx.message, owner).await?;
+    let pre = rpc.get_accounts(&keys).await?;
+    let simulation = rpc.simulate(tx, &keys).await?;
+    if let Some(err) = simulation.err {
+        return Ok(TxPreview {
+            logs: simulation.logs,
+            ..TxPreview::failed("solana", err)
+        });
+    }
+    if simulation.accounts.len() != keys.len() {
+        return Err(anyhow!("Simulation did not return the accounts"));
+    }
+
+    let fee = estimate_fee(&tx.message);
+    let mut native_delta = 0i128;
+    let mut token_deltas: Vec<(Pubkey, i128)> = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        let (pre, post) = (pre[i].as_ref(), simulation.accounts[i].as_ref());
+        if key == owner {
+            native_delta = lamports(post) as i128 - lamports(pre) as i128;
+            if tx.message.static_account_keys().first() == Some(owner) {
+                native_delta += fee as i128;
+            }
+            continue;
+        }
+
+        let pre = pre.and_then(owned_token_account(owner));
+        let post = post.and_then(owned_token_account(owner));
+        let Some(mint) = pre.or(post).map(|a| a.mint) else {
+            continue;
+        };
+        let delta = post.map_or(0, |a| a.amount as i128)
+            - pre.map_or(0, |a| a.amount as i128);
+        match token_deltas.iter_mut().find(|(m, _)| *m == mint) {
+            Some((_, total)) => *total += delta,
+            None => token_deltas.push((mint, delta)),
+        }
+    }
+    token_deltas.retain(|(_, delta)| *delta != 0);
+
+    let mints: Vec<Pubkey> = token_deltas.iter().map(|(m, _)| *m).collect();
+    let decimals: HashMap<Pubkey, u8> = if mints.is_empty() {
+        HashMap::new()
+    } else {
+        mints
+            .iter()
+            .zip(rpc.get_accounts(&mints).await?)
+            .filter_map(|(mint, account)| {
+                let account = account?;
+                let data = account.data.get(..Mint::LEN)?;
+                Some((*mint, Mint::unpack_from_slice(data).ok()?.decimals))
+            })
+            .collect()
+    };
+
+    let native = |raw_delta| BalanceChange {
+        token: "native".to_string(),
+        symbol: Some("SOL".to_string()),
+        decimals: 9,
+        raw_delta,
+    };
+    let mut balance_changes = Vec::new();
+    if native_delta != 0 {
+        balance_changes.push(native(native_delta));
+    }
+    for (mint, raw_delta) in token_deltas {
+        balance_changes.push(BalanceChange {
+            token: mint.to_string(),
+            symbol: (mint.to_string() == WSOL).then(|| "wSOL".to_string()),
+            decimals: decimals.get(&mint).copied().unwrap_or_default(),
+            raw_delta,
+        });
+    }
+
+    Ok(TxPreview {
+        chain: "solana".to_string(),
+        success: true,
+        error: None,
+        balance_changes,
+        fee: Some(native(-(fee as i128))),
+        logs: simulation.logs,
+    })
+}
+
+/// Preview of the transaction against `SOLANA_RPC_URL`, with the token
+/// symbols resolved
+pub async fn simulate_transaction(
+    tx: &VersionedTransaction,
+    owner: &Pubkey,
+) -> Result<TxPreview> {
+    let rpc = RpcClient::new(env("SOLANA_RPC_URL"));
+    let mut preview = preview_transaction(&rpc, tx, owner).await?;
+    resolve_symbols(&mut preview).await;
+    Ok(preview)
+}
+
+/// Fills in the symbols of the tokens from their metadata
+pub async fn resolve_symbols(preview: &mut TxPreview) {
+    for change in preview.balance_changes.iter_mut() {
+        if change.symbol.is_some() {
+            continue;
+        }
+        if let Ok(Some(metadata)) = LISTEN_API.metadata(&change.token).await {
+            change.symbol = Some(metadata.mpl.symbol);
+        }
+    }
+}
+
+/// Every account the transaction can touch, with the addresses of its
+/// lookup tables resolved
+async fn account_keys<R: SimulationRpc + ?Sized>(
+    rpc: &R,
+    message: &VersionedMessage,
+    owner: &Pubkey,
+) -> Result<Vec<Pubkey>> {
+    let mut keys = message.static_account_keys().to_vec();
+    if let Some(lookups) = message.address_table_lookups() {
+        let tables: Vec<Pubkey> =
+            lookups.iter().map(|l| l.account_key).collect();
+        let accounts = rpc.get_accounts(&tables).await?;
+        for (lookup, account) in lookups.iter().zip(accounts) {
+            let account = account.ok_or_else(|| {
+                anyhow!("Lookup table {} not found", lookup.account_key)
+            })?;
+            let table = AddressLookupTable::deserialize(&account.data)
+                .map_err(|e| anyhow!("Invalid lookup table: {}", e))?;
+            for index in lookup.writable_indexes.iter() {
+                if let Some(key) = table.addresses.get(*index as usize) {
+                    keys.push(*key);
+                }
+            }
+        }
+    }
+    if !keys.contains(owner) {
+        keys.push(*owner);
+    }
+    let mut seen = std::collections::HashSet::new();
+    keys.retain(|key| seen.insert(*key));
+    Ok(keys)
+}
+
+fn lamports(account: Option<&Account>) -> u64 {
+    account.map_or(0, |a| a.lamports)
+}
+
+fn owned_token_account(
+    owner: &Pubkey,
+) -> impl Fn(&Account) -> Option<TokenAccount> + '_ {
+    move |account| {
+        let program = account.owner.to_string();
+        if program != TOKEN_PROGRAM && program != TOKEN_2022_PROGRAM {
+            return None;
+        }
+        // token-2022 mints with extensions are as long, the account type
+        // byte after the base account tells them apart
+        if account.data.len() > TokenAccount::LEN
+            && account.data[TokenAccount::LEN] != 2
+        {
+            return None;
+        }
+        let data = account.data.get(..TokenAccount::LEN)?;
+        let token_account = TokenAccount::unpack_from_slice(data).ok()?;
+        (token_account.owner == *owner).then_some(token_account)
+    }
+}
+
+/// Base fee per signature plus the priority fee of the compute budget
+/// instructions
+pub fn estimate_fee(message: &VersionedMessage) -> u64 {
+    let keys = message.static_account_keys();
+    let mut unit_limit = None;
+    let mut unit_price = 0u64;
+    let mut instructions = 0u64;
+    for ix in message.instructions() {
+        if keys.get(ix.program_id_index as usize)
+            != Some(&compute_budget::id())
+        {
+            instructions += 1;
+            continue;
+        }
+        match ix.data.first() {
+            Some(2) if ix.data.len() >= 5 => {
+                unit_limit = Some(u32::from_le_bytes(
+                    ix.data[1..5].try_into().unwrap(),
+                ) as u64)
+            }
+            Some(3) if ix.data.len() >= 9 => {
+                unit_price =
+                    u64::from_le_bytes(ix.data[1..9].try_into().unwrap())
+            }
+            _ => {}
+        }
+    }
+    let unit_limit =
+        unit_limit.unwrap_or((instructions * 200_000).min(1_400_000));
+    let priority_fee =
+        (unit_limit as u128 * unit_price as u128).div_ceil(1_000_000) as u64;
+
+    LAMPORTS_PER_SIGNATURE * message.header().num_required_signatures as u64
+        + priority_fee
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use solana_sdk::compute_budget::ComputeBudgetInstruction;
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::Transaction;
+    use spl_token::state::AccountState;
+
+    #[derive(Default)]
+    struct MockRpc {
+        pre: HashMap<Pubkey, Account>,
+        post: HashMap<Pubkey, Account>,
+        err: Option<String>,
+    }
+
+    #[async_trait]
+    impl SimulationRpc for MockRpc {
+        async fn get_accounts(
+            &self,
+            keys: &[Pubkey],
+        ) -> Result<Vec<Option<Account>>> {
+            Ok(keys.iter().map(|k| self.pre.get(k).cloned()).collect())
+        }
+
+        async fn simulate(
+            &self,
+            _tx: &VersionedTransaction,
+            addresses: &[Pubkey],
+        ) -> Result<Simulation> {
+            Ok(Simulation {
+                err: self.err.clone(),
+                logs: vec![],
+                accounts: addresses
+                    .iter()
+                    .map(|k| self.post.get(k).cloned())
+                    .collect(),
+            })
+        }
+    }
+
+    fn system_account(lamports: u64) -> Account {
+        Account {
+            lamports,
+            ..Account::default()
+        }
+    }
+
+    fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        }
+        .pack_into_slice(&mut data);
+        Account {
+            lamports: 2_039_280,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        }
+    }
+
+    fn mint_account(decimals: u8) -> Account {
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            decimals,
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut data);
+        Account {
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        }
+    }
+
+    #[test]
+    fn test_estimate_fee() {
+        let payer = Pubkey::new_unique();
+        let tx = Transaction::new_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(10_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000_000),
+                system_instruction::transfer(
+                    &payer,
+                    &Pubkey::new_unique(),
+                    1,
+                ),
+            ],
+            Some(&payer),
+        );
+        let message = VersionedTransaction::from(tx).message;
+        assert_eq!(estimate_fee(&message), 5_000 + 10_000);
+    }
+
+    #[tokio::test]
+    async fn test_preview_transaction() {
+        let owner = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let tx = VersionedTransaction::from(Transaction::new_with_payer(
+            &[
+                system_instruction::transfer(
+                    &owner,
+                    &recipient,
+                    1_000_000_000,
+                ),
+                spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    &source,
+                    &destination,
+                    &owner,
+                    &[],
+                    250,
+                )
+                .unwrap(),
+            ],
+            Some(&owner),
+        ));
+
+        let rpc = MockRpc {
+            pre: HashMap::from([
+                (owner, system_account(3_000_000_000)),
+                (recipient, system_account(0)),
+                (source, token_account(mint, owner, 1_000)),
+                (destination, token_account(mint, recipient, 0)),
+                (mint, mint_account(2)),
+            ]),
+            post: HashMap::from([
+                (owner, system_account(2_000_000_000 - 5_000)),
+                (recipient, system_account(1_000_000_000)),
+                (source, token_account(mint, owner, 750)),
+                (destination, token_account(mint, recipient, 250)),
+            ]),
+            err: None,
+        };
+
+        let preview = preview_transaction(&rpc, &tx, &owner).await.unwrap();
+        assert!(preview.success);
+        assert_eq!(preview.balance_changes.len(), 2);
+        assert_eq!(preview.balance_changes[0].raw_delta, -1_000_000_000);
+        assert_eq!(preview.balance_changes[1].token, mint.to_string());
+        assert_eq!(preview.balance_changes[1].raw_delta, -250);
+        assert_eq!(preview.balance_changes[1].delta(), -2.5);
+        assert_eq!(preview.fee.as_ref().unwrap().raw_delta, -5_000);
+    }
+
+    #[tokio::test]
+    async fn test_preview_failed_simulation() {
+        let owner = Pubkey::new_unique();
+        let tx = VersionedTransaction::from(Transaction::new_with_payer(
+            &[system_instruction::transfer(&owner, &owner, 1)],
+            Some(&owner),
+        ));
+        let rpc = MockRpc {
+            err: Some("insufficient funds".to_string()),
+            ..MockRpc::default()
+        };
+
+        let preview = preview_transaction(&rpc, &tx, &owner).await.unwrap();
+        assert!(!preview.success);
+        assert_eq!(
+            preview.summary(),
+            "Simulation failed: insufficient funds"
+        );
+    }
+
     #[test]
     fn bench_get_jito_tip_pubkey() {
         for _ in 0..100 {
@@ -1,4 +1,6 @@
 use anyhow::{anyhow, Result};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
 use chrono::Local;
 use env_logger::Builder;
 use log::LevelFilter;
@@ -20,8 +22,10 @@ use std::sync::Arc;
 
 use crate::common::wrap_unsafe;
 use crate::ensure_solana_wallet_created;
+use crate::signer::approval::TxIntent;
 use crate::signer::solana::LocalSolanaSigner;
 use crate::signer::{SignerContext, TransactionSigner};
+use crate::solana::transaction::simulate_transaction;
 
 pub fn env(var: &str) -> String {
     std::env::var(var).unwrap_or_else(|_| panic!("{} env var not set", var))
@@ -176,8 +180,43 @@ where
         .await
         .map_err(|e| anyhow!("{:#?}", e))?;
 
-    wrap_unsafe(move || async move {
-        signer.sign_and_send_solana_transaction(&mut tx).await
+    let preview = if std::env::var("SKIP_SIMULATION").is_err() {
+        let tx = tx.clone();
+        let preview = wrap_unsafe(move || async move {
+            simulate_transaction(&tx, &owner).await
+        })
+        .await?;
+        if !preview.success {
+            return Err(anyhow!(
+                "Transaction simulation failed, not sending it: {}",
+                preview.summary()
+            ));
+        }
+        Some(preview)
+    } else {
+        None
+    };
+
+    let intent = TxIntent::current()
+        .unwrap_or_else(|| TxIntent::new("Sign a Solana transaction"))
+        .with_preview(preview.as_ref());
+    let encoded_tx = BASE64_STANDARD.encode(bincode::serialize(&tx)?);
+    signer
+        .request_approval(
+            intent.clone(),
+            serde_json::json!({
+                "chain": "solana",
+                "transaction": encoded_tx,
+                "preview": preview,
+            }),
+        )
+        .await?;
+
+    // the signer sees the intent too, policies are enforced when signing
+    wrap_unsafe(move || {
+        intent.scope(async move {
+            signer.sign_and_send_solana_transaction(&mut tx).await
+        })
     })
     .await
     .map_err(|e| anyhow!("{:#?}", e))
@@ -1,8 +1,23 @@
+use std::str::FromStr;
+use std::sync::LazyLock;
+
 use anyhow::{anyhow, Result};
+use listen_adapter_client::{CandlestickParams, ListenAdapterClient, TopTokensParams};
 use mcp_core::{tool_text_content, types::ToolResponseContent};
 use mcp_core_macros::tool;
 
-const API_BASE: &str = "https://api.listen-rs.com/v1/adapter";
+static API: LazyLock<ListenAdapterClient> = LazyLock::new(ListenAdapterClient::from_env);
+
+/// Tool parameters are all strings, empty ones are left unset
+fn parse_param<T: FromStr>(name: &str, value: &str) -> Result<Option<T>> {
+    match value.trim() {
+        "" => Ok(None),
+        value => value
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow!("Invalid {}: {}", name, value)),
+    }
+}
 
 #[tool(description = "
 Fetch the latest price for a token from the Listen API.
@@ -11,20 +26,12 @@ Parameters:
 - mint (string): The token's mint/pubkey address
 ")]
 async fn fetch_price(mint: String) -> Result<ToolResponseContent> {
-    let response = reqwest::get(format!("{}/price?mint={}", API_BASE, mint))
+    let price = API
+        .price(&mint)
         .await
-        .map_err(|e| anyhow!("Failed to fetch chart: {}", e))?;
-
-    let data = response
-        .json::<serde_json::Value>()
-        .await
-        .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
+        .map_err(|e| anyhow!("No price found for token {}: {}", mint, e))?;
 
-    if let Some(price) = data.get("price") {
-        Ok(tool_text_content!(serde_json::to_string(&price)?))
-    } else {
-        Err(anyhow!("No price found for token: {}", mint))
-    }
+    Ok(tool_text_content!(serde_json::to_string(&price.price)?))
 }
 
 #[tool(description = "
@@ -54,27 +61,20 @@ pub async fn fetch_top_tokens(
     timeframe: String,
     only_pumpfun_tokens: String,
 ) -> Result<ToolResponseContent> {
-    let mut url = format!("{}/top-tokens", API_BASE);
-    let mut query_params = vec![];
-
-    query_params.push(format!("limit={}", limit));
-    query_params.push(format!("min_volume={}", min_volume));
-    query_params.push(format!("min_market_cap={}", min_market_cap));
-    query_params.push(format!("max_market_cap={}", max_market_cap));
-    query_params.push(format!("timeframe={}", timeframe));
-    query_params.push(format!("only_pumpfun_tokens={}", only_pumpfun_tokens));
-
-    url = format!("{}?{}", url, query_params.join("&"));
-
-    let response = reqwest::get(&url)
+    let params = TopTokensParams {
+        limit: parse_param("limit", &limit)?,
+        min_volume: parse_param("min_volume", &min_volume)?,
+        min_market_cap: parse_param("min_market_cap", &min_market_cap)?,
+        max_market_cap: parse_param("max_market_cap", &max_market_cap)?,
+        timeframe: parse_param("timeframe", &timeframe)?,
+        only_pumpfun_tokens: parse_param("only_pumpfun_tokens", &only_pumpfun_tokens)?,
+    };
+
+    let tokens = API
+        .top_tokens(&params)
         .await
         .map_err(|e| anyhow!("Failed to fetch top tokens: {}", e))?;
 
-    let tokens = response
-        .json::<serde_json::Value>()
-        .await
-        .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
-
     Ok(tool_text_content!(serde_json::to_string(&tokens)?))
 }
 
@@ -92,17 +92,14 @@ Parameters:
   * '1d'  (1 day)
 ")]
 async fn fetch_price_chart(mint: String, interval: String) -> Result<ToolResponseContent> {
-    let response = reqwest::get(format!(
-        "{}/candlesticks?mint={}&interval={}",
-        API_BASE, mint, interval
-    ))
-    .await
-    .map_err(|e| anyhow!("Failed to fetch chart: {}", e))?;
-
-    let candlesticks = response
-        .json::<serde_json::Value>()
+    let candlesticks = API
+        .candlesticks(&CandlestickParams {
+            mint,
+            interval,
+            ..Default::default()
+        })
         .await
-        .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
+        .map_err(|e| anyhow!("Failed to fetch chart: {}", e))?;
 
     Ok(tool_text_content!(serde_json::to_string(&candlesticks)?))
 }
@@ -121,14 +118,11 @@ It returns metadata that includes:
 - IPFS metadata (name, description, image, social links)
 ")]
 async fn fetch_token_metadata(mint: String) -> Result<ToolResponseContent> {
-    let response = reqwest::get(format!("{}/metadata?mint={}", API_BASE, mint))
-        .await
-        .map_err(|e| anyhow!("Failed to fetch token metadata: {}", e))?;
-
-    let metadata = response
-        .json::<serde_json::Value>()
+    let metadata = API
+        .metadata(&mint)
         .await
-        .map_err(|e| anyhow!("Failed to parse JSON {}", e))?;
+        .map_err(|e| anyhow!("Failed to fetch token metadata: {}", e))?
+        .ok_or_else(|| anyhow!("No metadata found for token: {}", mint))?;
 
     Ok(tool_text_content!(serde_json::to_string(&metadata)?))
 }
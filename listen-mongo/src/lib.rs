@@ -1,6 +1,10 @@
 use anyhow::{anyhow, Result};
 use bson::doc;
-use mongodb::{options::ClientOptions, Client, Collection, Database};
+use futures::TryStreamExt;
+use mongodb::{
+    options::{ClientOptions, ReplaceOptions},
+    Client, Collection, Database,
+};
 use serde::{de::DeserializeOwned, Serialize};
 use std::env;
 
@@ -18,6 +22,8 @@ pub enum MongoError {
     FindError(mongodb::error::Error),
     #[error("Failed to update document: {0}")]
     UpdateError(mongodb::error::Error),
+    #[error("Failed to delete document: {0}")]
+    DeleteError(mongodb::error::Error),
     #[error("Missing environment variable: {0}")]
     MissingEnvVar(String),
     #[error("Failed to create client: {0}")]
@@ -178,6 +184,108 @@ impl MongoClient {
             .map_err(MongoError::UpdateError)?;
         Ok(())
     }
+
+    /// Finds the document whose `field` equals `value`
+    pub async fn find_one_by<T: DeserializeOwned + Unpin + Send + Sync>(
+        &self,
+        collection_name: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<Option<T>, MongoError> {
+        self.collection::<T>(collection_name)
+            .find_one(doc! { field: value }, None)
+            .await
+            .map_err(MongoError::FindError)
+    }
+
+    /// Replaces the document whose `field` equals `value`, inserting it if there is none
+    pub async fn upsert_by<T: Serialize + DeserializeOwned + Unpin + Send + Sync>(
+        &self,
+        collection_name: &str,
+        field: &str,
+        value: &str,
+        document: T,
+    ) -> Result<(), MongoError> {
+        self.collection::<T>(collection_name)
+            .replace_one(
+                doc! { field: value },
+                document,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(MongoError::UpdateError)?;
+        Ok(())
+    }
+
+    /// Finds every document whose `field` equals `value`
+    pub async fn find_many_by<T: DeserializeOwned + Unpin + Send + Sync>(
+        &self,
+        collection_name: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<Vec<T>, MongoError> {
+        self.collection::<T>(collection_name)
+            .find(doc! { field: value }, None)
+            .await
+            .map_err(MongoError::FindError)?
+            .try_collect()
+            .await
+            .map_err(MongoError::FindError)
+    }
+
+    /// Finds every document whose `field` is at most `value`
+    pub async fn find_many_lte<T: DeserializeOwned + Unpin + Send + Sync>(
+        &self,
+        collection_name: &str,
+        field: &str,
+        value: i64,
+    ) -> Result<Vec<T>, MongoError> {
+        self.collection::<T>(collection_name)
+            .find(doc! { field: { "$lte": value } }, None)
+            .await
+            .map_err(MongoError::FindError)?
+            .try_collect()
+            .await
+            .map_err(MongoError::FindError)
+    }
+
+    /// Sets `field` of the document whose `key` equals `key_value` to `value` if it still is
+    /// `expected`, returns whether it was set, so that only one caller wins a race for it
+    pub async fn compare_and_set(
+        &self,
+        collection_name: &str,
+        key: &str,
+        key_value: &str,
+        field: &str,
+        expected: i64,
+        value: i64,
+    ) -> Result<bool, MongoError> {
+        let result = self
+            .collection::<bson::Document>(collection_name)
+            .update_one(
+                doc! { key: key_value, field: expected },
+                doc! { "$set": { field: value } },
+                None,
+            )
+            .await
+            .map_err(MongoError::UpdateError)?;
+        Ok(result.modified_count > 0)
+    }
+
+    /// Deletes the document whose `field` equals `value`, returns whether there was one
+    pub async fn delete_one_by(
+        &self,
+        collection_name: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<bool, MongoError> {
+        let result = self
+            .collection::<bson::Document>(collection_name)
+            .delete_one(doc! { field: value }, None)
+            .await
+            .map_err(MongoError::DeleteError)?;
+        Ok(result.deleted_count > 0)
+    }
 }
 
 // Example usage